@@ -0,0 +1,8 @@
+use std::io;
+
+fn main() {
+    if let Err(err) = strandal::strandal::lsp::run(io::stdin().lock(), io::stdout().lock()) {
+        eprintln!("strandal-lsp: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -1,32 +1,163 @@
-use crate::strandal::{net::NetBuilder, term::TermPtr, var::VarUse};
+use crate::strandal::{
+    net::NetBuilder,
+    term::{CellUse, TermPtr},
+    var::VarUse,
+};
 
-/// M0 multiplexor
-pub fn m_0(net: &mut impl NetBuilder) -> TermPtr {
-    let era = net.era();
-    return era;
+/// Mn multiplexor: a tree of `n - 1` lambda cells gathering `n` wires
+/// into one. Returns the root and the leaves, left to right. M0 is an
+/// eraser and M1 a plain wire.
+pub fn m_n(net: &mut impl NetBuilder, n: usize) -> (TermPtr, Vec<VarUse>) {
+    if n == 0 {
+        return (net.era(), vec![]);
+    }
+    let (leaves, mut ports): (Vec<VarUse>, Vec<VarUse>) = (0..n).map(|_| net.var()).unzip();
+    let last = ports.pop().unwrap();
+    // nest to the right: (l0 (l1 (.. l(n-1))))
+    let root = ports
+        .into_iter()
+        .rev()
+        .fold(TermPtr::from(last), |tree, leaf| net.lam(leaf, tree).into());
+    return (root, leaves);
 }
 
-/// M1 multiplexor
-pub fn m_1(net: &mut impl NetBuilder) -> (VarUse, VarUse) {
-    let (root, x0) = net.var();
-    return (root, x0);
+/// Church pair `λf. f a b`. An application takes its argument in its first
+/// port and gives its result in its second one, so `f` is bound straight to
+/// the application of `a`, whose result is applied to `b`, giving the body.
+pub fn pair<T1, T2>(net: &mut impl NetBuilder, a: T1, b: T2) -> CellUse
+where
+    T1: Into<TermPtr>,
+    T2: Into<TermPtr>,
+{
+    let result = net.var();
+    let app_b = net.app(b, result.0);
+    let app_a = net.app(a, app_b);
+    return net.lam(app_a, result.1);
 }
 
-pub fn m_2(net: &mut impl NetBuilder) -> (TermPtr, [VarUse; 2]) {
-    let aux_0 = net.var();
-    let aux_1 = net.var();
-    let ctr = net.lam(aux_0.0, aux_1.0);
-    let (root, aux) = m_1(net);
-    net.eqn(aux, ctr);
-    return (root.into(), [aux_0.1, aux_1.1]);
+/// The first component of a pair: `p (λx. λy. x)`.
+pub fn fst<T: Into<TermPtr>>(net: &mut impl NetBuilder, p: T) -> VarUse {
+    let x = net.var();
+    let inner = net.lam(TermPtr::Era, x.1);
+    let select = net.lam(x.0, inner);
+    return apply(net, p, select);
 }
 
-pub fn m_3(net: &mut impl NetBuilder) -> (TermPtr, [VarUse; 3]) {
-    let (root, [aux_0, _aux_1]) = m_2(net);
-    let new_aux_1 = net.var();
-    let aux_2 = net.var();
-    let ctr = net.lam(new_aux_1.0, aux_2.0);
-    return (root.into(), [aux_0, new_aux_1.1, aux_2.1]);
+/// The second component of a pair: `p (λx. λy. y)`.
+pub fn snd<T: Into<TermPtr>>(net: &mut impl NetBuilder, p: T) -> VarUse {
+    let y = net.var();
+    let inner = net.lam(y.0, y.1);
+    let select = net.lam(TermPtr::Era, inner);
+    return apply(net, p, select);
+}
+
+/// Both components of a pair, sharing it through a freshly labelled
+/// duplicator so it cannot annihilate with duplicators inside the pair.
+pub fn unpair<T: Into<TermPtr>>(net: &mut impl NetBuilder, p: T) -> (VarUse, VarUse) {
+    let (left, right) = (net.var(), net.var());
+    let dup = net.fresh_dup(left.0, right.0);
+    net.eqn(p, dup);
+    return (fst(net, left.1), snd(net, right.1));
+}
+
+/// Apply `f` to `arg`, returning the result.
+pub fn apply<T1, T2>(net: &mut impl NetBuilder, f: T1, arg: T2) -> VarUse
+where
+    T1: Into<TermPtr>,
+    T2: Into<TermPtr>,
+{
+    let result = net.var();
+    let app = net.app(arg, result.0);
+    net.eqn(f, app);
+    return result.1;
+}
+
+/// The empty Church list `λc. λn. n`.
+pub fn nil(net: &mut impl NetBuilder) -> CellUse {
+    let n = net.var();
+    let inner = net.lam(n.0, n.1);
+    return net.lam(TermPtr::Era, inner);
+}
+
+/// The Church list `λc. λn. c head (tail c n)`. `c` is shared through a
+/// freshly labelled duplicator, so folding a list copies the step function
+/// once per element.
+pub fn cons<T1, T2>(net: &mut impl NetBuilder, head: T1, tail: T2) -> CellUse
+where
+    T1: Into<TermPtr>,
+    T2: Into<TermPtr>,
+{
+    let (c0, c1, n) = (net.var(), net.var(), net.var());
+    let tail_c = apply(net, tail, c1.1);
+    let rest = apply(net, tail_c, n.1);
+    let c_head = apply(net, c0.1, head);
+    let body = apply(net, c_head, rest);
+    let inner = net.lam(n.0, body);
+    let c = net.fresh_dup(c0.0, c1.0);
+    return net.lam(c, inner);
+}
+
+/// A Church list of `items`, first to last.
+pub fn list(net: &mut impl NetBuilder, items: Vec<TermPtr>) -> TermPtr {
+    let nil = nil(net).into();
+    return items
+        .into_iter()
+        .rev()
+        .fold(nil, |tail, head| cons(net, head, tail).into());
+}
+
+/// Fold a Church list from the right: `list f z`.
+pub fn fold<T1, T2, T3>(net: &mut impl NetBuilder, list: T1, f: T2, z: T3) -> VarUse
+where
+    T1: Into<TermPtr>,
+    T2: Into<TermPtr>,
+    T3: Into<TermPtr>,
+{
+    let partial = apply(net, list, f);
+    return apply(net, partial, z);
+}
+
+/// Map `f` over a Church list: `λc. λn. list (λh. λacc. c (f h) acc) n`.
+pub fn map<T1, T2>(net: &mut impl NetBuilder, f: T1, list: T2) -> CellUse
+where
+    T1: Into<TermPtr>,
+    T2: Into<TermPtr>,
+{
+    let (c, n, h, acc) = (net.var(), net.var(), net.var(), net.var());
+    let f_h = apply(net, f, h.1);
+    let c_f_h = apply(net, c.1, f_h);
+    let step_body = apply(net, c_f_h, acc.1);
+    let step_inner = net.lam(acc.0, step_body);
+    let step = net.lam(h.0, step_inner);
+    let mapped = fold(net, list, step, n.1);
+    let inner = net.lam(n.0, mapped);
+    return net.lam(c.0, inner);
+}
+
+/// Church true `λt. λf. t`.
+pub fn tru(net: &mut impl NetBuilder) -> CellUse {
+    let t = net.var();
+    let inner = net.lam(TermPtr::Era, t.1);
+    return net.lam(t.0, inner);
+}
+
+/// Church false `λt. λf. f`.
+pub fn fls(net: &mut impl NetBuilder) -> CellUse {
+    let f = net.var();
+    let inner = net.lam(f.0, f.1);
+    return net.lam(TermPtr::Era, inner);
+}
+
+/// `if cond then yes else no`, as `cond yes no`. The branch not taken meets
+/// the eraser of the boolean and is erased.
+pub fn ite<T1, T2, T3>(net: &mut impl NetBuilder, cond: T1, yes: T2, no: T3) -> VarUse
+where
+    T1: Into<TermPtr>,
+    T2: Into<TermPtr>,
+    T3: Into<TermPtr>,
+{
+    let partial = apply(net, cond, yes);
+    return apply(net, partial, no);
 }
 
 pub fn id(b: &mut impl NetBuilder) -> VarUse {
@@ -50,3 +181,131 @@ pub fn dup(b: &mut impl NetBuilder) -> VarUse {
     b.eqn(result.0, lam_ref);
     return result.1;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        runtime::Runtime,
+        term::TermPtr,
+    };
+
+    use super::{fls, fold, fst, ite, list, m_n, map, pair, snd, tru, unpair};
+
+    // a closed term with `depth` nested lambdas
+    fn value(net: &mut Net, depth: usize) -> TermPtr {
+        (0..depth).fold(TermPtr::Era, |tree, _| net.lam(TermPtr::Era, tree).into())
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_m_n() {
+        for n in 0..6 {
+            let mut net = Net::new();
+            let (root, leaves) = m_n(&mut net, n);
+            leaves.into_iter().for_each(|leaf| net.head(leaf));
+            // a matching tree of applications, sending value `i` to leaf `i`
+            let mut demux = value(&mut net, n.saturating_sub(1));
+            for i in (0..n.saturating_sub(1)).rev() {
+                let arg = value(&mut net, i);
+                demux = net.app(arg, demux).into();
+            }
+            net.eqn(root, demux);
+
+            let mut runtime = Runtime::new();
+            runtime.eval(&mut net).unwrap();
+            assert_eq!(runtime.stats.comm_app_lam(), n.saturating_sub(1));
+            let heads = net.normal_form().unwrap();
+            assert_eq!(heads.len(), n);
+            for (i, head) in heads.iter().enumerate() {
+                assert_eq!(head.to_string().matches('λ').count(), i, "m_{}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pair() {
+        let mut net = Net::new();
+        let (a, b) = (value(&mut net, 1), value(&mut net, 2));
+        let p = pair(&mut net, a, b);
+        let first = fst(&mut net, p);
+        net.head(first);
+        let (a, b) = (value(&mut net, 3), value(&mut net, 4));
+        let p = pair(&mut net, a, b);
+        let second = snd(&mut net, p);
+        net.head(second);
+        // a pair holding a duplicator, itself shared by a duplicator
+        let (a, b) = (value(&mut net, 5), value(&mut net, 6));
+        let shared = net.dup(a, b);
+        let p = pair(&mut net, shared, TermPtr::Era);
+        let (first, second) = unpair(&mut net, p);
+        net.head(first);
+        net.head(second);
+
+        Runtime::new().eval(&mut net).unwrap();
+        let heads = net.normal_form().unwrap();
+        let depths: Vec<_> = heads
+            .iter()
+            .map(|head| head.to_string().matches('λ').count())
+            .collect();
+        // the fresh label commutes through the inner duplicator, so the first
+        // component is still the whole shared term
+        assert_eq!(depths, [1, 4, 11, 0]);
+        assert!(heads[2].to_string().starts_with("(δ "));
+    }
+
+    #[test]
+    fn test_list() {
+        let mut net = Net::new();
+        let items = (1..4).map(|depth| value(&mut net, depth)).collect();
+        let items = list(&mut net, items);
+        // map `λx. λ*. x` to add a lambda to every item
+        let x = net.var();
+        let wrap_body = net.lam(TermPtr::Era, x.1);
+        let wrap = net.lam(x.0, wrap_body);
+        let mapped = map(&mut net, wrap, items);
+        // fold with `λh. λacc. λf. f h acc` to build the items into pairs
+        let (h, acc) = (net.var(), net.var());
+        let step_body = pair(&mut net, h.1, acc.1);
+        let step_inner = net.lam(acc.0, step_body);
+        let step = net.lam(h.0, step_inner);
+        let result = fold(&mut net, mapped, step, TermPtr::Era);
+        net.head(result);
+
+        Runtime::new().eval(&mut net).unwrap();
+        // var names depend on the allocation order
+        let head = net.normal_form().unwrap()[0].to_string();
+        let shape: Vec<_> = head
+            .split(' ')
+            .map(|token| match token.strip_prefix('x') {
+                Some(rest) => format!("x{}", rest.trim_start_matches(|c: char| c.is_ascii_digit())),
+                None => token.to_string(),
+            })
+            .collect();
+        assert_eq!(
+            shape.join(" "),
+            "(λ (@ (λ ε (λ ε ε)) (@ (λ (@ (λ ε (λ ε (λ ε ε))) (@ (λ (@ (λ ε (λ ε (λ ε (λ ε ε)))) \
+             (@ ε x)) x) x)) x) x)) x)"
+        );
+    }
+
+    #[test]
+    fn test_ite() {
+        for (cond, depth) in [(true, 1), (false, 2)] {
+            let mut net = Net::new();
+            let cond = match cond {
+                true => tru(&mut net),
+                false => fls(&mut net),
+            };
+            let (yes, no) = (value(&mut net, 1), value(&mut net, 2));
+            let result = ite(&mut net, cond, yes, no);
+            net.head(result);
+
+            Runtime::new().eval(&mut net).unwrap();
+            let head = net.normal_form().unwrap()[0].to_string();
+            assert_eq!(head.matches('λ').count(), depth);
+            // the other branch was erased cell by cell
+            assert!(net.leaks().is_empty(), "{}", net.leaks());
+        }
+    }
+}
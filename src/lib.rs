@@ -0,0 +1,2 @@
+pub mod lambda;
+pub mod strandal;
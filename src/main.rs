@@ -1,17 +1,874 @@
-mod lambda;
-mod strandal;
+mod tui;
 
+use ::strandal::{lambda, strandal};
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand};
 use lambda::{dup, id};
 use strandal::net::Net;
 
-use tracing::info;
+use tracing::{error, info};
 
 use crate::{
-    lambda::m_2,
-    strandal::{net::NetBuilder, runtime::Runtime},
+    lambda::{cons, fold, list, m_n, map, nil},
+    strandal::{
+        affinity::Affinity,
+        ast::Book,
+        check::check,
+        debugger::Breakpoint,
+        diff::NetDiff,
+        divergence::DivergenceConfig,
+        dump::{Dump, Slot},
+        expr::{eval_expr, parse_expr},
+        format::format_book,
+        hvm::{load_hvm, parse_hvm},
+        logging::{JsonLog, RuleFilter},
+        net::NetBuilder,
+        optimize::{optimize, OptimizeConfig},
+        parser::{parse, parse_ast, parse_from_entry, try_parse_ast, STDLIB},
+        plugin,
+        rules::{AgentNet, RuleTable},
+        runtime::{Runtime, RuntimeConfig},
+        soak::{soak, SoakConfig},
+        stats::{Concurrency, PhaseTimes, StatsDiff},
+        store::{Contention, Fragmentation, Index, Ptr, Store},
+        taint,
+    },
 };
 
+#[derive(Parser)]
+#[command(name = "strandal", about = "An interaction combinators engine")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)] // parsed once, the size of `Bench` does not matter
+enum Command {
+    /// Step through the evaluation of a net file in a terminal UI
+    Debug {
+        file: PathBuf,
+        /// Load only this definition and the ones it references
+        #[arg(long, value_name = "NAME")]
+        entry: Option<String>,
+        /// Pause when a cell built by this definition interacts
+        #[arg(long = "break-def", value_name = "NAME")]
+        break_defs: Vec<String>,
+        /// Pause when the cell at this store index interacts
+        #[arg(long = "break-cell", value_name = "INDEX")]
+        break_cells: Vec<Index>,
+    },
+    /// Reduce the nets of a rule file, with user-defined agents and rules,
+    /// written in Lafont's notation if the file ends in `.inet`
+    Rules {
+        file: PathBuf,
+        /// Print specialized code for the rules instead, importing the rule
+        /// machine from this module path
+        #[arg(long, value_name = "PATH")]
+        codegen: Option<String>,
+        /// Load agents and rewrites from this shared library first
+        #[arg(long = "plugin", value_name = "PATH")]
+        plugins: Vec<PathBuf>,
+    },
+    /// Check a net file, or an HVM2 book if it ends in `.hvm`, without
+    /// evaluating it, exiting with an error if it has any problem
+    Check { file: PathBuf },
+    /// Reprint a net file in the canonical layout
+    Fmt {
+        file: PathBuf,
+        /// Rewrite the file in place instead of printing it
+        #[arg(long, conflicts_with = "check")]
+        write: bool,
+        /// Only exit with an error if the file is not formatted
+        #[arg(long)]
+        check: bool,
+    },
+    /// Evaluate a net file, or an HVM2 book if it ends in `.hvm`, and report
+    /// its interactions per second, counted as HVM does (annihilations and
+    /// commutations only)
+    Bench {
+        file: PathBuf,
+        /// Load only this definition and the ones it references
+        #[arg(long, value_name = "NAME")]
+        entry: Option<String>,
+        /// Evaluate the file this many times, reporting the fastest run
+        #[arg(long, default_value_t = 1)]
+        runs: u32,
+        /// Write the net left by the last run to this file, for `inspect`
+        #[arg(long, value_name = "PATH")]
+        dump: Option<PathBuf>,
+        /// Write the net as loaded, before the last run, to this file
+        #[arg(long, value_name = "PATH")]
+        dump_before: Option<PathBuf>,
+        /// Also report the rewrites of each definition
+        #[arg(long)]
+        by_def: bool,
+        /// Print every rewrite that the value of this head flows through
+        #[arg(long = "trace-head", value_name = "INDEX")]
+        trace_heads: Vec<usize>,
+        /// Also write the traced rewrites of the last run to this file, as
+        /// JSON for tools that play them back
+        #[arg(long, value_name = "PATH", requires = "trace_heads")]
+        trace_json: Option<PathBuf>,
+        /// Run one task at a time in an order drawn from this seed, to replay
+        /// an interleaving
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Split the store into this many shards, each allocated from by its
+        /// own threads
+        #[arg(long, default_value_t = 1)]
+        shards: usize,
+        /// Check every access to the store, panicking on the first corrupt
+        /// slot, at the cost of throughput
+        #[arg(long)]
+        checked_store: bool,
+        /// Evaluate on a pool of this many threads, kept across the runs,
+        /// instead of one thread per core
+        #[arg(long, value_name = "N")]
+        threads: Option<usize>,
+        /// Run tasks inline instead of spawning them while fewer than K per
+        /// thread are pending, reporting how many were
+        #[arg(long, value_name = "K")]
+        sequential_below: Option<usize>,
+        /// Spawn at most N tasks at once, deferring the others to the tasks
+        /// already spawned, and report how many were
+        #[arg(long, value_name = "N")]
+        max_tasks: Option<usize>,
+        /// Pin the threads to cores, one thread per core unless `--threads`
+        /// says otherwise
+        #[arg(long)]
+        pin: bool,
+        /// Leave the first core to the main thread when pinning
+        #[arg(long, requires = "pin")]
+        reserve_core: bool,
+        /// Write when each task of the last run was spawned, started and
+        /// ended to this file, in the Chrome trace format
+        #[arg(long, value_name = "PATH")]
+        timeline: Option<PathBuf>,
+        /// Also report the time spent spawning the initial tasks, reducing and
+        /// ending the tasks
+        #[arg(long)]
+        phases: bool,
+        /// Also report how many tasks were queued and in flight at once, and
+        /// the contention the allocations and frees ran into
+        #[arg(long)]
+        concurrency: bool,
+        /// Log these rewrite rules at debug level, and nothing else, as in
+        /// `comm_app_dup,bind`
+        #[arg(long, value_name = "RULES")]
+        trace_rules: Option<RuleFilter>,
+        /// Log only every Nth rewrite of each rule at debug level
+        #[arg(long, value_name = "N")]
+        log_every: Option<usize>,
+        /// Also write every rewrite to this file, as a JSON object per line
+        #[arg(long, value_name = "PATH")]
+        log_json: Option<PathBuf>,
+        /// Stop a run once the net grows far past its loaded size or a
+        /// single dup label drives its interactions
+        #[arg(long)]
+        stop_divergent: bool,
+        /// Reduce the small referenced definitions once and inline them
+        /// before the runs, printing what was inlined
+        #[arg(long)]
+        inline: bool,
+    },
+    /// Evaluate a net file, or an HVM2 book if it ends in `.hvm`, and print
+    /// its normal form and the rewrites of each rule
+    Run {
+        file: PathBuf,
+        /// Load only this definition and the ones it references
+        #[arg(long, value_name = "NAME")]
+        entry: Option<String>,
+        /// Evaluate the file again whenever it changes, printing how the
+        /// rewrites of each rule changed since the last eval
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Evaluate an expression, a λ-term or equations in net syntax, with the
+    /// definitions of the stdlib and of a book, and print its normal form
+    Eval {
+        /// The expression, as in `(λx.x) λy.y` or `(x x) ~ [(y y) r]`
+        #[arg(long, short, value_name = "EXPR")]
+        expr: String,
+        /// Also reference the definitions of this net file, or HVM2 book if
+        /// it ends in `.hvm`, as in `@name`
+        #[arg(long, value_name = "PATH")]
+        book: Option<PathBuf>,
+    },
+    /// Load and evaluate a net file, or an HVM2 book if it ends in `.hvm`,
+    /// over and over in the same store, failing on the first iteration that
+    /// leaves more slots live than the first one did
+    Soak {
+        file: PathBuf,
+        /// Load only this definition and the ones it references
+        #[arg(long, value_name = "NAME")]
+        entry: Option<String>,
+        /// Stop after this many seconds
+        #[arg(long, value_name = "SECS", default_value_t = 10.0)]
+        duration: f64,
+        /// Stop once an eval leaves more than this many live slots
+        #[arg(long, value_name = "N")]
+        max_live: Option<Index>,
+        /// Stop after this many iterations
+        #[arg(long, value_name = "N")]
+        iterations: Option<usize>,
+        /// Check every access to the store, panicking on the first corrupt
+        /// slot, at the cost of throughput
+        #[arg(long)]
+        checked_store: bool,
+    },
+    /// Run a script building and evaluating nets
+    #[cfg(feature = "scripting")]
+    Script {
+        file: PathBuf,
+        /// Define a constant for the script, as a number when it parses as one
+        #[arg(long = "set", value_name = "NAME=VALUE")]
+        vars: Vec<String>,
+    },
+    /// Summarize a net dump, or show some of its slots and what points to them
+    Inspect {
+        dump: PathBuf,
+        /// Show the slot at this store index
+        #[arg(long = "slot", value_name = "INDEX")]
+        slots: Vec<Index>,
+    },
+    /// Draw the slots added, removed and changed between two net dumps as a
+    /// DOT graph
+    Diff {
+        before: PathBuf,
+        after: PathBuf,
+        /// Leave out the unchanged slots that are not next to a changed one
+        #[arg(long)]
+        changed_only: bool,
+        /// Write the graph to this file instead of the standard output
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
 fn main() {
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Debug {
+            file,
+            entry,
+            break_defs,
+            break_cells,
+        }) => {
+            let breakpoints = break_defs
+                .into_iter()
+                .map(Breakpoint::Definition)
+                .chain(
+                    break_cells
+                        .into_iter()
+                        .map(|idx| Breakpoint::Cell(Ptr::new(idx))),
+                )
+                .collect();
+            if let Err(err) = tui::run(&file, entry.as_deref(), breakpoints) {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Rules {
+            file,
+            codegen,
+            plugins,
+        }) => {
+            if let Err(err) = run_rules(&file, codegen.as_deref(), &plugins) {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Check { file }) => match run_check(&file) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Fmt { file, write, check }) => match run_fmt(&file, write, check) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Bench {
+            file,
+            entry,
+            runs,
+            dump,
+            dump_before,
+            by_def,
+            trace_heads,
+            trace_json,
+            seed,
+            shards,
+            checked_store,
+            threads,
+            sequential_below,
+            max_tasks,
+            pin,
+            reserve_core,
+            timeline,
+            phases,
+            concurrency,
+            trace_rules,
+            log_every,
+            log_json,
+            stop_divergent,
+            inline,
+        }) => {
+            let mut config = match by_def {
+                true => RuntimeConfig::default().attribute_definitions(),
+                false => RuntimeConfig::default(),
+            };
+            if let Some(seed) = seed {
+                config = config.seeded(seed);
+            }
+            if let Some(threads) = threads {
+                config = config.threads(threads);
+            }
+            if let Some(tasks_per_thread) = sequential_below {
+                config = config.sequential_below(tasks_per_thread);
+            }
+            if let Some(max_tasks) = max_tasks {
+                config = config.max_tasks(max_tasks);
+            }
+            if pin {
+                config = config.affinity(match reserve_core {
+                    true => Affinity::ReserveFirst,
+                    false => Affinity::Pinned,
+                });
+            }
+            if timeline.is_some() {
+                config = config.record_timeline();
+            }
+            if phases {
+                config = config.time_phases();
+            }
+            if concurrency {
+                config = config.track_concurrency();
+            }
+            if trace_rules.is_some() || log_every.is_some() {
+                tracing_subscriber::fmt()
+                    .with_max_level(tracing::Level::DEBUG)
+                    .init();
+                config.trace_rules = trace_rules;
+            }
+            if let Some(every) = log_every {
+                config = config.sample_logs(every);
+            }
+            let json_log = match log_json.as_deref().map(File::create).transpose() {
+                Ok(file) => file.map(|file| Arc::new(JsonLog::new(BufWriter::new(file)))),
+                Err(err) => {
+                    eprintln!("strandal: {}", err);
+                    std::process::exit(1);
+                }
+            };
+            if let Some(log) = &json_log {
+                config = config.json_log(log.clone());
+            }
+            if stop_divergent {
+                config = config.detect_divergence(DivergenceConfig {
+                    abort: true,
+                    ..Default::default()
+                });
+            }
+            let bench = Bench {
+                entry: entry.as_deref(),
+                runs,
+                dump: dump.as_deref(),
+                dump_before: dump_before.as_deref(),
+                trace_heads: &trace_heads,
+                trace_json: trace_json.as_deref(),
+                shards,
+                checked_store,
+                timeline: timeline.as_deref(),
+                inline,
+                config,
+            };
+            let result = run_bench(&file, bench);
+            let flushed = json_log.map_or(Ok(()), |log| log.flush());
+            if let Err(err) = result.and(flushed.map_err(Into::into)) {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Run { file, entry, watch }) => {
+            if let Err(err) = run_file(&file, entry.as_deref(), watch) {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Eval { expr, book }) => {
+            if let Err(err) = run_eval(&expr, book.as_deref()) {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Soak {
+            file,
+            entry,
+            duration,
+            max_live,
+            iterations,
+            checked_store,
+        }) => {
+            let config = SoakConfig {
+                duration: Duration::from_secs_f64(duration),
+                max_live,
+                iterations,
+            };
+            if let Err(err) = run_soak(&file, entry.as_deref(), &config, checked_store) {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        }
+        #[cfg(feature = "scripting")]
+        Some(Command::Script { file, vars }) => {
+            if let Err(err) = run_script(&file, &vars) {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Inspect { dump, slots }) => {
+            if let Err(err) = run_inspect(&dump, &slots) {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Diff {
+            before,
+            after,
+            changed_only,
+            output,
+        }) => {
+            if let Err(err) = run_diff(&before, &after, changed_only, output.as_deref()) {
+                eprintln!("strandal: {}", err);
+                std::process::exit(1);
+            }
+        }
+        None => demo(),
+    }
+}
+
+fn run_rules(
+    file: &Path,
+    codegen: Option<&str>,
+    plugins: &[PathBuf],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let src = std::fs::read_to_string(file)?;
+    let mut table = RuleTable::default();
+    for plugin in plugins {
+        plugin::load(plugin, &mut table)?;
+    }
+    let programs = match file.extension().is_some_and(|ext| ext == "inet") {
+        true => table.load_lafont(&src)?,
+        false => table.load(&src)?,
+    };
+    if let Some(path) = codegen {
+        print!("{}", table.codegen(path));
+        return Ok(());
+    }
+    for program in &programs {
+        let mut net = AgentNet::new(&table, program);
+        let interactions = net.reduce()?;
+        let heads: Vec<_> = net.read().iter().map(|head| head.to_string()).collect();
+        println!("({}) in {} interactions", heads.join(", "), interactions);
+    }
+    Ok(())
+}
+
+// print the problems found in `file`, returning whether there were none
+fn run_check(file: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let src = std::fs::read_to_string(file)?;
+    let book = match file.extension().is_some_and(|ext| ext == "hvm") {
+        true => parse_hvm(&src)?,
+        false => match try_parse_ast(&src) {
+            Ok(book) => book,
+            Err(errors) => {
+                for err in errors {
+                    eprintln!("{}:{}", file.display(), err);
+                }
+                return Ok(false);
+            }
+        },
+    };
+    let diagnostics = check(&book);
+    for diagnostic in &diagnostics {
+        eprintln!("{}: {}", file.display(), diagnostic);
+    }
+    Ok(diagnostics.is_empty())
+}
+
+// reprint `file`, returning whether it was formatted when only checking
+fn run_fmt(file: &Path, write: bool, check: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let src = std::fs::read_to_string(file)?;
+    let book = try_parse_ast(&src).map_err(|errors| {
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|err| format!("{}:{}", file.display(), err))
+            .collect();
+        errors.join("\n")
+    })?;
+    let formatted = format_book(&book);
+    if check {
+        if formatted != src {
+            eprintln!("{}: not formatted", file.display());
+        }
+        return Ok(formatted == src);
+    }
+    match write {
+        true => std::fs::write(file, formatted)?,
+        false => print!("{}", formatted),
+    }
+    Ok(true)
+}
+
+struct Bench<'a> {
+    entry: Option<&'a str>,
+    runs: u32,
+    dump: Option<&'a Path>,
+    dump_before: Option<&'a Path>,
+    trace_heads: &'a [usize],
+    trace_json: Option<&'a Path>,
+    shards: usize,
+    checked_store: bool,
+    timeline: Option<&'a Path>,
+    inline: bool,
+    config: RuntimeConfig,
+}
+
+fn run_bench(file: &Path, bench: Bench) -> Result<(), Box<dyn std::error::Error>> {
+    let Bench {
+        entry,
+        runs,
+        dump,
+        dump_before,
+        trace_heads,
+        trace_json,
+        shards,
+        checked_store,
+        timeline,
+        inline,
+        config,
+    } = bench;
+    let src = std::fs::read_to_string(file)?;
+    let hvm = file.extension().is_some_and(|ext| ext == "hvm");
+    // the book is optimized once, and loaded by every run
+    let optimized = match inline {
+        true if hvm => return Err("--inline only applies to net files".into()),
+        true => {
+            let mut book =
+                try_parse_ast(&src).map_err(|_| format!("could not parse {}", file.display()))?;
+            if let Some(entry) = entry {
+                book.prune(entry)?;
+            }
+            let (book, report) = optimize(&book, &OptimizeConfig::default())?;
+            if !report.inlined.is_empty() || !report.reduced.is_empty() {
+                println!("{}", report);
+            }
+            Some(book)
+        }
+        false => None,
+    };
+    let mut trace_events = Vec::new();
+    let time_phases = config.time_phases;
+    let sequential_below = config.sequential_below.is_some();
+    let max_tasks = config.max_tasks.is_some();
+    #[allow(clippy::type_complexity)]
+    let mut best: Option<(
+        usize,
+        Duration,
+        Fragmentation,
+        Option<String>,
+        PhaseTimes,
+        Option<(Concurrency, Contention)>,
+    )> = None;
+    // the runtime and the store are reused by every run, each one counted
+    // on its own
+    let mut runtime = Runtime::with_config(config);
+    let store = Store::with_shards(Store::DEFAULT_CAPACITY, shards);
+    let mut net = Net::with_store(match checked_store {
+        true => store.checked(),
+        false => store,
+    });
+    for _ in 0..runs.max(1) {
+        net.clear();
+        let loaded = match (&optimized, entry) {
+            (Some(book), _) => net.load(book).map(|_| true)?,
+            // HVM2 books load `main` unless told otherwise
+            _ if hvm => load_hvm(&src, &mut net, entry).map(|_| true)?,
+            (None, Some(entry)) => parse_from_entry(&src, &mut net, entry).is_some(),
+            (None, None) => parse(&src, &mut net),
+        };
+        if !loaded {
+            return Err(format!("could not parse {}", file.display()).into());
+        }
+        trace_heads.iter().for_each(|idx| net.trace_head(*idx));
+        if let Some(path) = dump_before {
+            net.dump(path)?;
+        }
+        let evaluated = runtime.eval_in(&mut net);
+        if let Some(path) = dump {
+            net.dump(path)?;
+        }
+        evaluated?;
+        if let (Some(path), Some(timeline)) = (timeline, runtime.timeline()) {
+            timeline.write_chrome_trace(&mut BufWriter::new(File::create(path)?))?;
+        }
+        trace_events = runtime.trace_events();
+        let run = (
+            runtime.stats.interactions(),
+            runtime.elapsed(),
+            net.store().fragmentation(),
+            runtime.definition_stats().map(|stats| stats.to_string()),
+            *runtime.phases(),
+            runtime
+                .concurrency()
+                .map(|concurrency| (concurrency, runtime.stats.contention())),
+        );
+        if best
+            .as_ref()
+            .is_none_or(|(_, elapsed, _, _, _, _)| run.1 < *elapsed)
+        {
+            best = Some(run);
+        }
+    }
+    let (interactions, elapsed, fragmentation, definitions, phases, concurrency) = best.unwrap();
+    println!("interactions: {}", interactions);
+    println!("time: {:.3} s", elapsed.as_secs_f64());
+    println!(
+        "MIPS: {:.2}",
+        interactions as f64 / elapsed.as_secs_f64().max(f64::EPSILON) / 1e6
+    );
+    println!("{}", fragmentation);
+    // of the last run
+    if sequential_below {
+        println!("inlined tasks: {}", runtime.inlined_tasks());
+    }
+    if max_tasks {
+        println!("deferred tasks: {}", runtime.deferred_tasks());
+    }
+    if time_phases {
+        println!("{}", phases);
+    }
+    if let Some((concurrency, contention)) = concurrency {
+        println!("{}", concurrency);
+        println!("{}", contention);
+    }
+    if let Some(definitions) = definitions {
+        println!("{}", definitions);
+    }
+    if let Some(path) = trace_json {
+        taint::write_json(&trace_events, &mut BufWriter::new(File::create(path)?))?;
+    }
+    for event in trace_events {
+        println!("TRACE   | {}", event);
+    }
+    Ok(())
+}
+
+// the book of `file`, with only `entry` and the definitions it references
+fn load_book(file: &Path, entry: Option<&str>) -> Result<Book, Box<dyn std::error::Error>> {
+    let src = std::fs::read_to_string(file)?;
+    let mut book = match file.extension().is_some_and(|ext| ext == "hvm") {
+        // HVM2 books load `main` unless told otherwise
+        true => {
+            let mut book = parse_hvm(&src)?;
+            book.prune(entry.unwrap_or("main"))?;
+            book
+        }
+        false => try_parse_ast(&src).map_err(|_| format!("could not parse {}", file.display()))?,
+    };
+    if let Some(entry) = entry {
+        book.prune(entry)?;
+    }
+    Ok(book)
+}
+
+// evaluate `file` and print its normal form, again on every change of the
+// file when watching it
+fn run_file(
+    file: &Path,
+    entry: Option<&str>,
+    watch: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut runtime = Runtime::new();
+    let mut last_rules = None;
+    let mut last_modified = None;
+    let mut last_error = None;
+    loop {
+        match std::fs::metadata(file).and_then(|metadata| metadata.modified()) {
+            Ok(modified) if last_modified != Some(modified) => {
+                last_modified = Some(modified);
+                last_error = None;
+                let mut net = Net::new();
+                let evaluated = load_book(file, entry)
+                    .and_then(|book| Ok(net.load(&book)?))
+                    .and_then(|()| Ok(runtime.eval_in(&mut net)?));
+                match evaluated {
+                    Ok(()) => {
+                        match net.normal_form() {
+                            Ok(heads) => heads.iter().for_each(|head| println!("{}", head)),
+                            Err(err) => println!("{}", err),
+                        }
+                        let rules = runtime.stats.rules();
+                        match last_rules.replace(rules) {
+                            Some(last_rules) => {
+                                println!("{}", StatsDiff::new(&last_rules, &rules))
+                            }
+                            None => println!("{}", runtime.stats),
+                        }
+                    }
+                    Err(err) if watch => eprintln!("strandal: {}", err),
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(_) => last_error = None,
+            // an editor may remove the file for a moment as it saves it: try
+            // again on the next tick, reporting the error once
+            Err(err) if watch => {
+                if last_error.as_ref() != Some(&err.kind()) {
+                    eprintln!("strandal: {}: {}", file.display(), err);
+                }
+                last_error = Some(err.kind());
+            }
+            Err(err) => return Err(err.into()),
+        }
+        if !watch {
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
+fn run_eval(expr: &str, book: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let expr = parse_expr(expr)?;
+    let mut stdlib = parse_ast(STDLIB).expect("the stdlib parses");
+    if let Some(book) = book {
+        stdlib.defs.extend(load_book(book, None)?.defs);
+    }
+    for head in eval_expr(&expr, &stdlib)? {
+        println!("{}", head);
+    }
+    Ok(())
+}
+
+fn run_soak(
+    file: &Path,
+    entry: Option<&str>,
+    config: &SoakConfig,
+    checked_store: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let book = load_book(file, entry)?;
+    let mut net = Net::with_store(match checked_store {
+        true => Store::new_checked(),
+        false => Store::new(),
+    });
+    let report = soak(&mut net, &mut Runtime::new(), config, |net| net.load(&book))?;
+    println!("{}", report);
+    Ok(())
+}
+
+#[cfg(feature = "scripting")]
+fn run_script(file: &Path, vars: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let src = std::fs::read_to_string(file)?;
+    let vars = vars
+        .iter()
+        .map(|var| {
+            let (name, value) = var
+                .split_once('=')
+                .ok_or_else(|| format!("expected NAME=VALUE, found `{}`", var))?;
+            let value = match value.parse::<i64>() {
+                Ok(number) => rhai::Dynamic::from(number),
+                Err(_) => rhai::Dynamic::from(value.to_string()),
+            };
+            Ok((name.to_string(), value))
+        })
+        .collect::<Result<_, String>>()?;
+    let value = strandal::script::run(&src, vars)?;
+    if !value.is_unit() {
+        println!("{}", value);
+    }
+    Ok(())
+}
+
+fn run_inspect(path: &Path, slots: &[Index]) -> Result<(), Box<dyn std::error::Error>> {
+    let dump = Dump::read(path)?;
+    if slots.is_empty() {
+        let mut kinds: BTreeMap<&str, usize> = BTreeMap::new();
+        for slot in dump.slots.values() {
+            let kind = match slot {
+                Slot::Var(None) => "unbound var",
+                Slot::Var(Some(_)) => "bound var",
+                Slot::Cell(_) => slot.kind(),
+            };
+            *kinds.entry(kind).or_default() += 1;
+        }
+        println!(
+            "{} slots, {} heads, {} pending equations",
+            dump.slots.len(),
+            dump.heads.len(),
+            dump.eqns.len()
+        );
+        for (kind, count) in kinds {
+            println!("{:>8} {}", count, kind);
+        }
+        return Ok(());
+    }
+    for idx in slots {
+        let ptr = Ptr::new(*idx);
+        match dump.slots.get(idx) {
+            Some(slot) => print!("{} {}", ptr, slot),
+            None => print!("{} free", ptr),
+        }
+        match dump.definition(ptr) {
+            Some(def) => println!(" (in {})", def),
+            None => println!(),
+        }
+        for referrer in dump.referrers(ptr) {
+            println!("  <- {}", referrer);
+        }
+    }
+    Ok(())
+}
+
+fn run_diff(
+    before: &Path,
+    after: &Path,
+    changed_only: bool,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let diff = NetDiff::new(&Dump::read(before)?, &Dump::read(after)?);
+    match output {
+        Some(path) => {
+            let mut out = BufWriter::new(File::create(path)?);
+            diff.write_dot(&mut out, changed_only)?;
+            out.flush()?;
+        }
+        None => diff.write_dot(&mut std::io::stdout().lock(), changed_only)?,
+    }
+    match diff.is_empty() {
+        true => eprintln!("the dumps hold the same slots"),
+        false => eprintln!("{}", diff),
+    }
+    Ok(())
+}
+
+fn demo() {
     tracing_subscriber::fmt::init();
 
     let mut net = Net::with_capacity(1 << 30);
@@ -19,9 +876,10 @@ fn main() {
     //
     let id = id(&mut net);
     let dup = dup(&mut net);
-    let m2 = m_2(&mut net);
+    let (m2, leaves) = m_n(&mut net, 2);
     net.eqn(id, dup);
-    net.head(m2.0);
+    net.head(m2);
+    leaves.into_iter().for_each(|leaf| net.head(leaf));
 
     //
     let r = net.var();
@@ -33,10 +891,30 @@ fn main() {
     net.head(r.1);
     net.eqn(i1, app);
 
-    // info!("Initial Net: {}", net);
+    // map the identity over a list of identities, then fold it back into
+    // a list: every cons shares its step function through a duplicator
+    let items = (0..4).map(|_| lambda::id(&mut net).into()).collect();
+    let items = list(&mut net, items);
+    let f = lambda::id(&mut net);
+    let mapped = map(&mut net, f, items);
+    let (h, acc) = (net.var(), net.var());
+    let consed = cons(&mut net, h.1, acc.1);
+    let step_inner = net.lam(acc.0, consed);
+    let step = net.lam(h.0, step_inner);
+    let z = nil(&mut net);
+    let rebuilt = fold(&mut net, mapped, step, z);
+    net.head(rebuilt);
 
-    let mut runtime = Runtime::new();
-    runtime.eval(&mut net);
-    // info!("Final Net: {}", net);
+    info!("Initial Net: {}", net);
+
+    let mut runtime = Runtime::with_config(RuntimeConfig::default().report_fragmentation());
+    if let Err(err) = runtime.eval(&mut net) {
+        error!("{}", err);
+    }
+    info!("Final Net: {}", net);
+    match net.normal_form() {
+        Ok(heads) => heads.iter().for_each(|head| info!("Normal form: {}", head)),
+        Err(err) => error!("{}", err),
+    }
     info!("{}", runtime.stats);
 }
@@ -1,8 +1,55 @@
-mod display;
+pub mod affinity;
+pub mod ast;
+pub mod canonical;
+pub mod check;
+pub mod circles;
+pub mod codec;
+pub mod compiled;
+pub mod debugger;
+pub mod diff;
+pub mod divergence;
+pub mod display;
+pub mod dump;
+pub mod expr;
+pub mod format;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+#[cfg(test)]
+mod golden;
+pub mod graphml;
+pub mod hvm;
+pub mod intern;
+pub mod json;
+pub mod leaks;
+pub mod logging;
+pub mod lsp;
+pub mod memo;
+#[cfg(test)]
+mod model;
 pub mod net;
+pub mod num;
+pub mod optimize;
+pub mod plugin;
+pub mod polarity;
+pub mod programs;
+pub mod readback;
+pub mod refs;
+pub mod rules;
 pub mod runtime;
+mod schedule;
+pub mod scope;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod session;
+pub mod soak;
 pub mod stats;
 pub mod store;
+#[cfg(feature = "testing")]
+pub mod strategies;
+pub mod sup;
+pub mod taint;
+pub mod template;
 pub mod term;
+pub mod timeline;
 pub mod var;
 pub mod parser;
\ No newline at end of file
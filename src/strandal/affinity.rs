@@ -0,0 +1,89 @@
+//! Pinning the threads of a runtime's pool to cores, see
+//! [`RuntimeConfig::affinity`](super::runtime::RuntimeConfig::affinity).
+//! Unpinned threads migrate between cores as the OS sees fit, which makes
+//! benchmarks on big machines noisy and hard to reproduce.
+//!
+//! The cores are the ones the process may run on, in order, and only Linux
+//! pins threads: elsewhere the threads are left unpinned with a warning.
+
+use tracing::warn;
+
+/// How the threads of a runtime's pool are pinned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affinity {
+    /// Pin the ith thread to the ith core.
+    Pinned,
+    /// Leave the first core to the thread calling eval and to IO, and pin
+    /// the ith thread to the core after the ith one.
+    ReserveFirst,
+}
+
+impl Affinity {
+    /// The core of each thread of a pool of `threads` threads, or of one
+    /// thread per core left when not given. The cores are reused in turn
+    /// when there are more threads than cores.
+    pub fn cores(&self, threads: Option<usize>) -> Vec<usize> {
+        let mut cores = allowed_cores();
+        if *self == Affinity::ReserveFirst && cores.len() > 1 {
+            cores.remove(0);
+        }
+        let threads = threads.unwrap_or(cores.len());
+        cores.iter().copied().cycle().take(threads).collect()
+    }
+}
+
+/// The cores the calling thread may run on.
+#[cfg(target_os = "linux")]
+pub fn allowed_cores() -> Vec<usize> {
+    // SAFETY: the set is zeroed, then only read and written through libc
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return vec![0];
+        }
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|core| libc::CPU_ISSET(*core, &set))
+            .collect()
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn allowed_cores() -> Vec<usize> {
+    (0..std::thread::available_parallelism().map_or(1, |threads| threads.get())).collect()
+}
+
+/// Pin the calling thread to `core`.
+#[cfg(target_os = "linux")]
+pub fn pin(core: usize) {
+    // SAFETY: the set is zeroed, then only read and written through libc
+    let pinned = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    };
+    if !pinned {
+        warn!("Could not pin a thread to core {}", core);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin(core: usize) {
+    warn!("Threads are only pinned on Linux, not to core {}", core);
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::{allowed_cores, Affinity};
+
+    #[test]
+    fn test_cores() {
+        let allowed = allowed_cores();
+        assert!(!allowed.is_empty());
+        assert_eq!(Affinity::Pinned.cores(None), allowed);
+        let cores = Affinity::Pinned.cores(Some(allowed.len() + 1));
+        assert_eq!(cores.last(), Some(&allowed[0]));
+        if allowed.len() > 1 {
+            assert_eq!(Affinity::ReserveFirst.cores(None), allowed[1..]);
+        }
+    }
+}
@@ -0,0 +1,1092 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+};
+
+use serde_json::{json, Value};
+
+use super::{
+    display::CellDisplay,
+    net::NetBuilder,
+    num::Op,
+    readback::NotNormal,
+    template::Template,
+    term::{self, Cell, Label, TermPtr},
+    var::VarUse,
+};
+
+/// A term tree that owns its children, independent of any store.
+///
+/// Vars are wires: every name must occur exactly twice in an [`Net`], once
+/// at each end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Era,
+    Var(String),
+    Lam(Box<Term>, Box<Term>),
+    App(Box<Term>, Box<Term>),
+    Dup(Box<Term>, Box<Term>, Option<Label>),
+    Num(u32),
+    /// An operator, flipped when the bool is set, see [`Cell::Op`].
+    Op(Op, bool, Box<Term>, Box<Term>),
+    Swi(Box<Term>, Box<Term>),
+    /// A reference to a definition by name, see [`super::refs`].
+    Ref(String),
+    /// A call to a definition by name, with the terms wired to all its head
+    /// ports but the last one, which the call stands for. See
+    /// [`Book::desugar`].
+    Call(String, Vec<Term>),
+    /// An operator written infix, as in `a + b`, standing for its result.
+    /// See [`Book::desugar`].
+    Infix(Op, Box<Term>, Box<Term>),
+}
+
+impl Term {
+    pub fn var(name: &str) -> Self {
+        Term::Var(name.to_string())
+    }
+
+    pub fn lam(binding: Term, body: Term) -> Self {
+        Term::Lam(Box::new(binding), Box::new(body))
+    }
+
+    pub fn app(arg: Term, result: Term) -> Self {
+        Term::App(Box::new(arg), Box::new(result))
+    }
+
+    pub fn dup(left: Term, right: Term) -> Self {
+        Term::Dup(Box::new(left), Box::new(right), None)
+    }
+
+    pub fn op(op: Op, right: Term, result: Term) -> Self {
+        Term::Op(op, false, Box::new(right), Box::new(result))
+    }
+
+    pub fn infix(op: Op, left: Term, right: Term) -> Self {
+        Term::Infix(op, Box::new(left), Box::new(right))
+    }
+
+    pub fn swi(branches: Term, result: Term) -> Self {
+        Term::Swi(Box::new(branches), Box::new(result))
+    }
+
+    pub fn reference(name: &str) -> Self {
+        Term::Ref(name.to_string())
+    }
+
+    fn vars<'a>(&'a self, names: &mut HashSet<&'a str>) {
+        match self {
+            Term::Era | Term::Num(_) | Term::Ref(_) => {}
+            Term::Var(name) => {
+                names.insert(name);
+            }
+            Term::Lam(p0, p1)
+            | Term::App(p0, p1)
+            | Term::Dup(p0, p1, _)
+            | Term::Op(_, _, p0, p1)
+            | Term::Infix(_, p0, p1)
+            | Term::Swi(p0, p1) => {
+                p0.vars(names);
+                p1.vars(names);
+            }
+            Term::Call(_, args) => args.iter().for_each(|arg| arg.vars(names)),
+        }
+    }
+
+    fn count_vars<'a>(&'a self, counts: &mut HashMap<&'a str, usize>) {
+        match self {
+            Term::Era | Term::Num(_) | Term::Ref(_) => {}
+            Term::Var(name) => *counts.entry(name).or_default() += 1,
+            Term::Lam(p0, p1)
+            | Term::App(p0, p1)
+            | Term::Dup(p0, p1, _)
+            | Term::Op(_, _, p0, p1)
+            | Term::Infix(_, p0, p1)
+            | Term::Swi(p0, p1) => {
+                p0.count_vars(counts);
+                p1.count_vars(counts);
+            }
+            Term::Call(_, args) => args.iter().for_each(|arg| arg.count_vars(counts)),
+        }
+    }
+
+    fn refs<'a>(&'a self, names: &mut HashSet<&'a str>) {
+        match self {
+            Term::Era | Term::Num(_) | Term::Var(_) => {}
+            Term::Ref(name) => {
+                names.insert(name);
+            }
+            Term::Lam(p0, p1)
+            | Term::App(p0, p1)
+            | Term::Dup(p0, p1, _)
+            | Term::Op(_, _, p0, p1)
+            | Term::Infix(_, p0, p1)
+            | Term::Swi(p0, p1) => {
+                p0.refs(names);
+                p1.refs(names);
+            }
+            Term::Call(_, args) => args.iter().for_each(|arg| arg.refs(names)),
+        }
+    }
+
+    fn calls<'a>(&'a self, names: &mut HashSet<&'a str>) {
+        match self {
+            Term::Era | Term::Num(_) | Term::Var(_) | Term::Ref(_) => {}
+            Term::Lam(p0, p1)
+            | Term::App(p0, p1)
+            | Term::Dup(p0, p1, _)
+            | Term::Op(_, _, p0, p1)
+            | Term::Infix(_, p0, p1)
+            | Term::Swi(p0, p1) => {
+                p0.calls(names);
+                p1.calls(names);
+            }
+            Term::Call(name, args) => {
+                names.insert(name);
+                args.iter().for_each(|arg| arg.calls(names));
+            }
+        }
+    }
+
+    /// The term as a tree of JSON objects, for tools that read results
+    /// without parsing the textual syntax. Each node has a `kind`, its
+    /// `children` in port order and, when it has one, a `label`: the name
+    /// of a var, reference or call, the value of a number, the symbol of an
+    /// operator, flipped ones marked as in [`CellDisplay::op_symbol`], or
+    /// the label of a duplicator.
+    pub fn to_json(&self) -> Value {
+        let node = |kind: &str, label: Option<Value>, children: Vec<&Term>| {
+            let children: Vec<_> = children.into_iter().map(Term::to_json).collect();
+            match label {
+                Some(label) => json!({ "kind": kind, "label": label, "children": children }),
+                None => json!({ "kind": kind, "children": children }),
+            }
+        };
+        match self {
+            Term::Era => node("era", None, vec![]),
+            Term::Var(name) => node("var", Some(json!(name)), vec![]),
+            Term::Lam(p0, p1) => node("lam", None, vec![p0, p1]),
+            Term::App(p0, p1) => node("app", None, vec![p0, p1]),
+            Term::Dup(p0, p1, lbl) => {
+                let lbl = lbl.map(|lbl| match lbl {
+                    Label::User(idx) => json!(idx),
+                    Label::Fresh(_) => json!(lbl.to_string()),
+                });
+                node("dup", lbl, vec![p0, p1])
+            }
+            Term::Num(value) => node("num", Some(json!(value)), vec![]),
+            Term::Op(op, flipped, p0, p1) => {
+                let symbol = CellDisplay::op_symbol(*op, *flipped);
+                node("op", Some(json!(symbol)), vec![p0, p1])
+            }
+            Term::Swi(p0, p1) => node("swi", None, vec![p0, p1]),
+            Term::Ref(name) => node("ref", Some(json!(name)), vec![]),
+            Term::Call(name, args) => node("call", Some(json!(name)), args.iter().collect()),
+            Term::Infix(op, p0, p1) => node("infix", Some(json!(op.symbol())), vec![p0, p1]),
+        }
+    }
+
+    // a copy of the term with the subterms `replace` gives a replacement for
+    // replaced, the others copied as they are
+    pub(crate) fn replace(&self, replace: &mut impl FnMut(&Term) -> Option<Term>) -> Term {
+        if let Some(term) = replace(self) {
+            return term;
+        }
+        let mut pair = |p0: &Term, p1: &Term| (Box::new(p0.replace(replace)), Box::new(p1.replace(replace)));
+        match self {
+            Term::Era | Term::Var(_) | Term::Num(_) | Term::Ref(_) => self.clone(),
+            Term::Lam(p0, p1) => {
+                let (p0, p1) = pair(p0, p1);
+                Term::Lam(p0, p1)
+            }
+            Term::App(p0, p1) => {
+                let (p0, p1) = pair(p0, p1);
+                Term::App(p0, p1)
+            }
+            Term::Dup(p0, p1, lbl) => {
+                let (p0, p1) = pair(p0, p1);
+                Term::Dup(p0, p1, *lbl)
+            }
+            Term::Op(op, flipped, p0, p1) => {
+                let (p0, p1) = pair(p0, p1);
+                Term::Op(*op, *flipped, p0, p1)
+            }
+            Term::Swi(p0, p1) => {
+                let (p0, p1) = pair(p0, p1);
+                Term::Swi(p0, p1)
+            }
+            Term::Infix(op, p0, p1) => {
+                let (p0, p1) = pair(p0, p1);
+                Term::Infix(*op, p0, p1)
+            }
+            Term::Call(name, args) => Term::Call(
+                name.clone(),
+                args.iter().map(|arg| arg.replace(replace)).collect(),
+            ),
+        }
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Term::Era => write!(f, "{}", CellDisplay::ERA_SYMBOL),
+            Term::Var(name) => write!(f, "{}", name),
+            Term::Lam(p0, p1) => write!(f, "({} {} {})", CellDisplay::LAM_SYMBOL, p0, p1),
+            Term::App(p0, p1) => write!(f, "({} {} {})", CellDisplay::APP_SYMBOL, p0, p1),
+            Term::Dup(p0, p1, None) => write!(f, "({} {} {})", CellDisplay::DUP_SYMBOL, p0, p1),
+            Term::Dup(p0, p1, Some(lbl)) => {
+                write!(f, "({} {} {} {{{}}})", CellDisplay::DUP_SYMBOL, p0, p1, lbl)
+            }
+            Term::Num(value) => write!(f, "{}", value),
+            Term::Op(op, flipped, p0, p1) => {
+                write!(
+                    f,
+                    "({} {} {})",
+                    CellDisplay::op_symbol(*op, *flipped),
+                    p0,
+                    p1
+                )
+            }
+            Term::Swi(p0, p1) => write!(f, "({} {} {})", CellDisplay::SWI_SYMBOL, p0, p1),
+            Term::Ref(name) => write!(f, "{}{}", CellDisplay::REF_SYMBOL, name),
+            Term::Infix(op, p0, p1) => write!(f, "({} {} {})", p0, op, p1),
+            Term::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                for (idx, arg) in args.iter().enumerate() {
+                    match idx {
+                        0 => write!(f, "{}", arg)?,
+                        _ => write!(f, ", {}", arg)?,
+                    }
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// The owned counterpart of [`super::net::Net`]: heads plus the equations
+/// left to reduce.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Net {
+    pub head: Vec<Term>,
+    pub body: Vec<(Term, Term)>,
+}
+
+impl Display for Net {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "head:")?;
+        for head in &self.head {
+            write!(f, " {}", head)?;
+        }
+        write!(f, "\nbody:")?;
+        for (left, right) in &self.body {
+            write!(f, "\n  {} ~ {}", left, right)?;
+        }
+        Ok(())
+    }
+}
+
+impl Net {
+    fn vars(&self) -> HashSet<&str> {
+        let mut names = HashSet::new();
+        for head in &self.head {
+            head.vars(&mut names);
+        }
+        for (left, right) in &self.body {
+            left.vars(&mut names);
+            right.vars(&mut names);
+        }
+        names
+    }
+
+    pub(crate) fn refs(&self) -> HashSet<&str> {
+        let mut names = HashSet::new();
+        for head in &self.head {
+            head.refs(&mut names);
+        }
+        for (left, right) in &self.body {
+            left.refs(&mut names);
+            right.refs(&mut names);
+        }
+        names
+    }
+
+    fn calls(&self) -> HashSet<&str> {
+        let mut names = HashSet::new();
+        for head in &self.head {
+            head.calls(&mut names);
+        }
+        for (left, right) in &self.body {
+            left.calls(&mut names);
+            right.calls(&mut names);
+        }
+        names
+    }
+
+    // whether every var is used exactly twice in the net itself
+    pub(crate) fn is_closed(&self) -> bool {
+        let mut counts = HashMap::new();
+        for head in &self.head {
+            head.count_vars(&mut counts);
+        }
+        for (left, right) in &self.body {
+            left.count_vars(&mut counts);
+            right.count_vars(&mut counts);
+        }
+        counts.values().all(|count| *count == 2)
+    }
+
+    /// A copy of the net with every var used exactly twice, as generated
+    /// nets need: the uses of a name past its second become erasers, and a
+    /// name used once is wired to a new head, in name order.
+    pub fn closed(&self) -> Net {
+        let mut uses: BTreeMap<String, usize> = BTreeMap::new();
+        let mut pair = |term: &Term| match term {
+            Term::Var(name) => {
+                let count = uses.entry(name.clone()).or_default();
+                *count += 1;
+                Some(match *count {
+                    1 | 2 => term.clone(),
+                    _ => Term::Era,
+                })
+            }
+            _ => None,
+        };
+        let mut head: Vec<_> = self.head.iter().map(|term| term.replace(&mut pair)).collect();
+        let body = self
+            .body
+            .iter()
+            .map(|(left, right)| (left.replace(&mut pair), right.replace(&mut pair)))
+            .collect();
+        let open = uses.into_iter().filter(|(_, count)| *count == 1);
+        head.extend(open.map(|(name, _)| Term::Var(name)));
+        Net { head, body }
+    }
+}
+
+/// A named definition of a book.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Def {
+    pub name: String,
+    pub net: Net,
+    /// The name of each head port declared as `out name`, by head position.
+    pub head_names: Vec<Option<String>>,
+}
+
+/// The definitions of a source file, in source order.
+///
+/// Var names are shared across the whole book: a var used in two definitions
+/// is the wire connecting them. A definition referenced as `@name` is not
+/// wired in, but expanded where its references meet a cell, see
+/// [`super::refs`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Book {
+    pub defs: Vec<Def>,
+}
+
+impl Book {
+    /// Keep only `entry` and the definitions it transitively references,
+    /// through shared vars or by name, returning the names of the ones
+    /// removed.
+    pub fn prune(&mut self, entry: &str) -> Result<Vec<String>, AstError> {
+        let Some(entry) = self.defs.iter().position(|def| def.name == entry) else {
+            return Err(AstError::UnknownDef(entry.to_string()));
+        };
+        let links = Links::new(self);
+        let reachable = links.connected(entry, true);
+        drop(links);
+
+        let removed = self
+            .defs
+            .iter()
+            .zip(&reachable)
+            .filter(|(_, reachable)| !**reachable)
+            .map(|(def, _)| def.name.clone())
+            .collect();
+        let mut reachable = reachable.into_iter();
+        self.defs.retain(|_| reachable.next().unwrap());
+        Ok(removed)
+    }
+
+    /// Split the definitions into the groups connected through shared vars
+    /// or references, as indices in source order.
+    pub fn components(&self) -> Vec<Vec<usize>> {
+        let links = Links::new(self);
+        let mut assigned = vec![false; self.defs.len()];
+        let mut components = Vec::new();
+        for entry in 0..self.defs.len() {
+            if assigned[entry] {
+                continue;
+            }
+            let component: Vec<_> = links
+                .connected(entry, false)
+                .into_iter()
+                .enumerate()
+                .filter_map(|(idx, reachable)| reachable.then_some(idx))
+                .collect();
+            component.iter().for_each(|idx| assigned[*idx] = true);
+            components.push(component);
+        }
+        components
+    }
+
+    /// The definitions referenced as `@name` anywhere in the book, in
+    /// source order.
+    pub fn referenced(&self) -> Vec<&Def> {
+        let names: HashSet<&str> = self.defs.iter().flat_map(|def| def.net.refs()).collect();
+        self.defs
+            .iter()
+            .filter(|def| names.contains(def.name.as_str()))
+            .collect()
+    }
+
+    /// Inline every call `name(args)`: each one is a copy of the definition
+    /// `name` with fresh var names, its head ports but the last one wired to
+    /// the arguments in order, and the call standing for the last one. Infix
+    /// operators become operator cells taking their left operand, with the
+    /// result wired out through a fresh var.
+    ///
+    /// A called definition must be closed and cannot call itself, even
+    /// through others. The definitions only called are left out, since they
+    /// are only there to be copied.
+    pub fn desugar(&self) -> Result<Book, AstError> {
+        let called: HashSet<&str> = self.defs.iter().flat_map(|def| def.net.calls()).collect();
+        let referenced: HashSet<&str> = self
+            .referenced()
+            .iter()
+            .map(|def| def.name.as_str())
+            .collect();
+        let mut expander = CallExpander {
+            book: self,
+            stack: Vec::new(),
+            instances: 0,
+        };
+        let mut defs = Vec::new();
+        for def in &self.defs {
+            let name = def.name.as_str();
+            if called.contains(name) && !referenced.contains(name) {
+                continue;
+            }
+            expander.stack.push(name);
+            let net = expander.net(&def.net, "")?;
+            expander.stack.pop();
+            defs.push(Def {
+                name: def.name.clone(),
+                net,
+                head_names: def.head_names.clone(),
+            });
+        }
+        Ok(Book { defs })
+    }
+}
+
+struct CallExpander<'a> {
+    book: &'a Book,
+    // the definitions being expanded, to catch recursive calls
+    stack: Vec<&'a str>,
+    // the copies and fresh vars made so far, to name the next ones
+    instances: usize,
+}
+
+impl<'a> CallExpander<'a> {
+    // copy `net` with its calls expanded and its var names prefixed
+    fn net(&mut self, net: &'a Net, prefix: &str) -> Result<Net, AstError> {
+        let mut body = Vec::new();
+        let head = net
+            .head
+            .iter()
+            .map(|head| self.term(head, prefix, &mut body))
+            .collect::<Result<_, _>>()?;
+        for (left, right) in &net.body {
+            let left = self.term(left, prefix, &mut body)?;
+            let right = self.term(right, prefix, &mut body)?;
+            body.push((left, right));
+        }
+        Ok(Net { head, body })
+    }
+
+    // copy `term`, adding the equations of the calls in it to `body`
+    fn term(
+        &mut self,
+        term: &'a Term,
+        prefix: &str,
+        body: &mut Vec<(Term, Term)>,
+    ) -> Result<Term, AstError> {
+        let mut pair = |this: &mut Self, p0, p1| -> Result<_, AstError> {
+            Ok((
+                Box::new(this.term(p0, prefix, body)?),
+                Box::new(this.term(p1, prefix, body)?),
+            ))
+        };
+        Ok(match term {
+            Term::Era => Term::Era,
+            Term::Var(name) => Term::Var(format!("{}{}", prefix, name)),
+            Term::Lam(p0, p1) => {
+                let (p0, p1) = pair(self, p0, p1)?;
+                Term::Lam(p0, p1)
+            }
+            Term::App(p0, p1) => {
+                let (p0, p1) = pair(self, p0, p1)?;
+                Term::App(p0, p1)
+            }
+            Term::Dup(p0, p1, lbl) => {
+                let (p0, p1) = pair(self, p0, p1)?;
+                Term::Dup(p0, p1, *lbl)
+            }
+            Term::Num(value) => Term::Num(*value),
+            Term::Op(op, flipped, p0, p1) => {
+                let (p0, p1) = pair(self, p0, p1)?;
+                Term::Op(*op, *flipped, p0, p1)
+            }
+            Term::Swi(p0, p1) => {
+                let (p0, p1) = pair(self, p0, p1)?;
+                Term::Swi(p0, p1)
+            }
+            Term::Ref(name) => Term::Ref(name.clone()),
+            Term::Infix(op, p0, p1) => {
+                let (left, right) = pair(self, p0, p1)?;
+                // `#` cannot occur in a parsed name
+                self.instances += 1;
+                let result = Term::Var(format!("{}#{}", prefix, self.instances));
+                body.push((*left, Term::Op(*op, false, right, Box::new(result.clone()))));
+                result
+            }
+            Term::Call(name, args) => {
+                let Some(def) = self.book.defs.iter().find(|def| def.name == *name) else {
+                    return Err(AstError::UnknownDef(name.clone()));
+                };
+                if def.net.head.len() != args.len() + 1 {
+                    let ports = def.net.head.len();
+                    return Err(AstError::CallArity(name.clone(), ports, args.len()));
+                }
+                if self.stack.contains(&name.as_str()) {
+                    return Err(AstError::RecursiveCall(name.clone()));
+                }
+                if !def.net.is_closed() {
+                    return Err(AstError::OpenCall(name.clone()));
+                }
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| self.term(arg, prefix, body))
+                    .collect::<Result<_, _>>()?;
+                // `#` cannot occur in a parsed name, so the copies never
+                // clash with the vars of the book or the fresh ones
+                self.instances += 1;
+                let prefix = format!("{}#{}.", name, self.instances);
+                self.stack.push(&def.name);
+                let mut copy = self.net(&def.net, &prefix)?;
+                self.stack.pop();
+                let result = copy.head.pop().expect("the definition has a head port");
+                body.append(&mut copy.body);
+                body.extend(copy.head.into_iter().zip(args));
+                result
+            }
+        })
+    }
+}
+
+// the var and reference names of each definition of a book
+struct Links<'a> {
+    names: Vec<&'a str>,
+    vars: Vec<HashSet<&'a str>>,
+    refs: Vec<HashSet<&'a str>>,
+    calls: Vec<HashSet<&'a str>>,
+}
+
+impl<'a> Links<'a> {
+    fn new(book: &'a Book) -> Self {
+        Self {
+            names: book.defs.iter().map(|def| def.name.as_str()).collect(),
+            vars: book.defs.iter().map(|def| def.net.vars()).collect(),
+            refs: book.defs.iter().map(|def| def.net.refs()).collect(),
+            calls: book.defs.iter().map(|def| def.net.calls()).collect(),
+        }
+    }
+
+    // whether `idx` shares a var with `other` or references or calls it, or
+    // is referenced or called by it unless `directed`
+    fn linked(&self, idx: usize, other: usize, directed: bool) -> bool {
+        let uses = |idx: usize, other: usize| {
+            self.refs[idx].contains(self.names[other])
+                || self.calls[idx].contains(self.names[other])
+        };
+        !self.vars[idx].is_disjoint(&self.vars[other])
+            || uses(idx, other)
+            || (!directed && uses(other, idx))
+    }
+
+    // the definitions reachable from `entry`
+    fn connected(&self, entry: usize, directed: bool) -> Vec<bool> {
+        let mut reachable = vec![false; self.vars.len()];
+        reachable[entry] = true;
+        let mut stack = vec![entry];
+        while let Some(idx) = stack.pop() {
+            for other in 0..self.vars.len() {
+                if !reachable[other] && self.linked(idx, other, directed) {
+                    reachable[other] = true;
+                    stack.push(other);
+                }
+            }
+        }
+        reachable
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AstError {
+    /// The var occurs only once, so one end of its wire is missing.
+    UnpairedVar(String),
+    /// The var occurs more than twice.
+    OverusedVar(String),
+    /// No definition has this name.
+    UnknownDef(String),
+    /// No unbound free var has this name.
+    UnknownVar(String),
+    /// The referenced definition has this many head ports instead of one.
+    RefPorts(String, usize),
+    /// The called definition has this many head ports, instead of one more
+    /// than this many arguments.
+    CallArity(String, usize, usize),
+    /// The definition calls itself, directly or through others.
+    RecursiveCall(String),
+    /// The called definition shares vars with other definitions.
+    OpenCall(String),
+}
+
+impl Display for AstError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AstError::UnpairedVar(name) => write!(f, "var `{}` is used only once", name),
+            AstError::OverusedVar(name) => write!(f, "var `{}` is used more than twice", name),
+            AstError::UnknownDef(name) => write!(f, "no definition named `{}`", name),
+            AstError::UnknownVar(name) => write!(f, "no free var named `{}`", name),
+            AstError::RefPorts(name, ports) => write!(
+                f,
+                "definition `{}` is referenced but has {} head ports instead of one",
+                name, ports
+            ),
+            AstError::CallArity(name, ports, args) => write!(
+                f,
+                "definition `{}` has {} head ports, so it cannot be called with {} arguments",
+                name, ports, args
+            ),
+            AstError::RecursiveCall(name) => write!(f, "definition `{}` calls itself", name),
+            AstError::OpenCall(name) => write!(
+                f,
+                "definition `{}` is called but shares vars with other definitions",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AstError {}
+
+struct AstBuilder<'a> {
+    net: &'a mut super::net::Net,
+    // vars seen once, waiting for their second use
+    open: HashMap<&'a str, VarUse>,
+    closed: Vec<&'a str>,
+    // reject vars used more than twice, instead of opening a new wire
+    strict: bool,
+}
+
+impl<'a> AstBuilder<'a> {
+    fn term(&mut self, term: &'a Term) -> Result<TermPtr, AstError> {
+        Ok(match term {
+            Term::Era => self.net.era(),
+            Term::Var(name) => {
+                if let Some(var_use) = self.open.remove(name.as_str()) {
+                    self.closed.push(name);
+                    TermPtr::Ptr(var_use.ptr())
+                } else if self.strict && self.closed.contains(&name.as_str()) {
+                    return Err(AstError::OverusedVar(name.clone()));
+                } else {
+                    let var = self.net.var();
+                    self.open.insert(name, var.0);
+                    var.1.into()
+                }
+            }
+            Term::Lam(p0, p1) => {
+                let (p0, p1) = (self.term(p0)?, self.term(p1)?);
+                self.net.lam(p0, p1).into()
+            }
+            Term::App(p0, p1) => {
+                let (p0, p1) = (self.term(p0)?, self.term(p1)?);
+                self.net.app(p0, p1).into()
+            }
+            Term::Dup(p0, p1, None) => {
+                let (p0, p1) = (self.term(p0)?, self.term(p1)?);
+                self.net.dup(p0, p1).into()
+            }
+            Term::Dup(p0, p1, Some(lbl)) => {
+                let (p0, p1) = (self.term(p0)?, self.term(p1)?);
+                let dup = Cell::Dup(Some((p0, p1)), Some(*lbl));
+                TermPtr::Ptr(self.net.store.alloc(Some(term::Term::Cell(dup))))
+            }
+            Term::Num(value) => self.net.num(*value).into(),
+            Term::Op(op, flipped, p0, p1) => {
+                let (p0, p1) = (self.term(p0)?, self.term(p1)?);
+                let op = Cell::Op(Some((p0, p1)), *op, *flipped);
+                TermPtr::Ptr(self.net.store.alloc(Some(term::Term::Cell(op))))
+            }
+            Term::Swi(p0, p1) => {
+                let (p0, p1) = (self.term(p0)?, self.term(p1)?);
+                self.net.swi(p0, p1).into()
+            }
+            Term::Ref(name) => match self.net.refs.id(name) {
+                Some(id) => self.net.reference(id).into(),
+                None => return Err(AstError::UnknownDef(name.clone())),
+            },
+            // a lone net has no definitions to call, see `Book::desugar`
+            Term::Call(name, _) => return Err(AstError::UnknownDef(name.clone())),
+            Term::Infix(op, p0, p1) => {
+                let (p0, p1) = (self.term(p0)?, self.term(p1)?);
+                let result = self.net.var();
+                let op = self.net.op(*op, p1, result.0);
+                self.net.eqn(p0, op);
+                result.1.into()
+            }
+        })
+    }
+
+    fn net(&mut self, ast: &'a Net) -> Result<(), AstError> {
+        for head in &ast.head {
+            let head = self.term(head)?;
+            self.net.head(head);
+        }
+        for (left, right) in &ast.body {
+            let (left, right) = (self.term(left)?, self.term(right)?);
+            self.net.eqn(left, right);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), AstError> {
+        match self.open.keys().min() {
+            Some(name) => Err(AstError::UnpairedVar(name.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl super::net::Net {
+    /// Build a net from owned terms, pairing the two uses of every var name.
+    pub fn from_ast(ast: &Net) -> Result<Self, AstError> {
+        let mut net = Self::new();
+        net.add_ast(ast)?;
+        Ok(net)
+    }
+
+    /// Add the heads and equations of `ast` to this net, pairing the two
+    /// uses of every var name.
+    pub(crate) fn add_ast(&mut self, ast: &Net) -> Result<(), AstError> {
+        let mut builder = AstBuilder {
+            net: self,
+            open: HashMap::new(),
+            closed: Vec::new(),
+            strict: true,
+        };
+        builder.net(ast)?;
+        builder.finish()
+    }
+
+    /// Add every definition of `book` to this net, pairing var names across
+    /// definitions, and record the store slots each one allocated. Unlike
+    /// [`Self::from_ast`], vars used only once are left as free wires, and a
+    /// third use of a name starts a new wire.
+    ///
+    /// The definitions referenced as `@name` are compiled with
+    /// [`Self::define_ref`] instead, so they must be closed and have a single
+    /// head port. The calls and infix operators are expanded first, see
+    /// [`Book::desugar`].
+    pub fn load(&mut self, book: &Book) -> Result<(), AstError> {
+        let book = &book.desugar()?;
+        self.define_refs(book)?;
+        let mut builder = AstBuilder {
+            net: self,
+            open: HashMap::new(),
+            closed: Vec::new(),
+            strict: false,
+        };
+        for def in &book.defs {
+            if builder.net.refs.id(&def.name).is_some() {
+                continue;
+            }
+            let head_start = builder.net.head.len();
+            for (idx, name) in def.head_names.iter().enumerate() {
+                if let Some(name) = name {
+                    builder
+                        .net
+                        .head_names
+                        .insert(name.clone(), head_start + idx);
+                }
+            }
+            let marks = builder.net.store.marks();
+            builder.net(&def.net)?;
+            let ranges = builder.net.store.handed_out_since(&marks);
+            builder.net.definitions.insert(def.name.clone(), ranges);
+        }
+        let AstBuilder { net, open, .. } = builder;
+        net.free_vars
+            .extend(open.into_iter().map(|(name, var)| (name.to_string(), var)));
+        Ok(())
+    }
+
+    // compile the definitions referenced in a desugared `book`, declaring
+    // them all first so they can reference each other
+    pub(crate) fn define_refs(&mut self, book: &Book) -> Result<(), AstError> {
+        let referenced = book.referenced();
+        for def in &referenced {
+            self.declare_ref(&def.name);
+        }
+        for def in &referenced {
+            let template = Template::from_ast_with_refs(&def.net, &self.refs)?;
+            self.define_ref(&def.name, template)?;
+        }
+        Ok(())
+    }
+
+    /// Build `term` in this net, returning its root for the caller to wire.
+    /// Its vars must be paired within it.
+    pub(crate) fn add_term(&mut self, term: &Term) -> Result<TermPtr, AstError> {
+        let mut builder = AstBuilder {
+            net: self,
+            open: HashMap::new(),
+            closed: Vec::new(),
+            strict: true,
+        };
+        let term_ptr = builder.term(term)?;
+        builder.finish()?;
+        Ok(term_ptr)
+    }
+
+    /// Connect the free var `name` of a loaded book to `term_ptr`, to supply
+    /// an argument before eval. Each free var can be bound once.
+    pub fn bind_input<T: Into<TermPtr>>(
+        &mut self,
+        name: &str,
+        term_ptr: T,
+    ) -> Result<(), AstError> {
+        let Some(var) = self.free_vars.remove(name) else {
+            return Err(AstError::UnknownVar(name.to_string()));
+        };
+        self.eqn(var, term_ptr);
+        Ok(())
+    }
+
+    /// Copy the heads and the pending equations out of the store. Free wires
+    /// are named `x<index>` after the store index of their var.
+    pub fn to_ast(&self) -> Result<Net, NotNormal> {
+        let mut path = Vec::new();
+        Ok(Net {
+            head: self
+                .head
+                .iter()
+                .map(|head| self.read_term(*head, &mut path))
+                .collect::<Result<_, _>>()?,
+            body: self
+                .body
+                .iter()
+                .map(|(eqn, _)| {
+                    Ok((
+                        self.read_term(eqn.left(), &mut path)?,
+                        self.read_term(eqn.right(), &mut path)?,
+                    ))
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        ast::{AstError, Net, Term},
+        display::CellDisplay,
+        net::{self, NetBuilder},
+        num::Op,
+        parser::{parse, parse_ast},
+        runtime::Runtime,
+        term::{Label, TermPtr},
+    };
+    use serde_json::json;
+
+    fn id_app_id() -> Net {
+        Net {
+            head: vec![Term::var("r")],
+            body: vec![(
+                Term::lam(Term::var("a"), Term::var("a")),
+                Term::app(Term::var("r"), Term::lam(Term::var("b"), Term::var("b"))),
+            )],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let net = net::Net::from_ast(&id_app_id()).unwrap();
+        assert_eq!(
+            net.to_ast().unwrap().to_string(),
+            "head: x0\nbody:\n  (λ x1 x1) ~ (@ x0 (λ x3 x3))"
+        );
+    }
+
+    #[test]
+    fn test_eval_from_ast() {
+        let mut net = net::Net::from_ast(&id_app_id()).unwrap();
+        Runtime::new().eval(&mut net).unwrap();
+        let ast = net.to_ast().unwrap();
+        assert!(ast.body.is_empty());
+        let Term::Lam(p0, p1) = &ast.head[0] else {
+            panic!("expected a lambda, got {}", ast.head[0]);
+        };
+        assert_eq!(p0, p1);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut net = net::Net::from_ast(&id_app_id()).unwrap();
+        Runtime::new().eval(&mut net).unwrap();
+        let json = net.normal_form().unwrap()[0].to_json();
+        assert_eq!(json["kind"], "lam");
+        assert_eq!(json["children"][0]["kind"], "var");
+        assert_eq!(json["children"][0], json["children"][1]);
+
+        let term = Term::Dup(
+            Box::new(Term::Num(7)),
+            Box::new(Term::Op(Op::Sub, true, Box::new(Term::Era), Box::new(Term::var("x")))),
+            Some(Label::User(3)),
+        );
+        assert_eq!(
+            term.to_json(),
+            json!({ "kind": "dup", "label": 3, "children": [
+                { "kind": "num", "label": 7, "children": [] },
+                { "kind": "op", "label": CellDisplay::op_symbol(Op::Sub, true), "children": [
+                    { "kind": "era", "children": [] },
+                    { "kind": "var", "label": "x", "children": [] },
+                ] },
+            ] })
+        );
+    }
+
+    #[test]
+    fn test_closed() {
+        let ast = Net {
+            head: vec![Term::var("a"), Term::var("b")],
+            body: vec![(Term::var("a"), Term::lam(Term::var("a"), Term::var("c")))],
+        };
+        assert!(!ast.is_closed());
+        let closed = ast.closed();
+        assert!(closed.is_closed());
+        assert_eq!(closed.to_string(), "head: a b b c\nbody:\n  a ~ (λ ε c)");
+        assert!(net::Net::from_ast(&closed).is_ok());
+    }
+
+    #[test]
+    fn test_var_errors() {
+        let unpaired = Net {
+            head: vec![Term::lam(Term::var("x"), Term::Era)],
+            body: vec![],
+        };
+        assert_eq!(
+            net::Net::from_ast(&unpaired).err(),
+            Some(AstError::UnpairedVar("x".to_string()))
+        );
+
+        let overused = Net {
+            head: vec![Term::var("x"), Term::dup(Term::var("x"), Term::var("x"))],
+            body: vec![],
+        };
+        assert_eq!(
+            net::Net::from_ast(&overused).err(),
+            Some(AstError::OverusedVar("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut book = parse_ast(
+            "
+            def main(R) = (f R) ~ [* *] ;
+            def lib(f) ;
+            def unused(x, x) ;
+            def also_unused([* *]) ;
+        ",
+        )
+        .unwrap();
+        assert_eq!(
+            book.prune("main").unwrap(),
+            vec!["unused".to_string(), "also_unused".to_string()]
+        );
+        let names: Vec<_> = book.defs.iter().map(|def| def.name.as_str()).collect();
+        assert_eq!(names, vec!["main", "lib"]);
+
+        let mut net = net::Net::new();
+        net.load(&book).unwrap();
+        assert_eq!(net.definitions.len(), 2);
+
+        assert_eq!(
+            book.prune("missing").err(),
+            Some(AstError::UnknownDef("missing".to_string()))
+        );
+
+        // a reference keeps the definition it names, but not its referrers
+        let mut book = parse_ast("def f((x x)) ; def main(r) = @f ~ r ; def g((y @main))").unwrap();
+        assert_eq!(book.prune("main").unwrap(), vec!["g".to_string()]);
+        assert_eq!(book.components(), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_bind_input() {
+        let mut net = net::Net::new();
+        assert!(parse("def main(out r) = n ~ [r (x x)]", &mut net));
+        let y = net.var();
+        let id = net.lam(y.0, y.1);
+        net.bind_input("n", id).unwrap();
+        assert_eq!(
+            net.bind_input("n", TermPtr::Era),
+            Err(AstError::UnknownVar("n".to_string()))
+        );
+
+        Runtime::new().eval(&mut net).unwrap();
+        let r = net.head_by_name("r").unwrap().unwrap();
+        let Term::Lam(p0, p1) = &r else {
+            panic!("expected a lambda, got {}", r);
+        };
+        assert_eq!(p0, p1);
+    }
+
+    #[test]
+    fn test_desugar() {
+        let book = parse_ast("def id(a, a) ; def main(r) = id((x x)) ~ r").unwrap();
+        let expanded = book.desugar().unwrap();
+        assert_eq!(expanded.defs.len(), 1);
+        assert_eq!(
+            expanded.defs[0].net.to_string(),
+            "head: r\nbody:\n  id#1.a ~ (λ x x)\n  id#1.a ~ r"
+        );
+        // a pruned book keeps the definitions its entry calls
+        let mut pruned = book.clone();
+        assert!(pruned.prune("main").unwrap().is_empty());
+
+        let errors: Vec<_> = [
+            "def f(a, b) = a ~ b ; def main(r) = f(1, 2) ~ r",
+            "def f(a, b) = f(a) ~ b ; def main(r) = f(1) ~ r",
+            "def f(a, b) = a ~ (+ c b) ; def main(r, c) = f(1) ~ r",
+            "def main(r) = g(1) ~ r",
+        ]
+        .iter()
+        .map(|src| parse_ast(src).unwrap().desugar().unwrap_err())
+        .collect();
+        assert_eq!(
+            errors,
+            vec![
+                AstError::CallArity("f".to_string(), 2, 2),
+                AstError::RecursiveCall("f".to_string()),
+                AstError::OpenCall("f".to_string()),
+                AstError::UnknownDef("g".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+};
+
+use super::{
+    ast::{self, AstError, Term},
+    net::Net,
+    readback::NotNormal,
+};
+
+/// Why [`Net::canonicalize`] could not rebuild the net.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CanonicalError {
+    /// The net could not be copied out of the store.
+    NotNormal(NotNormal),
+    /// A wire of the net has only one end in it, like a free var waiting for
+    /// [`Net::bind_input`].
+    Ast(AstError),
+}
+
+impl Display for CanonicalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanonicalError::NotNormal(err) => write!(f, "{}", err),
+            CanonicalError::Ast(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CanonicalError {}
+
+impl From<NotNormal> for CanonicalError {
+    fn from(err: NotNormal) -> Self {
+        CanonicalError::NotNormal(err)
+    }
+}
+
+impl From<AstError> for CanonicalError {
+    fn from(err: AstError) -> Self {
+        CanonicalError::Ast(err)
+    }
+}
+
+impl Net {
+    /// Rebuild the net into a fresh store, in a form that only depends on
+    /// its structure: slots are allocated walking the heads and then the
+    /// equations, var links are collapsed, and the fragments of the body
+    /// that no head reaches are sorted, with duplicates merged.
+    ///
+    /// Two nets equal up to where their slots live canonicalize to the same
+    /// store, so [`Net::to_ast`] can be compared or hashed. Definition
+    /// ranges, traced slots and interned constants refer to the old store
+    /// and are dropped.
+    pub fn canonicalize(&mut self) -> Result<(), CanonicalError> {
+        let ast = canonical(self.to_ast()?);
+        let mut net = Net::with_shards(self.store.capacity, self.store.shards());
+        net.add_ast(&ast)?;
+        self.store = net.store;
+        self.head = net.head;
+        self.body = net.body;
+        self.definitions.clear();
+        self.free_vars.clear();
+        self.traced.clear();
+        self.interner = Default::default();
+        Ok(())
+    }
+}
+
+// keep the equations reached from the heads in order, then the other
+// fragments by their canonical text, each one once
+fn canonical(ast: ast::Net) -> ast::Net {
+    let mut head_vars = Vec::new();
+    ast.head
+        .iter()
+        .for_each(|head| var_names(head, &mut head_vars));
+    let head_vars: HashSet<&str> = head_vars.into_iter().collect();
+
+    // group the equations sharing vars
+    let mut parents: Vec<usize> = (0..ast.body.len()).collect();
+    let mut owners: HashMap<&str, usize> = HashMap::new();
+    for (idx, (left, right)) in ast.body.iter().enumerate() {
+        let mut names = Vec::new();
+        var_names(left, &mut names);
+        var_names(right, &mut names);
+        for name in names {
+            let owner = *owners.entry(name).or_insert(idx);
+            let (root, other) = (find(&mut parents, owner), find(&mut parents, idx));
+            parents[other] = root;
+        }
+    }
+    let mut reached = vec![false; ast.body.len()];
+    for (name, owner) in &owners {
+        if head_vars.contains(name) {
+            let root = find(&mut parents, *owner);
+            reached[root] = true;
+        }
+    }
+
+    let mut body = Vec::new();
+    let mut fragments: BTreeMap<usize, Vec<(Term, Term)>> = BTreeMap::new();
+    for (idx, eqn) in ast.body.into_iter().enumerate() {
+        let root = find(&mut parents, idx);
+        match reached[root] {
+            true => body.push(eqn),
+            false => fragments.entry(root).or_default().push(eqn),
+        }
+    }
+    let fragments: BTreeMap<String, Vec<(Term, Term)>> = fragments
+        .into_values()
+        .map(|eqns| {
+            let eqns = rename_all(&eqns, "v");
+            let key = ast::Net {
+                head: vec![],
+                body: eqns.clone(),
+            }
+            .to_string();
+            (key, eqns)
+        })
+        .collect();
+    for (idx, eqns) in fragments.into_values().enumerate() {
+        body.extend(rename_all(&eqns, &format!("f{}_", idx)));
+    }
+    ast::Net {
+        head: ast.head,
+        body,
+    }
+}
+
+fn find(parents: &mut [usize], idx: usize) -> usize {
+    let mut root = idx;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[idx] = root;
+    root
+}
+
+// the var names of `term`, in order of use
+fn var_names<'a>(term: &'a Term, names: &mut Vec<&'a str>) {
+    match term {
+        Term::Era | Term::Num(_) | Term::Ref(_) => {}
+        Term::Var(name) => names.push(name),
+        Term::Lam(p0, p1)
+        | Term::App(p0, p1)
+        | Term::Dup(p0, p1, _)
+        | Term::Op(_, _, p0, p1)
+        | Term::Infix(_, p0, p1)
+        | Term::Swi(p0, p1) => {
+            var_names(p0, names);
+            var_names(p1, names);
+        }
+        Term::Call(_, args) => args.iter().for_each(|arg| var_names(arg, names)),
+    }
+}
+
+// name the vars of `eqns` `<prefix><n>` by order of first use
+fn rename_all(eqns: &[(Term, Term)], prefix: &str) -> Vec<(Term, Term)> {
+    let mut names = HashMap::new();
+    eqns.iter()
+        .map(|(left, right)| {
+            let left = rename(left, prefix, &mut names);
+            (left, rename(right, prefix, &mut names))
+        })
+        .collect()
+}
+
+fn rename(term: &Term, prefix: &str, names: &mut HashMap<String, String>) -> Term {
+    match term {
+        Term::Era => Term::Era,
+        Term::Var(name) => {
+            let count = names.len();
+            let renamed = names
+                .entry(name.clone())
+                .or_insert_with(|| format!("{}{}", prefix, count));
+            Term::Var(renamed.clone())
+        }
+        Term::Lam(p0, p1) => Term::lam(rename(p0, prefix, names), rename(p1, prefix, names)),
+        Term::App(p0, p1) => Term::app(rename(p0, prefix, names), rename(p1, prefix, names)),
+        Term::Dup(p0, p1, lbl) => Term::Dup(
+            Box::new(rename(p0, prefix, names)),
+            Box::new(rename(p1, prefix, names)),
+            *lbl,
+        ),
+        Term::Num(value) => Term::Num(*value),
+        Term::Op(op, flipped, p0, p1) => Term::Op(
+            *op,
+            *flipped,
+            Box::new(rename(p0, prefix, names)),
+            Box::new(rename(p1, prefix, names)),
+        ),
+        Term::Swi(p0, p1) => Term::Swi(
+            Box::new(rename(p0, prefix, names)),
+            Box::new(rename(p1, prefix, names)),
+        ),
+        Term::Ref(name) => Term::Ref(name.clone()),
+        Term::Call(name, args) => Term::Call(
+            name.clone(),
+            args.iter().map(|arg| rename(arg, prefix, names)).collect(),
+        ),
+        Term::Infix(op, p0, p1) => Term::infix(
+            *op,
+            rename(p0, prefix, names),
+            rename(p1, prefix, names),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        term::TermPtr,
+    };
+
+    // `r ~ (λx.x) applied to a`, with a redex no head reaches
+    fn build(net: &mut Net, a: TermPtr) {
+        let r = net.var();
+        let x = net.var();
+        let id = net.lam(x.0, x.1);
+        let app = net.app(r.0, a);
+        net.head(r.1);
+        net.eqn(id, app);
+    }
+
+    fn garbage(net: &mut Net) {
+        let y = net.var();
+        let id = net.lam(y.0, y.1);
+        net.eqn(id, TermPtr::Era);
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut left = Net::new();
+        build(&mut left, TermPtr::Era);
+        garbage(&mut left);
+
+        // the same net, allocated elsewhere and with the fragment twice
+        let mut right = Net::new();
+        garbage(&mut right);
+        build(&mut right, TermPtr::Era);
+        garbage(&mut right);
+        assert_ne!(left.to_ast(), right.to_ast());
+        left.canonicalize().unwrap();
+        right.canonicalize().unwrap();
+        assert_eq!(left.to_ast(), right.to_ast());
+        assert_eq!(right.body.len(), 2);
+        assert_eq!(left.store.len(), right.store.len());
+    }
+}
@@ -0,0 +1,224 @@
+//! Static checks of a book, without evaluating it: that it loads, and that
+//! every wire joins a producer and a consumer, see
+//! [`PolarityChecker`](super::polarity::PolarityChecker).
+//!
+//! The definitions referenced as `@name` are checked one by one, since
+//! they are expanded apart, and the other ones together, since they share
+//! their var names. Vars used once or more than twice are left to
+//! [`Net::load`], which wires them as free vars and new wires.
+
+use std::{collections::HashMap, fmt::Display};
+
+use super::{
+    ast::{AstError, Book, Def, Term},
+    net::{Net, NetBuilder},
+    polarity::{PolarityChecker, WiringError},
+    refs::RefId,
+    term::{Label, TermPtr},
+    var::VarUse,
+};
+
+/// A problem found by [`check`], in the definition it was found in when
+/// known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub def: Option<String>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.def {
+            Some(def) => write!(f, "in `{}`: {}", def, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Check `book`, returning every problem found: none if it is well formed
+/// and well polarized.
+pub fn check(book: &Book) -> Vec<Diagnostic> {
+    let failed = |err: AstError| {
+        vec![Diagnostic {
+            def: None,
+            message: err.to_string(),
+        }]
+    };
+    let book = match book.desugar() {
+        Ok(book) => book,
+        Err(err) => return failed(err),
+    };
+    if let Err(err) = Net::new().load(&book) {
+        return failed(err);
+    }
+    let mut net = Net::new();
+    let referenced = book.referenced();
+    let refs: HashMap<&str, RefId> = referenced
+        .iter()
+        .map(|def| (def.name.as_str(), net.declare_ref(&def.name)))
+        .collect();
+    let mut diagnostics = Vec::new();
+    for def in &referenced {
+        let mut checker = PolarityChecker::new(&mut net);
+        let mut builder = CheckBuilder::new(&mut checker, &refs);
+        builder.def(def);
+        diagnostics.extend(polarity_errors(def, checker.errors()));
+    }
+    let mut checker = PolarityChecker::new(&mut net);
+    let mut vars = HashMap::new();
+    for def in book
+        .defs
+        .iter()
+        .filter(|def| !refs.contains_key(def.name.as_str()))
+    {
+        let errors = checker.errors().len();
+        let mut builder = CheckBuilder {
+            builder: &mut checker,
+            refs: &refs,
+            vars,
+        };
+        builder.def(def);
+        vars = builder.vars;
+        diagnostics.extend(polarity_errors(def, &checker.errors()[errors..]));
+    }
+    diagnostics
+}
+
+// only the polarity conflicts, see the module docs
+fn polarity_errors<'a>(
+    def: &'a Def,
+    errors: &'a [WiringError],
+) -> impl Iterator<Item = Diagnostic> + 'a {
+    errors
+        .iter()
+        .filter(|err| matches!(err, WiringError::Polarity(_)))
+        .map(|_| Diagnostic {
+            def: Some(def.name.clone()),
+            message: "a wire joins two ports of the same polarity".to_string(),
+        })
+}
+
+// builds the terms of a book with any builder, pairing var names
+struct CheckBuilder<'a, B: NetBuilder> {
+    builder: &'a mut B,
+    refs: &'a HashMap<&'a str, RefId>,
+    // the other end of the vars used once so far
+    vars: HashMap<String, VarUse>,
+}
+
+impl<'a, B: NetBuilder> CheckBuilder<'a, B> {
+    fn new(builder: &'a mut B, refs: &'a HashMap<&'a str, RefId>) -> Self {
+        Self {
+            builder,
+            refs,
+            vars: HashMap::new(),
+        }
+    }
+
+    fn def(&mut self, def: &Def) {
+        for head in &def.net.head {
+            let head = self.term(head);
+            self.builder.head(head);
+        }
+        for (left, right) in &def.net.body {
+            let (left, right) = (self.term(left), self.term(right));
+            self.builder.eqn(left, right);
+        }
+    }
+
+    fn term(&mut self, term: &Term) -> TermPtr {
+        match term {
+            Term::Era => self.builder.era(),
+            Term::Var(name) => match self.vars.remove(name) {
+                Some(var_use) => var_use.into(),
+                None => {
+                    let var = self.builder.var();
+                    self.vars.insert(name.clone(), var.0);
+                    var.1.into()
+                }
+            },
+            Term::Lam(p0, p1) => {
+                let (p0, p1) = (self.term(p0), self.term(p1));
+                self.builder.lam(p0, p1).into()
+            }
+            Term::App(p0, p1) => {
+                let (p0, p1) = (self.term(p0), self.term(p1));
+                self.builder.app(p0, p1).into()
+            }
+            Term::Dup(p0, p1, None) => {
+                let (p0, p1) = (self.term(p0), self.term(p1));
+                self.builder.dup(p0, p1).into()
+            }
+            Term::Dup(p0, p1, Some(Label::User(label))) => {
+                let (p0, p1) = (self.term(p0), self.term(p1));
+                self.builder.labeled_dup(*label, p0, p1).into()
+            }
+            // only a readback has fresh labels, and polarities ignore labels
+            Term::Dup(p0, p1, Some(Label::Fresh(_))) => {
+                let (p0, p1) = (self.term(p0), self.term(p1));
+                self.builder.fresh_dup(p0, p1).into()
+            }
+            Term::Num(value) => self.builder.num(*value).into(),
+            // a flipped operator holds its left operand instead of its right
+            // one, with the same polarities
+            Term::Op(op, _, p0, p1) => {
+                let (p0, p1) = (self.term(p0), self.term(p1));
+                self.builder.op(*op, p0, p1).into()
+            }
+            // the branches pair is not a lambda, it consumes both branches
+            Term::Swi(branches, p1) if matches!(**branches, Term::Lam(..)) => {
+                let Term::Lam(zero, succ) = &**branches else {
+                    unreachable!()
+                };
+                let (zero, succ, p1) = (self.term(zero), self.term(succ), self.term(p1));
+                self.builder.switch(zero, succ, p1).into()
+            }
+            Term::Swi(p0, p1) => {
+                let (p0, p1) = (self.term(p0), self.term(p1));
+                self.builder.swi(p0, p1).into()
+            }
+            Term::Ref(name) => self.builder.reference(self.refs[name.as_str()]).into(),
+            Term::Call(..) | Term::Infix(..) => {
+                unreachable!("the book is desugared before checking")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::parser::{parse_ast, try_parse_ast};
+
+    use super::check;
+
+    fn diagnostics(src: &str) -> Vec<String> {
+        check(&parse_ast(src).unwrap())
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let fixture = include_str!("../../tests/fixtures/factorial.strandal");
+        assert!(diagnostics(fixture).is_empty());
+
+        // a lambda applied to a lambda, and a lambda bound to a lambda
+        assert!(diagnostics("def main(r) = (x x) ~ [(y y) r]").is_empty());
+        assert_eq!(
+            diagnostics("def main(r) = (x x) ~ a & a ~ (y (* r))"),
+            vec!["in `main`: a wire joins two ports of the same polarity"]
+        );
+        assert_eq!(
+            diagnostics("def main(r) = @missing ~ r"),
+            vec!["no definition named `missing`"]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let errors = try_parse_ast("\n  def main(r) = r ~ (x").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (2, 23));
+    }
+}
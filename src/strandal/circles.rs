@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use super::{
+    net::Net,
+    store::Ptr,
+    term::{TermPtr, TermRef},
+};
+
+// where a cell or var use is wired
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wire {
+    Head,
+    // the equation index, and whether this is its left side
+    Eqn(usize, bool),
+    // an auxiliary port of the cell
+    Port(Ptr),
+}
+
+struct Wiring<'a> {
+    net: &'a Net,
+    wires: HashMap<Ptr, Vec<Wire>>,
+}
+
+impl<'a> Wiring<'a> {
+    fn new(net: &'a Net) -> Self {
+        let mut wires: HashMap<Ptr, Vec<Wire>> = HashMap::new();
+        let mut add = |term_ptr: TermPtr, wire: Wire| {
+            if let TermPtr::Ptr(ptr) = term_ptr {
+                wires.entry(ptr).or_default().push(wire);
+            }
+        };
+        net.head.iter().for_each(|head| add(*head, Wire::Head));
+        for (idx, (eqn, _)) in net.body.iter().enumerate() {
+            add(eqn.left(), Wire::Eqn(idx, true));
+            add(eqn.right(), Wire::Eqn(idx, false));
+        }
+        for (ptr, term) in net.store.iter() {
+            if let TermRef::Cell(cell) = term {
+                if let Some((p0, p1)) = cell.ports() {
+                    add(p0, Wire::Port(ptr));
+                    add(p1, Wire::Port(ptr));
+                }
+            }
+        }
+        Self { net, wires }
+    }
+
+    fn is_cell(&self, ptr: Ptr) -> bool {
+        matches!(self.net.store.get(ptr), Some(TermRef::Cell(_)))
+    }
+
+    // the cell whose auxiliary port the principal port of `cell` faces, if
+    // any: following vars, until reaching a head, an eraser or another
+    // principal port
+    fn facing(&self, cell: Ptr) -> Option<Ptr> {
+        let mut wire = *self.wires.get(&cell)?.first()?;
+        // every var is crossed at most once, so var loops end
+        for _ in 0..=self.wires.len() {
+            let Wire::Eqn(idx, left) = wire else {
+                break;
+            };
+            let (eqn, _) = self.net.body[idx];
+            let TermPtr::Ptr(other) = (if left { eqn.right() } else { eqn.left() }) else {
+                return None;
+            };
+            if self.is_cell(other) {
+                return None;
+            }
+            // continue from the other use of the var
+            let uses = self.wires.get(&other)?;
+            let from = uses.iter().position(|w| *w == Wire::Eqn(idx, !left))?;
+            wire = *uses.get(1 - from)?;
+        }
+        match wire {
+            Wire::Port(parent) => Some(parent),
+            Wire::Head | Wire::Eqn(_, _) => None,
+        }
+    }
+}
+
+impl Net {
+    /// Find the vicious circles of a net that has not been evaluated yet:
+    /// cycles of cells where each principal port faces an auxiliary port of
+    /// the next. No cell in a circle can ever take part in a redex, so the
+    /// circle never reduces and its readback never ends.
+    ///
+    /// Returns the cells of each circle, in wiring order.
+    pub fn vicious_circles(&self) -> Vec<Vec<Ptr>> {
+        let wiring = Wiring::new(self);
+        let mut circles = Vec::new();
+        // cells already walked, by the walk that reached them
+        let mut walked: HashMap<Ptr, usize> = HashMap::new();
+        for start in self.store.ptrs() {
+            let idx = start.index();
+            if !wiring.is_cell(start) || walked.contains_key(&start) {
+                continue;
+            }
+            let mut path = Vec::new();
+            let mut next = Some(start);
+            while let Some(cell) = next {
+                match walked.get(&cell) {
+                    Some(walk) if *walk == idx as usize => {
+                        let from = path.iter().position(|ptr| *ptr == cell).unwrap();
+                        circles.push(path.split_off(from));
+                        break;
+                    }
+                    Some(_) => break,
+                    None => {}
+                }
+                walked.insert(cell, idx as usize);
+                path.push(cell);
+                next = wiring.facing(cell);
+            }
+        }
+        circles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        term::TermPtr,
+    };
+
+    #[test]
+    fn test_vicious_circles() {
+        let mut net = Net::new();
+        let x = net.var();
+        let id = net.lam(x.0, x.1);
+        let r = net.var();
+        let app = net.app(r.0, TermPtr::Era);
+        net.head(r.1);
+        net.eqn(id, app);
+        assert!(net.vicious_circles().is_empty());
+
+        // a lambda returning itself: its principal port is wired to its body
+        let w = net.var();
+        let lam = net.lam(TermPtr::Era, w.0);
+        let lam_ptr = lam.ptr();
+        net.eqn(w.1, lam);
+        assert_eq!(net.vicious_circles(), vec![vec![lam_ptr]]);
+
+        // two cells, each wired to an auxiliary port of the other
+        let mut net = Net::new();
+        let w = net.var();
+        let inner = net.app(TermPtr::Era, w.0);
+        let inner_ptr = inner.ptr();
+        let outer = net.lam(inner, TermPtr::Era);
+        let outer_ptr = outer.ptr();
+        net.eqn(w.1, outer);
+        assert_eq!(net.vicious_circles(), vec![vec![inner_ptr, outer_ptr]]);
+    }
+}
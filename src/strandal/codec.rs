@@ -0,0 +1,136 @@
+//! The layout of the words that pack store pointers: the value of a
+//! [`Var`](super::var::Var), a [`Ptr`](super::store::Ptr) within it and a
+//! [`PackedTermPtr`](super::term::PackedTermPtr).
+//!
+//! A var value is a 64 bit word, its 2 high bits a [`Tag`] and the low 62
+//! bits a pointer. A pointer is its index in the low 32 bits and, in debug
+//! builds, the generation of its slot in the 30 bits above. Wide indices
+//! take all 62 bits and leave no room for the generation.
+//!
+//! ```text
+//! 63  62 61            32 31             0
+//! | tag | generation     | index          |
+//! | tag | wide index                      |
+//! ```
+
+/// The kind of a var value, in its 2 high bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag {
+    /// The var is not bound yet, and the word is all zeros.
+    Empty = 0,
+    Var = 1,
+    Era = 2,
+    Cell = 3,
+}
+
+/// Where the tag starts.
+pub const TAG_SHIFT: u32 = 62;
+
+/// The bits below the tag.
+pub const PAYLOAD_MASK: u64 = (1 << TAG_SHIFT) - 1;
+
+/// The bits of a pointer holding its index.
+#[cfg(not(feature = "wide-ptrs"))]
+pub const INDEX_MASK: u64 = u32::MAX as u64;
+#[cfg(feature = "wide-ptrs")]
+pub const INDEX_MASK: u64 = PAYLOAD_MASK;
+
+/// Where the generation of a narrow pointer starts.
+pub const GEN_SHIFT: u32 = 32;
+
+/// The bits of a generation, all ones meaning unchecked.
+pub const GEN_MASK: u32 = (1 << (TAG_SHIFT - GEN_SHIFT)) - 1;
+
+/// Pack `tag` above `payload`, which must fit in [`PAYLOAD_MASK`].
+#[inline]
+pub fn pack(tag: Tag, payload: u64) -> u64 {
+    debug_assert!(payload <= PAYLOAD_MASK, "payload {:#x} overlaps the tag", payload);
+    (tag as u64) << TAG_SHIFT | payload
+}
+
+/// The tag and payload of a word built by [`pack`]. Every word unpacks, the
+/// 2 bits of a tag taking all 4 values.
+#[inline]
+pub fn unpack(word: u64) -> (Tag, u64) {
+    let tag = match word >> TAG_SHIFT {
+        0 => Tag::Empty,
+        1 => Tag::Var,
+        2 => Tag::Era,
+        _ => Tag::Cell,
+    };
+    (tag, word & PAYLOAD_MASK)
+}
+
+/// Pack a pointer: its index, and the generation when it has room.
+#[inline]
+pub fn pack_ptr(index: u64, gen: u32) -> u64 {
+    debug_assert!(index <= INDEX_MASK, "index {:#x} is too wide", index);
+    match cfg!(feature = "wide-ptrs") {
+        true => index,
+        false => (gen as u64 & GEN_MASK as u64) << GEN_SHIFT | index,
+    }
+}
+
+/// The index and generation of a pointer built by [`pack_ptr`], or of a
+/// payload built from one. Wide pointers give the unchecked generation.
+#[inline]
+pub fn unpack_ptr(bits: u64) -> (u64, u32) {
+    match cfg!(feature = "wide-ptrs") {
+        true => (bits & INDEX_MASK, GEN_MASK),
+        false => (
+            bits & INDEX_MASK,
+            (bits >> GEN_SHIFT) as u32 & GEN_MASK,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pack, pack_ptr, unpack, unpack_ptr, Tag, GEN_MASK, INDEX_MASK, PAYLOAD_MASK};
+
+    const TAGS: [Tag; 4] = [Tag::Empty, Tag::Var, Tag::Era, Tag::Cell];
+
+    #[test]
+    fn test_pack() {
+        for tag in TAGS {
+            for payload in [0, 1, INDEX_MASK, PAYLOAD_MASK >> 1, PAYLOAD_MASK] {
+                assert_eq!(unpack(pack(tag, payload)), (tag, payload));
+            }
+        }
+        assert_eq!(pack(Tag::Empty, 0), 0);
+        assert_eq!(pack(Tag::Cell, PAYLOAD_MASK), u64::MAX);
+        // every word unpacks to a tag and payload that pack back to it
+        for word in [0, 1, 1 << 62, 2 << 62, 3 << 62, u64::MAX, 0x5555_5555_5555_5555] {
+            let (tag, payload) = unpack(word);
+            assert_eq!(pack(tag, payload), word);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps the tag")]
+    #[cfg(debug_assertions)]
+    fn test_pack_overflow() {
+        pack(Tag::Var, PAYLOAD_MASK + 1);
+    }
+
+    #[test]
+    fn test_pack_ptr() {
+        for index in [0, 1, INDEX_MASK - 1, INDEX_MASK] {
+            for gen in [0, 1, GEN_MASK - 1, GEN_MASK] {
+                let bits = pack_ptr(index, gen);
+                assert!(bits <= PAYLOAD_MASK);
+                let expected = match cfg!(feature = "wide-ptrs") {
+                    true => GEN_MASK,
+                    false => gen,
+                };
+                assert_eq!(unpack_ptr(bits), (index, expected));
+                // the tag leaves the pointer alone
+                for tag in TAGS {
+                    assert_eq!(unpack(pack(tag, bits)), (tag, bits));
+                }
+            }
+        }
+        // a generation past its bits wraps instead of reaching the tag
+        assert_eq!(unpack_ptr(pack_ptr(7, u32::MAX)).0, 7);
+    }
+}
@@ -0,0 +1,212 @@
+//! A definition compiled once and called like a Rust function: its head
+//! ports but the last one take the arguments, in order, and the last one
+//! gives the result, as with a call `name(args)` in source.
+//!
+//! ```text
+//! def add(a, b, r) = a ~ (+ b r)    add.call(&[2, 3]) == 5
+//! ```
+//!
+//! Every call copies the compiled template into a net of its own, so calls
+//! are independent and can run from several threads at once. The nets and
+//! runtimes are pooled and reused from call to call, a net being cleared
+//! once its call returns.
+
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+
+use super::{
+    ast::{AstError, Book},
+    net::{Net, NetBuilder},
+    readback::{NotNormal, OwnedTerm},
+    refs::Refs,
+    runtime::{EvalError, Runtime, RuntimeConfig},
+    template::Template,
+};
+
+/// Why [`CompiledNet::call`] did not return a result.
+#[derive(Debug)]
+pub enum CallError {
+    /// The definition takes `expected` arguments.
+    Arity { expected: usize, found: usize },
+    /// An argument could not be built, like a term with an unpaired var.
+    Ast(AstError),
+    Eval(EvalError),
+    /// The result did not reduce to a term that can be read back.
+    NotNormal(NotNormal),
+}
+
+impl Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallError::Arity { expected, found } => {
+                write!(f, "expected {} arguments, found {}", expected, found)
+            }
+            CallError::Ast(err) => write!(f, "{}", err),
+            CallError::Eval(err) => write!(f, "{}", err),
+            CallError::NotNormal(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+impl From<AstError> for CallError {
+    fn from(err: AstError) -> Self {
+        CallError::Ast(err)
+    }
+}
+
+impl From<EvalError> for CallError {
+    fn from(err: EvalError) -> Self {
+        CallError::Eval(err)
+    }
+}
+
+impl From<NotNormal> for CallError {
+    fn from(err: NotNormal) -> Self {
+        CallError::NotNormal(err)
+    }
+}
+
+/// A definition ready to be called, see the [module docs](self).
+pub struct CompiledNet {
+    template: Template,
+    // the definitions the template references
+    refs: Arc<Refs>,
+    config: RuntimeConfig,
+    // the nets and runtimes not in use by a call
+    scratch: Mutex<Vec<(Net, Runtime)>>,
+}
+
+impl CompiledNet {
+    /// Compile the definition `name` of `book`, with the definitions it
+    /// references. It must be closed and have at least one head port.
+    pub fn new(book: &Book, name: &str) -> Result<Self, AstError> {
+        let book = book.desugar()?;
+        let Some(def) = book.defs.iter().find(|def| def.name == name) else {
+            return Err(AstError::UnknownDef(name.to_string()));
+        };
+        if def.net.head.is_empty() {
+            return Err(AstError::CallArity(name.to_string(), 0, 0));
+        }
+        let mut net = Net::new();
+        net.define_refs(&book)?;
+        let template = Template::from_ast_with_refs(&def.net, &net.refs)?;
+        Ok(Self {
+            template,
+            refs: net.refs,
+            config: RuntimeConfig::default(),
+            scratch: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Evaluate the calls with `config` instead of the default one.
+    pub fn with_config(mut self, config: RuntimeConfig) -> Self {
+        self.config = config;
+        self.scratch.get_mut().unwrap().clear();
+        self
+    }
+
+    /// The number of arguments the definition takes.
+    pub fn arity(&self) -> usize {
+        self.template.ports() - 1
+    }
+
+    /// Evaluate the definition on `args`, returning its result in normal
+    /// form. Every var of an argument must be paired within it.
+    pub fn call(&self, args: &[OwnedTerm]) -> Result<OwnedTerm, CallError> {
+        if args.len() != self.arity() {
+            return Err(CallError::Arity {
+                expected: self.arity(),
+                found: args.len(),
+            });
+        }
+        let (mut net, mut runtime) = self
+            .scratch
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| (Net::new(), Runtime::with_config(self.config.clone())));
+        let result = self.call_in(&mut net, &mut runtime, args);
+        net.clear();
+        self.scratch.lock().unwrap().push((net, runtime));
+        result
+    }
+
+    fn call_in(
+        &self,
+        net: &mut Net,
+        runtime: &mut Runtime,
+        args: &[OwnedTerm],
+    ) -> Result<OwnedTerm, CallError> {
+        net.refs = self.refs.clone();
+        let mut ports = self.template.clone_into(net);
+        let result = ports.pop().expect("the definition has a head port");
+        for (port, arg) in ports.into_iter().zip(args) {
+            let arg = net.add_term(arg)?;
+            net.eqn(port, arg);
+        }
+        net.head(result);
+        runtime.eval_in(net)?;
+        Ok(net.normal_form()?.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        ast::{AstError, Term},
+        parser::parse_ast,
+    };
+
+    use super::{CallError, CompiledNet};
+
+    #[test]
+    fn test_call() {
+        let book = parse_ast("def add(a, b, r) = a ~ (+ b r)").unwrap();
+        let add = CompiledNet::new(&book, "add").unwrap();
+        assert_eq!(add.arity(), 2);
+        assert_eq!(add.call(&[Term::Num(2), Term::Num(3)]).unwrap(), Term::Num(5));
+        assert_eq!(add.call(&[Term::Num(7), Term::Num(8)]).unwrap(), Term::Num(15));
+        // the net and runtime of the first call were reused by the second one
+        assert_eq!(add.scratch.lock().unwrap().len(), 1);
+        let add = &add;
+        std::thread::scope(|scope| {
+            let calls: Vec<_> = (0..4)
+                .map(|n| scope.spawn(move || add.call(&[Term::Num(n), Term::Num(1)]).unwrap()))
+                .collect();
+            for (n, call) in calls.into_iter().enumerate() {
+                assert_eq!(call.join().unwrap(), Term::Num(n as u32 + 1));
+            }
+        });
+
+        assert!(matches!(
+            add.call(&[Term::Num(2)]),
+            Err(CallError::Arity {
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            add.call(&[Term::Num(2), Term::var("x")]),
+            Err(CallError::Ast(AstError::UnpairedVar(_)))
+        ));
+        assert_eq!(
+            CompiledNet::new(&book, "sub").err(),
+            Some(AstError::UnknownDef("sub".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_call_refs() {
+        let src = format!(
+            "{};\ndef fact_of(k, f) = @fact ~ [k f]",
+            include_str!("../../tests/fixtures/factorial.strandal")
+        );
+        let fact = CompiledNet::new(&parse_ast(&src).unwrap(), "fact_of").unwrap();
+        let results = [0, 5, 10].map(|n| fact.call(&[Term::Num(n)]).unwrap());
+        assert_eq!(results, [1, 120, 3628800].map(Term::Num));
+    }
+}
@@ -0,0 +1,394 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+};
+
+use super::{
+    display::CellDisplay,
+    refs::{RefId, Refs},
+    stats::GlobalStats,
+    store::{Index, Ptr, Store},
+    term::Cell,
+};
+
+/// A condition under which evaluation pauses and hands control to the
+/// [`Debugger`] callback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stop when a cell built by the named definition interacts.
+    ///
+    /// A definition loaded into the net up front fires every time one of the
+    /// cells allocated while loading it takes part in an interaction. A
+    /// definition referenced as `@name` is only instantiated lazily, so it
+    /// fires every time one of its references is expanded, recursive calls
+    /// included; copying or erasing a reference does not expand it.
+    Definition(String),
+    /// Stop when the cell at the given pointer interacts.
+    Cell(Ptr),
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Definition(name) => write!(f, "def {}", name),
+            Breakpoint::Cell(ptr) => write!(f, "cell {}", ptr),
+        }
+    }
+}
+
+/// One side of an active pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Agent {
+    Era,
+    /// A cell and its store pointer, if it lives in the store (cells created
+    /// by commutations may only exist on the stack).
+    Cell(Option<Ptr>, Cell),
+}
+
+impl Agent {
+    pub fn ptr(&self) -> Option<Ptr> {
+        match self {
+            Agent::Era => None,
+            Agent::Cell(ptr, _) => *ptr,
+        }
+    }
+}
+
+/// What the runtime should do once the [`Debugger`] callback returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// Pause again at the next interaction.
+    Step,
+    /// Run until the next breakpoint is hit.
+    Continue,
+}
+
+/// A snapshot of the equation being reduced when evaluation pauses.
+///
+/// The cells are copies taken before the interaction is performed, and the
+/// store can be used to inspect the terms connected to their ports.
+pub struct EquationView<'a> {
+    /// The breakpoint that was hit, or `None` when pausing after a step.
+    pub breakpoint: Option<&'a Breakpoint>,
+    pub store: &'a Store,
+    /// Global stats, including only the tasks that already completed.
+    pub stats: &'a GlobalStats,
+    pub left: Agent,
+    pub right: Agent,
+    debugger: &'a Debugger,
+}
+
+impl<'a> EquationView<'a> {
+    /// Equations spawned as tasks that did not start running yet.
+    pub fn pending_equations(&self) -> Vec<String> {
+        self.debugger
+            .pending
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+impl<'a> Display for EquationView<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.breakpoint {
+            Some(breakpoint) => write!(f, "[{}] ", breakpoint)?,
+            None => write!(f, "[step] ")?,
+        }
+        write!(
+            f,
+            "{} ⋈ {}",
+            AgentDisplay(self.store, &self.left),
+            AgentDisplay(self.store, &self.right)
+        )
+    }
+}
+
+struct AgentDisplay<'a>(&'a Store, &'a Agent);
+impl<'a> Display for AgentDisplay<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.1 {
+            Agent::Era => write!(f, "{}", CellDisplay::ERA_SYMBOL),
+            Agent::Cell(ptr, cell) => CellDisplay(self.0, *ptr, cell).fmt(f),
+        }
+    }
+}
+
+/// Breakpoints plus the callback invoked when evaluation pauses.
+///
+/// The callback runs on the worker thread that reached the breakpoint. While
+/// it runs, every other worker blocks before starting its next interaction.
+/// Workers already in the middle of an interaction are not waited for and
+/// keep rewriting the store, so the terms read through [`EquationView::store`]
+/// may change under the callback's feet; only the two agents are a stable
+/// snapshot.
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    on_break: Box<dyn Fn(&EquationView) -> Resume + Send + Sync>,
+    // pointer ranges that trigger each breakpoint, resolved before eval
+    resolved: Vec<(usize, Range<Index>)>,
+    // references whose expansion triggers each breakpoint
+    expanded: Vec<(usize, RefId)>,
+    gate: RwLock<()>,
+    stepping: AtomicBool,
+    // only tracked while debugging: rendering every spawned task is not cheap
+    pending: Mutex<BTreeMap<u64, String>>,
+    next_pending: AtomicU64,
+}
+
+impl Debugger {
+    pub fn new<F>(on_break: F) -> Self
+    where
+        F: Fn(&EquationView) -> Resume + Send + Sync + 'static,
+    {
+        Debugger {
+            breakpoints: Vec::new(),
+            on_break: Box::new(on_break),
+            resolved: Vec::new(),
+            expanded: Vec::new(),
+            gate: RwLock::new(()),
+            stepping: AtomicBool::new(false),
+            pending: Mutex::new(BTreeMap::new()),
+            next_pending: AtomicU64::new(0),
+        }
+    }
+
+    pub fn breakpoint(mut self, breakpoint: Breakpoint) -> Self {
+        self.breakpoints.push(breakpoint);
+        self
+    }
+
+    /// Pause at the very first interaction.
+    pub fn step(self) -> Self {
+        self.stepping.store(true, Ordering::Relaxed);
+        self
+    }
+
+    /// Map every breakpoint to the store pointers it covers, and definition
+    /// breakpoints to the references expanding them. Definition breakpoints
+    /// naming an unknown definition never fire.
+    pub(crate) fn resolve(
+        &mut self,
+        definitions: &HashMap<String, Vec<Range<Index>>>,
+        refs: &Refs,
+    ) {
+        self.resolved = self
+            .breakpoints
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, bp)| match bp {
+                Breakpoint::Definition(name) => definitions
+                    .get(name)
+                    .into_iter()
+                    .flatten()
+                    .map(|range| (idx, range.clone()))
+                    .collect(),
+                Breakpoint::Cell(ptr) => vec![(idx, ptr.index()..ptr.index() + 1)],
+            })
+            .collect();
+        self.expanded = self
+            .breakpoints
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, bp)| match bp {
+                Breakpoint::Definition(name) => refs.id(name).map(|id| (idx, id)),
+                Breakpoint::Cell(_) => None,
+            })
+            .collect();
+    }
+
+    // `other` is the agent `agent` interacts with, telling whether a
+    // reference is expanded rather than copied or erased
+    fn hit(&self, agent: &Agent, other: &Agent) -> Option<&Breakpoint> {
+        match (agent, other) {
+            (Agent::Cell(_, Cell::Ref(id)), Agent::Cell(_, other))
+                if !matches!(other, Cell::Dup(..)) =>
+            {
+                if let Some((idx, _)) = self.expanded.iter().find(|(_, ref_id)| ref_id == id) {
+                    return Some(&self.breakpoints[*idx]);
+                }
+            }
+            _ => {}
+        }
+        let ptr = agent.ptr()?;
+        self.resolved
+            .iter()
+            .find(|(_, range)| range.contains(&ptr.index()))
+            .map(|(idx, _)| &self.breakpoints[*idx])
+    }
+
+    /// Called by the runtime right before `left` and `right` interact.
+    pub(crate) fn on_interaction(
+        &self,
+        store: &Store,
+        stats: &GlobalStats,
+        left: Agent,
+        right: Agent,
+    ) {
+        let breakpoint = self
+            .hit(&left, &right)
+            .or_else(|| self.hit(&right, &left));
+        if breakpoint.is_some() || self.stepping.load(Ordering::Relaxed) {
+            let _paused = self.gate.write().unwrap();
+            let resume = (self.on_break)(&EquationView {
+                breakpoint,
+                store,
+                stats,
+                left,
+                right,
+                debugger: self,
+            });
+            self.stepping
+                .store(resume == Resume::Step, Ordering::Relaxed);
+        } else {
+            // wait for any pause in progress to finish
+            drop(self.gate.read().unwrap());
+        }
+    }
+
+    /// Record a spawned equation until its task starts running.
+    pub(crate) fn push_pending(&self, equation: String) -> u64 {
+        let id = self.next_pending.fetch_add(1, Ordering::Relaxed);
+        self.pending.lock().unwrap().insert(id, equation);
+        id
+    }
+
+    pub(crate) fn pop_pending(&self, id: u64) {
+        self.pending.lock().unwrap().remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::strandal::{
+        debugger::{Breakpoint, Debugger, Resume},
+        net::{Net, NetBuilder},
+        parser::parse,
+        runtime::Runtime,
+        term::TermPtr,
+    };
+
+    #[test]
+    fn test_cell_breakpoint() {
+        let mut net = Net::new();
+        let r = net.var();
+        let i1_var = net.var();
+        let i1 = net.lam(i1_var.0, i1_var.1);
+        let i2_var = net.var();
+        let i2 = net.lam(i2_var.0, i2_var.1);
+        let app = net.app(r.0, i2);
+        let app_ptr = app.ptr();
+        net.head(r.1);
+        net.eqn(i1, app);
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let debugger = Debugger::new(move |view| {
+            assert_eq!(view.breakpoint, Some(&Breakpoint::Cell(app_ptr)));
+            assert_eq!(
+                view.to_string(),
+                "[cell #5] (λ.2 x.1 x.1) ⋈ (@.5 x.0 (λ.4 x.3 x.3))"
+            );
+            counter.fetch_add(1, Ordering::Relaxed);
+            Resume::Continue
+        })
+        .breakpoint(Breakpoint::Cell(app_ptr));
+
+        let mut runtime = Runtime::with_debugger(debugger);
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(hits.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_definition_breakpoint() {
+        let mut net = Net::new();
+        assert!(parse(
+            "def a(R) = * ~ (x x) ; def b(S) = (y y) ~ [S *]",
+            &mut net
+        ));
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let debugger = Debugger::new(move |view| {
+            assert_eq!(
+                view.breakpoint,
+                Some(&Breakpoint::Definition("b".to_string()))
+            );
+            counter.fetch_add(1, Ordering::Relaxed);
+            Resume::Continue
+        })
+        .breakpoint(Breakpoint::Definition("b".to_string()))
+        .breakpoint(Breakpoint::Definition("missing".to_string()));
+
+        let mut runtime = Runtime::with_debugger(debugger);
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(hits.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_recursive_definition_breakpoint() {
+        // `count` is only instantiated by expanding its references, once per
+        // number down to zero
+        let mut net = Net::new();
+        assert!(parse(
+            "
+            def main(r) = @count ~ [5 r] ;
+            def count((n r)) = n ~ (? (0 @step) r) ;
+            def step((p s)) = @count ~ [p s]
+            ",
+            &mut net
+        ));
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let debugger = Debugger::new(move |view| {
+            assert_eq!(
+                view.breakpoint,
+                Some(&Breakpoint::Definition("count".to_string()))
+            );
+            counter.fetch_add(1, Ordering::Relaxed);
+            Resume::Continue
+        })
+        .breakpoint(Breakpoint::Definition("count".to_string()));
+
+        let mut runtime = Runtime::with_debugger(debugger);
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(hits.load(Ordering::Relaxed), 6);
+        assert_eq!(runtime.stats.expand_ref(), 6 + 5);
+    }
+
+    #[test]
+    fn test_step() {
+        let mut net = Net::new();
+        assert!(parse(
+            "def a(R) = * ~ (x x) ; def b(S) = (y y) ~ [S *]",
+            &mut net
+        ));
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let debugger = Debugger::new(move |view| {
+            assert_eq!(view.breakpoint, None);
+            counter.fetch_add(1, Ordering::Relaxed);
+            Resume::Step
+        })
+        .step();
+
+        let mut runtime = Runtime::with_debugger(debugger);
+        runtime.eval(&mut net).unwrap();
+        // ERA-LAM in `a` and APP-LAM in `b`
+        assert_eq!(hits.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,237 @@
+//! Compare two net dumps slot by slot, for instance the net before and after
+//! an eval, or the same program compiled by two versions, and draw the
+//! differences as a Graphviz DOT graph:
+//!
+//! ```text
+//! strandal bench prog.strandal --dump-before before.dump --dump after.dump
+//! strandal diff before.dump after.dump | dot -Tsvg > diff.svg
+//! ```
+//!
+//! Slots only in the second dump are green, slots only in the first one are
+//! red and dashed, and slots whose content changed are yellow.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    io::{self, Write},
+};
+
+use super::{
+    dump::{Dump, Slot},
+    store::{Index, Ptr},
+    term::TermPtr,
+};
+
+/// How a store slot differs between two dumps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotChange {
+    Same(Slot),
+    Added(Slot),
+    Removed(Slot),
+    Changed(Slot, Slot),
+}
+
+impl SlotChange {
+    /// The slot as of the second dump, or the first one if it was removed.
+    pub fn slot(&self) -> &Slot {
+        match self {
+            SlotChange::Same(slot)
+            | SlotChange::Added(slot)
+            | SlotChange::Removed(slot)
+            | SlotChange::Changed(_, slot) => slot,
+        }
+    }
+
+    pub fn is_same(&self) -> bool {
+        matches!(self, SlotChange::Same(_))
+    }
+
+    fn style(&self) -> &'static str {
+        match self {
+            SlotChange::Same(_) => "fillcolor=white",
+            SlotChange::Added(_) => "fillcolor=palegreen",
+            SlotChange::Removed(_) => "fillcolor=lightpink, style=\"filled,dashed\"",
+            SlotChange::Changed(_, _) => "fillcolor=gold",
+        }
+    }
+}
+
+/// The slot by slot difference between two dumps, see the
+/// [module docs](self).
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct NetDiff {
+    pub slots: BTreeMap<Index, SlotChange>,
+    /// The heads of the second dump.
+    pub heads: Vec<TermPtr>,
+}
+
+impl NetDiff {
+    pub fn new(before: &Dump, after: &Dump) -> Self {
+        let indices: BTreeSet<Index> = before
+            .slots
+            .keys()
+            .chain(after.slots.keys())
+            .copied()
+            .collect();
+        let slots = indices
+            .into_iter()
+            .map(|idx| {
+                let change = match (before.slots.get(&idx), after.slots.get(&idx)) {
+                    (Some(old), Some(new)) if old == new => SlotChange::Same(*new),
+                    (Some(old), Some(new)) => SlotChange::Changed(*old, *new),
+                    (None, Some(new)) => SlotChange::Added(*new),
+                    (Some(old), None) => SlotChange::Removed(*old),
+                    (None, None) => unreachable!("the index comes from a dump"),
+                };
+                (idx, change)
+            })
+            .collect();
+        NetDiff {
+            slots,
+            heads: after.heads.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.values().all(SlotChange::is_same)
+    }
+
+    /// Write the diff as a DOT graph. With `changed_only`, unchanged slots
+    /// are left out unless they point to or from a changed one.
+    pub fn write_dot(&self, out: &mut impl Write, changed_only: bool) -> io::Result<()> {
+        let shown: BTreeSet<Index> = match changed_only {
+            false => self.slots.keys().copied().collect(),
+            true => self
+                .slots
+                .iter()
+                .filter(|(_, change)| !change.is_same())
+                .flat_map(|(idx, change)| {
+                    let targets = change.slot().targets().into_iter().map(|ptr| ptr.index());
+                    let referrers = self
+                        .slots
+                        .iter()
+                        .filter(|(_, other)| other.slot().targets().contains(&Ptr::new(*idx)))
+                        .map(|(other, _)| *other);
+                    std::iter::once(*idx)
+                        .chain(targets)
+                        .chain(referrers)
+                        .collect::<Vec<_>>()
+                })
+                .collect(),
+        };
+
+        writeln!(out, "digraph diff {{")?;
+        writeln!(out, "  node [shape=box, style=filled, fontname=monospace];")?;
+        for (idx, head) in self.heads.iter().enumerate() {
+            writeln!(out, "  h{} [label=\"head {}\", shape=plaintext];", idx, idx)?;
+            if let TermPtr::Ptr(ptr) = head {
+                if shown.contains(&ptr.index()) {
+                    writeln!(out, "  h{} -> s{};", idx, ptr.index())?;
+                }
+            }
+        }
+        for idx in &shown {
+            let Some(change) = self.slots.get(idx) else {
+                continue;
+            };
+            writeln!(
+                out,
+                "  s{} [label=\"{}\", {}];",
+                idx,
+                SlotLabel(*idx, change),
+                change.style()
+            )?;
+            for target in change.slot().targets() {
+                if shown.contains(&target.index()) {
+                    writeln!(out, "  s{} -> s{};", idx, target.index())?;
+                }
+            }
+        }
+        writeln!(out, "}}")
+    }
+}
+
+impl Display for NetDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut counts = [0; 4];
+        for change in self.slots.values() {
+            let kind = match change {
+                SlotChange::Same(_) => 0,
+                SlotChange::Added(_) => 1,
+                SlotChange::Removed(_) => 2,
+                SlotChange::Changed(_, _) => 3,
+            };
+            counts[kind] += 1;
+        }
+        write!(
+            f,
+            "DIFF    | same: {}, added: {}, removed: {}, changed: {}",
+            counts[0], counts[1], counts[2], counts[3]
+        )
+    }
+}
+
+struct SlotLabel<'a>(Index, &'a SlotChange);
+
+impl Display for SlotLabel<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.1 {
+            SlotChange::Changed(old, new) => write!(f, "{}: {}\\n→ {}", self.0, old, new),
+            change => write!(f, "{}: {}", self.0, change.slot()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        diff::{NetDiff, SlotChange},
+        dump::{Dump, Slot},
+        net::{Net, NetBuilder},
+        runtime::Runtime,
+        term::TermPtr,
+    };
+
+    fn dump(net: &Net) -> Dump {
+        let mut out = Vec::new();
+        net.dump_to(&mut out).unwrap();
+        Dump::parse(&String::from_utf8(out).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_diff() {
+        // (λx.x) *
+        let mut net = Net::new();
+        let x = net.var();
+        let id = net.lam(x.0, x.1);
+        let id_idx = id.ptr().index();
+        let r = net.var();
+        let r_idx = r.1.ptr().index();
+        let app = net.app(r.0, TermPtr::Era);
+        net.head(r.1);
+        net.eqn(id, app);
+        let before = dump(&net);
+        assert!(NetDiff::new(&before, &before).is_empty());
+
+        Runtime::new().eval(&mut net).unwrap();
+        let after = dump(&net);
+        let diff = NetDiff::new(&before, &after);
+        assert!(!diff.is_empty());
+        assert!(matches!(
+            diff.slots[&id_idx],
+            SlotChange::Removed(Slot::Cell(_))
+        ));
+        assert!(matches!(
+            diff.slots[&r_idx],
+            SlotChange::Changed(Slot::Var(None), Slot::Var(Some(_)))
+        ));
+
+        let mut out = Vec::new();
+        diff.write_dot(&mut out, false).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(dot.starts_with("digraph diff {"));
+        assert!(dot.contains(&format!("s{} [label=\"{}: lam", id_idx, id_idx)));
+        assert!(dot.contains("fillcolor=gold"));
+        assert!(dot.contains(&format!("h0 -> s{};", r_idx)));
+    }
+}
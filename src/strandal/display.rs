@@ -1,9 +1,11 @@
-use std::fmt::Display;
+use std::{collections::HashSet, fmt::Display};
 
 use super::{
-    store::{Ptr, Store},
-    term::{Cell, Term, TermPtr},
-    var::Var,
+    net::Net,
+    num::Op,
+    store::{Index, Ptr, Store},
+    term::{Cell, Label, TermPtr, TermRef},
+    var::{Var, VarValue},
 };
 
 pub struct VarDisplay<'a>(pub Ptr, pub &'a Var);
@@ -18,8 +20,8 @@ pub struct CellPtrDisplay<'a>(pub &'a Store, pub Ptr);
 impl<'a> Display for CellPtrDisplay<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.0.get(self.1) {
-            Some(Term::Cell(cell)) => CellDisplay(self.0, Some(self.1), cell).fmt(f),
-            Some(Term::Var(var)) => VarDisplay(self.1, var).fmt(f),
+            Some(TermRef::Cell(cell)) => CellDisplay(self.0, Some(self.1), &cell).fmt(f),
+            Some(TermRef::Var(var)) => VarDisplay(self.1, var).fmt(f),
             None => write!(f, "<n/a>"),
         }
     }
@@ -31,6 +33,18 @@ impl<'a> CellDisplay<'a> {
     pub const DUP_SYMBOL: &'static str = "δ";
     pub const APP_SYMBOL: &'static str = "@";
     pub const LAM_SYMBOL: &'static str = "λ";
+    pub const SWI_SYMBOL: &'static str = "?";
+    /// Prefixes a reference, as in `@name`; in the store, as in `@3`, by id.
+    pub const REF_SYMBOL: &'static str = "@";
+    /// Marks an operator waiting for its right operand, as in `:-`.
+    pub const FLIPPED_MARK: &'static str = ":";
+
+    pub fn op_symbol(op: Op, flipped: bool) -> String {
+        match flipped {
+            false => op.symbol().to_string(),
+            true => format!("{}{}", CellDisplay::FLIPPED_MARK, op.symbol()),
+        }
+    }
 }
 
 impl<'a> Display for CellDisplay<'a> {
@@ -47,19 +61,36 @@ impl<'a> Display for CellDisplay<'a> {
             Cell::Lam(ports) => {
                 display_cell(self.0, f, CellDisplay::LAM_SYMBOL, ports, &None, self.1)
             }
+
+            Cell::Num(value) => write!(f, "{}", value),
+
+            Cell::Op(ports, op, flipped) => display_cell(
+                self.0,
+                f,
+                &CellDisplay::op_symbol(*op, *flipped),
+                ports,
+                &None,
+                self.1,
+            ),
+
+            Cell::Swi(ports) => {
+                display_cell(self.0, f, CellDisplay::SWI_SYMBOL, ports, &None, self.1)
+            }
+
+            Cell::Ref(id) => write!(f, "{}{}", CellDisplay::REF_SYMBOL, id),
         }
     }
 }
 
-pub struct TermDisplay<'a>(&'a Store, &'a TermPtr);
+pub struct TermDisplay<'a>(pub &'a Store, pub &'a TermPtr);
 
 impl<'a> Display for TermDisplay<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.1 {
             TermPtr::Era => write!(f, "{}", CellDisplay::ERA_SYMBOL),
             TermPtr::Ptr(ptr) => match self.0.get(*ptr) {
-                Some(Term::Cell(cell)) => CellDisplay(self.0, Some(*ptr), cell).fmt(f),
-                Some(Term::Var(var)) => VarDisplay(*ptr, var).fmt(f),
+                Some(TermRef::Cell(cell)) => CellDisplay(self.0, Some(*ptr), &cell).fmt(f),
+                Some(TermRef::Var(var)) => VarDisplay(*ptr, var).fmt(f),
                 None => write!(f, "<n/a>"),
             },
         }
@@ -69,9 +100,9 @@ impl<'a> Display for TermDisplay<'a> {
 fn display_cell<'a>(
     store: &'a Store,
     f: &mut std::fmt::Formatter<'_>,
-    symbol: &'static str,
+    symbol: &str,
     ports: &Option<(TermPtr, TermPtr)>,
-    lbl: &Option<Ptr>,
+    lbl: &Option<Label>,
     ptr: Option<Ptr>,
 ) -> std::fmt::Result {
     match ports {
@@ -84,7 +115,7 @@ fn display_cell<'a>(
                     ptr.index(),
                     TermDisplay(store, p0),
                     TermDisplay(store, p1),
-                    lbl.index()
+                    lbl
                 ),
                 None => write!(
                     f,
@@ -102,7 +133,7 @@ fn display_cell<'a>(
                     symbol,
                     TermDisplay(store, p0),
                     TermDisplay(store, p1),
-                    lbl.index()
+                    lbl
                 ),
                 None => write!(
                     f,
@@ -119,3 +150,181 @@ fn display_cell<'a>(
         },
     }
 }
+
+/// Renders the heads, the pending equations and every bound var of the net.
+///
+/// Cells are expanded at most once: cells reached again (shared through vars
+/// or cyclic) are printed as a `#ptr` reference. Expansion stops after
+/// [`NetDisplay::LIMIT`] cells, or after as many cells as the formatter
+/// precision asks for (`{:.100}`).
+impl Display for Net {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let limit = f.precision().unwrap_or(NetDisplay::LIMIT);
+        NetDisplay {
+            store: &self.store,
+            printed: HashSet::new(),
+            limit,
+            truncated: false,
+        }
+        .fmt_net(f, self)
+    }
+}
+
+pub struct NetDisplay<'a> {
+    store: &'a Store,
+    printed: HashSet<Index>,
+    limit: usize,
+    truncated: bool,
+}
+
+impl<'a> NetDisplay<'a> {
+    pub const LIMIT: usize = 1_000;
+
+    fn fmt_net(&mut self, f: &mut std::fmt::Formatter<'_>, net: &Net) -> std::fmt::Result {
+        write!(f, "head:")?;
+        for head in &net.head {
+            write!(f, " ")?;
+            self.fmt_term(f, head)?;
+        }
+
+        write!(f, "\nbody:")?;
+        for (eqn, kind) in &net.body {
+            write!(f, "\n  {:<7} ", kind.name())?;
+            self.fmt_term(f, &eqn.left())?;
+            write!(f, " ~ ")?;
+            self.fmt_term(f, &eqn.right())?;
+        }
+
+        write!(f, "\nvars:")?;
+        for ptr in self.store.ptrs() {
+            let Some(TermRef::Var(var)) = self.store.get(ptr) else {
+                continue;
+            };
+            let Some(value) = var.read() else {
+                continue;
+            };
+            write!(f, "\n  {} = ", VarDisplay(ptr, var))?;
+            match value {
+                VarValue::Era => write!(f, "{}", CellDisplay::ERA_SYMBOL)?,
+                VarValue::Var(other) => write!(f, "x.{}", other.index())?,
+                VarValue::Cell(cell_ptr) => self.fmt_term(f, &TermPtr::Ptr(cell_ptr))?,
+            }
+        }
+
+        write!(
+            f,
+            "\nstore: {} live, {} allocated",
+            self.store.len(),
+            self.store.next()
+        )?;
+        if self.truncated {
+            write!(f, " (output truncated after {} cells)", self.limit)?;
+        }
+        Ok(())
+    }
+
+    fn fmt_term(
+        &mut self,
+        f: &mut std::fmt::Formatter<'_>,
+        term_ptr: &TermPtr,
+    ) -> std::fmt::Result {
+        match term_ptr {
+            TermPtr::Era => write!(f, "{}", CellDisplay::ERA_SYMBOL),
+            TermPtr::Ptr(ptr) if !self.store.handed_out(*ptr) => write!(f, "<n/a>"),
+            TermPtr::Ptr(ptr) => match self.store.get(*ptr) {
+                Some(TermRef::Cell(cell)) => self.fmt_cell(f, *ptr, &cell),
+                Some(TermRef::Var(var)) => VarDisplay(*ptr, var).fmt(f),
+                None => write!(f, "<n/a>"),
+            },
+        }
+    }
+
+    fn fmt_cell(
+        &mut self,
+        f: &mut std::fmt::Formatter<'_>,
+        ptr: Ptr,
+        cell: &Cell,
+    ) -> std::fmt::Result {
+        if self.printed.contains(&ptr.index()) {
+            return write!(f, "{}", ptr);
+        }
+        if self.printed.len() >= self.limit {
+            self.truncated = true;
+            return write!(f, "…");
+        }
+        self.printed.insert(ptr.index());
+
+        let (symbol, ports, lbl) = match cell {
+            Cell::Dup(ports, lbl) => (CellDisplay::DUP_SYMBOL.to_string(), ports, lbl),
+            Cell::App(ports) => (CellDisplay::APP_SYMBOL.to_string(), ports, &None),
+            Cell::Lam(ports) => (CellDisplay::LAM_SYMBOL.to_string(), ports, &None),
+            Cell::Num(value) => return write!(f, "{}", value),
+            Cell::Ref(id) => return write!(f, "{}{}", CellDisplay::REF_SYMBOL, id),
+            Cell::Op(ports, op, flipped) => (CellDisplay::op_symbol(*op, *flipped), ports, &None),
+            Cell::Swi(ports) => (CellDisplay::SWI_SYMBOL.to_string(), ports, &None),
+        };
+        match ports {
+            Some((p0, p1)) => {
+                write!(f, "({}.{} ", symbol, ptr.index())?;
+                self.fmt_term(f, p0)?;
+                write!(f, " ")?;
+                self.fmt_term(f, p1)?;
+                if let Some(lbl) = lbl {
+                    write!(f, " {{{lbl}}}")?;
+                }
+                write!(f, ")")
+            }
+            None => write!(f, "({}.{} ⊢ ⊣)", symbol, ptr.index()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        runtime::Runtime,
+        term::TermPtr,
+    };
+
+    #[test]
+    fn test_net_display() {
+        let mut net = Net::new();
+        let r = net.var();
+        let i1_var = net.var();
+        let i1 = net.lam(i1_var.0, i1_var.1);
+        let i2_var = net.var();
+        let i2 = net.lam(i2_var.0, i2_var.1);
+        let app = net.app(r.0, i2);
+        net.head(r.1);
+        net.eqn(i1, app);
+
+        assert_eq!(
+            net.to_string(),
+            "head: x.0\n\
+             body:\n  REDEX   (λ.2 x.1 x.1) ~ (@.5 x.0 (λ.4 x.3 x.3))\n\
+             vars:\n\
+             store: 6 live, 6 allocated"
+        );
+
+        Runtime::new().eval(&mut net).unwrap();
+        let display = net.to_string();
+        assert!(display.starts_with("head: x.0\nbody:\nvars:\n"));
+        assert!(display.contains("(λ.4 x.3 x.3)"));
+    }
+
+    #[test]
+    fn test_net_display_limit() {
+        let mut net = Net::new();
+        let mut tree = TermPtr::Era;
+        for _ in 0..10 {
+            tree = net.lam(TermPtr::Era, tree).into();
+        }
+        net.head(tree);
+
+        let display = format!("{:.3}", net);
+        assert!(display.starts_with("head: (λ.9 ε (λ.8 ε (λ.7 ε …)))"));
+        assert!(display.ends_with("(output truncated after 3 cells)"));
+        assert!(!net.to_string().contains('…'));
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use super::{
+    store::Index,
+    term::Label,
+};
+
+// dup labels are counted in this many buckets, by index
+const LABEL_BUCKETS: usize = 64;
+
+/// When [`RuntimeConfig::detect_divergence`](super::runtime::RuntimeConfig::detect_divergence)
+/// warns that an eval is likely divergent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DivergenceConfig {
+    /// Warn once the live slots grow past this many times the slots live
+    /// when the eval started.
+    pub growth_factor: Index,
+    /// Never warn about growth below this many live slots.
+    pub min_live: Index,
+    /// Warn once a single dup label takes part in this share of the
+    /// interactions of labelled dups...
+    pub label_share: f64,
+    /// ...after at least this many of them.
+    pub min_label_interactions: usize,
+    /// Stop the eval on the first warning, failing with
+    /// [`EvalError::LikelyDivergent`](super::runtime::EvalError::LikelyDivergent).
+    pub abort: bool,
+}
+
+impl Default for DivergenceConfig {
+    fn default() -> Self {
+        Self {
+            growth_factor: 16,
+            min_live: 1 << 16,
+            label_share: 0.9,
+            min_label_interactions: 1 << 20,
+            abort: false,
+        }
+    }
+}
+
+/// A pattern suggesting an eval will not terminate, with its evidence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DivergenceWarning {
+    /// The net kept growing.
+    Growth {
+        initial: Index,
+        live: Index,
+        /// Commutation counts (the rules that grow the net), largest first.
+        growth: Vec<(&'static str, usize)>,
+    },
+    /// One dup label drives most interactions, as when a term keeps
+    /// duplicating itself.
+    DominantLabel {
+        label: Label,
+        interactions: usize,
+        total: usize,
+    },
+}
+
+impl Display for DivergenceWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DivergenceWarning::Growth {
+                initial,
+                live,
+                growth,
+            } => {
+                write!(
+                    f,
+                    "likely divergent: the net grew from {} to {} live slots, driven by ",
+                    initial, live
+                )?;
+                for (idx, (rule, count)) in growth.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", rule, count)?;
+                }
+                Ok(())
+            }
+            DivergenceWarning::DominantLabel {
+                label,
+                interactions,
+                total,
+            } => write!(
+                f,
+                "likely divergent: dup label {} took part in {} of {} labelled dup interactions",
+                label, interactions, total
+            ),
+        }
+    }
+}
+
+/// Watches an eval for the patterns of [`DivergenceWarning`].
+pub(crate) struct DivergenceDetector {
+    config: DivergenceConfig,
+    initial: Index,
+    grew: AtomicBool,
+    dominated: AtomicBool,
+    labels: [AtomicUsize; LABEL_BUCKETS],
+    // the last label counted in each bucket
+    bucket_labels: [AtomicU64; LABEL_BUCKETS],
+    total: AtomicUsize,
+    warnings: Mutex<Vec<DivergenceWarning>>,
+}
+
+impl DivergenceDetector {
+    pub fn new(config: DivergenceConfig, initial: Index) -> Self {
+        Self {
+            config,
+            initial,
+            grew: AtomicBool::new(false),
+            dominated: AtomicBool::new(false),
+            labels: std::array::from_fn(|_| AtomicUsize::new(0)),
+            bucket_labels: std::array::from_fn(|_| AtomicU64::new(0)),
+            total: AtomicUsize::new(0),
+            warnings: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn aborts(&self) -> bool {
+        self.config.abort
+    }
+
+    /// Check the live slots, returning the warning the first time they
+    /// grew too much.
+    pub fn check_growth(
+        &self,
+        live: Index,
+        growth: impl FnOnce() -> Vec<(&'static str, usize)>,
+    ) -> Option<DivergenceWarning> {
+        let limit = self
+            .initial
+            .max(self.config.min_live)
+            .saturating_mul(self.config.growth_factor);
+        if live <= limit || self.grew.swap(true, Ordering::Relaxed) {
+            return None;
+        }
+        Some(self.warn(DivergenceWarning::Growth {
+            initial: self.initial,
+            live,
+            growth: growth(),
+        }))
+    }
+
+    /// Count an interaction of a dup with `label`, returning the warning the
+    /// first time a label dominates.
+    pub fn record_label(&self, label: Option<Label>) -> Option<DivergenceWarning> {
+        let label = label?;
+        let bucket = label.index() as usize % LABEL_BUCKETS;
+        let count = self.labels[bucket].fetch_add(1, Ordering::Relaxed) + 1;
+        self.bucket_labels[bucket].store(label.to_bits(), Ordering::Relaxed);
+        let total = self.total.fetch_add(1, Ordering::Relaxed) + 1;
+        if total < self.config.min_label_interactions
+            || (count as f64) < self.config.label_share * total as f64
+            || self.dominated.swap(true, Ordering::Relaxed)
+        {
+            return None;
+        }
+        Some(self.warn(DivergenceWarning::DominantLabel {
+            label: Label::from_bits(self.bucket_labels[bucket].load(Ordering::Relaxed)),
+            interactions: count,
+            total,
+        }))
+    }
+
+    fn warn(&self, warning: DivergenceWarning) -> DivergenceWarning {
+        self.warnings.lock().unwrap().push(warning.clone());
+        warning
+    }
+
+    pub fn warnings(&self) -> Vec<DivergenceWarning> {
+        self.warnings.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,432 @@
+//! A line-oriented dump of every live store slot, written by [`Net::dump`]
+//! and read back by [`Dump::read`] for post-mortem inspection:
+//!
+//! ```text
+//! strandal-dump 1
+//! def main 0 5
+//! head 4
+//! eqn 1 3
+//! 0 var -
+//! 1 lam 0 0
+//! 2 var cell 1
+//! 3 app 2 *
+//! 4 dup * * 4
+//! 5 num 7
+//! 6 op 5 * +
+//! 7 swi 1 *
+//! 8 ref 0
+//! ```
+//!
+//! Ports are `*` for an eraser or the index of a slot. A cell without ports
+//! is written with a single `-`, as is a dup without a label. An operator
+//! ends with its symbol, prefixed with `:` once flipped, and a reference
+//! holds the id of its definition. A var holds
+//! `-` while unbound, then `*`, `var <index>` or `cell <index>`.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::Path,
+};
+
+use super::{
+    display::CellDisplay,
+    net::Net,
+    store::{Index, Ptr},
+    term::{Cell, TermPtr, TermRef},
+    var::VarValue,
+};
+
+const HEADER: &str = "strandal-dump 1";
+
+/// A live slot as it was dumped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Var(Option<VarValue>),
+    Cell(Cell),
+}
+
+impl Slot {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Slot::Var(_) => "var",
+            Slot::Cell(Cell::Lam(_)) => "lam",
+            Slot::Cell(Cell::App(_)) => "app",
+            Slot::Cell(Cell::Dup(_, _)) => "dup",
+            Slot::Cell(Cell::Num(_)) => "num",
+            Slot::Cell(Cell::Op(..)) => "op",
+            Slot::Cell(Cell::Swi(_)) => "swi",
+            Slot::Cell(Cell::Ref(_)) => "ref",
+        }
+    }
+
+    fn of(term: TermRef) -> Self {
+        match term {
+            TermRef::Var(var) => Slot::Var(var.read()),
+            TermRef::Cell(cell) => Slot::Cell(cell),
+        }
+    }
+
+    /// The slots this one points to.
+    pub fn targets(&self) -> Vec<Ptr> {
+        match self {
+            Slot::Var(Some(VarValue::Var(ptr))) | Slot::Var(Some(VarValue::Cell(ptr))) => {
+                vec![*ptr]
+            }
+            Slot::Var(_) => vec![],
+            Slot::Cell(cell) => cell
+                .ports()
+                .iter()
+                .flat_map(|(p0, p1)| [*p0, *p1])
+                .filter_map(|port| match port {
+                    TermPtr::Ptr(ptr) => Some(ptr),
+                    TermPtr::Era => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+struct PortDisplay(TermPtr);
+
+impl Display for PortDisplay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            TermPtr::Era => write!(f, "*"),
+            TermPtr::Ptr(ptr) => write!(f, "{}", ptr.index()),
+        }
+    }
+}
+
+impl Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (ports, lbl) = match self {
+            Slot::Var(None) => return write!(f, "var -"),
+            Slot::Var(Some(VarValue::Era)) => return write!(f, "var *"),
+            Slot::Var(Some(VarValue::Var(ptr))) => return write!(f, "var var {}", ptr.index()),
+            Slot::Var(Some(VarValue::Cell(ptr))) => return write!(f, "var cell {}", ptr.index()),
+            Slot::Cell(Cell::Num(value)) => return write!(f, "num {}", value),
+            Slot::Cell(Cell::Ref(id)) => return write!(f, "ref {}", id),
+            Slot::Cell(Cell::Lam(ports) | Cell::App(ports) | Cell::Swi(ports)) => (ports, None),
+            Slot::Cell(Cell::Dup(ports, lbl)) => (ports, Some(lbl)),
+            Slot::Cell(Cell::Op(ports, op, flipped)) => {
+                match ports {
+                    Some((p0, p1)) => write!(f, "op {} {}", PortDisplay(*p0), PortDisplay(*p1))?,
+                    None => write!(f, "op -")?,
+                }
+                return write!(f, " {}", CellDisplay::op_symbol(*op, *flipped));
+            }
+        };
+        match ports {
+            Some((p0, p1)) => write!(
+                f,
+                "{} {} {}",
+                self.kind(),
+                PortDisplay(*p0),
+                PortDisplay(*p1)
+            )?,
+            None => write!(f, "{} -", self.kind())?,
+        }
+        match lbl {
+            Some(Some(lbl)) => write!(f, " {lbl}"),
+            Some(None) => write!(f, " -"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Where a dumped slot is referenced from, see [`Dump::referrers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Referrer {
+    Head(usize),
+    Eqn(usize),
+    Slot(Ptr),
+}
+
+impl Display for Referrer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Referrer::Head(idx) => write!(f, "head {}", idx),
+            Referrer::Eqn(idx) => write!(f, "eqn {}", idx),
+            Referrer::Slot(ptr) => write!(f, "{}", ptr),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DumpError {
+    Io(io::Error),
+    /// The dump does not start with the expected header.
+    Header,
+    /// The line, numbered from 1, is not a valid record.
+    Syntax(usize, String),
+}
+
+impl Display for DumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DumpError::Io(err) => write!(f, "{}", err),
+            DumpError::Header => write!(f, "not a strandal dump (expected '{}')", HEADER),
+            DumpError::Syntax(line, text) => write!(f, "line {}: invalid record '{}'", line, text),
+        }
+    }
+}
+
+impl std::error::Error for DumpError {}
+
+impl From<io::Error> for DumpError {
+    fn from(err: io::Error) -> Self {
+        DumpError::Io(err)
+    }
+}
+
+/// A net dump read back from disk.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Dump {
+    pub definitions: Vec<(String, Range<Index>)>,
+    pub heads: Vec<TermPtr>,
+    pub eqns: Vec<(TermPtr, TermPtr)>,
+    pub slots: BTreeMap<Index, Slot>,
+}
+
+impl Dump {
+    pub fn read(path: impl AsRef<Path>) -> Result<Dump, DumpError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(src: &str) -> Result<Dump, DumpError> {
+        let mut lines = src.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(HEADER) {
+            return Err(DumpError::Header);
+        }
+        let mut dump = Dump::default();
+        for (idx, line) in lines {
+            let words: Vec<_> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            dump.record(&words)
+                .ok_or_else(|| DumpError::Syntax(idx + 1, line.to_string()))?;
+        }
+        Ok(dump)
+    }
+
+    fn record(&mut self, words: &[&str]) -> Option<()> {
+        match words {
+            ["def", name, start, end] => {
+                self.definitions
+                    .push((name.to_string(), start.parse().ok()?..end.parse().ok()?));
+            }
+            ["head", port] => self.heads.push(parse_port(port)?),
+            ["eqn", left, right] => self.eqns.push((parse_port(left)?, parse_port(right)?)),
+            [idx, rest @ ..] => {
+                let slot = parse_slot(rest)?;
+                self.slots.insert(idx.parse().ok()?, slot);
+            }
+            [] => {}
+        }
+        Some(())
+    }
+
+    /// The definition that allocated `ptr`, if any.
+    pub fn definition(&self, ptr: Ptr) -> Option<&str> {
+        self.definitions
+            .iter()
+            .find(|(_, range)| range.contains(&ptr.index()))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Every head, equation and slot pointing to `ptr`.
+    pub fn referrers(&self, ptr: Ptr) -> Vec<Referrer> {
+        let target = TermPtr::Ptr(ptr);
+        let heads = self
+            .heads
+            .iter()
+            .enumerate()
+            .filter(|(_, head)| **head == target)
+            .map(|(idx, _)| Referrer::Head(idx));
+        let eqns = self
+            .eqns
+            .iter()
+            .enumerate()
+            .filter(|(_, (left, right))| *left == target || *right == target)
+            .map(|(idx, _)| Referrer::Eqn(idx));
+        let slots = self
+            .slots
+            .iter()
+            .filter(|(_, slot)| slot.targets().contains(&ptr))
+            .map(|(idx, _)| Referrer::Slot(Ptr::new(*idx)));
+        heads.chain(eqns).chain(slots).collect()
+    }
+}
+
+fn parse_port(word: &str) -> Option<TermPtr> {
+    match word {
+        "*" => Some(TermPtr::Era),
+        idx => Some(TermPtr::Ptr(Ptr::new(idx.parse().ok()?))),
+    }
+}
+
+fn parse_ports(words: &[&str]) -> Option<Option<(TermPtr, TermPtr)>> {
+    match words {
+        ["-"] => Some(None),
+        [p0, p1] => Some(Some((parse_port(p0)?, parse_port(p1)?))),
+        _ => None,
+    }
+}
+
+fn parse_slot(words: &[&str]) -> Option<Slot> {
+    let ptr = |word: &str| word.parse().ok().map(Ptr::new);
+    let slot = match words {
+        ["var", "-"] => Slot::Var(None),
+        ["var", "*"] => Slot::Var(Some(VarValue::Era)),
+        ["var", "var", idx] => Slot::Var(Some(VarValue::Var(ptr(idx)?))),
+        ["var", "cell", idx] => Slot::Var(Some(VarValue::Cell(ptr(idx)?))),
+        ["lam", ports @ ..] => Slot::Cell(Cell::Lam(parse_ports(ports)?)),
+        ["app", ports @ ..] => Slot::Cell(Cell::App(parse_ports(ports)?)),
+        ["num", value] => Slot::Cell(Cell::Num(value.parse().ok()?)),
+        ["op", ports @ .., symbol] => {
+            let (symbol, flipped) = match symbol.strip_prefix(CellDisplay::FLIPPED_MARK) {
+                Some(symbol) => (symbol, true),
+                None => (*symbol, false),
+            };
+            Slot::Cell(Cell::Op(parse_ports(ports)?, symbol.parse().ok()?, flipped))
+        }
+        ["swi", ports @ ..] => Slot::Cell(Cell::Swi(parse_ports(ports)?)),
+        ["ref", id] => Slot::Cell(Cell::Ref(id.parse().ok()?)),
+        ["dup", ports @ .., lbl] => {
+            let lbl = match *lbl {
+                "-" => None,
+                lbl => Some(lbl.parse().ok()?),
+            };
+            Slot::Cell(Cell::Dup(parse_ports(ports)?, lbl))
+        }
+        _ => return None,
+    };
+    Some(slot)
+}
+
+impl Net {
+    /// Write every live slot, with the heads, pending equations and
+    /// definition ranges, to `path`. See the [module docs](self) for the
+    /// format.
+    pub fn dump(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.dump_to(&mut out)?;
+        out.flush()
+    }
+
+    pub fn dump_to(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        let mut definitions: Vec<_> = self
+            .definitions
+            .iter()
+            .flat_map(|(name, ranges)| ranges.iter().map(move |range| (name, range)))
+            .collect();
+        definitions.sort_by_key(|(_, range)| range.start);
+        for (name, range) in definitions {
+            writeln!(out, "def {} {} {}", name, range.start, range.end)?;
+        }
+        for head in &self.head {
+            writeln!(out, "head {}", PortDisplay(*head))?;
+        }
+        for (eqn, _) in &self.body {
+            writeln!(
+                out,
+                "eqn {} {}",
+                PortDisplay(eqn.left()),
+                PortDisplay(eqn.right())
+            )?;
+        }
+        for (ptr, term) in self.store.iter() {
+            writeln!(out, "{} {}", ptr.index(), Slot::of(term))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        dump::{Dump, Referrer, Slot},
+        net::{Net, NetBuilder},
+        num::Op,
+        parser::parse,
+        store::Ptr,
+        term::{Cell, Label, TermPtr},
+        var::VarValue,
+    };
+
+    #[test]
+    fn test_dump() {
+        let mut net = Net::new();
+        let x = net.var();
+        let x_ptr = x.0.ptr();
+        let id = net.lam(x.0, x.1);
+        let id_ptr = id.ptr();
+        let r = net.var();
+        let r_ptr = r.1.ptr();
+        let app = net.app(r.0, TermPtr::Era);
+        let dup = net.fresh_dup(TermPtr::Era, TermPtr::Era);
+        let dup_ptr = dup.ptr();
+        net.head(r.1);
+        net.head(dup);
+        net.eqn(id, app);
+
+        let mut out = Vec::new();
+        net.dump_to(&mut out).unwrap();
+        let dump = Dump::parse(&String::from_utf8(out).unwrap()).unwrap();
+        assert_eq!(dump.slots.len(), 5);
+        assert_eq!(dump.heads[0], TermPtr::Ptr(r_ptr));
+        assert_eq!(dump.slots[&x_ptr.index()], Slot::Var(None),);
+        assert!(matches!(
+            dump.slots[&dup_ptr.index()],
+            Slot::Cell(Cell::Dup(Some((TermPtr::Era, TermPtr::Era)), Some(Label::Fresh(_))))
+        ));
+        assert_eq!(dump.referrers(id_ptr), vec![Referrer::Eqn(0)]);
+        assert_eq!(dump.referrers(x_ptr), vec![Referrer::Slot(id_ptr)]);
+        assert_eq!(dump.definition(id_ptr), None);
+    }
+
+    #[test]
+    fn test_dump_definitions() {
+        let mut net = Net::new();
+        assert!(parse("def main(r) = (x x) ~ [* r]", &mut net));
+        let mut out = Vec::new();
+        net.dump_to(&mut out).unwrap();
+        let dump = Dump::parse(&String::from_utf8(out).unwrap()).unwrap();
+        let main = &dump.definitions[0];
+        assert_eq!(main.0, "main");
+        assert!(main
+            .1
+            .clone()
+            .all(|idx| dump.definition(Ptr::new(idx)) == Some("main")));
+    }
+
+    #[test]
+    fn test_dump_errors() {
+        assert!(Dump::parse("0 var -").is_err());
+        let err = Dump::parse("strandal-dump 1\n0 var cell\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: invalid record '0 var cell'");
+        let dump = Dump::parse("strandal-dump 1\n3 var cell 1\n").unwrap();
+        assert_eq!(dump.slots[&3], Slot::Var(Some(VarValue::Cell(Ptr::new(1)))));
+    }
+
+    #[test]
+    fn test_dump_numbers() {
+        let src = "strandal-dump 1\n5 num 7\n6 op 5 * :-\n7 swi - \n";
+        let dump = Dump::parse(src).unwrap();
+        let op = Cell::Op(
+            Some((TermPtr::Ptr(Ptr::new(5)), TermPtr::Era)),
+            Op::Sub,
+            true,
+        );
+        assert_eq!(dump.slots[&5], Slot::Cell(Cell::Num(7)));
+        assert_eq!(dump.slots[&6], Slot::Cell(op));
+        assert_eq!(dump.slots[&7], Slot::Cell(Cell::Swi(None)));
+        assert_eq!(dump.slots[&6].to_string(), "op 5 * :-");
+    }
+}
@@ -0,0 +1,259 @@
+//! One-off expressions, evaluated against the definitions of a book: a
+//! λ-term such as `(λx.x) λy.y`, or equations in the syntax of
+//! [`super::parser`] such as `(x x) ~ [(y y) r]`. Either reaches the
+//! definitions of the book by reference only, as in `@true`.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use super::{
+    ast::{self, AstError, Book, Def, Term},
+    net::Net,
+    parser::{try_parse_eqns, try_parse_lambda, ParseError},
+    readback::{NotNormal, OwnedTerm},
+    runtime::{EvalError, Runtime},
+    store::Index,
+    term::Label,
+};
+
+/// A λ-term, its vars as de Bruijn indices: 0 is the closest binder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lambda {
+    Var(usize),
+    Lam(Box<Lambda>),
+    App(Box<Lambda>, Box<Lambda>),
+    /// A reference to a definition by name, see [`super::refs`].
+    Ref(String),
+    Num(u32),
+}
+
+impl Lambda {
+    /// The net of the term, its only head the root. A binder used more
+    /// than once is shared through a tree of duplicators labelled after it,
+    /// and one never used is erased.
+    pub fn to_net(&self) -> ast::Net {
+        let mut body = Vec::new();
+        let head = self.compile(&mut Vec::new(), &mut body, &mut 0);
+        ast::Net {
+            head: vec![head],
+            body,
+        }
+    }
+
+    // the term wired to the root of `self`, with the names of the uses of
+    // each binder in scope, innermost last
+    fn compile(
+        &self,
+        scope: &mut Vec<(usize, Vec<String>)>,
+        body: &mut Vec<(Term, Term)>,
+        fresh: &mut usize,
+    ) -> Term {
+        match self {
+            Lambda::Var(idx) => {
+                let depth = scope.len() - 1 - idx;
+                let (binder, uses) = &mut scope[depth];
+                let name = format!("x{}_{}", binder, uses.len());
+                uses.push(name.clone());
+                Term::Var(name)
+            }
+            Lambda::Lam(lam_body) => {
+                let binder = *fresh;
+                *fresh += 1;
+                scope.push((binder, Vec::new()));
+                let lam_body = lam_body.compile(scope, body, fresh);
+                let (_, uses) = scope.pop().unwrap();
+                let label = Some(Label::User(binder as Index));
+                let port = uses
+                    .into_iter()
+                    .rev()
+                    .map(Term::Var)
+                    .reduce(|copies, copy| Term::Dup(Box::new(copy), Box::new(copies), label))
+                    .unwrap_or(Term::Era);
+                Term::lam(port, lam_body)
+            }
+            Lambda::App(fun, arg) => {
+                let result = format!("r{}", *fresh);
+                *fresh += 1;
+                let fun = fun.compile(scope, body, fresh);
+                let arg = arg.compile(scope, body, fresh);
+                body.push((fun, Term::App(Box::new(arg), Box::new(Term::var(&result)))));
+                Term::Var(result)
+            }
+            Lambda::Ref(name) => Term::Ref(name.clone()),
+            Lambda::Num(value) => Term::Num(*value),
+        }
+    }
+
+    fn fmt_in(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        match self {
+            Lambda::Var(idx) => write!(f, "x{}", depth - 1 - idx),
+            Lambda::Lam(body) => {
+                write!(f, "(λx{}.", depth)?;
+                body.fmt_in(f, depth + 1)?;
+                write!(f, ")")
+            }
+            Lambda::App(fun, arg) => {
+                write!(f, "(")?;
+                fun.fmt_in(f, depth)?;
+                write!(f, " ")?;
+                arg.fmt_in(f, depth)?;
+                write!(f, ")")
+            }
+            Lambda::Ref(name) => write!(f, "@{}", name),
+            Lambda::Num(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// In the usual notation, the vars named after the depth of their binder,
+/// as in `(λx0.(λx1.(x0 x1)))`.
+impl Display for Lambda {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_in(f, 0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExprError {
+    Parse(Vec<ParseError>),
+    Ast(AstError),
+    Eval(EvalError),
+    NotNormal(NotNormal),
+}
+
+impl Display for ExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExprError::Parse(errs) => {
+                let errs: Vec<_> = errs.iter().map(|err| err.to_string()).collect();
+                write!(f, "{}", errs.join("\n"))
+            }
+            ExprError::Ast(err) => write!(f, "{}", err),
+            ExprError::Eval(err) => write!(f, "{}", err),
+            ExprError::NotNormal(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+impl From<AstError> for ExprError {
+    fn from(err: AstError) -> Self {
+        ExprError::Ast(err)
+    }
+}
+
+impl From<EvalError> for ExprError {
+    fn from(err: EvalError) -> Self {
+        ExprError::Eval(err)
+    }
+}
+
+impl From<NotNormal> for ExprError {
+    fn from(err: NotNormal) -> Self {
+        ExprError::NotNormal(err)
+    }
+}
+
+/// The net of an expression: equations if it has a `~`, whose heads are
+/// the vars used once in name order, and a λ-term otherwise.
+pub fn parse_expr(src: &str) -> Result<ast::Net, ExprError> {
+    if !src.contains('~') {
+        return Ok(try_parse_lambda(src).map_err(ExprError::Parse)?.to_net());
+    }
+    let body = try_parse_eqns(src).map_err(ExprError::Parse)?;
+    let mut uses: BTreeMap<String, usize> = BTreeMap::new();
+    let mut count = |term: &Term| {
+        if let Term::Var(name) = term {
+            *uses.entry(name.clone()).or_default() += 1;
+        }
+        None
+    };
+    for (left, right) in &body {
+        left.replace(&mut count);
+        right.replace(&mut count);
+    }
+    let head = uses
+        .into_iter()
+        .filter(|(_, count)| *count == 1)
+        .map(|(name, _)| Term::Var(name))
+        .collect();
+    Ok(ast::Net { head, body })
+}
+
+/// Evaluate `expr` with the definitions of `book` it references, reading
+/// back its heads. The other definitions are not evaluated.
+pub fn eval_expr(expr: &ast::Net, book: &Book) -> Result<Vec<OwnedTerm>, ExprError> {
+    // desugared along with the book, for the calls it makes
+    const EXPR: &str = "-e";
+    let mut book = book.clone();
+    book.defs.push(Def {
+        name: EXPR.to_string(),
+        net: expr.clone(),
+        head_names: vec![None; expr.head.len()],
+    });
+    let book = book.desugar()?;
+    let expr = book.defs.iter().find(|def| def.name == EXPR).unwrap();
+    let mut net = Net::new();
+    net.define_refs(&book)?;
+    net.add_ast(&expr.net)?;
+    Runtime::new().eval(&mut net)?;
+    Ok(net.normal_form()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        ast::Book,
+        parser::{parse_ast, try_parse_lambda, STDLIB},
+        readback::OwnedTerm,
+    };
+
+    use super::{eval_expr, parse_expr, ExprError, Lambda};
+
+    fn eval(src: &str, book: &Book) -> Result<Vec<String>, ExprError> {
+        let heads = eval_expr(&parse_expr(src)?, book)?;
+        Ok(heads.iter().map(|head| head.to_string()).collect())
+    }
+
+    #[test]
+    fn test_to_net() {
+        // λf.λx.f (f x)
+        let two = Lambda::Lam(Box::new(Lambda::Lam(Box::new(Lambda::App(
+            Box::new(Lambda::Var(1)),
+            Box::new(Lambda::App(Box::new(Lambda::Var(1)), Box::new(Lambda::Var(0)))),
+        )))));
+        assert_eq!(two.to_string(), "(λx0.(λx1.(x0 (x0 x1))))");
+        assert_eq!(
+            two.to_net().to_string(),
+            "head: (λ (δ x0_0 x0_1 {0}) (λ x1_0 r2))\nbody:\n  x0_1 ~ (@ x1_0 r3)\n  x0_0 ~ (@ r3 r2)"
+        );
+    }
+
+    #[test]
+    fn test_parse_lambda() {
+        let parsed = |src| try_parse_lambda(src).map(|term| term.to_string());
+        assert_eq!(parsed("λf x. f (f x)"), Ok("(λx0.(λx1.(x0 (x0 x1))))".to_string()));
+        assert_eq!(parsed("\\x. x λy. y @id 1"), Ok("(λx0.(x0 (λx1.((x1 @id) 1))))".to_string()));
+        assert_eq!(parsed("(λx.x) (λy.y)"), parsed("(λx.x) λy.y"));
+        let errs = try_parse_lambda("λx.\n  x y").unwrap_err();
+        assert_eq!(errs[0].to_string(), "2:5: unbound variable `y`");
+        assert!(try_parse_lambda("λ.x").is_err());
+    }
+
+    #[test]
+    fn test_eval_expr() {
+        let stdlib = parse_ast(STDLIB).unwrap();
+        assert_eq!(eval("(λx.x) (λy.y) 7", &stdlib), Ok(vec!["7".to_string()]));
+        assert_eq!(eval("(x x) ~ [(y y) [7 r]]", &stdlib), Ok(vec!["7".to_string()]));
+        // the church booleans of the stdlib, chosen between by `λc.c 1 2`
+        assert_eq!(eval("@true 1 2", &stdlib), Ok(vec!["1".to_string()]));
+        assert_eq!(eval("(λb. b 1 2) @false", &stdlib), Ok(vec!["2".to_string()]));
+
+        let book = parse_ast("def inc((n r)) = n ~ (+ 1 r); def main(r) = @inc ~ [1 r]").unwrap();
+        let heads = eval_expr(&parse_expr("@inc 41").unwrap(), &book).unwrap();
+        assert_eq!(heads, vec![OwnedTerm::Num(42)]);
+        assert_eq!(eval("a ~ [1 b] & @inc ~ a", &book), Ok(vec!["2".to_string()]));
+        assert!(matches!(eval("@nope", &book), Err(ExprError::Ast(_))));
+        assert!(matches!(eval("x ~ (x x)", &book), Err(ExprError::Ast(_))));
+    }
+}
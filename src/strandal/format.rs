@@ -0,0 +1,183 @@
+//! Prints books back in the syntax of [`super::parser`], in a canonical
+//! layout: one definition per paragraph, in source order since the heads of
+//! a book follow it, with single spaces between tokens and the equations of
+//! a definition too wide for one line wrapped one per line.
+//!
+//! Parsing the output gives back the same book, and formatting it again the
+//! same text. The grammar has no syntax for labeled dups and flipped
+//! operators, which only readback and the HVM2 importer build, so those are
+//! printed as their [`Display`] impl does.
+
+use std::fmt::Display;
+
+use super::{
+    ast::{Book, Def, Term},
+    display::CellDisplay,
+};
+
+/// The widest line [`format_book`] keeps a definition on.
+pub const WIDTH: usize = 80;
+
+const INDENT: &str = "    ";
+
+/// A term in source syntax, as in `(x [y z])`.
+pub struct Source<'a>(pub &'a Term);
+
+impl Display for Source<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Term::Era => write!(f, "*"),
+            Term::Var(name) => write!(f, "{}", name),
+            Term::Lam(p0, p1) => write!(f, "({} {})", Source(p0), Source(p1)),
+            Term::App(p0, p1) => write!(f, "[{} {}]", Source(p0), Source(p1)),
+            Term::Dup(p0, p1, None) => write!(f, "{{{} {}}}", Source(p0), Source(p1)),
+            Term::Num(value) => write!(f, "{}", value),
+            Term::Op(op, false, p0, p1) => {
+                write!(f, "({} {} {})", op.symbol(), Source(p0), Source(p1))
+            }
+            Term::Swi(p0, p1) => write!(
+                f,
+                "({} {} {})",
+                CellDisplay::SWI_SYMBOL,
+                Source(p0),
+                Source(p1)
+            ),
+            Term::Ref(name) => write!(f, "{}{}", CellDisplay::REF_SYMBOL, name),
+            Term::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                for (idx, arg) in args.iter().enumerate() {
+                    match idx {
+                        0 => write!(f, "{}", Source(arg))?,
+                        _ => write!(f, ", {}", Source(arg))?,
+                    }
+                }
+                write!(f, ")")
+            }
+            Term::Infix(op, p0, p1) => {
+                // group the operands binding looser, or as loose on the right
+                let operand = |term: &Term, right: bool| match term {
+                    Term::Infix(inner, _, _)
+                        if inner.precedence() < op.precedence()
+                            || (right && inner.precedence() == op.precedence()) =>
+                    {
+                        format!("({})", Source(term))
+                    }
+                    _ => Source(term).to_string(),
+                };
+                write!(
+                    f,
+                    "{} {} {}",
+                    operand(p0, false),
+                    op.symbol(),
+                    operand(p1, true)
+                )
+            }
+            Term::Dup(_, _, Some(_)) | Term::Op(_, true, _, _) => write!(f, "{}", self.0),
+        }
+    }
+}
+
+/// The header of `def`, as in `def main(out r)`.
+pub fn signature(def: &Def) -> String {
+    let head: Vec<_> = def
+        .net
+        .head
+        .iter()
+        .zip(def.head_names.iter().chain(std::iter::repeat(&None)))
+        .map(|(term, name)| match name {
+            Some(name) => format!("out {}", name),
+            None => Source(term).to_string(),
+        })
+        .collect();
+    format!("def {}({})", def.name, head.join(", "))
+}
+
+/// Format `def` without its trailing `;`, on one line if it fits in
+/// [`WIDTH`] columns.
+pub fn format_def(def: &Def) -> String {
+    let signature = signature(def);
+    if def.net.body.is_empty() {
+        return signature;
+    }
+    let eqns: Vec<_> = def
+        .net
+        .body
+        .iter()
+        .map(|(left, right)| format!("{} ~ {}", Source(left), Source(right)))
+        .collect();
+    let line = format!("{} = {}", signature, eqns.join(" & "));
+    // the `;` ending the definition counts towards the width
+    if line.len() < WIDTH {
+        return line;
+    }
+    let mut wrapped = format!("{} =\n{}{}", signature, INDENT, eqns[0]);
+    for eqn in &eqns[1..] {
+        wrapped.push_str(&format!("\n{}& {}", INDENT, eqn));
+    }
+    wrapped
+}
+
+/// Format every definition of `book`, ending each one with a `;` and
+/// separating them with a blank line.
+pub fn format_book(book: &Book) -> String {
+    book.defs
+        .iter()
+        .map(|def| format!("{};\n", format_def(def)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::parser::parse_ast;
+
+    use super::format_book;
+
+    #[test]
+    fn test_format() {
+        let src = "def main(out r)=@fact~[5 r];def fact((n r)) = n ~ (?(1 @fact_s) r)";
+        assert_eq!(
+            format_book(&parse_ast(src).unwrap()),
+            "def main(out r) = @fact ~ [5 r];\n\ndef fact((n r)) = n ~ (? (1 @fact_s) r);\n"
+        );
+
+        let fixture = include_str!("../../tests/fixtures/fibonacci.strandal");
+        let book = parse_ast(fixture).unwrap();
+        let formatted = format_book(&book);
+        assert!(
+            formatted.lines().all(|line| line.len() <= 80),
+            "{}",
+            formatted
+        );
+        // formatting keeps the book, and is stable
+        let reparsed = parse_ast(&formatted).unwrap();
+        assert_eq!(reparsed, book);
+        assert_eq!(format_book(&reparsed), formatted);
+    }
+
+    #[test]
+    fn test_infix() {
+        let src = "def main(out r) = r ~ (1 + 2) * 3 - 4 / (5 - 6) == 7 | 8 << 9";
+        let formatted = format_book(&parse_ast(src).unwrap());
+        assert_eq!(
+            formatted,
+            "def main(out r) = r ~ (1 + 2) * 3 - 4 / (5 - 6) == 7 | 8 << 9;\n"
+        );
+        let src = "def main(out r) = r ~ 1 - (2 - 3) & 1 - 2 - 3 ~ s";
+        assert_eq!(
+            format_book(&parse_ast(src).unwrap()),
+            "def main(out r) = r ~ 1 - (2 - 3) & 1 - 2 - 3 ~ s;\n"
+        );
+    }
+
+    #[test]
+    fn test_wrap() {
+        let src = "def long(out result) = result ~ (+ first second) & first ~ 1 \
+                   & second ~ {third fourth} & third ~ fourth";
+        assert_eq!(
+            format_book(&parse_ast(src).unwrap()),
+            "def long(out result) =\n    result ~ (+ first second)\n    & first ~ 1\n    \
+             & second ~ {third fourth}\n    & third ~ fourth;\n"
+        );
+    }
+}
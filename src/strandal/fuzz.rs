@@ -0,0 +1,192 @@
+//! Entry points for cargo-fuzz, under the `fuzzing` feature, and the
+//! [`Arbitrary`] impls of the owned AST they take. A fuzz target only
+//! forwards its input:
+//!
+//! ```ignore
+//! #![no_main]
+//! use strandal::strandal::{ast, fuzz};
+//!
+//! libfuzzer_sys::fuzz_target!(|net: ast::Net| fuzz::fuzz_eval(&net));
+//! ```
+//!
+//! Both entry points panic on a bug and return quietly on an input that is
+//! merely invalid, so every crash the fuzzer reports is worth a look. An
+//! arbitrary [`ast::Net`] is always well formed, see [`ast::Net::closed`].
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::{
+    ast::{self, Term},
+    format::format_book,
+    hvm::parse_hvm,
+    net::Net,
+    num::Op,
+    parser::try_parse_ast,
+    readback::NotNormal,
+    runtime::{EvalError, Runtime, RuntimeConfig},
+    store::{Index, Store},
+    term::Label,
+};
+
+// the names of the vars of an arbitrary net, few enough for most to pair up
+const VARS: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "h"];
+
+// how deep an arbitrary term nests
+const MAX_DEPTH: usize = 4;
+
+// an eval stops once the net grows past this many live slots, or after
+// this many interactions. The slots freed during an eval are not all
+// reused by it, so the store leaves room for every interaction to allocate
+// a whole commutation.
+const MAX_CELLS: Index = 1 << 12;
+const MAX_INTERACTIONS: usize = 1 << 14;
+const CAPACITY: Index = 1 << 20;
+
+impl<'a> Arbitrary<'a> for Term {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        term(u, MAX_DEPTH)
+    }
+}
+
+fn term(u: &mut Unstructured, depth: usize) -> Result<Term> {
+    let leaf = depth == 0 || u.is_empty();
+    let pair = |u: &mut Unstructured| -> Result<_> {
+        Ok((Box::new(term(u, depth - 1)?), Box::new(term(u, depth - 1)?)))
+    };
+    Ok(match u.int_in_range(0..=if leaf { 2 } else { 8 })? {
+        0 => Term::Era,
+        1 => Term::var(u.choose(&VARS)?),
+        2 => Term::Num(u.arbitrary()?),
+        3 => {
+            let (p0, p1) = pair(u)?;
+            Term::Lam(p0, p1)
+        }
+        4 => {
+            let (p0, p1) = pair(u)?;
+            Term::App(p0, p1)
+        }
+        5 => {
+            let label = u.arbitrary::<Option<u8>>()?.map(|lbl| Label::User(lbl.into()));
+            let (p0, p1) = pair(u)?;
+            Term::Dup(p0, p1, label)
+        }
+        6 => {
+            let (op, flipped) = (*u.choose(&Op::ALL)?, u.arbitrary()?);
+            let (p0, p1) = pair(u)?;
+            Term::Op(op, flipped, p0, p1)
+        }
+        7 => {
+            let (p0, p1) = pair(u)?;
+            Term::Swi(p0, p1)
+        }
+        _ => {
+            let op = *u.choose(&Op::ALL)?;
+            let (p0, p1) = pair(u)?;
+            Term::Infix(op, p0, p1)
+        }
+    })
+}
+
+impl<'a> Arbitrary<'a> for ast::Net {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let heads = u.int_in_range(0..=3)?;
+        let eqns = u.int_in_range(0..=4)?;
+        let head = (0..heads).map(|_| u.arbitrary()).collect::<Result<_>>()?;
+        let body = (0..eqns).map(|_| u.arbitrary()).collect::<Result<_>>()?;
+        Ok(ast::Net { head, body }.closed())
+    }
+}
+
+/// Parse `bytes` as a book, in the syntax of [`super::parser`] and as an
+/// HVM2 book. A book that parses must format back to itself, and load into
+/// a net or fail with an [`ast::AstError`].
+pub fn fuzz_parse(bytes: &[u8]) {
+    let Ok(src) = std::str::from_utf8(bytes) else {
+        return;
+    };
+    let _ = parse_hvm(src);
+    let Ok(book) = try_parse_ast(src) else {
+        return;
+    };
+    let formatted = format_book(&book);
+    match try_parse_ast(&formatted) {
+        Ok(reparsed) => {
+            assert_eq!(reparsed, book, "{} formats to {}", src, formatted);
+            assert_eq!(format_book(&reparsed), formatted);
+        }
+        Err(errs) => panic!("{} formats to {}, which does not parse: {:?}", src, formatted, errs),
+    }
+    let _ = Net::new().load(&book);
+}
+
+/// Evaluate `ast` on a checked store, which panics on the first corrupt
+/// slot. The eval must end in a net that reads back, unless it grows or
+/// runs for too long, as a net that never reaches a normal form does.
+pub fn fuzz_eval(ast: &ast::Net) {
+    let mut net = Net::with_store(Store::with_capacity(CAPACITY).checked());
+    if net.add_ast(ast).is_err() {
+        return;
+    }
+    let config = RuntimeConfig::default()
+        .max_cells(MAX_CELLS)
+        .max_interactions(MAX_INTERACTIONS);
+    match Runtime::with_config(config).eval(&mut net) {
+        Ok(()) => match net.normal_form() {
+            // vicious circles and cells wired to themselves read back as
+            // such, but nothing is left pending or freed under a head
+            Ok(_) | Err(NotNormal::Cycle(_) | NotNormal::Disconnected(_)) => {}
+            Err(err) => panic!("{} evaluates to a net that does not read back: {}", ast, err),
+        },
+        Err(EvalError::MaxCellsExceeded { .. } | EvalError::MaxInteractionsExceeded(_)) => {}
+        Err(err) => panic!("{} fails to evaluate: {}", ast, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use crate::strandal::ast;
+
+    use super::{fuzz_eval, fuzz_parse};
+
+    // pseudo-random inputs, the same on every run
+    fn inputs(count: usize) -> impl Iterator<Item = Vec<u8>> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..count).map(move |idx| {
+            (0..64 + idx % 192)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_fuzz_parse() {
+        for fixture in [
+            include_str!("../../tests/fixtures/fibonacci.strandal"),
+            include_str!("../../tests/fixtures/calls.strandal"),
+            include_str!("../../tests/fixtures/hvm_fib.hvm"),
+        ] {
+            fuzz_parse(fixture.as_bytes());
+        }
+        for bytes in inputs(256) {
+            fuzz_parse(&bytes);
+        }
+    }
+
+    #[test]
+    fn test_fuzz_eval() {
+        let mut evaluated = 0;
+        for bytes in inputs(256) {
+            let net = ast::Net::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+            evaluated += (!net.body.is_empty()) as usize;
+            fuzz_eval(&net);
+        }
+        assert!(evaluated > 0);
+    }
+}
@@ -0,0 +1,97 @@
+//! Normalize every `.strandal` program and `.hvm` book in `tests/fixtures`
+//! and compare its heads, one per line, with the `.nf` file of the same
+//! name. Set
+//! `STRANDAL_BLESS=1` to write the current results as the expected ones.
+
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use super::{ast::Term, hvm::load_hvm, net::Net, parser::parse, runtime::Runtime};
+
+// rename vars `x0`, `x1`, ... in order of appearance, since store indices
+// depend on the evaluation order
+fn rename(term: &Term, names: &mut HashMap<String, String>) -> Term {
+    match term {
+        Term::Era => Term::Era,
+        Term::Var(name) => {
+            let next = format!("x{}", names.len());
+            Term::Var(names.entry(name.clone()).or_insert(next).clone())
+        }
+        Term::Lam(p0, p1) => Term::lam(rename(p0, names), rename(p1, names)),
+        Term::App(p0, p1) => Term::app(rename(p0, names), rename(p1, names)),
+        Term::Dup(p0, p1, lbl) => Term::Dup(
+            Box::new(rename(p0, names)),
+            Box::new(rename(p1, names)),
+            *lbl,
+        ),
+        Term::Num(value) => Term::Num(*value),
+        Term::Op(op, flipped, p0, p1) => Term::Op(
+            *op,
+            *flipped,
+            Box::new(rename(p0, names)),
+            Box::new(rename(p1, names)),
+        ),
+        Term::Swi(p0, p1) => Term::Swi(Box::new(rename(p0, names)), Box::new(rename(p1, names))),
+        Term::Ref(name) => Term::Ref(name.clone()),
+        Term::Call(name, args) => Term::Call(
+            name.clone(),
+            args.iter().map(|arg| rename(arg, names)).collect(),
+        ),
+        Term::Infix(op, p0, p1) => Term::infix(*op, rename(p0, names), rename(p1, names)),
+    }
+}
+
+fn normalize(src: &str, hvm: bool) -> Result<String, String> {
+    let mut net = Net::new();
+    if hvm {
+        load_hvm(src, &mut net, None).map_err(|err| err.to_string())?;
+    } else if !parse(src, &mut net) {
+        return Err("parse error".to_string());
+    }
+    Runtime::new()
+        .eval(&mut net)
+        .map_err(|err| err.to_string())?;
+    let heads = net.normal_form().map_err(|err| err.to_string())?;
+    let mut names = HashMap::new();
+    Ok(heads
+        .iter()
+        .map(|head| format!("{}\n", rename(head, &mut names)))
+        .collect())
+}
+
+#[test]
+fn test_golden() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let bless = std::env::var_os("STRANDAL_BLESS").is_some();
+    let mut programs: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == "strandal" || ext == "hvm")
+        })
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty(), "no fixtures in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for program in programs {
+        let src = fs::read_to_string(&program).unwrap();
+        let hvm = program.extension().is_some_and(|ext| ext == "hvm");
+        let actual = normalize(&src, hvm).unwrap_or_else(|err| format!("error: {}\n", err));
+        let expected_path = program.with_extension("nf");
+        if bless {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        if actual != expected {
+            failures.push(format!(
+                "{}\n--- expected\n{}+++ actual\n{}",
+                program.display(),
+                expected,
+                actual
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
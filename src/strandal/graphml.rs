@@ -0,0 +1,152 @@
+use std::fmt::Write;
+
+use super::{
+    leaks::SlotKind,
+    net::Net,
+    term::{Cell, Label, TermPtr, TermRef},
+    var::VarValue,
+};
+
+/// Writes the store as an undirected graph: one node per live slot, head
+/// and eraser, one edge per cell port, var value and pending equation.
+struct GraphMl {
+    out: String,
+    // erasers are nullary cells: every use gets its own node
+    eras: usize,
+}
+
+impl GraphMl {
+    const HEADER: &'static str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="kind" for="node" attr.name="kind" attr.type="string"/>
+  <key id="label" for="node" attr.name="label" attr.type="string"/>
+  <key id="port" for="edge" attr.name="port" attr.type="string"/>
+  <graph id="net" edgedefault="undirected">
+"#;
+    const FOOTER: &'static str = "  </graph>\n</graphml>\n";
+
+    fn node(&mut self, id: &str, kind: &str, label: Option<Label>) {
+        write!(
+            self.out,
+            r#"    <node id="{}"><data key="kind">{}</data>"#,
+            id, kind
+        )
+        .unwrap();
+        if let Some(label) = label {
+            write!(self.out, r#"<data key="label">{}</data>"#, label).unwrap();
+        }
+        self.out.push_str("</node>\n");
+    }
+
+    // the node id of a term, adding a node for erasers
+    fn endpoint(&mut self, term_ptr: TermPtr) -> String {
+        match term_ptr {
+            TermPtr::Ptr(ptr) => format!("n{}", ptr.index()),
+            TermPtr::Era => {
+                self.eras += 1;
+                let id = format!("e{}", self.eras - 1);
+                self.node(&id, "ERA", None);
+                id
+            }
+        }
+    }
+
+    fn edge(&mut self, source: &str, target: TermPtr, port: &str) {
+        let target = self.endpoint(target);
+        writeln!(
+            self.out,
+            r#"    <edge source="{}" target="{}"><data key="port">{}</data></edge>"#,
+            source, target, port
+        )
+        .unwrap();
+    }
+}
+
+impl Net {
+    /// Export the net as GraphML, to analyze its structure with tools like
+    /// Gephi or NetworkX.
+    ///
+    /// Nodes are the live store slots (with a `kind` of `VAR`, `LAM`, `APP`
+    /// or `DUP`), the heads (`HEAD`) and one `ERA` node per eraser. Edges
+    /// are labelled with a `port`: `p0`/`p1` for cell ports, `link`/`bind`
+    /// for var values, `head` and `eqn`.
+    pub fn to_graphml(&self) -> String {
+        let mut graph = GraphMl {
+            out: GraphMl::HEADER.to_string(),
+            eras: 0,
+        };
+        let mut edges = Vec::new();
+        for (ptr, term) in self.store.iter() {
+            let idx = ptr.index();
+            let id = format!("n{}", idx);
+            let label = match term {
+                TermRef::Cell(Cell::Dup(_, lbl)) => lbl,
+                _ => None,
+            };
+            graph.node(&id, &SlotKind::of(term).to_string(), label);
+            match term {
+                TermRef::Cell(cell) => {
+                    if let Some((p0, p1)) = cell.ports() {
+                        edges.push((id.clone(), p0, "p0"));
+                        edges.push((id, p1, "p1"));
+                    }
+                }
+                TermRef::Var(var) => match var.read() {
+                    // linked vars point at each other: keep one edge
+                    Some(VarValue::Var(other)) if other.index() > idx => {
+                        edges.push((id, TermPtr::Ptr(other), "link"))
+                    }
+                    Some(VarValue::Var(_)) | None => {}
+                    Some(VarValue::Cell(cell_ptr)) => {
+                        edges.push((id, TermPtr::Ptr(cell_ptr), "bind"))
+                    }
+                    Some(VarValue::Era) => edges.push((id, TermPtr::Era, "bind")),
+                },
+            }
+        }
+        for (idx, head) in self.head.iter().enumerate() {
+            let id = format!("h{}", idx);
+            graph.node(&id, "HEAD", None);
+            edges.push((id, *head, "head"));
+        }
+        for (eqn, _) in &self.body {
+            let left = graph.endpoint(eqn.left());
+            edges.push((left, eqn.right(), "eqn"));
+        }
+        for (source, target, port) in edges {
+            graph.edge(&source, target, port);
+        }
+        graph.out.push_str(GraphMl::FOOTER);
+        graph.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        term::TermPtr,
+    };
+
+    #[test]
+    fn test_to_graphml() {
+        let mut net = Net::new();
+        let x = net.var();
+        let lam = net.lam(x.0, x.1);
+        let r = net.var();
+        let app = net.app(r.0, TermPtr::Era);
+        net.head(r.1);
+        net.eqn(lam, app);
+
+        let graphml = net.to_graphml();
+        assert!(graphml.starts_with("<?xml"));
+        assert!(graphml.ends_with("</graphml>\n"));
+        // 2 vars, 2 cells, 1 head and 1 eraser
+        assert_eq!(graphml.matches("<node ").count(), 6);
+        // 4 cell ports, 1 head and 1 equation
+        assert_eq!(graphml.matches("<edge ").count(), 6);
+        assert!(
+            graphml.contains(r#"<edge source="n1" target="n3"><data key="port">eqn</data></edge>"#)
+        );
+    }
+}
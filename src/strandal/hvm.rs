@@ -0,0 +1,395 @@
+//! Import of HVM2 books, so HVM programs can be evaluated and benchmarked
+//! as strandal definitions:
+//!
+//! ```text
+//! @main = a
+//!   & @fib ~ (10 a)
+//! @fib = (?((0 @fib_s) a) a)
+//! @fib_s = (?((1 @fib_ss) a) a)
+//! @fib_ss = ({$([+1] a) b} c)
+//!   & @fib ~ (a $([+] $(d c)))
+//!   & @fib ~ (b d)
+//! ```
+//!
+//! Every definition becomes a definition of one head port, referenced as
+//! `@name`. HVM2 vars are local to their definition, so they are prefixed
+//! with its name. An HVM2 constructor `(a b)` is both the lambda and the
+//! application: it is lowered to a lambda where it produces a value and to
+//! an application where it consumes one, which only matters for readback
+//! since both annihilate alike. The value side of a redex is its reference
+//! or number, or else its left side.
+//!
+//! Numbers are the unsigned ones, computed on 32 bits. An operator node is
+//! supported in the two shapes compilers emit: `$([op n] r)`, with the
+//! partially applied operator `n op _` (or `_ op n` when flipped as
+//! `[:op n]`), and `$([op] $(b r))` for `_ op b` (or `b op _`). Signed and
+//! float numbers are not supported.
+
+use std::fmt::Display;
+
+use chumsky::{error::EmptyErr, prelude::*, Parser};
+
+use super::{
+    ast::{self, AstError, Term},
+    net::Net,
+    num::Op,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HvmError {
+    /// The source is not an HVM2 book.
+    Parse(String),
+    /// A definition uses a node or number strandal has no counterpart for.
+    Unsupported(String, String),
+    /// The lowered book does not load.
+    Ast(AstError),
+}
+
+impl Display for HvmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HvmError::Parse(err) => write!(f, "parse error: {}", err),
+            HvmError::Unsupported(def, what) => {
+                write!(f, "unsupported in `@{}`: {}", def, what)
+            }
+            HvmError::Ast(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for HvmError {}
+
+impl From<AstError> for HvmError {
+    fn from(err: AstError) -> Self {
+        HvmError::Ast(err)
+    }
+}
+
+// an HVM2 number: an unsigned one, an operator `[op]` or a partially
+// applied operator `[op n]`, or a signed or float one kept as written
+#[derive(Debug, Clone, PartialEq)]
+enum Numb {
+    Unsigned(u32),
+    Sym(Op, bool, Option<u32>),
+    Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tree {
+    Era,
+    Var(String),
+    Ref(String),
+    Num(Numb),
+    Con(Box<Tree>, Box<Tree>),
+    Dup(Box<Tree>, Box<Tree>),
+    Opr(Box<Tree>, Box<Tree>),
+    Swi(Box<Tree>, Box<Tree>),
+}
+
+struct HvmDef {
+    name: String,
+    root: Tree,
+    redexes: Vec<(Tree, Tree)>,
+}
+
+// HVM2 writes equality and inequality as `=` and `!`
+fn hvm_symbol(op: Op) -> &'static str {
+    match op {
+        Op::Eq => "=",
+        Op::Ne => "!",
+        _ => op.symbol(),
+    }
+}
+
+fn parse_name<'a>() -> impl Parser<'a, &'a str, String> + Clone {
+    any()
+        .filter(|c: &char| c.is_ascii_alphanumeric() || "_./-".contains(*c))
+        .repeated()
+        .at_least(1)
+        .collect::<String>()
+}
+
+fn parse_numb<'a>() -> impl Parser<'a, &'a str, Numb> + Clone {
+    // longer symbols first, so `<<` is not read as `<`
+    let mut ops = Op::ALL;
+    ops.sort_by_key(|op| std::cmp::Reverse(hvm_symbol(*op).len()));
+    let op = just(':')
+        .or_not()
+        .then(choice(ops.map(|op| just(hvm_symbol(op)).to(op))))
+        .map(|(flipped, op)| (op, flipped.is_some()));
+    let sym = op
+        .then(text::int(10).or_not())
+        .delimited_by(just('['), just(']'))
+        .try_map(|((op, flipped), digits): (_, Option<&str>), _| {
+            let value = digits.map(str::parse).transpose();
+            value
+                .map(|value| Numb::Sym(op, flipped, value))
+                .map_err(|_| EmptyErr::default())
+        });
+    let other = one_of("+-")
+        .or_not()
+        .then(text::int(10))
+        .then(just('.').then(text::digits(10)).or_not())
+        .to_slice()
+        .map(|literal: &str| match literal.parse::<u32>() {
+            Ok(value) => Numb::Unsigned(value),
+            Err(_) => Numb::Other(literal.to_string()),
+        });
+    choice((sym, other))
+}
+
+fn parse_tree<'a>() -> impl Parser<'a, &'a str, Tree> {
+    recursive(|tree| {
+        let pair = |open: &'static str, close: char| {
+            tree.clone()
+                .then(tree.clone())
+                .delimited_by(just(open).padded(), just(close).padded())
+                .map(|(left, right): (Tree, Tree)| (Box::new(left), Box::new(right)))
+        };
+        choice((
+            just('*').padded().to(Tree::Era),
+            just('@').ignore_then(parse_name()).padded().map(Tree::Ref),
+            pair("$(", ')').map(|(left, right)| Tree::Opr(left, right)),
+            pair("?(", ')').map(|(left, right)| Tree::Swi(left, right)),
+            pair("(", ')').map(|(left, right)| Tree::Con(left, right)),
+            pair("{", '}').map(|(left, right)| Tree::Dup(left, right)),
+            parse_numb().padded().map(Tree::Num),
+            parse_name().padded().map(Tree::Var),
+        ))
+    })
+}
+
+fn parse_defs<'a>() -> impl Parser<'a, &'a str, Vec<HvmDef>> {
+    let redex = just('&')
+        .then(just('!').or_not())
+        .padded()
+        .ignore_then(parse_tree())
+        .then_ignore(just('~').padded())
+        .then(parse_tree());
+    just('@')
+        .ignore_then(parse_name())
+        .padded()
+        .then_ignore(just('=').padded())
+        .then(parse_tree())
+        .then(redex.repeated().collect())
+        .map(|((name, root), redexes)| HvmDef {
+            name,
+            root,
+            redexes,
+        })
+        .repeated()
+        .collect()
+}
+
+// drop `//` comments, which run to the end of the line
+fn strip_comments(src: &str) -> String {
+    src.lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse an HVM2 book and lower its definitions to a strandal [`ast::Book`],
+/// in source order.
+pub fn parse_hvm(src: &str) -> Result<ast::Book, HvmError> {
+    let src = strip_comments(src);
+    let defs = parse_defs()
+        .then_ignore(end())
+        .parse(src.trim())
+        .into_result()
+        .map_err(|errs| {
+            HvmError::Parse(
+                errs.iter()
+                    .map(|err| err.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )
+        })?;
+    defs.iter()
+        .map(lower_def)
+        .collect::<Result<_, _>>()
+        .map(|defs| ast::Book { defs })
+}
+
+/// Load the `entry` definition of an HVM2 book into `net`, `main` by
+/// default, with the definitions it references.
+pub fn load_hvm(src: &str, net: &mut Net, entry: Option<&str>) -> Result<(), HvmError> {
+    let mut book = parse_hvm(src)?;
+    book.prune(entry.unwrap_or("main"))?;
+    net.load(&book)?;
+    Ok(())
+}
+
+fn lower_def(def: &HvmDef) -> Result<ast::Def, HvmError> {
+    let lower = Lowering { def: &def.name };
+    let head = lower.tree(&def.root, true)?;
+    let body = def
+        .redexes
+        .iter()
+        .map(|(left, right)| {
+            let left_value = !matches!(right, Tree::Ref(_) | Tree::Num(_))
+                || matches!(left, Tree::Ref(_) | Tree::Num(_));
+            Ok((
+                lower.tree(left, left_value)?,
+                lower.tree(right, !left_value)?,
+            ))
+        })
+        .collect::<Result<_, HvmError>>()?;
+    Ok(ast::Def {
+        name: def.name.clone(),
+        net: ast::Net {
+            head: vec![head],
+            body,
+        },
+        head_names: vec![None],
+    })
+}
+
+struct Lowering<'a> {
+    def: &'a str,
+}
+
+impl Lowering<'_> {
+    fn unsupported(&self, what: impl Display) -> HvmError {
+        HvmError::Unsupported(self.def.to_string(), what.to_string())
+    }
+
+    // `value` tells whether the tree produces a value, see the module docs
+    fn tree(&self, tree: &Tree, value: bool) -> Result<Term, HvmError> {
+        Ok(match tree {
+            Tree::Era => Term::Era,
+            Tree::Var(name) => Term::Var(format!("{}.{}", self.def, name)),
+            Tree::Ref(name) => Term::Ref(name.clone()),
+            Tree::Num(Numb::Unsigned(value)) => Term::Num(*value),
+            Tree::Num(Numb::Sym(..)) => return Err(self.unsupported("an operator outside `$(..)`")),
+            Tree::Num(Numb::Other(literal)) => {
+                return Err(self.unsupported(format!("number `{}`", literal)))
+            }
+            Tree::Con(p0, p1) if value => Term::lam(self.tree(p0, false)?, self.tree(p1, true)?),
+            Tree::Con(p0, p1) => Term::app(self.tree(p0, true)?, self.tree(p1, false)?),
+            Tree::Dup(p0, p1) => Term::dup(self.tree(p0, value)?, self.tree(p1, value)?),
+            Tree::Opr(operator, result) => self.opr(operator, result)?,
+            Tree::Swi(branches, result) => {
+                let Tree::Con(zero, succ) = branches.as_ref() else {
+                    return Err(self.unsupported("a switch without a `(zero succ)` pair"));
+                };
+                let branches = Term::lam(self.tree(zero, true)?, self.tree(succ, true)?);
+                Term::swi(branches, self.tree(result, false)?)
+            }
+        })
+    }
+
+    // the principal port of `$(operator result)` takes the operand `x`
+    fn opr(&self, operator: &Tree, result: &Tree) -> Result<Term, HvmError> {
+        let (op, flipped, operand, result) = match (operator, result) {
+            // `[op n]` computes `n op x`, `[:op n]` computes `x op n`
+            (Tree::Num(Numb::Sym(op, flipped, Some(n))), _) => {
+                (*op, !flipped, Term::Num(*n), self.tree(result, false)?)
+            }
+            // `[op]` with `$(b r)` computes `x op b`, `[:op]` computes `b op x`
+            (Tree::Num(Numb::Sym(op, flipped, None)), Tree::Opr(operand, result)) => (
+                *op,
+                *flipped,
+                self.tree(operand, true)?,
+                self.tree(result, false)?,
+            ),
+            _ => return Err(self.unsupported("an operator node of another shape")),
+        };
+        Ok(Term::Op(op, flipped, Box::new(operand), Box::new(result)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        ast::{AstError, Term},
+        net::Net,
+        num::Op,
+        readback::OwnedTerm,
+        runtime::Runtime,
+    };
+
+    use super::{load_hvm, parse_hvm, HvmError};
+
+    fn eval(src: &str) -> Vec<OwnedTerm> {
+        let mut net = Net::new();
+        load_hvm(src, &mut net, None).unwrap();
+        Runtime::new().eval(&mut net).unwrap();
+        assert!(net.leaks().is_empty(), "{}", net.leaks());
+        net.normal_form().unwrap()
+    }
+
+    #[test]
+    fn test_lower() {
+        let book = parse_hvm(
+            "
+            // the identity, applied
+            @main = a & @id ~ (* a)
+            @id = (x x)
+            ",
+        )
+        .unwrap();
+        assert_eq!(book.defs.len(), 2);
+        let main = &book.defs[0].net;
+        assert_eq!(main.head, vec![Term::var("main.a")]);
+        assert_eq!(
+            main.body,
+            vec![(
+                Term::reference("id"),
+                Term::app(Term::Era, Term::var("main.a"))
+            )]
+        );
+        assert_eq!(
+            book.defs[1].net.head,
+            vec![Term::lam(Term::var("id.x"), Term::var("id.x"))]
+        );
+
+        // `[-1]` computes `1 - x` and `[:-1]` computes `x - 1`
+        let book = parse_hvm("@dec = ($([:-1] r) r)").unwrap();
+        assert_eq!(
+            book.defs[0].net.head,
+            vec![Term::lam(
+                Term::Op(
+                    Op::Sub,
+                    false,
+                    Box::new(Term::Num(1)),
+                    Box::new(Term::var("dec.r"))
+                ),
+                Term::var("dec.r")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_eval_hvm() {
+        assert_eq!(eval("@main = a & 5 ~ $([:-1] a)"), vec![OwnedTerm::Num(4)]);
+        assert_eq!(
+            eval("@main = a & 5 ~ $([-1] a)"),
+            vec![OwnedTerm::Num(u32::MAX - 3)]
+        );
+        assert_eq!(
+            eval("@main = a & 6 ~ $([*] $(7 a))"),
+            vec![OwnedTerm::Num(42)]
+        );
+        assert_eq!(
+            eval("@main = a & 6 ~ $([:<<] $(1 a))"),
+            vec![OwnedTerm::Num(64)]
+        );
+    }
+
+    #[test]
+    fn test_hvm_errors() {
+        assert!(matches!(parse_hvm("@main = ("), Err(HvmError::Parse(_))));
+        assert_eq!(
+            parse_hvm("@main = a & 1.5 ~ $([+1] a)").err(),
+            Some(HvmError::Unsupported(
+                "main".to_string(),
+                "number `1.5`".to_string()
+            ))
+        );
+        assert_eq!(
+            load_hvm("@main = @missing", &mut Net::new(), None),
+            Err(HvmError::Ast(AstError::UnknownDef("missing".to_string())))
+        );
+    }
+}
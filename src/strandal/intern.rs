@@ -0,0 +1,112 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::{net::Net, store::Ptr, template::Template, term::TermPtr};
+
+/// Constants shared by every place that uses them, see [`Net::intern`].
+///
+/// A constant is copied into the store once. Its root cell is never consumed
+/// by a rewrite: erasing it drops the reference, a duplicator hands the same
+/// root to both of its ports when the constant holds no duplicator of its
+/// own, and any other interaction copies it first and rewrites the copy.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    roots: HashMap<Arc<Template>, Ptr>,
+    templates: HashMap<Ptr, Arc<Template>>,
+}
+
+impl Interner {
+    /// Number of distinct constants.
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// The template of the constant rooted at `ptr`, if it is one.
+    #[inline]
+    pub(crate) fn template(&self, ptr: Option<Ptr>) -> Option<&Template> {
+        self.templates.get(&ptr?).map(|template| &**template)
+    }
+
+    /// The root cells of the constants.
+    pub(crate) fn roots(&self) -> impl Iterator<Item = Ptr> + '_ {
+        self.templates.keys().copied()
+    }
+}
+
+impl Net {
+    /// The root of the shared copy of `template`, allocating it the first
+    /// time an equal template is interned. `None` if the template is not a
+    /// constant: a closed cell tree, see [`Template::is_constant`].
+    pub fn intern(&mut self, template: &Template) -> Option<TermPtr> {
+        if !template.is_constant() {
+            return None;
+        }
+        if let Some(root) = self.interner.roots.get(template) {
+            return Some(TermPtr::Ptr(*root));
+        }
+        let (ports, _) = template.alloc_in(&self.store);
+        let TermPtr::Ptr(root) = ports[0] else {
+            unreachable!("a constant is rooted at a cell");
+        };
+        let template = Arc::new(template.clone());
+        let interner = Arc::make_mut(&mut self.interner);
+        interner.roots.insert(template.clone(), root);
+        interner.templates.insert(root, template);
+        Some(ports[0])
+    }
+
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        ast,
+        net::{Net, NetBuilder},
+        parser::stdlib,
+        runtime::Runtime,
+        term::TermPtr,
+    };
+
+    #[test]
+    fn test_intern() {
+        let stdlib = stdlib();
+        let mut net = Net::new();
+        let tru = net.intern(&stdlib["true"]).unwrap();
+        assert_eq!(net.intern(&stdlib["true"]), Some(tru));
+        assert_eq!(net.intern(&stdlib["ite"]), None);
+        assert_eq!(net.interner().len(), 1);
+
+        // a duplicator shares the constant, erasing it drops a reference
+        let copies = net.var();
+        let dup = net.dup(copies.0, TermPtr::Era);
+        net.eqn(dup, tru);
+        // the others use a copy
+        let x = net.var();
+        let id = net.lam(x.0, x.1);
+        let ite = net.instantiate(&stdlib["ite"]);
+        net.eqn(copies.1, ite.ports[0]);
+        net.eqn(id, ite.ports[1]);
+        net.eqn(TermPtr::Era, ite.ports[2]);
+        net.head(ite.ports[3]);
+        net.head(tru);
+
+        Runtime::new().eval(&mut net).unwrap();
+        let normal_form = net.normal_form().unwrap();
+        let ast::Term::Lam(p0, p1) = &normal_form[0] else {
+            panic!("expected the identity, got {}", normal_form[0]);
+        };
+        assert_eq!(p0, p1);
+        // the constant is intact
+        let ast::Term::Lam(t, body) = &normal_form[1] else {
+            panic!("expected true, got {}", normal_form[1]);
+        };
+        assert_eq!(**body, ast::Term::lam(ast::Term::Era, (**t).clone()));
+        assert!(net.leaks().is_empty());
+    }
+}
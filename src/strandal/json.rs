@@ -0,0 +1,221 @@
+//! Build a [`Net`] from a JSON description, for compilers that target
+//! strandal from other languages.
+//!
+//! ```json
+//! {
+//!   "vars": 2,
+//!   "cells": [
+//!     { "kind": "lam", "ports": [{ "var": 1 }, { "var": 1 }] },
+//!     { "kind": "app", "ports": [{ "var": 0 }, "era"] },
+//!     { "kind": "dup", "ports": ["era", "era"], "label": 7 }
+//!   ],
+//!   "head": [{ "var": 0 }, { "cell": 2 }],
+//!   "body": [[{ "cell": 0 }, { "cell": 1 }]]
+//! }
+//! ```
+//!
+//! A port is `"era"`, `{ "var": n }` or `{ "cell": n }`, indexing into the
+//! vars and the `cells` list. Every var must be used exactly twice and
+//! every cell exactly once, as a head, an equation side or a port of
+//! another cell. `label` is optional and only valid on `dup` cells. All
+//! fields but `vars` default to empty.
+
+use std::fmt::Display;
+
+use serde::Deserialize;
+
+use super::{
+    ast::{self, AstError},
+    net::Net,
+    store::Index,
+    term::Label,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonNet {
+    vars: u32,
+    #[serde(default)]
+    cells: Vec<JsonCell>,
+    #[serde(default)]
+    head: Vec<JsonPort>,
+    #[serde(default)]
+    body: Vec<(JsonPort, JsonPort)>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+enum JsonCell {
+    Lam {
+        ports: (JsonPort, JsonPort),
+    },
+    App {
+        ports: (JsonPort, JsonPort),
+    },
+    Dup {
+        ports: (JsonPort, JsonPort),
+        #[serde(default)]
+        label: Option<Index>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsonPort {
+    Era,
+    Var(u32),
+    Cell(u32),
+}
+
+#[derive(Debug)]
+pub enum JsonError {
+    /// The input is not valid JSON or does not follow the schema.
+    Parse(serde_json::Error),
+    /// A port refers to a var index not below `vars`.
+    UnknownVar(u32),
+    /// A port refers to a cell index past the end of `cells`.
+    UnknownCell(u32),
+    /// The cell is used more than once, or is reachable from its own ports.
+    ReusedCell(u32),
+    /// The cell is not used anywhere.
+    UnusedCell(u32),
+    /// A var is not used exactly twice.
+    Var(AstError),
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Parse(err) => write!(f, "invalid net description: {}", err),
+            JsonError::UnknownVar(idx) => write!(f, "var {} is out of range", idx),
+            JsonError::UnknownCell(idx) => write!(f, "cell {} is out of range", idx),
+            JsonError::ReusedCell(idx) => write!(f, "cell {} is used more than once", idx),
+            JsonError::UnusedCell(idx) => write!(f, "cell {} is never used", idx),
+            JsonError::Var(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(err: serde_json::Error) -> Self {
+        JsonError::Parse(err)
+    }
+}
+
+struct JsonReader<'a> {
+    json: &'a JsonNet,
+    used: Vec<bool>,
+}
+
+impl<'a> JsonReader<'a> {
+    // cells become owned trees: a cell is marked before its ports are read,
+    // so cycles show up as a reuse
+    fn term(&mut self, port: JsonPort) -> Result<ast::Term, JsonError> {
+        let idx = match port {
+            JsonPort::Era => return Ok(ast::Term::Era),
+            JsonPort::Var(idx) if idx < self.json.vars => {
+                return Ok(ast::Term::Var(format!("v{}", idx)))
+            }
+            JsonPort::Var(idx) => return Err(JsonError::UnknownVar(idx)),
+            JsonPort::Cell(idx) => idx,
+        };
+        let Some(cell) = self.json.cells.get(idx as usize) else {
+            return Err(JsonError::UnknownCell(idx));
+        };
+        if std::mem::replace(&mut self.used[idx as usize], true) {
+            return Err(JsonError::ReusedCell(idx));
+        }
+        Ok(match cell {
+            JsonCell::Lam { ports } => ast::Term::lam(self.term(ports.0)?, self.term(ports.1)?),
+            JsonCell::App { ports } => ast::Term::app(self.term(ports.0)?, self.term(ports.1)?),
+            JsonCell::Dup { ports, label } => ast::Term::Dup(
+                Box::new(self.term(ports.0)?),
+                Box::new(self.term(ports.1)?),
+                label.map(Label::User),
+            ),
+        })
+    }
+}
+
+impl Net {
+    /// Build a net from the JSON description documented in
+    /// [`crate::strandal::json`].
+    pub fn from_json(src: &str) -> Result<Self, JsonError> {
+        let json: JsonNet = serde_json::from_str(src)?;
+        let mut reader = JsonReader {
+            json: &json,
+            used: vec![false; json.cells.len()],
+        };
+        let head = json
+            .head
+            .iter()
+            .map(|port| reader.term(*port))
+            .collect::<Result<_, _>>()?;
+        let body = json
+            .body
+            .iter()
+            .map(|(left, right)| Ok::<_, JsonError>((reader.term(*left)?, reader.term(*right)?)))
+            .collect::<Result<_, _>>()?;
+        if let Some(idx) = reader.used.iter().position(|used| !used) {
+            return Err(JsonError::UnusedCell(idx as u32));
+        }
+        Net::from_ast(&ast::Net { head, body }).map_err(JsonError::Var)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{json::JsonError, net::Net, runtime::Runtime};
+
+    #[test]
+    fn test_from_json() {
+        let mut net = Net::from_json(
+            r#"{
+                "vars": 3,
+                "cells": [
+                    { "kind": "lam", "ports": [{ "var": 1 }, { "var": 1 }] },
+                    { "kind": "lam", "ports": [{ "var": 2 }, { "var": 2 }] },
+                    { "kind": "app", "ports": [{ "var": 0 }, { "cell": 1 }] }
+                ],
+                "head": [{ "var": 0 }],
+                "body": [[{ "cell": 0 }, { "cell": 2 }]]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            net.to_ast().unwrap().to_string(),
+            "head: x0\nbody:\n  (λ x1 x1) ~ (@ x0 (λ x3 x3))"
+        );
+        Runtime::new().eval(&mut net).unwrap();
+        assert_eq!(net.normal_form().unwrap()[0].to_string(), "(λ x3 x3)");
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        let error = |src: &str| Net::from_json(src).err().unwrap().to_string();
+        assert_eq!(
+            error(r#"{ "vars": 1, "head": [{ "var": 1 }] }"#),
+            "var 1 is out of range"
+        );
+        assert_eq!(
+            error(
+                r#"{ "vars": 0, "cells": [{ "kind": "lam", "ports": ["era", { "cell": 0 }] }], "head": [{ "cell": 0 }] }"#
+            ),
+            "cell 0 is used more than once"
+        );
+        assert_eq!(
+            error(r#"{ "vars": 0, "cells": [{ "kind": "app", "ports": ["era", "era"] }] }"#),
+            "cell 0 is never used"
+        );
+        assert_eq!(
+            error(r#"{ "vars": 1, "head": [{ "var": 0 }] }"#),
+            "var `v0` is used only once"
+        );
+        assert!(matches!(
+            Net::from_json(r#"{ "vars": 0, "cells": [{ "kind": "era" }] }"#),
+            Err(JsonError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+};
+
+use super::{
+    net::Net,
+    store::Ptr,
+    term::{Cell, TermPtr, TermRef},
+    var::VarValue,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SlotKind {
+    Var,
+    Lam,
+    App,
+    Dup,
+    Num,
+    Op,
+    Swi,
+    Ref,
+}
+
+impl SlotKind {
+    pub(crate) fn of(term: TermRef) -> Self {
+        match term {
+            TermRef::Var(_) => SlotKind::Var,
+            TermRef::Cell(Cell::Lam(_)) => SlotKind::Lam,
+            TermRef::Cell(Cell::App(_)) => SlotKind::App,
+            TermRef::Cell(Cell::Dup(_, _)) => SlotKind::Dup,
+            TermRef::Cell(Cell::Num(_)) => SlotKind::Num,
+            TermRef::Cell(Cell::Op(..)) => SlotKind::Op,
+            TermRef::Cell(Cell::Swi(_)) => SlotKind::Swi,
+            TermRef::Cell(Cell::Ref(_)) => SlotKind::Ref,
+        }
+    }
+}
+
+impl Display for SlotKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            SlotKind::Var => "VAR",
+            SlotKind::Lam => "LAM",
+            SlotKind::App => "APP",
+            SlotKind::Dup => "DUP",
+            SlotKind::Num => "NUM",
+            SlotKind::Op => "OP",
+            SlotKind::Swi => "SWI",
+            SlotKind::Ref => "REF",
+        })
+    }
+}
+
+/// Live store slots that cannot be reached from the net heads (or from
+/// equations still waiting in the body), grouped by what they hold.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LeakReport {
+    pub leaks: BTreeMap<SlotKind, Vec<Ptr>>,
+}
+
+impl LeakReport {
+    pub fn is_empty(&self) -> bool {
+        self.leaks.is_empty()
+    }
+
+    pub fn total(&self) -> usize {
+        self.leaks.values().map(|ptrs| ptrs.len()).sum()
+    }
+}
+
+impl Display for LeakReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LEAKS   | total: {}", self.total())?;
+        for (kind, ptrs) in &self.leaks {
+            write!(f, "\n{:<7} | {}:", kind, ptrs.len())?;
+            for ptr in ptrs.iter().take(10) {
+                write!(f, " {}", ptr)?;
+            }
+            if ptrs.len() > 10 {
+                write!(f, " ...")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Net {
+    /// Walk everything reachable from the heads, the pending body equations
+    /// and the interned constants, and report the live slots that were never visited.
+    ///
+    /// After a complete evaluation every remaining slot should be part of the
+    /// result, so any leak points at a reduction rule that forgot to recycle
+    /// a pointer.
+    pub fn leaks(&self) -> LeakReport {
+        let mut reachable: HashSet<Ptr> = HashSet::new();
+        let mut stack: Vec<TermPtr> = self.head.clone();
+        for (eqn, _) in &self.body {
+            stack.push(eqn.left());
+            stack.push(eqn.right());
+        }
+        stack.extend(self.interner.roots().map(TermPtr::Ptr));
+
+        while let Some(term_ptr) = stack.pop() {
+            let TermPtr::Ptr(ptr) = term_ptr else {
+                continue;
+            };
+            if !self.store.handed_out(ptr) || !reachable.insert(ptr) {
+                continue;
+            }
+            match self.store.get(ptr) {
+                Some(TermRef::Cell(cell)) => {
+                    if let Some((p0, p1)) = cell.ports() {
+                        stack.push(p0);
+                        stack.push(p1);
+                    }
+                }
+                Some(TermRef::Var(var)) => match var.read() {
+                    Some(VarValue::Var(ptr)) | Some(VarValue::Cell(ptr)) => {
+                        stack.push(TermPtr::Ptr(ptr))
+                    }
+                    Some(VarValue::Era) | None => {}
+                },
+                None => {}
+            }
+        }
+
+        let mut report = LeakReport::default();
+        for ptr in self.store.ptrs() {
+            if reachable.contains(&ptr) {
+                continue;
+            }
+            if let Some(term) = self.store.get(ptr) {
+                report
+                    .leaks
+                    .entry(SlotKind::of(term))
+                    .or_default()
+                    .push(ptr);
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        leaks::SlotKind,
+        net::{Net, NetBuilder},
+        term::TermPtr,
+    };
+
+    #[test]
+    fn test_no_leaks() {
+        let mut net = Net::new();
+        let x = net.var();
+        let lam = net.lam(x.0, x.1);
+        let r = net.var();
+        net.head(r.1);
+        net.eqn(r.0, lam);
+        assert!(net.leaks().is_empty());
+    }
+
+    #[test]
+    fn test_unreachable_cells() {
+        let mut net = Net::new();
+        let r = net.var();
+        net.head(r.1);
+        let x = net.var();
+        let x_ptr = x.0.ptr();
+        let lam_ptr = net.lam(x.0, x.1).ptr();
+        let dup_ptr = net.dup(TermPtr::Era, TermPtr::Era).ptr();
+
+        let report = net.leaks();
+        assert_eq!(report.total(), 3);
+        assert_eq!(report.leaks[&SlotKind::Lam], vec![lam_ptr]);
+        assert_eq!(report.leaks[&SlotKind::Dup], vec![dup_ptr]);
+        assert_eq!(report.leaks[&SlotKind::Var], vec![x_ptr]);
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "LEAKS   | total: 3\nVAR     | 1: {}\nLAM     | 1: {}\nDUP     | 1: {}",
+                x_ptr, lam_ptr, dup_ptr
+            )
+        );
+    }
+}
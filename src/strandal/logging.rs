@@ -0,0 +1,315 @@
+//! The rewrite rules the runtime logs at debug level, and which of them to
+//! log: on a big net logging every bind and connect buries the few rules
+//! under investigation, so
+//! [`RuntimeConfig::trace_rules`](super::runtime::RuntimeConfig::trace_rules)
+//! can keep just those. On a million-rewrite run even a single rule logs
+//! too much, so [`RuntimeConfig::sample_logs`](super::runtime::RuntimeConfig::sample_logs)
+//! only logs every Nth rewrite of each rule, see [`Sampler`].
+//!
+//! For tools rather than people, [`RuntimeConfig::json_log`](super::runtime::RuntimeConfig::json_log)
+//! also writes every rewrite as a JSON object of its own line, see
+//! [`JsonLog`].
+
+use std::{
+    fmt::{Debug, Display},
+    io::Write,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use serde_json::json;
+
+use super::store::Ptr;
+
+/// A rewrite rule, named as its counter in the stats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    AnniEraEra,
+    AnniLamLam,
+    AnniAppApp,
+    AnniDupDup,
+    CommEraApp,
+    CommEraLam,
+    CommEraDup,
+    CommEraNum,
+    CommEraOps,
+    CommEraRef,
+    CommAppLam,
+    CommAppDup,
+    CommLamDup,
+    CommDupDup,
+    CommNum,
+    CommOps,
+    CommRef,
+    OperNum,
+    SwiNum,
+    ExpandRef,
+    Bind,
+    Connect,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 22] = [
+        Rule::AnniEraEra,
+        Rule::AnniLamLam,
+        Rule::AnniAppApp,
+        Rule::AnniDupDup,
+        Rule::CommEraApp,
+        Rule::CommEraLam,
+        Rule::CommEraDup,
+        Rule::CommEraNum,
+        Rule::CommEraOps,
+        Rule::CommEraRef,
+        Rule::CommAppLam,
+        Rule::CommAppDup,
+        Rule::CommLamDup,
+        Rule::CommDupDup,
+        Rule::CommNum,
+        Rule::CommOps,
+        Rule::CommRef,
+        Rule::OperNum,
+        Rule::SwiNum,
+        Rule::ExpandRef,
+        Rule::Bind,
+        Rule::Connect,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::AnniEraEra => "anni_era_era",
+            Rule::AnniLamLam => "anni_lam_lam",
+            Rule::AnniAppApp => "anni_app_app",
+            Rule::AnniDupDup => "anni_dup_dup",
+            Rule::CommEraApp => "comm_era_app",
+            Rule::CommEraLam => "comm_era_lam",
+            Rule::CommEraDup => "comm_era_dup",
+            Rule::CommEraNum => "comm_era_num",
+            Rule::CommEraOps => "comm_era_ops",
+            Rule::CommEraRef => "comm_era_ref",
+            Rule::CommAppLam => "comm_app_lam",
+            Rule::CommAppDup => "comm_app_dup",
+            Rule::CommLamDup => "comm_lam_dup",
+            Rule::CommDupDup => "comm_dup_dup",
+            Rule::CommNum => "comm_num",
+            Rule::CommOps => "comm_ops",
+            Rule::CommRef => "comm_ref",
+            Rule::OperNum => "oper_num",
+            Rule::SwiNum => "swi_num",
+            Rule::ExpandRef => "expand_ref",
+            Rule::Bind => "bind",
+            Rule::Connect => "connect",
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.name() == name)
+            .ok_or_else(|| format!("unknown rule `{}`", name))
+    }
+}
+
+/// A set of rules to log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleFilter(u32);
+
+impl RuleFilter {
+    pub fn contains(&self, rule: Rule) -> bool {
+        self.0 & (1 << rule as u32) != 0
+    }
+
+    pub fn insert(&mut self, rule: Rule) {
+        self.0 |= 1 << rule as u32;
+    }
+}
+
+impl FromIterator<Rule> for RuleFilter {
+    fn from_iter<T: IntoIterator<Item = Rule>>(rules: T) -> Self {
+        let mut filter = RuleFilter::default();
+        rules.into_iter().for_each(|rule| filter.insert(rule));
+        filter
+    }
+}
+
+impl FromStr for RuleFilter {
+    type Err = String;
+
+    /// Rule names separated by commas, as in `comm_app_dup,bind`.
+    fn from_str(names: &str) -> Result<Self, Self::Err> {
+        names.split(',').map(|name| name.trim().parse()).collect()
+    }
+}
+
+/// Picks every Nth event of each rule to log: the first one, the `N + 1`th
+/// and so on, counting the events of every rule apart so a rare rule still
+/// shows up next to a frequent one.
+#[derive(Debug)]
+pub struct Sampler {
+    every: usize,
+    // one count per rule, and one for the events of no rule
+    counts: [AtomicUsize; Rule::ALL.len() + 1],
+}
+
+impl Sampler {
+    /// Sample every `every`th event, or every event when `every` is 0 or 1.
+    pub fn new(every: usize) -> Self {
+        Self {
+            every: every.max(1),
+            counts: std::array::from_fn(|_| AtomicUsize::new(0)),
+        }
+    }
+
+    /// Count an event of `rule`, or of no rule when `None`, returning
+    /// whether to log it.
+    #[inline]
+    pub fn sample(&self, rule: Option<Rule>) -> bool {
+        if self.every == 1 {
+            return true;
+        }
+        let idx = rule.map_or(Rule::ALL.len(), |rule| rule as usize);
+        self.counts[idx]
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(self.every)
+    }
+}
+
+/// Every rewrite as one JSON object per line, for `jq` and the like:
+///
+/// ```text
+/// {"ns":48211,"ptrs":[18,7],"rule":"COMM APP-LAM","thread":2}
+/// ```
+///
+/// `ptrs` are the store indices of the two agents, `null` for an eraser
+/// or a cell not allocated yet, and `ns` the nanoseconds since the log was
+/// created. Rules are named as in traces, and logged before they apply, so
+/// the objects of one thread are in the order it rewrote.
+pub struct JsonLog {
+    out: Mutex<Box<dyn Write + Send>>,
+    start: Instant,
+}
+
+impl JsonLog {
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        JsonLog {
+            out: Mutex::new(Box::new(out)),
+            start: Instant::now(),
+        }
+    }
+
+    /// Log a rewrite of `rule` by `thread`, between the agents at `ptrs`. A
+    /// log that fails to write is not worth aborting the eval for, so write
+    /// errors are dropped.
+    pub fn rewrite(&self, rule: &str, thread: usize, ptrs: [Option<Ptr>; 2]) {
+        let ns = self.start.elapsed().as_nanos() as u64;
+        let ptrs = ptrs.map(|ptr| ptr.map(|ptr| ptr.index()));
+        let line = json!({ "rule": rule, "thread": thread, "ptrs": ptrs, "ns": ns });
+        let mut out = self.out.lock().unwrap();
+        let _ = serde_json::to_writer(&mut *out, &line);
+        let _ = writeln!(out);
+    }
+
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.out.lock().unwrap().flush()
+    }
+}
+
+impl Debug for JsonLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "JsonLog")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::strandal::{
+        ast::{self, Term},
+        net::Net,
+        parser::try_parse_eqns,
+        runtime::{Runtime, RuntimeConfig},
+    };
+
+    use super::{JsonLog, Rule, RuleFilter, Sampler};
+
+    // a writer whose output outlives the log
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_rule_filter() {
+        for rule in Rule::ALL {
+            assert_eq!(rule.name().parse(), Ok(rule));
+        }
+        let filter: RuleFilter = "comm_app_dup, bind".parse().unwrap();
+        assert!(filter.contains(Rule::CommAppDup) && filter.contains(Rule::Bind));
+        assert_eq!(filter, [Rule::Bind, Rule::CommAppDup].into_iter().collect());
+        assert!(!filter.contains(Rule::Connect));
+        assert_eq!(
+            "bind,comm_app".parse::<RuleFilter>(),
+            Err("unknown rule `comm_app`".to_string())
+        );
+    }
+    #[test]
+    fn test_sampler() {
+        let sampler = Sampler::new(3);
+        let binds: Vec<_> = (0..7).map(|_| sampler.sample(Some(Rule::Bind))).collect();
+        assert_eq!(binds, [true, false, false, true, false, false, true]);
+        // every rule is counted apart
+        assert!(sampler.sample(Some(Rule::Connect)));
+        assert!(sampler.sample(None));
+        assert!(!sampler.sample(Some(Rule::Bind)));
+
+        let sampler = Sampler::new(0);
+        assert!((0..3).all(|_| sampler.sample(Some(Rule::Bind))));
+    }
+
+    #[test]
+    fn test_json_log() {
+        let out = Shared::default();
+        let log = Arc::new(JsonLog::new(out.clone()));
+        let ast = ast::Net {
+            head: vec![Term::var("r")],
+            body: try_parse_eqns("(x x) ~ [(y y) r]").unwrap(),
+        };
+        let mut net = Net::from_ast(&ast).unwrap();
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().json_log(log.clone()));
+        runtime.eval(&mut net).unwrap();
+        log.flush().unwrap();
+
+        let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> =
+            out.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), runtime.stats.rewrites());
+        let rules: Vec<_> = lines.iter().map(|line| line["rule"].as_str().unwrap()).collect();
+        assert_eq!(rules, ["COMM APP-LAM", "CONNECT", "BIND"]);
+        assert!(lines.iter().all(|line| line["ptrs"].as_array().unwrap().len() == 2));
+    }
+}
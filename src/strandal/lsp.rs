@@ -0,0 +1,339 @@
+//! A language server for net files, run by the `strandal-lsp` binary over
+//! the standard input and output.
+//!
+//! It publishes the problems found by [`try_parse_ast`] and [`check`] as
+//! diagnostics, jumps from a `@name` reference to its definition and shows
+//! the interface of a definition on hover. Only full document syncs are
+//! supported, and columns are counted in chars.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+
+use super::{
+    ast::Def,
+    check::check,
+    format::signature,
+    parser::{parse_ast, try_parse_ast},
+};
+
+/// The open documents, by URI.
+#[derive(Debug, Default)]
+pub struct Server {
+    docs: HashMap<String, String>,
+}
+
+impl Server {
+    /// Handle a request or notification, returning the messages to send
+    /// back: the response to a request, and the diagnostics of a document
+    /// that changed.
+    pub fn handle(&mut self, msg: &Value) -> Vec<Value> {
+        let method = msg["method"].as_str().unwrap_or_default();
+        let params = &msg["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                },
+                "serverInfo": { "name": "strandal-lsp" },
+            }),
+            "shutdown" => Value::Null,
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.docs.insert(uri.to_string(), text.to_string());
+                return vec![self.publish(uri)];
+            }
+            "textDocument/didChange" => {
+                // full syncs send the whole text as the last change
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()?["text"].as_str()) {
+                    self.docs.insert(uri.to_string(), text.to_string());
+                }
+                return vec![self.publish(uri)];
+            }
+            "textDocument/didClose" => {
+                self.docs.remove(uri);
+                return vec![notification(
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                )];
+            }
+            "textDocument/definition" => match self.definition(uri, &params["position"]) {
+                Some(range) => json!({ "uri": uri, "range": range }),
+                None => Value::Null,
+            },
+            "textDocument/hover" => self.hover(uri, &params["position"]).unwrap_or_default(),
+            _ if msg.get("id").is_none() => return vec![],
+            _ => {
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": msg["id"],
+                    "error": { "code": -32601, "message": format!("unknown method `{}`", method) },
+                })]
+            }
+        };
+        match msg.get("id") {
+            Some(id) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+            None => vec![],
+        }
+    }
+
+    fn publish(&self, uri: &str) -> Value {
+        let src = self.docs.get(uri).map(String::as_str).unwrap_or_default();
+        notification(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics(src) }),
+        )
+    }
+
+    // the range of the definition named by the word at `position`
+    fn definition(&self, uri: &str, position: &Value) -> Option<Value> {
+        let src = self.docs.get(uri)?;
+        let (_, name) = word_at(src, position)?;
+        let (_, range) = definitions(src).into_iter().find(|(def, _)| *def == name)?;
+        Some(range)
+    }
+
+    // the interface of the definition named by the word at `position`
+    fn hover(&self, uri: &str, position: &Value) -> Option<Value> {
+        let src = self.docs.get(uri)?;
+        let (range, name) = word_at(src, position)?;
+        let book = parse_ast(src)?;
+        let def = book.defs.iter().find(|def| def.name == name)?;
+        Some(json!({
+            "contents": { "kind": "markdown", "value": interface(def) },
+            "range": range,
+        }))
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+// an LSP range on a single line, from 0-based positions
+fn range(line: usize, start: usize, end: usize) -> Value {
+    json!({
+        "start": { "line": line, "character": start },
+        "end": { "line": line, "character": end },
+    })
+}
+
+// the parse errors of `src`, or else the problems found by `check`, at the
+// name of their definition
+fn diagnostics(src: &str) -> Vec<Value> {
+    let diagnostic = |range, message: String| {
+        json!({ "range": range, "severity": 1, "source": "strandal", "message": message })
+    };
+    let book = match try_parse_ast(src) {
+        Ok(book) => book,
+        Err(errors) => {
+            return errors
+                .into_iter()
+                .map(|err| {
+                    let (line, column) = (err.line - 1, err.column - 1);
+                    diagnostic(range(line, column, column + 1), err.reason)
+                })
+                .collect()
+        }
+    };
+    let defs = definitions(src);
+    check(&book)
+        .into_iter()
+        .map(|found| {
+            let at = found
+                .def
+                .as_ref()
+                .and_then(|name| defs.iter().find(|(def, _)| def == name))
+                .map_or_else(|| range(0, 0, 0), |(_, range)| range.clone());
+            diagnostic(at, found.to_string())
+        })
+        .collect()
+}
+
+// the header of a definition and its number of head ports
+fn interface(def: &Def) -> String {
+    let ports = match def.net.head.len() {
+        1 => "1 port".to_string(),
+        ports => format!("{} ports", ports),
+    };
+    format!("```\n{}\n```\n{}", signature(def), ports)
+}
+
+// the words of `src`, as the line, the char range and the word
+fn words(src: &str) -> impl Iterator<Item = (usize, usize, usize, &str)> {
+    src.lines().enumerate().flat_map(|(line, text)| {
+        let mut words = Vec::new();
+        let mut start = None;
+        for (column, (byte, c)) in text.char_indices().chain([(text.len(), ' ')]).enumerate() {
+            match (c.is_alphanumeric() || c == '_', start) {
+                (true, None) => start = Some((column, byte)),
+                (false, Some((column_start, byte_start))) => {
+                    words.push((line, column_start, column, &text[byte_start..byte]));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        words
+    })
+}
+
+// the name of every definition of `src` and its range, from the word that
+// follows each `def`
+fn definitions(src: &str) -> Vec<(&str, Value)> {
+    let words: Vec<_> = words(src).collect();
+    words
+        .windows(2)
+        .filter(|pair| pair[0].3 == "def")
+        .map(|pair| {
+            let (line, start, end, name) = pair[1];
+            (name, range(line, start, end))
+        })
+        .collect()
+}
+
+// the word at an LSP position, and its range
+fn word_at<'a>(src: &'a str, position: &Value) -> Option<(Value, &'a str)> {
+    let line = position["line"].as_u64()? as usize;
+    let column = position["character"].as_u64()? as usize;
+    words(src)
+        .find(|(at, start, end, _)| *at == line && (*start..=*end).contains(&column))
+        .map(|(line, start, end, word)| (range(line, start, end), word))
+}
+
+/// Read a message framed by a `Content-Length` header, or `None` at the end
+/// of the input.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing Content-Length",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Write `msg` framed by a `Content-Length` header.
+pub fn write_message(output: &mut impl Write, msg: &Value) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// Serve the messages of `input` until the client sends `exit`.
+pub fn run(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut server = Server::default();
+    while let Some(msg) = read_message(&mut input)? {
+        if msg["method"] == "exit" {
+            break;
+        }
+        for reply in server.handle(&msg) {
+            write_message(&mut output, &reply)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{read_message, run, Server};
+
+    const URI: &str = "file:///fact.strandal";
+    const SRC: &str = "def main(out r) = @fact ~ [5 r];\n\
+                       def fact((n r)) = n ~ (? (1 @fact_s) r);\n\
+                       def fact_s((p s)) = p ~ {p1 p2} & p1 ~ (+ 1 m) & @fact ~ [p2 f] & m ~ (* f s)";
+
+    fn open(server: &mut Server, text: &str) -> Value {
+        let msg = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": URI, "text": text } },
+        });
+        server.handle(&msg).remove(0)["params"]["diagnostics"].clone()
+    }
+
+    fn request(server: &mut Server, method: &str, line: usize, character: usize) -> Value {
+        let msg = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+            },
+        });
+        server.handle(&msg).remove(0)["result"].clone()
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut server = Server::default();
+        assert_eq!(open(&mut server, SRC), json!([]));
+
+        let diagnostics = open(&mut server, "def main(r) =\n  r ~ (x");
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 1, "character": 8 }));
+
+        let diagnostics = open(&mut server, "def main(r) = (x x) ~ a & a ~ (y (* r))");
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 0, "character": 4 }));
+    }
+
+    #[test]
+    fn test_definition_and_hover() {
+        let mut server = Server::default();
+        open(&mut server, SRC);
+        // `@fact` in `main` jumps to `def fact`
+        let location = request(&mut server, "textDocument/definition", 0, 20);
+        assert_eq!(location["range"]["start"], json!({ "line": 1, "character": 4 }));
+        assert_eq!(
+            request(&mut server, "textDocument/definition", 0, 27),
+            Value::Null
+        );
+
+        let hover = request(&mut server, "textDocument/hover", 1, 30);
+        assert_eq!(
+            hover["contents"]["value"],
+            "```\ndef fact_s((p s))\n```\n1 port"
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let mut input = Vec::new();
+        for msg in [
+            json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ] {
+            let body = msg.to_string();
+            input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).bytes());
+        }
+        let mut output = Vec::new();
+        run(&input[..], &mut output).unwrap();
+        let reply = read_message(&mut &output[..]).unwrap().unwrap();
+        assert_eq!(reply["result"]["capabilities"]["hoverProvider"], true);
+    }
+}
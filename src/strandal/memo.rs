@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use super::{
+    ast,
+    net::{Net, NetBuilder},
+    runtime::{Runtime, RuntimeConfig},
+    store::Index,
+    template::Template,
+};
+
+/// Redexes with more cells than this are reduced normally: capturing and
+/// hashing them costs more than it can save.
+pub const MEMO_MAX_CELLS: usize = 64;
+
+/// Live slots allowed while reducing a captured redex on its own. Redexes
+/// that grow past it are remembered as not worth memoizing.
+const MEMO_MAX_SCRATCH: Index = 1 << 16;
+
+// the slots of a scratch net, leaving room for the rewrites still running
+// when a reduction goes past `MEMO_MAX_SCRATCH`
+const MEMO_SCRATCH_CAPACITY: Index = 2 * MEMO_MAX_SCRATCH;
+
+/// Reduced forms of closed redexes, keyed by their canonical [`Template`].
+///
+/// The reduced form of a redex is its normal form with the boundary vars as
+/// heads, so it can be spliced back in place of any redex with the same
+/// structure, whatever the boundary vars are connected to.
+#[derive(Default)]
+pub struct Memo {
+    // `None` marks redexes that could not be reduced on their own
+    table: Mutex<HashMap<Template, Option<Arc<Template>>>>,
+    // the scratch nets and runtimes not in use by a reduction
+    scratch: Mutex<Vec<(Net, Runtime)>>,
+}
+
+impl Memo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The reduced form of `redex`, and whether it was already known.
+    pub(crate) fn reduce(&self, redex: &Template) -> (Option<Arc<Template>>, bool) {
+        if let Some(reduced) = self.table.lock().unwrap().get(redex) {
+            return (reduced.clone(), true);
+        }
+        // not holding the lock: reducing runs nested tasks on this pool
+        let reduced = self.reduce_alone(redex).map(Arc::new);
+        self.table
+            .lock()
+            .unwrap()
+            .insert(redex.clone(), reduced.clone());
+        (reduced, false)
+    }
+
+    fn reduce_alone(&self, redex: &Template) -> Option<Template> {
+        let (mut scratch, mut runtime) = self.scratch.lock().unwrap().pop().unwrap_or_else(|| {
+            let config = RuntimeConfig::default().max_cells(MEMO_MAX_SCRATCH);
+            (
+                Net::with_capacity(MEMO_SCRATCH_CAPACITY),
+                Runtime::with_config(config),
+            )
+        });
+        let reduced = Self::reduce_in(&mut scratch, &mut runtime, redex);
+        scratch.clear();
+        self.scratch.lock().unwrap().push((scratch, runtime));
+        reduced
+    }
+
+    fn reduce_in(scratch: &mut Net, runtime: &mut Runtime, redex: &Template) -> Option<Template> {
+        let iface = scratch.instantiate(redex);
+        for port in iface.ports {
+            scratch.head(port);
+        }
+        runtime.eval_in(scratch).ok()?;
+        let head = scratch.normal_form().ok()?;
+        Template::from_ast(&ast::Net {
+            head,
+            body: Vec::new(),
+        })
+        .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        ast::{self, Term},
+        memo::Memo,
+        template::Template,
+    };
+
+    #[test]
+    fn test_reduce() {
+        let redex = Template::from_ast(&ast::Net {
+            head: vec![Term::var("r")],
+            body: vec![(
+                Term::lam(Term::var("x"), Term::var("x")),
+                Term::app(Term::var("r"), Term::lam(Term::var("y"), Term::var("y"))),
+            )],
+        })
+        .unwrap();
+
+        let memo = Memo::new();
+        let (reduced, known) = memo.reduce(&redex);
+        assert!(!known);
+        let (again, known) = memo.reduce(&redex);
+        assert!(known);
+        assert_eq!(reduced, again);
+        assert_eq!(memo.len(), 1);
+        // the scratch net and runtime of the first reduction are kept
+        assert_eq!(memo.scratch.lock().unwrap().len(), 1);
+
+        let id = Template::from_ast(&ast::Net {
+            head: vec![Term::lam(Term::var("z"), Term::var("z"))],
+            body: vec![],
+        })
+        .unwrap();
+        assert_eq!(reduced.as_deref(), Some(&id));
+    }
+}
@@ -0,0 +1,226 @@
+//! Enumerate every net of up to three cells among lambdas, applications,
+//! duplicators and erasers, wired in every possible way, and check that the
+//! parallel runtime reduces each one to the same normal form as the
+//! sequential [`rules`](super::rules) machine, loaded with the interaction
+//! rules of those cells as a reference.
+//!
+//! The reference has no rule for two lambdas or two applications, which
+//! the runtime erases, so the nets that reach such a pair are skipped, and
+//! so are those that do not terminate within a few rewrites. Interaction
+//! nets are strongly confluent: a net the reference normalizes reaches the
+//! same normal form in any order, whatever the schedule of the runtime.
+
+use std::collections::HashMap;
+
+use super::{
+    ast::{self, Term},
+    net::Net,
+    rules::{AgentNet, AgentProgram, AgentTerm, RuleError, RuleTable},
+    runtime::Runtime,
+};
+
+const REFERENCE: &str = "
+    agent Lam 2 ;
+    agent App 2 ;
+    agent Dup 2 ;
+    agent Era 0 ;
+    rule Lam(x, b) >< App(a, r) = x ~ a & b ~ r ;
+    rule Dup(a, b) >< Dup(c, d) = a ~ c & b ~ d ;
+    rule Lam(a, b) >< Dup(c, d) = a ~ Dup(w, x) & b ~ Dup(y, z) & c ~ Lam(w, y) & d ~ Lam(x, z) ;
+    rule App(a, b) >< Dup(c, d) = a ~ Dup(w, x) & b ~ Dup(y, z) & c ~ App(w, y) & d ~ App(x, z) ;
+    rule Era >< Lam(a, b) = a ~ Era & b ~ Era ;
+    rule Era >< App(a, b) = a ~ Era & b ~ Era ;
+    rule Era >< Dup(a, b) = a ~ Era & b ~ Era ;
+    rule Era >< Era = ;
+";
+
+// rewrites after which a net is taken not to terminate
+const MAX_REWRITES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Lam,
+    App,
+    Dup,
+    Era,
+}
+
+impl Kind {
+    const ALL: [Kind; 4] = [Kind::Lam, Kind::App, Kind::Dup, Kind::Era];
+
+    fn arity(&self) -> usize {
+        match self {
+            Kind::Era => 0,
+            _ => 2,
+        }
+    }
+}
+
+// a net as the wire of each port: the heads, then the principal and
+// auxiliary ports of each cell in turn
+struct Wiring {
+    kinds: Vec<Kind>,
+    heads: usize,
+    wires: Vec<usize>,
+}
+
+impl Wiring {
+    // each cell as an equation between the wire of its principal port and
+    // the cell, its auxiliary ports named after their wires
+    fn eqns<T>(&self, var: impl Fn(usize) -> T, cell: impl Fn(Kind, Vec<T>) -> T) -> Vec<(T, T)> {
+        let mut port = self.heads;
+        let mut eqns = Vec::new();
+        for kind in &self.kinds {
+            let principal = var(self.wires[port]);
+            let aux = (1..=kind.arity()).map(|idx| var(self.wires[port + idx])).collect();
+            eqns.push((principal, cell(*kind, aux)));
+            port += 1 + kind.arity();
+        }
+        eqns
+    }
+
+    fn to_ast(&self) -> ast::Net {
+        let var = |wire: usize| Term::var(&format!("w{}", wire));
+        let cell = |kind: Kind, mut aux: Vec<Term>| match kind {
+            Kind::Era => Term::Era,
+            _ => {
+                let (p1, p0) = (aux.pop().unwrap(), aux.pop().unwrap());
+                match kind {
+                    Kind::Lam => Term::lam(p0, p1),
+                    Kind::App => Term::app(p0, p1),
+                    _ => Term::dup(p0, p1),
+                }
+            }
+        };
+        ast::Net {
+            head: (0..self.heads).map(|head| var(self.wires[head])).collect(),
+            body: self.eqns(var, cell),
+        }
+    }
+
+    fn to_program(&self) -> AgentProgram {
+        let var = |wire: usize| AgentTerm::Var(format!("w{}", wire));
+        let cell = |kind: Kind, aux| AgentTerm::Agent(format!("{:?}", kind), aux);
+        AgentProgram {
+            head: (0..self.heads).map(|head| var(self.wires[head])).collect(),
+            body: self.eqns(var, cell),
+        }
+    }
+}
+
+// every way to pair up `ports` ports, as the wire of each port
+fn matchings(ports: usize) -> Vec<Vec<usize>> {
+    fn pair(wires: &mut Vec<Option<usize>>, next: usize, out: &mut Vec<Vec<usize>>) {
+        let Some(first) = wires.iter().position(Option::is_none) else {
+            out.push(wires.iter().map(|wire| wire.unwrap()).collect());
+            return;
+        };
+        wires[first] = Some(next);
+        for other in first + 1..wires.len() {
+            if wires[other].is_none() {
+                wires[other] = Some(next);
+                pair(wires, next + 1, out);
+                wires[other] = None;
+            }
+        }
+        wires[first] = None;
+    }
+    let mut out = Vec::new();
+    pair(&mut vec![None; ports], 0, &mut out);
+    out
+}
+
+// every multiset of `cells` kinds, in order
+fn kinds(cells: usize) -> Vec<Vec<Kind>> {
+    match cells {
+        0 => vec![Vec::new()],
+        _ => kinds(cells - 1)
+            .into_iter()
+            .flat_map(|kinds| {
+                let min = kinds.last().copied().unwrap_or(Kind::Lam);
+                Kind::ALL
+                    .into_iter()
+                    .filter(move |kind| *kind >= min)
+                    .map(move |kind| [kinds.clone(), vec![kind]].concat())
+            })
+            .collect(),
+    }
+}
+
+// render a head, naming the vars in order of appearance
+fn render(term: &Term, names: &mut HashMap<String, usize>) -> String {
+    let mut var = |name: &String| {
+        let next = names.len();
+        format!("x{}", names.entry(name.clone()).or_insert(next))
+    };
+    match term {
+        Term::Era => "Era".to_string(),
+        Term::Var(name) => var(name),
+        Term::Lam(p0, p1) => format!("Lam({}, {})", render(p0, names), render(p1, names)),
+        Term::App(p0, p1) => format!("App({}, {})", render(p0, names), render(p1, names)),
+        Term::Dup(p0, p1, _) => format!("Dup({}, {})", render(p0, names), render(p1, names)),
+        other => panic!("unexpected {}", other),
+    }
+}
+
+fn render_agent(term: &AgentTerm, names: &mut HashMap<String, usize>) -> String {
+    match term {
+        AgentTerm::Var(name) => {
+            let next = names.len();
+            format!("x{}", names.entry(name.clone()).or_insert(next))
+        }
+        AgentTerm::Agent(name, ports) if ports.is_empty() => name.clone(),
+        AgentTerm::Agent(name, ports) => {
+            let ports: Vec<_> = ports.iter().map(|port| render_agent(port, names)).collect();
+            format!("{}({})", name, ports.join(", "))
+        }
+    }
+}
+
+#[test]
+fn test_small_nets() {
+    let (table, _) = RuleTable::parse(REFERENCE).unwrap();
+    let (mut compared, mut skipped) = (0, 0);
+    for cells in 1..=3 {
+        for kinds in kinds(cells) {
+            let ports: usize = kinds.iter().map(|kind| 1 + kind.arity()).sum();
+            // one or two heads, as the parity of the ports allows
+            let heads = 2 - ports % 2;
+            for wires in matchings(heads + ports) {
+                let wiring = Wiring {
+                    kinds: kinds.clone(),
+                    heads,
+                    wires,
+                };
+                let mut reference = AgentNet::new(&table, &wiring.to_program());
+                match reference.reduce_bounded(MAX_REWRITES) {
+                    Ok(true) => {}
+                    Ok(false) | Err(RuleError::NoRule(..)) => {
+                        skipped += 1;
+                        continue;
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+                let mut names = HashMap::new();
+                let expected: Vec<_> = reference
+                    .read()
+                    .iter()
+                    .map(|head| render_agent(head, &mut names))
+                    .collect();
+
+                let ast = wiring.to_ast();
+                let mut net = Net::from_ast(&ast).unwrap();
+                Runtime::new().eval(&mut net).unwrap();
+                let mut names = HashMap::new();
+                let found: Vec<_> = match net.normal_form() {
+                    Ok(heads) => heads.iter().map(|head| render(head, &mut names)).collect(),
+                    Err(err) => panic!("{} does not read back: {}", ast, err),
+                };
+                assert_eq!(found, expected, "{}", ast);
+                compared += 1;
+            }
+        }
+    }
+    assert!(compared > skipped, "{} nets compared, {} skipped", compared, skipped);
+}
+
@@ -1,17 +1,71 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    ops::Range,
+    sync::Arc,
+};
+
+use tracing::warn;
+
 use super::{
-    store::Store,
-    term::{Cell, Term, TermPtr},
-    var::{Var, VarUse},
+    intern::Interner,
+    num::Op,
+    refs::{RefId, Refs},
+    scope::{Interface, Scope, ScopeError},
+    store::{Index, Ptr, Store},
+    term::{Cell, CellUse, Label, PackedTermPtr, Term, TermPtr, TermRef},
+    var::{Var, VarUse, VarValue},
 };
 
+/// Two terms to connect, packed in 8 bytes in release builds, see
+/// [`PackedTermPtr`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Equation {
-    pub left: TermPtr,
-    pub right: TermPtr,
+    left: PackedTermPtr,
+    right: PackedTermPtr,
 }
+#[cfg(not(any(debug_assertions, feature = "wide-ptrs")))]
+const _: () = assert!(std::mem::size_of::<Equation>() == 8);
+
 impl Equation {
+    #[inline]
     pub fn new(left: TermPtr, right: TermPtr) -> Self {
-        Self { left, right }
+        Self {
+            left: left.into(),
+            right: right.into(),
+        }
+    }
+
+    #[inline]
+    pub fn left(&self) -> TermPtr {
+        self.left.into()
+    }
+
+    #[inline]
+    pub fn right(&self) -> TermPtr {
+        self.right.into()
+    }
+}
+
+/// What an equation of the body connects, told apart when it is added by
+/// [`NetBuilder::eqn`], so the runtime need not read its terms to dispatch it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EquationKind {
+    /// Two cells or erasers, which interact.
+    Redex,
+    /// A var, always on the left, and a cell or an eraser.
+    Bind,
+    /// Two vars.
+    Connect,
+}
+
+impl EquationKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EquationKind::Redex => "REDEX",
+            EquationKind::Bind => "BIND",
+            EquationKind::Connect => "CONNECT",
+        }
     }
 }
 
@@ -22,49 +76,270 @@ pub trait NetBuilder {
 
     fn var(&mut self) -> (VarUse, VarUse);
 
-    fn lam<T1, T2>(&mut self, binding: T1, body: T2) -> TermPtr
+    fn lam<T1, T2>(&mut self, binding: T1, body: T2) -> CellUse
     where
         T1: Into<TermPtr>,
         T2: Into<TermPtr>;
 
-    fn app<T1, T2>(&mut self, result: T1, arg: T2) -> TermPtr
+    /// An application of the function on its principal port: it takes
+    /// `arg` in its first port, which meets the binding of a lambda, and
+    /// gives `result` in its second one.
+    fn app<T1, T2>(&mut self, arg: T1, result: T2) -> CellUse
     where
         T1: Into<TermPtr>,
         T2: Into<TermPtr>;
 
-    fn dup<T1, T2>(&mut self, left: T1, right: T2) -> TermPtr
+    fn dup<T1, T2>(&mut self, left: T1, right: T2) -> CellUse
     where
         T1: Into<TermPtr>,
         T2: Into<TermPtr>;
 
+    /// A duplicator with a label of its own, so that it only annihilates
+    /// with its own copies and commutes with every other duplicator.
+    fn fresh_dup<T1, T2>(&mut self, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>;
+
+    /// A duplicator with the given label: it annihilates with the cells
+    /// of the same label and commutes with the others.
+    fn labeled_dup<T1, T2>(&mut self, label: Index, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>;
+
+    /// A superposition of `left` and `right`, see [`super::sup`].
+    fn sup<T1, T2>(&mut self, label: Index, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        self.labeled_dup(label, left, right)
+    }
+
+    /// A number, see [`super::num`].
+    fn num(&mut self, value: u32) -> CellUse;
+
+    /// An operator taking its left operand on its principal port.
+    fn op<T1, T2>(&mut self, op: Op, right: T1, result: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>;
+
+    /// A switch on the number meeting its principal port, choosing between
+    /// the `branches` built by [`NetBuilder::switch`].
+    fn swi<T1, T2>(&mut self, branches: T1, result: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>;
+
+    /// A switch giving `zero` for 0, and `succ` applied to the predecessor
+    /// of any other number.
+    fn switch<T1, T2, T3>(&mut self, zero: T1, succ: T2, result: T3) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+        T3: Into<TermPtr>,
+    {
+        let branches = self.lam(zero, succ);
+        self.swi(branches, result)
+    }
+
+    /// A reference to the definition `id`, see [`super::refs`].
+    fn reference(&mut self, id: RefId) -> CellUse;
+
     fn era(&mut self) -> TermPtr;
 
     fn eqn<T1, T2>(&mut self, left: T1, right: T2)
     where
         T1: Into<TermPtr>,
         T2: Into<TermPtr>;
+
+    /// Build a fragment with `build` and return its interface: the var uses
+    /// and cells it leaves for the caller to wire. Fails if a var or cell
+    /// allocated in the fragment is neither wired inside nor exposed.
+    fn scope<I, F>(&mut self, build: F) -> Result<I, ScopeError>
+    where
+        Self: Sized,
+        I: Interface,
+        F: FnOnce(&mut Scope<Self>) -> I,
+    {
+        let mut scope = Scope::new(self);
+        let interface = build(&mut scope);
+        scope.close(interface)
+    }
+}
+
+type OnHeadBound = dyn Fn(&Store, TermPtr) + Send + Sync;
+
+/// Called with the value a head was bound to, see [`Net::on_head_bound`].
+pub(crate) struct HeadCallback(pub(crate) Box<OnHeadBound>);
+
+impl Debug for HeadCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HeadCallback")
+    }
 }
 
 #[derive(Debug)]
 pub struct Net {
     pub(crate) head: Vec<TermPtr>,
-    pub(crate) body: Vec<Equation>,
+    pub(crate) body: Vec<(Equation, EquationKind)>,
     pub(crate) store: Store,
+    /// Store pointers allocated while loading each named definition, one
+    /// range per shard they were allocated from.
+    pub(crate) definitions: HashMap<String, Vec<Range<Index>>>,
+    /// Index into `head` of each named head port.
+    pub(crate) head_names: HashMap<String, usize>,
+    /// Unused end of each var named only once in the loaded book.
+    pub(crate) free_vars: HashMap<String, VarUse>,
+    /// Callbacks waiting for a head to be bound, by head index.
+    pub(crate) head_callbacks: Vec<(usize, HeadCallback)>,
+    /// Vars and cells whose rewrites the next eval traces.
+    pub(crate) traced: Vec<Ptr>,
+    /// Constants shared across the net, see [`Net::intern`].
+    pub(crate) interner: Arc<Interner>,
+    /// Definitions expanded by reference cells, see [`super::refs`].
+    pub(crate) refs: Arc<Refs>,
 }
 
 impl Net {
     pub fn new() -> Self {
+        Self::with_store(Store::new())
+    }
+    pub fn with_capacity(capacity: Index) -> Self {
+        Self::with_store(Store::with_capacity(capacity))
+    }
+    /// A net whose store is split into `shards`, see [`Store::with_shards`].
+    pub fn with_shards(capacity: Index, shards: usize) -> Self {
+        Self::with_store(Store::with_shards(capacity, shards))
+    }
+    /// A net over `store`, such as a checked one, see [`Store::checked`].
+    pub fn with_store(store: Store) -> Self {
         Net {
             head: Default::default(),
             body: Default::default(),
-            store: Store::new(),
+            store,
+            definitions: Default::default(),
+            head_names: Default::default(),
+            free_vars: Default::default(),
+            head_callbacks: Default::default(),
+            traced: Default::default(),
+            interner: Default::default(),
+            refs: Default::default(),
         }
     }
-    pub fn with_capacity(capacity: u32) -> Self {
-        Net {
-            head: Default::default(),
-            body: Default::default(),
-            store: Store::with_capacity(capacity),
+
+    /// Add a head port that can be looked up by `name` once evaluated. A
+    /// name given twice refers to the latest port.
+    pub fn named_head<T: Into<TermPtr>>(&mut self, name: &str, term_ptr: T) {
+        self.head_names.insert(name.to_string(), self.head.len());
+        self.head.push(term_ptr.into());
+    }
+
+    /// Call `callback` during the next eval, as soon as the head at `idx` is
+    /// bound to an eraser or a cell. The cell's ports may still be reducing
+    /// on other threads when it runs. Heads that are not vars are already
+    /// bound, so their callbacks run when eval starts.
+    pub fn on_head_bound<F>(&mut self, idx: usize, callback: F)
+    where
+        F: Fn(&Store, TermPtr) + Send + Sync + 'static,
+    {
+        self.head_callbacks
+            .push((idx, HeadCallback(Box::new(callback))));
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// How many of the pending equations are of each kind.
+    pub fn equation_kinds(&self) -> HashMap<EquationKind, usize> {
+        let mut kinds = HashMap::new();
+        for (_, kind) in &self.body {
+            *kinds.entry(*kind).or_default() += 1;
+        }
+        kinds
+    }
+
+    /// Empty the net to build it again in place, reusing the memory of its
+    /// store, see [`Store::clear`].
+    pub fn clear(&mut self) {
+        self.head.clear();
+        self.body.clear();
+        self.store.clear();
+        self.definitions.clear();
+        self.head_names.clear();
+        self.free_vars.clear();
+        self.head_callbacks.clear();
+        self.traced.clear();
+        self.interner = Default::default();
+        self.refs = Default::default();
+    }
+
+    /// Free every slot reachable from the heads and the pending equations,
+    /// and forget them, to build the net again in the same store. Unlike
+    /// [`Net::clear`], the slots the net leaked stay live, and so do the
+    /// interned constants and the definitions of the references.
+    pub fn release(&mut self) {
+        let roots = self
+            .head
+            .drain(..)
+            .chain(self.body.drain(..).flat_map(|(eqn, _)| [eqn.left(), eqn.right()]))
+            .collect();
+        self.free_reachable(roots);
+        self.definitions.clear();
+        self.head_names.clear();
+        self.free_vars.clear();
+        self.head_callbacks.clear();
+        self.traced.clear();
+    }
+
+    // free every slot reachable from `roots` but the interned constants,
+    // which the whole net shares
+    pub(crate) fn free_reachable(&self, mut roots: Vec<TermPtr>) {
+        let interned: HashSet<Ptr> = self.interner.roots().collect();
+        let mut reached = HashSet::new();
+        while let Some(term_ptr) = roots.pop() {
+            let TermPtr::Ptr(ptr) = term_ptr else {
+                continue;
+            };
+            if !self.store.handed_out(ptr) || interned.contains(&ptr) || !reached.insert(ptr) {
+                continue;
+            }
+            match self.store.get(ptr) {
+                Some(TermRef::Cell(cell)) => {
+                    roots.extend(cell.ports().iter().flat_map(|(p0, p1)| [*p0, *p1]))
+                }
+                Some(TermRef::Var(var)) => match var.read() {
+                    Some(VarValue::Var(ptr)) | Some(VarValue::Cell(ptr)) => {
+                        roots.push(TermPtr::Ptr(ptr))
+                    }
+                    Some(VarValue::Era) | None => {}
+                },
+                None => {
+                    reached.remove(&ptr);
+                }
+            }
+        }
+        for ptr in reached {
+            self.store.free(ptr);
+        }
+    }
+
+    /// Trace the var or cell at `ptr` during the next eval: every rewrite
+    /// touching it is logged, and so is every later rewrite of the wires its
+    /// value flows into. See [`Runtime::trace_events`](super::runtime::Runtime::trace_events).
+    pub fn trace(&mut self, ptr: Ptr) {
+        self.traced.push(ptr);
+    }
+
+    /// Trace the head at `idx`, see [`Net::trace`].
+    pub fn trace_head(&mut self, idx: usize) {
+        match self.head.get(idx) {
+            Some(TermPtr::Ptr(ptr)) => self.traced.push(*ptr),
+            Some(TermPtr::Era) => {}
+            None => warn!("No head {} to trace", idx),
         }
     }
 }
@@ -84,36 +359,92 @@ impl NetBuilder for Net {
     }
 
     #[inline]
-    fn lam<T1, T2>(&mut self, binding: T1, body: T2) -> TermPtr
+    fn lam<T1, T2>(&mut self, binding: T1, body: T2) -> CellUse
     where
         T1: Into<TermPtr>,
         T2: Into<TermPtr>,
     {
         let lam = Cell::Lam((binding.into(), body.into()).into());
         let cell_ptr = self.store.alloc(Term::Cell(lam).into());
-        TermPtr::Ptr(cell_ptr)
+        CellUse::new(cell_ptr)
     }
 
     #[inline]
-    fn app<T1, T2>(&mut self, lam: T1, arg: T2) -> TermPtr
+    fn app<T1, T2>(&mut self, arg: T1, result: T2) -> CellUse
     where
         T1: Into<TermPtr>,
         T2: Into<TermPtr>,
     {
-        let app = Cell::App((lam.into(), arg.into()).into());
+        let app = Cell::App((arg.into(), result.into()).into());
         let cell_ptr = self.store.alloc(Term::Cell(app).into());
-        TermPtr::Ptr(cell_ptr)
+        CellUse::new(cell_ptr)
     }
 
     #[inline]
-    fn dup<T1, T2>(&mut self, left: T1, right: T2) -> TermPtr
+    fn dup<T1, T2>(&mut self, left: T1, right: T2) -> CellUse
     where
         T1: Into<TermPtr>,
         T2: Into<TermPtr>,
     {
         let dup = Cell::Dup((left.into(), right.into()).into(), None);
         let cell_ptr = self.store.alloc(Term::Cell(dup).into());
-        TermPtr::Ptr(cell_ptr)
+        CellUse::new(cell_ptr)
+    }
+
+    #[inline]
+    fn fresh_dup<T1, T2>(&mut self, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let dup = Cell::Dup((left.into(), right.into()).into(), Some(Label::fresh()));
+        let cell_ptr = self.store.alloc(Term::Cell(dup).into());
+        CellUse::new(cell_ptr)
+    }
+
+    #[inline]
+    fn labeled_dup<T1, T2>(&mut self, label: Index, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let dup = Cell::Dup((left.into(), right.into()).into(), Some(Label::User(label)));
+        let cell_ptr = self.store.alloc(Term::Cell(dup).into());
+        CellUse::new(cell_ptr)
+    }
+
+    #[inline]
+    fn num(&mut self, value: u32) -> CellUse {
+        let cell_ptr = self.store.alloc(Term::Cell(Cell::Num(value)).into());
+        CellUse::new(cell_ptr)
+    }
+
+    #[inline]
+    fn op<T1, T2>(&mut self, op: Op, right: T1, result: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let op = Cell::Op((right.into(), result.into()).into(), op, false);
+        let cell_ptr = self.store.alloc(Term::Cell(op).into());
+        CellUse::new(cell_ptr)
+    }
+
+    #[inline]
+    fn swi<T1, T2>(&mut self, branches: T1, result: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let swi = Cell::Swi((branches.into(), result.into()).into());
+        let cell_ptr = self.store.alloc(Term::Cell(swi).into());
+        CellUse::new(cell_ptr)
+    }
+
+    #[inline]
+    fn reference(&mut self, id: RefId) -> CellUse {
+        let cell_ptr = self.store.alloc(Term::Cell(Cell::Ref(id)).into());
+        CellUse::new(cell_ptr)
     }
 
     #[inline]
@@ -123,17 +454,214 @@ impl NetBuilder for Net {
 
     #[inline]
     fn eqn<T1: Into<TermPtr>, T2: Into<TermPtr>>(&mut self, left: T1, right: T2) {
-        self.body.push(Equation::new(left.into(), right.into()));
+        let (left, right) = (left.into(), right.into());
+        let is_var = |term_ptr| match term_ptr {
+            TermPtr::Ptr(ptr) => matches!(self.store.get(ptr), Some(TermRef::Var(_))),
+            TermPtr::Era => false,
+        };
+        let (left, right, kind) = match (is_var(left), is_var(right)) {
+            (true, true) => (left, right, EquationKind::Connect),
+            (true, false) => (left, right, EquationKind::Bind),
+            (false, true) => (right, left, EquationKind::Bind),
+            (false, false) => (left, right, EquationKind::Redex),
+        };
+        self.body.push((Equation::new(left, right), kind));
+    }
+}
+
+/// Build a [`Net`] declaratively:
+///
+/// ```ignore
+/// let net = net! {
+///     r = var;
+///     x = var;
+///     y = var;
+///     head r;
+///     eqn lam(x, x), app(r, lam(y, y));
+/// };
+/// ```
+///
+/// Ports are `*` (an eraser), a name, or a nested `lam(..)`, `app(..)` or
+/// `dup(..)`. A name bound with `name = var;` is a wire and must be used
+/// exactly twice; a name bound to a term (`i = lam(x, x);`) can only be
+/// used once. The compiler checks both: each statement rebinds the names
+/// it uses to a type counting their uses, which allows no use past the
+/// last one, and the net is only built once every var is used twice.
+///
+/// ```compile_fail
+/// strandal::net! {
+///     x = var;
+///     head lam(x, *);
+/// };
+/// ```
+///
+/// ```compile_fail
+/// strandal::net! {
+///     x = var;
+///     head dup(x, x);
+///     head x;
+/// };
+/// ```
+#[macro_export]
+macro_rules! net {
+    (@stmts $net:ident [$($vars:ident)*]) => {
+        $($crate::strandal::net::MacroPaired::check(&$vars);)*
+    };
+    (@stmts $net:ident [$($vars:ident)*] $name:ident = var; $($rest:tt)*) => {
+        let $name = $crate::strandal::net::MacroVar::new($net.var());
+        $crate::net!(@stmts $net [$($vars)* $name] $($rest)*);
+    };
+    (@stmts $net:ident [$($vars:ident)*]
+        $name:ident = $term:tt $(($($args:tt)*))?; $($rest:tt)*) => {
+        let term = $crate::net!(@term $net $term $(($($args)*))?);
+        $crate::net!(@uses $term $(($($args)*))?);
+        let $name = $crate::strandal::net::MacroTerm::new(term);
+        $crate::net!(@stmts $net [$($vars)*] $($rest)*);
+    };
+    (@stmts $net:ident [$($vars:ident)*] head $term:tt $(($($args:tt)*))?; $($rest:tt)*) => {
+        let head = $crate::net!(@term $net $term $(($($args)*))?);
+        $crate::net!(@uses $term $(($($args)*))?);
+        $net.head(head);
+        $crate::net!(@stmts $net [$($vars)*] $($rest)*);
+    };
+    (@stmts $net:ident [$($vars:ident)*]
+        eqn $left:tt $(($($left_args:tt)*))?, $right:tt $(($($right_args:tt)*))?;
+        $($rest:tt)*) => {
+        let left = $crate::net!(@term $net $left $(($($left_args)*))?);
+        let right = $crate::net!(@term $net $right $(($($right_args)*))?);
+        $crate::net!(@uses $left $(($($left_args)*))?);
+        $crate::net!(@uses $right $(($($right_args)*))?);
+        $net.eqn(left, right);
+        $crate::net!(@stmts $net [$($vars)*] $($rest)*);
+    };
+
+    (@term $net:ident *) => {
+        $crate::strandal::term::TermPtr::Era
+    };
+    (@term $net:ident $cell:ident(
+        $p0:tt $(($($p0_args:tt)*))?, $p1:tt $(($($p1_args:tt)*))?
+    )) => {{
+        let p0 = $crate::net!(@term $net $p0 $(($($p0_args)*))?);
+        let p1 = $crate::net!(@term $net $p1 $(($($p1_args)*))?);
+        $crate::strandal::term::TermPtr::from($net.$cell(p0, p1))
+    }};
+    (@term $net:ident $name:ident) => {
+        $name.take()
+    };
+
+    // rebind the names a statement used, each once per use
+    (@uses *) => {};
+    (@uses $cell:ident(
+        $p0:tt $(($($p0_args:tt)*))?, $p1:tt $(($($p1_args:tt)*))?
+    )) => {
+        $crate::net!(@uses $p0 $(($($p0_args)*))?);
+        $crate::net!(@uses $p1 $(($($p1_args)*))?);
+    };
+    (@uses $name:ident) => {
+        #[allow(unused_variables)]
+        let $name = $crate::strandal::net::MacroUse::used($name);
+    };
+
+    ($($body:tt)*) => {{
+        #[allow(unused_imports)]
+        use $crate::strandal::net::NetBuilder as _;
+        let mut net = $crate::strandal::net::Net::new();
+        $crate::net!(@stmts net [] $($body)*);
+        net
+    }};
+}
+
+/// A var bound by [`net!`], handing out its two uses, and used `USES` times
+/// by the statements so far.
+#[doc(hidden)]
+pub struct MacroVar<const USES: u8>(std::cell::RefCell<Vec<VarUse>>);
+
+impl MacroVar<0> {
+    pub fn new(var: (VarUse, VarUse)) -> Self {
+        MacroVar(vec![var.1, var.0].into())
+    }
+}
+
+impl<const USES: u8> MacroVar<USES> {
+    pub fn take(&self) -> TermPtr {
+        // the compiler counts the uses, see `MacroUse`
+        let var_use = self.0.borrow_mut().pop();
+        var_use.expect("a var is used twice at most").into()
+    }
+}
+
+/// A term bound by [`net!`], used `USES` times by the statements so far.
+#[doc(hidden)]
+pub struct MacroTerm<const USES: u8>(TermPtr);
+
+impl MacroTerm<0> {
+    pub fn new(term: TermPtr) -> Self {
+        MacroTerm(term)
+    }
+}
+
+impl<const USES: u8> MacroTerm<USES> {
+    pub fn take(&self) -> TermPtr {
+        self.0
+    }
+}
+
+/// A name of [`net!`] that can be used once more.
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "a name of `net!` is used too many times",
+    label = "a var can only be used twice, and a term once"
+)]
+pub trait MacroUse {
+    type Used;
+
+    fn used(self) -> Self::Used;
+}
+
+impl MacroUse for MacroVar<0> {
+    type Used = MacroVar<1>;
+
+    fn used(self) -> MacroVar<1> {
+        MacroVar(self.0)
+    }
+}
+
+impl MacroUse for MacroVar<1> {
+    type Used = MacroVar<2>;
+
+    fn used(self) -> MacroVar<2> {
+        MacroVar(self.0)
+    }
+}
+
+impl MacroUse for MacroTerm<0> {
+    type Used = MacroTerm<1>;
+
+    fn used(self) -> MacroTerm<1> {
+        MacroTerm(self.0)
     }
 }
 
+/// A var of [`net!`] used twice.
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "a var of `net!` must be used exactly twice",
+    label = "this var is used fewer times"
+)]
+pub trait MacroPaired {
+    fn check(&self) {}
+}
+
+impl MacroPaired for MacroVar<2> {}
+
 #[cfg(test)]
 mod tests {
     use tracing::info;
 
     use crate::strandal::{
-        net::{Net, NetBuilder},
+        net::{EquationKind, Net, NetBuilder},
         runtime::Runtime,
+        term::TermPtr,
     };
 
     #[test]
@@ -149,8 +677,82 @@ mod tests {
         net.eqn(i1, app);
 
         let mut runtime = Runtime::new();
-        runtime.eval(&mut net);
+        runtime.eval(&mut net).unwrap();
 
         info!("net: {}", runtime.stats);
     }
+
+    #[test]
+    fn test_clear() {
+        let mut net = Net::new();
+        let mut runtime = Runtime::new();
+        for value in [1, 2] {
+            net.clear();
+            let r = net.var();
+            let x = net.var();
+            let id = net.lam(x.0, x.1);
+            let num = net.num(value);
+            let app = net.app(num, r.0);
+            net.head(r.1);
+            net.eqn(id, app);
+            runtime.eval(&mut net).unwrap();
+            assert_eq!(net.normal_form().unwrap()[0].to_string(), value.to_string());
+            // the second net is built over the slots of the first one
+            assert_eq!(net.store().next(), 5);
+        }
+    }
+
+    #[test]
+    fn test_equation_kinds() {
+        let mut net = Net::new();
+        let (r0, r1) = net.var();
+        let (x0, x1) = net.var();
+        let (y0, y1) = net.var();
+        let lam = net.lam(x0, x1);
+        let app = net.app(y0, TermPtr::Era);
+        net.head(r0);
+        net.eqn(lam, app);
+        net.eqn(TermPtr::Era, y1);
+        net.eqn(r1, TermPtr::Era);
+        let kinds: Vec<_> = net.body.iter().map(|(_, kind)| *kind).collect();
+        assert_eq!(kinds, [EquationKind::Redex, EquationKind::Bind, EquationKind::Bind]);
+        // the var of a bind is always on the left
+        assert!(net.body[1..].iter().all(|(eqn, _)| eqn.right() == TermPtr::Era));
+        assert_eq!(net.equation_kinds()[&EquationKind::Bind], 2);
+        assert!(net.to_string().contains("\n  BIND    x.2 ~ ε"));
+
+        let mut connected = Net::new();
+        let (a0, a1) = connected.var();
+        let (b0, b1) = connected.var();
+        connected.head(a0);
+        connected.head(b0);
+        connected.eqn(a1, b1);
+        assert_eq!(connected.equation_kinds()[&EquationKind::Connect], 1);
+        Runtime::new().eval(&mut connected).unwrap();
+        assert_eq!(connected.normal_form().unwrap().len(), 2);
+
+        Runtime::new().eval(&mut net).unwrap();
+        assert!(net.equation_kinds().is_empty());
+        assert_eq!(net.normal_form().unwrap()[0].to_string(), "ε");
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_net_macro() {
+        let mut net = crate::net! {
+            r = var;
+            x = var;
+            y = var;
+            i = lam(y, y);
+            head r;
+            eqn lam(x, x), app(r, i);
+            eqn *, dup(*, *);
+        };
+        assert_eq!(net.body.len(), 2);
+
+        let mut runtime = Runtime::new();
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(runtime.stats.comm_app_lam(), 1);
+        assert_eq!(net.normal_form().unwrap()[0].to_string(), "(λ x2 x2)");
+    }
 }
@@ -0,0 +1,234 @@
+//! Native numbers, as in HVM: a number cell holds a 32-bit value, an
+//! operator cell computes with the numbers it meets, and a switch cell
+//! selects a branch by whether its number is zero:
+//!
+//! ```text
+//! n ~ (+ b r)        r ~ n + b, once `n` and `b` are numbers
+//! 0 ~ (? (λ z s) r)  r ~ z, with `s` erased
+//! n ~ (? (λ z s) r)  s ~ (@ n-1 r), with `z` erased
+//! ```
+//!
+//! An operator cell waits for its left operand on its principal port. If
+//! its right operand is not a number yet, the left one is moved into its
+//! first port and the flipped cell waits for the right operand instead.
+//!
+//! A switch pairs its branches in a lambda cell, only used for its ports:
+//! the zero branch is connected to the result, and the successor branch is
+//! applied to the predecessor. [`NetBuilder::switch`](super::net::NetBuilder::switch)
+//! builds both cells.
+//!
+//! Numbers are closed values: erasing them, or meeting a lambda, an
+//! application or a duplicator, copies them to every port. Operators and
+//! switches commute with lambdas, applications and duplicators like any
+//! other cell.
+
+use std::{fmt::Display, str::FromStr};
+
+/// A binary operator on numbers. Arithmetic wraps around, dividing by zero
+/// gives zero and comparisons give 1 for true and 0 for false.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+}
+
+impl Op {
+    pub const ALL: [Op; 16] = [
+        Op::Add,
+        Op::Sub,
+        Op::Mul,
+        Op::Div,
+        Op::Rem,
+        Op::Eq,
+        Op::Ne,
+        Op::Lt,
+        Op::Gt,
+        Op::Le,
+        Op::Ge,
+        Op::And,
+        Op::Or,
+        Op::Xor,
+        Op::Shl,
+        Op::Shr,
+    ];
+
+    pub fn apply(&self, left: u32, right: u32) -> u32 {
+        match self {
+            Op::Add => left.wrapping_add(right),
+            Op::Sub => left.wrapping_sub(right),
+            Op::Mul => left.wrapping_mul(right),
+            Op::Div => left.checked_div(right).unwrap_or(0),
+            Op::Rem => left.checked_rem(right).unwrap_or(0),
+            Op::Eq => (left == right) as u32,
+            Op::Ne => (left != right) as u32,
+            Op::Lt => (left < right) as u32,
+            Op::Gt => (left > right) as u32,
+            Op::Le => (left <= right) as u32,
+            Op::Ge => (left >= right) as u32,
+            Op::And => left & right,
+            Op::Or => left | right,
+            Op::Xor => left ^ right,
+            Op::Shl => left.wrapping_shl(right),
+            Op::Shr => left.wrapping_shr(right),
+        }
+    }
+
+    /// How tightly the operator binds when written infix, as in `a + b * c`,
+    /// higher binding tighter. `&` separates equations, so the bitwise and
+    /// is only written prefix.
+    pub fn precedence(&self) -> Option<u8> {
+        match self {
+            Op::Or => Some(0),
+            Op::Xor => Some(1),
+            Op::Eq | Op::Ne => Some(2),
+            Op::Lt | Op::Gt | Op::Le | Op::Ge => Some(3),
+            Op::Shl | Op::Shr => Some(4),
+            Op::Add | Op::Sub => Some(5),
+            Op::Mul | Op::Div | Op::Rem => Some(6),
+            Op::And => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Rem => "%",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Gt => ">",
+            Op::Le => "<=",
+            Op::Ge => ">=",
+            Op::And => "&",
+            Op::Or => "|",
+            Op::Xor => "^",
+            Op::Shl => "<<",
+            Op::Shr => ">>",
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl FromStr for Op {
+    type Err = ();
+
+    fn from_str(symbol: &str) -> Result<Self, Self::Err> {
+        Op::ALL
+            .into_iter()
+            .find(|op| op.symbol() == symbol)
+            .ok_or(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        readback::OwnedTerm,
+        runtime::Runtime,
+        term::{CellUse, TermPtr},
+    };
+
+    use super::Op;
+
+    #[test]
+    fn test_op() {
+        assert_eq!(Op::Sub.apply(2, 3), u32::MAX);
+        assert_eq!(Op::Div.apply(7, 0), 0);
+        assert_eq!(Op::Lt.apply(2, 3), 1);
+        for op in Op::ALL {
+            assert_eq!(op.symbol().parse(), Ok(op));
+        }
+    }
+
+    fn eval(net: &mut Net) -> Vec<OwnedTerm> {
+        Runtime::new().eval(net).unwrap();
+        net.normal_form().unwrap()
+    }
+
+    #[test]
+    fn test_oper() {
+        // (10 - 3), with the left operand first and then the right one first
+        for left_first in [true, false] {
+            let mut net = Net::new();
+            let (b, r) = (net.var(), net.var());
+            let sub = net.op(Op::Sub, b.0, r.0);
+            let (ten, three) = (net.num(10), net.num(3));
+            if left_first {
+                net.eqn(ten, sub);
+                net.eqn(three, b.1);
+            } else {
+                net.eqn(three, b.1);
+                net.eqn(ten, sub);
+            }
+            net.head(r.1);
+            assert_eq!(eval(&mut net), vec![OwnedTerm::Num(7)]);
+        }
+    }
+
+    // a switch on `n` giving `zero`, or `n - 1` plus one
+    fn switch(net: &mut Net, n: u32, zero: u32) -> TermPtr {
+        let r = net.var();
+        let (pred, succ_r) = (net.var(), net.var());
+        let one = net.num(1);
+        let add = net.op(Op::Add, one, succ_r.0);
+        net.eqn(pred.1, add);
+        let succ = net.lam(pred.0, succ_r.1);
+        let zero = net.num(zero);
+        let swi = net.switch(zero, succ, r.0);
+        let n: CellUse = net.num(n);
+        net.eqn(n, swi);
+        r.1.into()
+    }
+
+    #[test]
+    fn test_switch() {
+        let mut net = Net::new();
+        let r = switch(&mut net, 0, 42);
+        net.head(r);
+        let r = switch(&mut net, 5, 42);
+        net.head(r);
+        assert_eq!(eval(&mut net), vec![OwnedTerm::Num(42), OwnedTerm::Num(5)]);
+    }
+
+    #[test]
+    fn test_copy_num() {
+        // a number copied by a dup, erased and applied
+        let mut net = Net::new();
+        let (a, b) = (net.var(), net.var());
+        let dup = net.dup(a.0, b.0);
+        let n = net.num(3);
+        net.eqn(n, dup);
+        let (x, y) = (net.var(), net.var());
+        let mul = net.op(Op::Mul, x.0, y.0);
+        net.eqn(a.1, mul);
+        net.eqn(b.1, x.1);
+        let n = net.num(7);
+        net.eqn(n, TermPtr::Era);
+        net.head(y.1);
+        assert_eq!(eval(&mut net), vec![OwnedTerm::Num(9)]);
+        assert!(net.leaks().is_empty(), "{}", net.leaks());
+    }
+}
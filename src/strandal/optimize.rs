@@ -0,0 +1,231 @@
+//! A pass over a book before it is loaded, for programs built from many
+//! small definitions. A reference costs an expansion each time it meets a
+//! cell, copying the whole template of its definition, see [`super::refs`].
+//!
+//! - The referenced definitions that reference no other one are reduced
+//!   once, here, so every expansion copies their normal form instead of
+//!   doing the same rewrites again.
+//! - Those left with at most [`OptimizeConfig::max_cells`] cells are then
+//!   inlined at every reference, which becomes a copy of the definition.
+//!
+//! Both steps repeat until nothing changes, since inlining can leave a
+//! definition referencing no other one. A recursive definition is never
+//! inlined, and a definition whose references were all inlined is dropped.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+};
+
+use tracing::info;
+
+use super::{
+    ast::{self, AstError, Book, Term},
+    net::Net,
+    runtime::{Runtime, RuntimeConfig},
+    store::Index,
+};
+
+/// Live slots allowed while reducing a definition. The definitions that
+/// grow past it are left as they are.
+const REDUCE_MAX_SCRATCH: Index = 1 << 16;
+
+#[derive(Debug, Clone)]
+pub struct OptimizeConfig {
+    /// Inline the definitions of at most this many cells.
+    pub max_cells: usize,
+    /// Reduce the definitions that reference no other one.
+    pub reduce: bool,
+}
+
+impl Default for OptimizeConfig {
+    fn default() -> Self {
+        Self {
+            max_cells: 16,
+            reduce: true,
+        }
+    }
+}
+
+/// What [`optimize`] did, in the order it did it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptimizeReport {
+    /// The definitions reduced, with their cells before and after.
+    pub reduced: Vec<(String, usize, usize)>,
+    /// The definitions inlined, with the references they replaced.
+    pub inlined: Vec<(String, usize)>,
+}
+
+impl Display for OptimizeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut lines = Vec::new();
+        for (name, before, after) in &self.reduced {
+            lines.push(format!("reduced `{}` from {} to {} cells", name, before, after));
+        }
+        for (name, sites) in &self.inlined {
+            let plural = if *sites == 1 { "" } else { "s" };
+            lines.push(format!("inlined `{}` at {} reference{}", name, sites, plural));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Reduce and inline the referenced definitions of `book`, see the
+/// [module docs](self). The book is desugared first, see [`Book::desugar`].
+pub fn optimize(book: &Book, config: &OptimizeConfig) -> Result<(Book, OptimizeReport), AstError> {
+    let mut book = book.desugar()?;
+    let mut report = OptimizeReport::default();
+    let referenced: HashSet<String> = book
+        .referenced()
+        .iter()
+        .map(|def| def.name.clone())
+        .collect();
+    let mut visited = HashSet::new();
+    let mut instances = 0;
+    loop {
+        let live: HashSet<String> = book
+            .referenced()
+            .iter()
+            .map(|def| def.name.clone())
+            .collect();
+        let mut small = HashMap::new();
+        for def in book.defs.iter_mut() {
+            if !live.contains(&def.name) || !def.net.refs().is_empty() {
+                continue;
+            }
+            if config.reduce && visited.insert(def.name.clone()) {
+                if let Some(net) = reduce(&def.net) {
+                    let (before, after) = (cells(&def.net), cells(&net));
+                    if after < before {
+                        info!("reduced `{}` from {} to {} cells", def.name, before, after);
+                        report.reduced.push((def.name.clone(), before, after));
+                        def.net = net;
+                    }
+                }
+            }
+            if cells(&def.net) <= config.max_cells {
+                small.insert(def.name.clone(), def.net.clone());
+            }
+        }
+        let mut sites = BTreeMap::new();
+        for def in book.defs.iter_mut() {
+            let mut body = Vec::new();
+            let mut inline = |term: &Term| {
+                let Term::Ref(name) = term else {
+                    return None;
+                };
+                let net = small.get(name)?;
+                *sites.entry(name.clone()).or_insert(0) += 1;
+                // `#` cannot occur in a parsed name, so the copies never
+                // clash with the vars of the book
+                instances += 1;
+                let prefix = format!("{}#{}.", name, instances);
+                let mut rename = |term: &Term| match term {
+                    Term::Var(var) => Some(Term::Var(format!("{}{}", prefix, var))),
+                    _ => None,
+                };
+                for (left, right) in &net.body {
+                    body.push((left.replace(&mut rename), right.replace(&mut rename)));
+                }
+                Some(net.head[0].replace(&mut rename))
+            };
+            let head = def.net.head.iter().map(|head| head.replace(&mut inline)).collect();
+            let eqns: Vec<_> = def
+                .net
+                .body
+                .iter()
+                .map(|(left, right)| (left.replace(&mut inline), right.replace(&mut inline)))
+                .collect();
+            def.net.head = head;
+            def.net.body = eqns.into_iter().chain(body).collect();
+        }
+        if sites.is_empty() {
+            break;
+        }
+        for (name, count) in sites {
+            info!("inlined `{}` at {} references", name, count);
+            report.inlined.push((name, count));
+        }
+    }
+    // the definitions only referenced were never loaded as part of the net
+    let live: HashSet<String> = book
+        .referenced()
+        .iter()
+        .map(|def| def.name.clone())
+        .collect();
+    book.defs
+        .retain(|def| !referenced.contains(&def.name) || live.contains(&def.name));
+    Ok((book, report))
+}
+
+// the normal form of a closed definition, reduced on its own
+fn reduce(net: &ast::Net) -> Option<ast::Net> {
+    let mut scratch = Net::from_ast(net).ok()?;
+    let mut runtime = Runtime::with_config(RuntimeConfig::default().max_cells(REDUCE_MAX_SCRATCH));
+    runtime.eval(&mut scratch).ok()?;
+    Some(ast::Net {
+        head: scratch.normal_form().ok()?,
+        body: Vec::new(),
+    })
+}
+
+// the cells of a net, everything but its vars
+fn cells(net: &ast::Net) -> usize {
+    fn term_cells(term: &Term) -> usize {
+        match term {
+            Term::Var(_) => 0,
+            Term::Era | Term::Num(_) | Term::Ref(_) => 1,
+            Term::Lam(p0, p1)
+            | Term::App(p0, p1)
+            | Term::Dup(p0, p1, _)
+            | Term::Op(_, _, p0, p1)
+            | Term::Infix(_, p0, p1)
+            | Term::Swi(p0, p1) => 1 + term_cells(p0) + term_cells(p1),
+            Term::Call(_, args) => 1 + args.iter().map(term_cells).sum::<usize>(),
+        }
+    }
+    let eqns = net
+        .body
+        .iter()
+        .map(|(left, right)| term_cells(left) + term_cells(right));
+    net.head.iter().map(term_cells).chain(eqns).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::Net,
+        parser::parse_ast,
+        runtime::Runtime,
+    };
+
+    use super::{optimize, OptimizeConfig};
+
+    #[test]
+    fn test_optimize() {
+        // `two` applies the identity to the first projection, which reduces
+        // away; `main` references it twice
+        let src = "def main(a, b) = @two ~ [1 f] & f ~ [2 a] & @two ~ [3 g] & g ~ [4 b];\n\
+                   def two(r) = (x x) ~ [(y (* y)) r];\n\
+                   def loop(r) = r ~ (? (0 @loop) s) & s ~ *";
+        let book = parse_ast(src).unwrap();
+        let (optimized, report) = optimize(&book, &OptimizeConfig::default()).unwrap();
+        assert_eq!(report.reduced, vec![("two".to_string(), 5, 3)]);
+        assert_eq!(report.inlined, vec![("two".to_string(), 2)]);
+        assert_eq!(
+            report.to_string(),
+            "reduced `two` from 5 to 3 cells\ninlined `two` at 2 references"
+        );
+        // `two` is gone, the recursive `loop` is left alone
+        let names: Vec<_> = optimized.defs.iter().map(|def| def.name.as_str()).collect();
+        assert_eq!(names, ["main", "loop"]);
+
+        let normal_form = |book| {
+            let mut net = Net::new();
+            net.load(book).unwrap();
+            Runtime::new().eval(&mut net).unwrap();
+            net.normal_form().unwrap()
+        };
+        assert_eq!(normal_form(&optimized), normal_form(&book));
+    }
+}
@@ -1,146 +1,421 @@
 use std::collections::HashMap;
 
-use super::{
-    net::{Net, NetBuilder},
-    term::TermPtr,
-    var::VarUse,
-};
-use chumsky::{extra::State, prelude::*, text::keyword, Parser};
+use super::{ast, expr::Lambda, net::Net, num::Op, template::Template};
+use chumsky::{extra::Full, prelude::*, text::keyword, Parser};
 
 // <book> ::= (<def> (';' <def>)* ';')?
 // <def> ::= 'def' <ident> '(' <terms> ')' '=' <eqns>
-// <terms> ::= '(' <term> (',' <term>)* ')'
-// <term> ::= <var> | era | ctr | dup
+// <terms> ::= '(' <port> (',' <port>)* ')'
+// <port> ::= 'out' <var> | <term>
+// <term> ::= <var> | era | ctr | app | dup | op | swi | ref | num | call
 // <eqns> ::= (<eqn> ('&' <eqn>)*)?
-// <eqn> ::= <term> '~' <term>
+// <eqn> ::= <expr> '~' <expr>
+// <expr> ::= <term> | <expr> <infix> <expr> | '(' <expr> ')'
 // <var> ::= <ident>
 // <era> ::= '*'
 // <ctr> ::= (<term> <term>)
-// <dup> ::= [<term> <term>]
+// <app> ::= [<term> <term>]
+// <dup> ::= {<term> <term>}
+// <op> ::= (<symbol> <term> <term>)
+// <swi> ::= (? <term> <term>)
+// <ref> ::= '@' <ident>
+// <num> ::= [0-9]+ | '0x' [0-9a-fA-F]+ | '0b' [01]+
+// <call> ::= <ident> '(' (<term> (',' <term>)*)? ')'
+//
+// λ-terms, applications to the left and abstractions to the right:
+// <lambda> ::= <abs> | <atom>+ <abs>?
+// <abs> ::= ('λ' | '\') <ident>+ '.' <lambda>
+// <atom> ::= <ident> | <ref> | <num> | '(' <lambda> ')'
 
 pub fn parse(src: &str, net: &mut Net) -> bool {
-    let mut state = ParserState::new(net);
-    match parse_book()
+    match parse_ast(src) {
+        Some(book) => net.load(&book).is_ok(),
+        None => false,
+    }
+}
+
+/// Parse a book into owned terms, without loading it into a net.
+pub fn parse_ast(src: &str) -> Option<ast::Book> {
+    try_parse_ast(src).ok()
+}
+
+/// Where and why a source does not parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based, as editors show them.
+    pub line: usize,
+    pub column: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.reason)
+    }
+}
+
+/// Like [`parse_ast`], describing where and why the source does not parse.
+pub fn try_parse_ast(src: &str) -> Result<ast::Book, Vec<ParseError>> {
+    try_parse(parse_book(), src)
+}
+
+/// Parse equations separated by `&`, as in the body of a definition.
+pub fn try_parse_eqns(src: &str) -> Result<Vec<(ast::Term, ast::Term)>, Vec<ParseError>> {
+    try_parse(parse_eqns(), src)
+}
+
+/// Parse a closed λ-term, such as `(λx.x) λy.y`. Its free names must be
+/// references, as in `@true`.
+pub fn try_parse_lambda(src: &str) -> Result<Lambda, Vec<ParseError>> {
+    let named = try_parse(parse_lambda(), src)?;
+    let offset = src.len() - src.trim_start().len();
+    named.resolve(&mut Vec::new()).map_err(|(name, pos)| {
+        let (line, column) = line_column(src, offset + pos);
+        vec![ParseError {
+            line,
+            column,
+            reason: format!("unbound variable `{}`", name),
+        }]
+    })
+}
+
+fn try_parse<'a, T>(
+    parser: impl Parser<'a, &'a str, T, Extra<'a>>,
+    src: &'a str,
+) -> Result<T, Vec<ParseError>> {
+    let mut state = ParserState::new();
+    let offset = src.len() - src.trim_start().len();
+    parser
         .parse_with_state(src.trim(), &mut state)
         .into_result()
-    {
-        Ok(_) => true,
-        Err(_) => false,
-    }
+        .map_err(|errs| {
+            errs.iter()
+                .map(|err| {
+                    let (line, column) = line_column(src, offset + err.span().start);
+                    ParseError {
+                        line,
+                        column,
+                        reason: err.reason().to_string(),
+                    }
+                })
+                .collect()
+        })
+}
+
+// the 1-based line and column of the byte `pos` of `src`
+fn line_column(src: &str, pos: usize) -> (usize, usize) {
+    let before = &src[..pos.min(src.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+    (line, column)
+}
+
+/// Parse a book and load only the `entry` definition and the definitions it
+/// transitively references. Returns the names of the definitions left out.
+pub fn parse_from_entry(src: &str, net: &mut Net, entry: &str) -> Option<Vec<String>> {
+    let mut book = parse_ast(src)?;
+    let removed = book.prune(entry).ok()?;
+    net.load(&book).ok()?;
+    Some(removed)
 }
 // let src = std::fs::read_to_string(std::env::args().nth(1).unwrap()).unwrap();
 
+/// Church booleans and a conditional. The head ports of each definition are
+/// its interface: `ite` takes the condition, the two branches and the
+/// result. Var names are prefixed so the definitions stay apart when loaded
+/// together.
+pub const STDLIB: &str = "
+    def true((true_t (* true_t)));
+    def false((* (false_f false_f)));
+    def ite(ite_c, ite_t, ite_e, ite_r) = ite_c ~ [ite_t [ite_e ite_r]];
+";
+
+/// Compile every definition of [`STDLIB`] to a template, by name.
+pub fn stdlib() -> HashMap<String, Template> {
+    let book = parse_ast(STDLIB).expect("the stdlib parses");
+    return book
+        .defs
+        .iter()
+        .map(|def| {
+            let template = Template::from_ast(&def.net).expect("the stdlib is closed");
+            (def.name.clone(), template)
+        })
+        .collect();
+}
+
+type Extra<'a> = Full<Rich<'a, char>, ParserState<'a>, ()>;
+
 struct ParserState<'a> {
-    net: &'a mut Net,
-    vars: HashMap<&'a str, VarUse>,
+    // vars used only once so far, with the index of the def using them
+    vars: HashMap<&'a str, u32>,
     defs: HashMap<&'a str, u32>,
 }
 impl<'a> ParserState<'a> {
-    pub fn new(net: &'a mut Net) -> Self {
+    pub fn new() -> Self {
         Self {
-            net,
             vars: Default::default(),
             defs: Default::default(),
         }
     }
 }
 
-fn parse_term<'a>() -> impl Parser<'a, &'a str, TermPtr, State<ParserState<'a>>> {
-    return recursive::<'a, &'a str, TermPtr, State<ParserState<'a>>, _, _>(|term| {
-        let era =
-            just('*')
-                .ignored()
-                .padded()
-                .map_with_state(|_, _, state: &mut ParserState<'a>| {
-                    //
-                    state.net.era()
-                });
+fn parse_var<'a>() -> impl Parser<'a, &'a str, &'a str, Extra<'a>> + Clone {
+    return text::ident()
+        .padded()
+        .map_with_state(|name, _, state: &mut ParserState<'a>| {
+            if state.vars.remove(name).is_none() {
+                let def = state.defs.len() as u32;
+                state.vars.insert(name, def);
+            }
+            name
+        });
+}
 
-        let var = text::ident()
+fn parse_term<'a>() -> impl Parser<'a, &'a str, ast::Term, Extra<'a>> {
+    return recursive::<'a, &'a str, ast::Term, Extra<'a>, _, _>(|term| {
+        let era = just('*').ignored().padded().map(|_| ast::Term::Era);
+
+        let var = parse_var().map(ast::Term::var);
+
+        let reference = just('@')
+            .ignore_then(text::ident())
             .padded()
-            .map_with_state(|name, _, state: &mut ParserState| {
-                if let Some(var_use) = state.vars.remove(name) {
-                    return TermPtr::Ptr(var_use.ptr());
-                } else {
-                    let var = state.net.var();
-                    state.vars.insert(name, var.0);
-                    TermPtr::Ptr(var.1.ptr())
-                }
-            });
+            .map(|name: &str| ast::Term::Ref(name.to_string()));
 
-        let dup = term
+        let app = term
             .clone()
             .then(term.clone())
             .delimited_by(just('[').padded(), just(']').padded())
-            .map_with_state(|(left, right), _, state: &mut ParserState<'a>| {
-                state.net.app(left, right)
-            });
+            .map(|(left, right)| ast::Term::app(left, right));
+        let dup = term
+            .clone()
+            .then(term.clone())
+            .delimited_by(just('{').padded(), just('}').padded())
+            .map(|(left, right)| ast::Term::dup(left, right));
+        // longer symbols first, so `<<` is not read as `<`
+        let mut ops = Op::ALL;
+        ops.sort_by_key(|op| std::cmp::Reverse(op.symbol().len()));
+        let op = choice(ops.map(|op| just(op.symbol()).to(op)))
+            .padded()
+            .then(term.clone())
+            .then(term.clone())
+            .delimited_by(just('(').padded(), just(')').padded())
+            .map(|((op, right), result)| ast::Term::op(op, right, result));
+        let swi = just('?')
+            .padded()
+            .ignore_then(term.clone())
+            .then(term.clone())
+            .delimited_by(just('(').padded(), just(')').padded())
+            .map(|(branches, result)| ast::Term::swi(branches, result));
+        let num = parse_num().map(ast::Term::Num);
         let ctr = term
             .clone()
             .then(term.clone())
             .delimited_by(just('(').padded(), just(')').padded())
-            .map_with_state(|(left, right), _, state: &mut ParserState<'a>| {
-                state.net.lam(left, right)
+            .map(|(left, right)| ast::Term::lam(left, right));
+        // the arguments open right after the name, so `(f (x y))` stays a
+        // lambda
+        let call = text::ident()
+            .then(
+                term.clone()
+                    .separated_by(just(',').padded())
+                    .collect::<Vec<_>>()
+                    .delimited_by(just('('), just(')')),
+            )
+            .padded()
+            .map(|(name, args): (&str, _)| ast::Term::Call(name.to_string(), args));
+        return choice((era, app, dup, swi, op, ctr, reference, num, call, var));
+    });
+}
+
+fn parse_num<'a>() -> impl Parser<'a, &'a str, u32, Extra<'a>> + Clone {
+    // prefixed literals first, so `0x1f` is not read as `0`
+    let digits = |radix| text::digits(radix).collect::<String>();
+    choice((
+        just("0x").ignore_then(digits(16)).map(|digits| (digits, 16)),
+        just("0b").ignore_then(digits(2)).map(|digits| (digits, 2)),
+        digits(10).map(|digits| (digits, 10)),
+    ))
+    .padded()
+    // reported without backtracking, so the error is not lost to the
+    // other alternatives
+    .validate(|(digits, radix), extra, emitter| {
+        u32::from_str_radix(&digits, radix).unwrap_or_else(|_| {
+            emitter.emit(Rich::custom(extra.span(), "number out of range"));
+            0
+        })
+    })
+}
+
+// a λ-term as written, its vars by name and where they are
+enum Named<'a> {
+    Var(&'a str, usize),
+    Lam(Vec<&'a str>, Box<Named<'a>>),
+    App(Box<Named<'a>>, Box<Named<'a>>),
+    Ref(&'a str),
+    Num(u32),
+}
+
+impl<'a> Named<'a> {
+    // the term with de Bruijn indices, given the binders in scope, innermost
+    // last, or the first unbound var and where it is
+    fn resolve(self, scope: &mut Vec<&'a str>) -> Result<Lambda, (&'a str, usize)> {
+        Ok(match self {
+            Named::Var(name, pos) => match scope.iter().rev().position(|bound| *bound == name) {
+                Some(idx) => Lambda::Var(idx),
+                None => return Err((name, pos)),
+            },
+            Named::Lam(binders, body) => {
+                let len = scope.len();
+                scope.extend(&binders);
+                let body = body.resolve(scope);
+                scope.truncate(len);
+                (0..binders.len()).try_fold(body?, |body, _| Ok(Lambda::Lam(Box::new(body))))?
+            }
+            Named::App(fun, arg) => {
+                Lambda::App(Box::new(fun.resolve(scope)?), Box::new(arg.resolve(scope)?))
+            }
+            Named::Ref(name) => Lambda::Ref(name.to_string()),
+            Named::Num(value) => Lambda::Num(value),
+        })
+    }
+}
+
+fn parse_lambda<'a>() -> impl Parser<'a, &'a str, Named<'a>, Extra<'a>> {
+    recursive(|lambda| {
+        let abs = one_of("λ\\")
+            .padded()
+            .ignore_then(text::ident().padded().repeated().at_least(1).collect::<Vec<_>>())
+            .then_ignore(just('.').padded())
+            .then(lambda.clone())
+            .map(|(binders, body)| Named::Lam(binders, Box::new(body)));
+        // `λ` starts an identifier too, so `f λx.x` is not read as `f λx`
+        let var = text::ident()
+            .filter(|name: &&str| !name.starts_with('λ'))
+            .map_with_state(|name, span: SimpleSpan, _| Named::Var(name, span.start))
+            .padded();
+        let reference = just('@')
+            .ignore_then(text::ident())
+            .padded()
+            .map(Named::Ref);
+        let num = parse_num().map(Named::Num);
+        let group = lambda.delimited_by(just('(').padded(), just(')').padded());
+        let atom = choice((group, reference, num, var));
+        let app = atom
+            .repeated()
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .then(abs.clone().or_not())
+            .map(|(atoms, last)| {
+                atoms
+                    .into_iter()
+                    .chain(last)
+                    .reduce(|fun, arg| Named::App(Box::new(fun), Box::new(arg)))
+                    .unwrap()
             });
-        return choice((era, dup, ctr, var));
+        choice((abs, app))
+    })
+}
+
+// an equation side, where operators can also be written infix, binding by
+// their precedence and to the left
+fn parse_expr<'a>() -> impl Parser<'a, &'a str, ast::Term, Extra<'a>> {
+    return recursive(|expr| {
+        let group = expr.delimited_by(just('(').padded(), just(')').padded());
+        let mut level = choice((parse_term(), group)).boxed();
+        // from the tightest binding operators out, longer symbols first
+        for precedence in (0..=6).rev() {
+            let mut ops: Vec<_> = Op::ALL
+                .into_iter()
+                .filter(|op| op.precedence() == Some(precedence))
+                .collect();
+            ops.sort_by_key(|op| std::cmp::Reverse(op.symbol().len()));
+            let op = choice(ops.into_iter().map(|op| just(op.symbol()).to(op)).collect::<Vec<_>>())
+                .padded();
+            level = level
+                .clone()
+                .then(op.then(level).repeated().collect::<Vec<_>>())
+                .map(|(first, rest)| {
+                    rest.into_iter()
+                        .fold(first, |left, (op, right)| ast::Term::infix(op, left, right))
+                })
+                .boxed();
+        }
+        level
     });
 }
 
-fn parse_eqn<'a>() -> impl Parser<'a, &'a str, (TermPtr, TermPtr), State<ParserState<'a>>> {
-    return parse_term()
+fn parse_eqn<'a>() -> impl Parser<'a, &'a str, (ast::Term, ast::Term), Extra<'a>> {
+    return parse_expr()
         .then_ignore(just('~').padded())
-        .then(parse_term());
+        .then(parse_expr());
 }
 
-fn parse_eqns<'a>() -> impl Parser<'a, &'a str, (), State<ParserState<'a>>> {
-    return parse_eqn()
-        .map_with_state(|eqn, _, state| state.net.eqn(eqn.0, eqn.1))
-        .separated_by(just('&').padded())
-        .collect();
+fn parse_eqns<'a>() -> impl Parser<'a, &'a str, Vec<(ast::Term, ast::Term)>, Extra<'a>> {
+    return parse_eqn().separated_by(just('&').padded()).collect();
+}
+
+// a named port is also a var, so it is paired like any other use
+fn parse_port<'a>() -> impl Parser<'a, &'a str, (Option<&'a str>, ast::Term), Extra<'a>> {
+    let named = keyword("out")
+        .padded()
+        .ignore_then(parse_var())
+        .map(|name| (Some(name), ast::Term::var(name)));
+    return choice((named, parse_term().map(|term| (None, term))));
 }
 
-fn parse_head<'a>() -> impl Parser<'a, &'a str, (), State<ParserState<'a>>> {
-    return parse_term()
+fn parse_head<'a>() -> impl Parser<'a, &'a str, Vec<(Option<&'a str>, ast::Term)>, Extra<'a>> {
+    return parse_port()
         .separated_by(just(',').padded())
         .at_least(1)
-        .collect::<Vec<TermPtr>>()
-        .delimited_by(just('(').padded(), just(')').padded())
-        .map_with_state(|term_ptrs, _, state| {
-            for term_ptr in term_ptrs {
-                state.net.head(term_ptr)
-            }
-        });
+        .collect::<Vec<_>>()
+        .delimited_by(just('(').padded(), just(')').padded());
 }
 
-fn parse_def<'a>() -> impl Parser<'a, &'a str, &'a str, State<ParserState<'a>>> {
+fn parse_def<'a>() -> impl Parser<'a, &'a str, ast::Def, Extra<'a>> {
     return keyword("def")
         .padded()
         .ignore_then(text::ident().padded())
         .then(parse_head())
         .then(just('=').padded().ignore_then(parse_eqns()).or_not())
-        .map_with_state(|out, _, state| out.0 .0);
+        .map_with_state(|((name, head), body), _, state: &mut ParserState<'a>| {
+            let def = state.defs.len() as u32;
+            state.defs.insert(name, def);
+            let (head_names, head) = head
+                .into_iter()
+                .map(|(port, term)| (port.map(str::to_string), term))
+                .unzip();
+            ast::Def {
+                name: name.to_string(),
+                net: ast::Net {
+                    head,
+                    body: body.unwrap_or_default(),
+                },
+                head_names,
+            }
+        });
 }
 
 // type NetState<'a, I: Input<'a>> = Full<Simple<'a, I>, ParserState<'a>, ()>;
 
-fn parse_book<'a>() -> impl Parser<'a, &'a str, Vec<&'a str>, State<ParserState<'a>>> {
+fn parse_book<'a>() -> impl Parser<'a, &'a str, ast::Book, Extra<'a>> {
     return parse_def()
         .separated_by(just(';').padded())
         .allow_trailing()
-        .collect::<Vec<_>>();
+        .collect::<Vec<_>>()
+        .map(|defs| ast::Book { defs });
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::strandal::runtime::Runtime;
+    use crate::strandal::{net::NetBuilder, runtime::Runtime, term::TermPtr};
 
     use super::*;
 
     #[test]
     fn test_term() {
         let src = "([* *] a)";
-        let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let a = parse_term().parse_with_state(src, &mut state);
         assert_eq!(state.vars.contains_key("a"), true);
         println!("{:?}", a);
@@ -149,8 +424,7 @@ mod tests {
     #[test]
     fn test_term2() {
         let src = "a";
-        let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let a = parse_term().parse_with_state(src, &mut state);
         assert_eq!(state.vars.contains_key("a"), true);
         println!("{:?}", a);
@@ -159,8 +433,7 @@ mod tests {
     #[test]
     fn test_eqn() {
         let src = "([* *] *) ~ a";
-        let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let a = parse_eqn().parse_with_state(src, &mut state);
         assert_eq!(state.vars.contains_key("a"), true);
         println!("{:?}", a);
@@ -169,65 +442,53 @@ mod tests {
     #[test]
     fn test_eqns() {
         let src = "* ~ * & * ~ R";
-        let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let a = parse_eqns().parse_with_state(src, &mut state);
         assert_eq!(state.vars.len(), 1);
         println!("{:?}", a);
-        println!("{:?}", state.net);
     }
 
     #[test]
     fn test_head() {
         let src = "(R, *, f)";
-        let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let a = parse_head().parse_with_state(src, &mut state);
         println!("{:?}", a);
-        println!("{:?}", state.net);
     }
 
     #[test]
     fn test_def() {
         let src = "def a(R, *) = * ~ *";
-        let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let a = parse_def().parse_with_state(src, &mut state);
         println!("{:?}", a);
-        println!("{:?}", state.net);
         println!("{:?}", state.defs);
     }
 
     #[test]
     fn test_def2() {
         let src = "def a(R, *)";
-        let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let a = parse_def().parse_with_state(src, &mut state);
         println!("{:?}", a);
-        println!("{:?}", state.net);
         println!("{:?}", state.defs);
     }
 
     #[test]
     fn test_def3() {
         let src = "def a(*)";
-        let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let a = parse_def().parse_with_state(src, &mut state);
         println!("{:?}", a);
-        println!("{:?}", state.net);
         println!("{:?}", state.defs);
     }
 
     #[test]
     fn test_def4() {
         let src = "def a()"; // ERROR
-        let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let a = parse_def().parse_with_state(src, &mut state);
         println!("{:?}", a);
-        println!("{:?}", state.net);
         println!("{:?}", state.defs);
     }
 
@@ -236,11 +497,9 @@ mod tests {
         let src = "
             def c10([* *])
         "; // ERROR
-        let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let a = parse_book().parse_with_state(src, &mut state);
         println!("{:?}", a);
-        println!("{:?}", state.net);
         println!("{:?}", state.defs);
     }
 
@@ -252,23 +511,107 @@ mod tests {
             def b(*, R) = R ~ * ;
         ";
         let mut net = Net::new();
-        let mut state = ParserState::new(&mut net);
+        let mut state = ParserState::new();
         let result = parse_book()
             .parse_with_state(src.trim(), &mut state)
             .into_result();
         match result {
-            Ok(_) => {
-                println!("Success!! {:?}", state.net);
+            Ok(book) => {
+                println!("Success!! {:?}", book);
                 println!("{:?}", state.defs);
+                net.load(&book).unwrap();
                 let mut runtime = Runtime::new();
-                runtime.eval(&mut state.net);
-                println!("Executed:  {:?}", state.net);
+                runtime.eval(&mut net).unwrap();
+                println!("Executed:  {:?}", net);
             }
             Err(errs) => {
                 errs.into_iter().for_each(|e| println!("{}", e));
-                // println!("{:?}", state.net);
                 println!("{:?}", state.defs);
             }
         }
     }
+
+    #[test]
+    fn test_num() {
+        let terms: Vec<_> = ["42", "0x2A", "0b101010", "(+ 0x1 r)"]
+            .iter()
+            .map(|src| {
+                let mut state = ParserState::new();
+                parse_term().parse_with_state(src, &mut state).into_result()
+            })
+            .collect();
+        assert_eq!(terms[0], Ok(ast::Term::Num(42)));
+        assert_eq!(terms[1], terms[0]);
+        assert_eq!(terms[2], terms[0]);
+        assert_eq!(
+            terms[3],
+            Ok(ast::Term::op(Op::Add, ast::Term::Num(1), ast::Term::var("r")))
+        );
+
+        let errors = try_parse_ast("def main(r) = r ~ 0x100000000").unwrap_err();
+        assert_eq!(errors[0].reason, "number out of range");
+        assert!(try_parse_ast("def main(r) = r ~ 0b2").is_err());
+    }
+
+    #[test]
+    fn test_infix() {
+        let eqns = |src: &str| parse_ast(src).unwrap().defs.remove(0).net.body;
+        let (num, var) = (ast::Term::Num, ast::Term::var);
+        assert_eq!(
+            eqns("def main(r) = r ~ 1 + 2 * 3"),
+            vec![(
+                var("r"),
+                ast::Term::infix(
+                    Op::Add,
+                    num(1),
+                    ast::Term::infix(Op::Mul, num(2), num(3))
+                )
+            )]
+        );
+        assert_eq!(
+            eqns("def main(r) = r ~ (1 - 2) - 3"),
+            eqns("def main(r) = r ~ 1 - 2 - 3")
+        );
+        // comparisons bind looser than shifts, though `<` starts `<<`
+        assert_eq!(
+            eqns("def main(r) = r ~ 1 << 2 < 3"),
+            vec![(
+                var("r"),
+                ast::Term::infix(
+                    Op::Lt,
+                    ast::Term::infix(Op::Shl, num(1), num(2)),
+                    num(3)
+                )
+            )]
+        );
+
+        let mut net = Net::new();
+        assert!(parse("def main(out r) = r ~ (1 + 2) * 3 - 4 / 2", &mut net));
+        Runtime::new().eval(&mut net).unwrap();
+        assert_eq!(net.normal_form().unwrap(), vec![ast::Term::Num(7)]);
+    }
+
+    #[test]
+    fn test_stdlib() {
+        let stdlib = stdlib();
+        for (cond, taken) in [("true", 0), ("false", 1)] {
+            let mut net = Net::new();
+            let cond = net.instantiate(&stdlib[cond]);
+            let ite = net.instantiate(&stdlib["ite"]);
+            let x = net.var();
+            let branches = [net.lam(x.0, x.1).into(), TermPtr::Era];
+            net.eqn(cond.ports[0], ite.ports[0]);
+            net.eqn(branches[taken], ite.ports[1]);
+            net.eqn(branches[1 - taken], ite.ports[2]);
+            net.head(ite.ports[3]);
+
+            Runtime::new().eval(&mut net).unwrap();
+            let normal_form = net.normal_form().unwrap();
+            let ast::Term::Lam(p0, p1) = &normal_form[0] else {
+                panic!("expected the identity, got {}", normal_form[0]);
+            };
+            assert_eq!(p0, p1);
+            assert!(net.leaks().is_empty());
+        }
+    }
 }
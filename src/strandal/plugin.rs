@@ -0,0 +1,457 @@
+//! A C-compatible interface for agents whose rewrites are implemented in a
+//! shared library, loaded into a [`RuleTable`] at runtime.
+//!
+//! A plugin exports an init function named `strandal_plugin_init`, of type
+//! [`PluginInit`]. It is called with the ABI version of the host, and
+//! returns false if it does not support it. Through the [`PluginRegistrar`]
+//! it declares agents and the pairs of agents it rewrites:
+//!
+//! ```ignore
+//! #[no_mangle]
+//! pub unsafe extern "C" fn strandal_plugin_init(
+//!     abi: u32,
+//!     registrar: *const PluginRegistrar,
+//! ) -> bool {
+//!     let registrar = &*registrar;
+//!     let big = (registrar.agent)(registrar.ctx, c"Big".as_ptr(), 2);
+//!     let add = (registrar.agent)(registrar.ctx, c"BigAdd".as_ptr(), 2);
+//!     abi == ABI_VERSION && (registrar.interaction)(registrar.ctx, add, big, add_big)
+//! }
+//! ```
+//!
+//! An [`InteractFn`] is called with the nodes of an active pair, and rewires
+//! the net through the [`PluginNet`] it is given: it reads what the auxiliary
+//! ports of the pair are wired to, allocates and links new nodes, and
+//! removes the pair. Agents declared by plugins are used in rule files like
+//! the ones declared there, without declaring them again. The callbacks of
+//! the [`PluginNet`] check the ids they are given, answering [`NO_AGENT`] or
+//! [`NO_NODE`] for unknown ones, and ignoring removed or unknown nodes.
+//!
+//! Plugins are only loaded on Unix, through `dlopen`. Elsewhere [`load`]
+//! fails, while [`register`] still takes init functions linked in.
+
+use std::{
+    ffi::{c_char, c_void, CStr},
+    fmt::Display,
+    path::Path,
+};
+
+use super::rules::{AgentId, AgentNet, Port, RuleError, RuleTable};
+
+/// The version of this interface, bumped on any change to its types.
+pub const ABI_VERSION: u32 = 1;
+
+/// The symbol of the init function of a plugin.
+pub const INIT_SYMBOL: &CStr = c"strandal_plugin_init";
+
+/// Returned instead of an agent id when the agent could not be declared.
+pub const NO_AGENT: usize = usize::MAX;
+
+/// Returned instead of a node, or in the `node` of a port, when there is no
+/// such node.
+pub const NO_NODE: usize = usize::MAX - 1;
+
+/// The `node` of a head port, whose index is then `port`.
+pub const HEAD: usize = usize::MAX;
+
+pub type PluginInit = unsafe extern "C" fn(abi: u32, registrar: *const PluginRegistrar) -> bool;
+
+/// Rewrites the active pair of nodes `left` and `right`, whose agents are
+/// in the order they were registered in.
+pub type InteractFn = unsafe extern "C" fn(net: *const PluginNet, left: usize, right: usize);
+
+/// A port of a node, the principal one being 0, or a head, see [`HEAD`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PluginPort {
+    pub node: usize,
+    pub port: usize,
+}
+
+impl From<Port> for PluginPort {
+    fn from(port: Port) -> Self {
+        match port {
+            Port::Node(node, port) => PluginPort { node, port },
+            Port::Head(idx) => PluginPort {
+                node: HEAD,
+                port: idx,
+            },
+        }
+    }
+}
+
+impl From<PluginPort> for Port {
+    fn from(port: PluginPort) -> Self {
+        match port.node {
+            HEAD => Port::Head(port.port),
+            node => Port::Node(node, port.port),
+        }
+    }
+}
+
+/// What a plugin registers its agents and rewrites through.
+#[repr(C)]
+pub struct PluginRegistrar {
+    pub ctx: *mut c_void,
+    /// Declare the agent `name` with `arity` auxiliary ports, returning its
+    /// id. An agent already declared with the same arity keeps its id.
+    pub agent: extern "C" fn(ctx: *mut c_void, name: *const c_char, arity: usize) -> usize,
+    /// Rewrite `left >< right` with `interact`. Returns false if the pair
+    /// already has a rule.
+    pub interaction:
+        extern "C" fn(ctx: *mut c_void, left: usize, right: usize, interact: InteractFn) -> bool,
+}
+
+/// The net an [`InteractFn`] rewrites.
+#[repr(C)]
+pub struct PluginNet {
+    pub net: *mut c_void,
+    /// The agent id of a node, or [`NO_AGENT`] if there is no such node.
+    pub agent: extern "C" fn(net: *mut c_void, node: usize) -> usize,
+    /// The number of auxiliary ports of an agent, or [`NO_AGENT`] if there
+    /// is no such agent.
+    pub arity: extern "C" fn(net: *mut c_void, agent: usize) -> usize,
+    /// The port wired to a port, or one of [`NO_NODE`] if there is no such
+    /// port.
+    pub target: extern "C" fn(net: *mut c_void, port: PluginPort) -> PluginPort,
+    /// Add a node with its ports unwired, returning it, or [`NO_NODE`] if
+    /// there is no such agent.
+    pub alloc_node: extern "C" fn(net: *mut c_void, agent: usize, arity: usize) -> usize,
+    pub link: extern "C" fn(net: *mut c_void, left: PluginPort, right: PluginPort),
+    pub remove: extern "C" fn(net: *mut c_void, node: usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginError {
+    Open(String),
+    /// The library has no [`INIT_SYMBOL`].
+    NoInit(String),
+    /// The init function returned false, as for an unsupported ABI version.
+    Rejected(String),
+    Rule(String, RuleError),
+}
+
+impl Display for PluginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PluginError::Open(err) => write!(f, "could not load plugin: {}", err),
+            PluginError::NoInit(path) => {
+                write!(f, "plugin {} has no `{:?}`", path, INIT_SYMBOL)
+            }
+            PluginError::Rejected(path) => write!(
+                f,
+                "plugin {} does not support ABI version {}",
+                path, ABI_VERSION
+            ),
+            PluginError::Rule(path, err) => write!(f, "plugin {}: {}", path, err),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// Load the plugin at `path` into `table`. The library stays loaded for the
+/// life of the process, as the table keeps pointers to its functions.
+#[cfg(unix)]
+pub fn load(path: &Path, table: &mut RuleTable) -> Result<(), PluginError> {
+    let name = path.display().to_string();
+    let c_path = std::ffi::CString::new(name.clone()).map_err(|err| PluginError::Open(err.to_string()))?;
+    // SAFETY: loading a library runs its initializers, which plugins are
+    // trusted with as with any native code
+    let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+    if handle.is_null() {
+        return Err(PluginError::Open(dl_error()));
+    }
+    let symbol = unsafe { libc::dlsym(handle, INIT_SYMBOL.as_ptr()) };
+    if symbol.is_null() {
+        return Err(PluginError::NoInit(name));
+    }
+    // SAFETY: plugins export their init function with this signature
+    let init: PluginInit = unsafe { std::mem::transmute(symbol) };
+    unsafe { register(table, init) }.map_err(|err| match err {
+        PluginError::Rejected(_) => PluginError::Rejected(name),
+        PluginError::Rule(_, err) => PluginError::Rule(name, err),
+        err => err,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn load(path: &Path, _table: &mut RuleTable) -> Result<(), PluginError> {
+    Err(PluginError::Open(format!(
+        "{}: plugins are only loaded on Unix",
+        path.display()
+    )))
+}
+
+#[cfg(unix)]
+fn dl_error() -> String {
+    let err = unsafe { libc::dlerror() };
+    if err.is_null() {
+        return "unknown error".to_string();
+    }
+    unsafe { CStr::from_ptr(err) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+struct Registration<'a> {
+    table: &'a mut RuleTable,
+    error: Option<RuleError>,
+}
+
+/// Register the agents and rewrites of a plugin already loaded.
+///
+/// # Safety
+///
+/// `init` must follow this interface: only use the registrar during the
+/// call, and register [`InteractFn`]s that only rewire the pair they are
+/// called with.
+pub unsafe fn register(table: &mut RuleTable, init: PluginInit) -> Result<(), PluginError> {
+    let mut registration = Registration { table, error: None };
+    let registrar = PluginRegistrar {
+        ctx: &mut registration as *mut Registration as *mut c_void,
+        agent: register_agent,
+        interaction: register_interaction,
+    };
+    let accepted = init(ABI_VERSION, &registrar);
+    if let Some(err) = registration.error {
+        return Err(PluginError::Rule(String::new(), err));
+    }
+    if !accepted {
+        return Err(PluginError::Rejected(String::new()));
+    }
+    Ok(())
+}
+
+fn registration<'a>(ctx: *mut c_void) -> &'a mut Registration<'a> {
+    unsafe { &mut *(ctx as *mut Registration) }
+}
+
+extern "C" fn register_agent(ctx: *mut c_void, name: *const c_char, arity: usize) -> usize {
+    let registration = registration(ctx);
+    let name = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+    let declared = match registration.table.agent_id(&name) {
+        Some(id) if registration.table.agent(id).arity == arity => Ok(id),
+        Some(id) => Err(RuleError::Arity {
+            expected: registration.table.agent(id).arity,
+            agent: name,
+            found: arity,
+        }),
+        None => registration
+            .table
+            .add_agent(super::rules::AgentDef { name, arity }),
+    };
+    declared.unwrap_or_else(|err| {
+        registration.error.get_or_insert(err);
+        NO_AGENT
+    })
+}
+
+extern "C" fn register_interaction(
+    ctx: *mut c_void,
+    left: usize,
+    right: usize,
+    interact: InteractFn,
+) -> bool {
+    let registration = registration(ctx);
+    let agents = registration.table.agents();
+    let added = match [left, right].into_iter().find(|id| *id >= agents) {
+        Some(id) => Err(RuleError::UnknownAgent(format!("#{}", id))),
+        None => registration.table.add_native(left, right, interact),
+    };
+    added
+        .map_err(|err| registration.error.get_or_insert(err))
+        .is_ok()
+}
+
+/// Rewrite the active pair `left` and `right` with a plugin.
+pub(crate) fn interact(net: &mut AgentNet, interact: InteractFn, left: usize, right: usize) {
+    let plugin_net = PluginNet {
+        net: net as *mut AgentNet as *mut c_void,
+        agent: net_agent,
+        arity: net_arity,
+        target: net_target,
+        alloc_node: net_alloc_node,
+        link: net_link,
+        remove: net_remove,
+    };
+    unsafe { interact(&plugin_net, left, right) }
+}
+
+fn agent_net<'a>(net: *mut c_void) -> &'a mut AgentNet<'a> {
+    unsafe { &mut *(net as *mut AgentNet) }
+}
+
+// a panic cannot unwind out of these callbacks, so the ids a plugin passes
+// are checked before they reach the net
+
+extern "C" fn net_agent(net: *mut c_void, node: usize) -> AgentId {
+    let net = agent_net(net);
+    match net.has_port(Port::Node(node, 0)) {
+        true => net.agent(node),
+        false => NO_AGENT,
+    }
+}
+
+extern "C" fn net_arity(net: *mut c_void, agent: AgentId) -> usize {
+    let table = agent_net(net).table();
+    match agent < table.agents() {
+        true => table.agent(agent).arity,
+        false => NO_AGENT,
+    }
+}
+
+extern "C" fn net_target(net: *mut c_void, port: PluginPort) -> PluginPort {
+    let net = agent_net(net);
+    match net.has_port(port.into()) {
+        true => net.target(port.into()).into(),
+        false => PluginPort {
+            node: NO_NODE,
+            port: 0,
+        },
+    }
+}
+
+extern "C" fn net_alloc_node(net: *mut c_void, agent: AgentId, arity: usize) -> usize {
+    let net = agent_net(net);
+    match agent < net.table().agents() {
+        true => net.alloc_node(agent, arity),
+        false => NO_NODE,
+    }
+}
+
+extern "C" fn net_link(net: *mut c_void, left: PluginPort, right: PluginPort) {
+    let net = agent_net(net);
+    if net.has_port(left.into()) && net.has_port(right.into()) {
+        net.link(left.into(), right.into())
+    }
+}
+
+extern "C" fn net_remove(net: *mut c_void, node: usize) {
+    let net = agent_net(net);
+    if net.has_port(Port::Node(node, 0)) {
+        net.remove(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::rules::{AgentNet, RuleError, RuleTable};
+
+    use super::{
+        net_agent, net_alloc_node, net_arity, net_link, net_remove, net_target, register,
+        PluginError, PluginNet, PluginPort, PluginRegistrar, ABI_VERSION, HEAD, NO_AGENT, NO_NODE,
+    };
+
+    // agents declared by the plugin, in order
+    const Z: usize = 0;
+    const S: usize = 1;
+    const DBL: usize = 2;
+
+    unsafe extern "C" fn init(abi: u32, registrar: *const PluginRegistrar) -> bool {
+        let registrar = &*registrar;
+        let agent =
+            |name: &std::ffi::CStr, arity| (registrar.agent)(registrar.ctx, name.as_ptr(), arity);
+        assert_eq!(agent(c"Z", 0), Z);
+        assert_eq!(agent(c"S", 1), S);
+        assert_eq!(agent(c"Dbl", 1), DBL);
+        abi == ABI_VERSION
+            && (registrar.interaction)(registrar.ctx, DBL, Z, dbl_z)
+            && (registrar.interaction)(registrar.ctx, DBL, S, dbl_s)
+    }
+
+    fn port(node: usize, port: usize) -> PluginPort {
+        PluginPort { node, port }
+    }
+
+    // Dbl(r) >< Z = r ~ Z
+    unsafe extern "C" fn dbl_z(net: *const PluginNet, dbl: usize, z: usize) {
+        let net = &*net;
+        let r = (net.target)(net.net, port(dbl, 1));
+        (net.remove)(net.net, dbl);
+        (net.link)(net.net, r, port(z, 0));
+    }
+
+    // Dbl(r) >< S(x) = Dbl(y) ~ x & r ~ S(S(y))
+    unsafe extern "C" fn dbl_s(net: *const PluginNet, dbl: usize, s: usize) {
+        let net = &*net;
+        let r = (net.target)(net.net, port(dbl, 1));
+        let x = (net.target)(net.net, port(s, 1));
+        (net.remove)(net.net, s);
+        let outer = (net.alloc_node)(net.net, S, 1);
+        let inner = (net.alloc_node)(net.net, S, 1);
+        (net.link)(net.net, r, port(outer, 0));
+        (net.link)(net.net, port(outer, 1), port(inner, 0));
+        (net.link)(net.net, port(inner, 1), port(dbl, 1));
+        (net.link)(net.net, port(dbl, 0), x);
+    }
+
+    #[test]
+    fn test_plugin() {
+        let mut table = RuleTable::default();
+        unsafe { register(&mut table, init) }.unwrap();
+        let programs = table
+            .load("agent Add 2 ; rule Add(r, y) >< Z = r ~ y ; net (r) = Dbl(r) ~ S(S(Z)) ;")
+            .unwrap();
+        let mut net = AgentNet::new(&table, &programs[0]);
+        assert_eq!(net.reduce().unwrap(), 3);
+        assert_eq!(net.read()[0].to_string(), "S(S(S(S(Z))))");
+    }
+
+    #[test]
+    fn test_plugin_errors() {
+        unsafe extern "C" fn old_abi(_: u32, _: *const PluginRegistrar) -> bool {
+            false
+        }
+        unsafe extern "C" fn bad_arity(_: u32, registrar: *const PluginRegistrar) -> bool {
+            let registrar = &*registrar;
+            (registrar.agent)(registrar.ctx, c"Z".as_ptr(), 1);
+            true
+        }
+        unsafe extern "C" fn twice(_: u32, registrar: *const PluginRegistrar) -> bool {
+            let registrar = &*registrar;
+            (registrar.interaction)(registrar.ctx, DBL, Z, dbl_z)
+        }
+        let mut table = RuleTable::default();
+        assert_eq!(
+            unsafe { register(&mut table, old_abi) },
+            Err(PluginError::Rejected(String::new()))
+        );
+        unsafe { register(&mut table, init) }.unwrap();
+        assert!(matches!(
+            unsafe { register(&mut table, bad_arity) },
+            Err(PluginError::Rule(_, RuleError::Arity { .. }))
+        ));
+        assert!(matches!(
+            unsafe { register(&mut table, twice) },
+            Err(PluginError::Rule(_, RuleError::DuplicateRule(_, _)))
+        ));
+    }
+
+    #[test]
+    fn test_plugin_bad_ids() {
+        let mut table = RuleTable::default();
+        unsafe { register(&mut table, init) }.unwrap();
+        let programs = table.load("net (r) = r ~ S(Z) ;").unwrap();
+        let mut net = AgentNet::new(&table, &programs[0]);
+        let ptr = &mut net as *mut AgentNet as *mut std::ffi::c_void;
+        let head = port(HEAD, 0);
+        let s = net_target(ptr, head).node;
+        assert_eq!(net_agent(ptr, s), S);
+
+        assert_eq!(net_agent(ptr, 99), NO_AGENT);
+        assert_eq!(net_arity(ptr, 99), NO_AGENT);
+        assert_eq!(net_alloc_node(ptr, 99, 1), NO_NODE);
+        assert_eq!(net_target(ptr, port(s, 7)).node, NO_NODE);
+        assert_eq!(net_target(ptr, port(HEAD, 7)).node, NO_NODE);
+        net_link(ptr, head, port(99, 0));
+        net_remove(ptr, 99);
+        // a removed node is unknown too
+        net_remove(ptr, s);
+        net_remove(ptr, s);
+        assert_eq!(net_agent(ptr, s), NO_AGENT);
+        assert_eq!(net_target(ptr, head), port(s, 0));
+    }
+}
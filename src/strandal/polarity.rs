@@ -0,0 +1,391 @@
+use std::{collections::HashMap, fmt::Display};
+
+use super::{
+    net::{Net, NetBuilder},
+    num::Op,
+    refs::RefId,
+    store::{Index, Ptr},
+    term::{CellUse, TermPtr},
+    var::VarUse,
+};
+
+/// A wiring rejected by [`PolarityChecker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WiringError {
+    /// Connecting the cell or var here joins two ports of the same
+    /// polarity, such as two lambdas through a var, which can never reduce.
+    Polarity(Ptr),
+    /// The var was wired more than twice.
+    OverusedVar(Ptr),
+    /// The var was wired only once.
+    UnpairedVar(Ptr),
+}
+
+impl Display for WiringError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WiringError::Polarity(ptr) => {
+                write!(f, "{} connects two ports of the same polarity", ptr)
+            }
+            WiringError::OverusedVar(ptr) => write!(f, "var {} is used more than twice", ptr),
+            WiringError::UnpairedVar(ptr) => write!(f, "var {} is used only once", ptr),
+        }
+    }
+}
+
+impl std::error::Error for WiringError {}
+
+// a polarity unknown, relative to `node`: flipped when the bool is set
+type Polarity = (usize, bool);
+
+// node 0 is the positive polarity, every other node is unknown
+const POSITIVE: Polarity = (0, false);
+const NEGATIVE: Polarity = (0, true);
+
+/// A [`NetBuilder`] that checks the polarity of every connection.
+///
+/// Ports produce (positive) or consume (negative) a value: a lambda produces
+/// a function and its bound var and consumes its body, an application
+/// consumes a function and an argument (in its first port) and produces its
+/// result (in its second port). Numbers are produced, and operators and
+/// switches consume them to produce their result. A wire must join a
+/// producer and a consumer. Duplicators work in either direction, so their
+/// polarity is inferred from how they are wired, and erasers and heads accept
+/// anything.
+pub struct PolarityChecker<'a> {
+    net: &'a mut Net,
+    // union-find of polarities: the parent and the flip relative to it
+    parents: Vec<(usize, bool)>,
+    cells: HashMap<Ptr, Polarity>,
+    // polarity of the first use, and how many uses were wired
+    vars: HashMap<Ptr, (Polarity, u8)>,
+    errors: Vec<WiringError>,
+}
+
+impl<'a> PolarityChecker<'a> {
+    pub fn new(net: &'a mut Net) -> Self {
+        Self {
+            net,
+            parents: vec![(0, false)],
+            cells: HashMap::new(),
+            vars: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// The wirings rejected so far.
+    pub fn errors(&self) -> &[WiringError] {
+        &self.errors
+    }
+
+    /// Check that every var was wired twice, and return all the errors.
+    pub fn finish(mut self) -> Result<(), Vec<WiringError>> {
+        let mut unpaired: Vec<_> = self
+            .vars
+            .iter()
+            .filter(|(_, (_, uses))| *uses == 1)
+            .map(|(ptr, _)| *ptr)
+            .collect();
+        unpaired.sort_by_key(|ptr| ptr.index());
+        self.errors
+            .extend(unpaired.into_iter().map(WiringError::UnpairedVar));
+        match self.errors.is_empty() {
+            true => Ok(()),
+            false => Err(self.errors),
+        }
+    }
+
+    fn unknown(&mut self) -> Polarity {
+        self.parents.push((self.parents.len(), false));
+        (self.parents.len() - 1, false)
+    }
+
+    fn find(&mut self, node: usize) -> (usize, bool) {
+        let (parent, flip) = self.parents[node];
+        if parent == node {
+            return (node, false);
+        }
+        let (root, parent_flip) = self.find(parent);
+        self.parents[node] = (root, flip ^ parent_flip);
+        (root, flip ^ parent_flip)
+    }
+
+    // require two polarities to be equal, returning false on a conflict
+    fn unify(&mut self, left: Polarity, right: Polarity) -> bool {
+        let (left_root, left_flip) = self.find(left.0);
+        let (right_root, right_flip) = self.find(right.0);
+        let flip = left_flip ^ left.1 ^ right_flip ^ right.1;
+        if left_root == right_root {
+            return !flip;
+        }
+        // keep the positive constant as a root
+        if left_root == 0 {
+            self.parents[right_root] = (0, flip);
+        } else {
+            self.parents[left_root] = (right_root, flip);
+        }
+        true
+    }
+
+    // wire `term_ptr` to a port of polarity `port`: its own end must have
+    // the opposite polarity
+    fn connect(&mut self, term_ptr: TermPtr, port: Polarity) {
+        let TermPtr::Ptr(ptr) = term_ptr else {
+            return;
+        };
+        let end = match self.cells.get(&ptr) {
+            Some(principal) => *principal,
+            None => match self.vars.get(&ptr).copied() {
+                None => {
+                    let first = self.unknown();
+                    self.vars.insert(ptr, (first, 1));
+                    first
+                }
+                Some((first, 1)) => {
+                    self.vars.insert(ptr, (first, 2));
+                    (first.0, !first.1)
+                }
+                Some(_) => {
+                    self.errors.push(WiringError::OverusedVar(ptr));
+                    return;
+                }
+            },
+        };
+        if !self.unify(end, (port.0, !port.1)) {
+            self.errors.push(WiringError::Polarity(ptr));
+        }
+    }
+
+    fn cell<const N: usize>(
+        &mut self,
+        cell: &CellUse,
+        principal: Polarity,
+        ports: [(TermPtr, Polarity); N],
+    ) {
+        for (port, polarity) in ports {
+            self.connect(port, polarity);
+        }
+        self.cells.insert(cell.ptr(), principal);
+    }
+}
+
+impl<'a> NetBuilder for PolarityChecker<'a> {
+    fn head<T: Into<TermPtr>>(&mut self, term_ptr: T) {
+        let term_ptr = term_ptr.into();
+        let port = self.unknown();
+        self.connect(term_ptr, port);
+        self.net.head(term_ptr);
+    }
+
+    fn var(&mut self) -> (VarUse, VarUse) {
+        self.net.var()
+    }
+
+    fn lam<T1, T2>(&mut self, binding: T1, body: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (binding, body) = (binding.into(), body.into());
+        let lam = self.net.lam(binding, body);
+        self.cell(&lam, POSITIVE, [(binding, POSITIVE), (body, NEGATIVE)]);
+        lam
+    }
+
+    fn app<T1, T2>(&mut self, arg: T1, result: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (arg, result) = (arg.into(), result.into());
+        let app = self.net.app(arg, result);
+        // cells annihilate port by port, so the first port of an application
+        // meets the binding of a lambda: it takes the argument, and the
+        // second port gives the result
+        self.cell(&app, NEGATIVE, [(arg, NEGATIVE), (result, POSITIVE)]);
+        app
+    }
+
+    fn dup<T1, T2>(&mut self, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (left, right) = (left.into(), right.into());
+        let dup = self.net.dup(left, right);
+        let principal = self.unknown();
+        let ports = (principal.0, !principal.1);
+        self.cell(&dup, principal, [(left, ports), (right, ports)]);
+        dup
+    }
+
+    fn fresh_dup<T1, T2>(&mut self, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (left, right) = (left.into(), right.into());
+        let dup = self.net.fresh_dup(left, right);
+        let principal = self.unknown();
+        let ports = (principal.0, !principal.1);
+        self.cell(&dup, principal, [(left, ports), (right, ports)]);
+        dup
+    }
+
+    fn labeled_dup<T1, T2>(&mut self, label: Index, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (left, right) = (left.into(), right.into());
+        let dup = self.net.labeled_dup(label, left, right);
+        let principal = self.unknown();
+        let ports = (principal.0, !principal.1);
+        self.cell(&dup, principal, [(left, ports), (right, ports)]);
+        dup
+    }
+
+    // a superposition produces its value, like a lambda
+    fn sup<T1, T2>(&mut self, label: Index, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (left, right) = (left.into(), right.into());
+        let sup = self.net.sup(label, left, right);
+        self.cell(&sup, POSITIVE, [(left, NEGATIVE), (right, NEGATIVE)]);
+        sup
+    }
+
+    fn num(&mut self, value: u32) -> CellUse {
+        let num = self.net.num(value);
+        self.cell(&num, POSITIVE, []);
+        num
+    }
+
+    // an operator consumes both operands and produces the result
+    fn op<T1, T2>(&mut self, op: Op, right: T1, result: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (right, result) = (right.into(), result.into());
+        let op = self.net.op(op, right, result);
+        self.cell(&op, NEGATIVE, [(right, NEGATIVE), (result, POSITIVE)]);
+        op
+    }
+
+    fn swi<T1, T2>(&mut self, branches: T1, result: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (branches, result) = (branches.into(), result.into());
+        let swi = self.net.swi(branches, result);
+        self.cell(&swi, NEGATIVE, [(branches, NEGATIVE), (result, POSITIVE)]);
+        swi
+    }
+
+    // the branches are paired in a lambda that consumes both of them
+    fn switch<T1, T2, T3>(&mut self, zero: T1, succ: T2, result: T3) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+        T3: Into<TermPtr>,
+    {
+        let (zero, succ) = (zero.into(), succ.into());
+        let branches = self.net.lam(zero, succ);
+        self.cell(&branches, POSITIVE, [(zero, NEGATIVE), (succ, NEGATIVE)]);
+        self.swi(branches, result)
+    }
+
+    // a definition can expand to a producer or a consumer
+    fn reference(&mut self, id: RefId) -> CellUse {
+        let reference = self.net.reference(id);
+        let principal = self.unknown();
+        self.cell(&reference, principal, []);
+        reference
+    }
+
+    fn era(&mut self) -> TermPtr {
+        self.net.era()
+    }
+
+    fn eqn<T1: Into<TermPtr>, T2: Into<TermPtr>>(&mut self, left: T1, right: T2) {
+        let (left, right) = (left.into(), right.into());
+        // the two sides act as ports for each other
+        let side = self.unknown();
+        self.connect(left, side);
+        self.connect(right, (side.0, !side.1));
+        self.net.eqn(left, right);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        lambda,
+        strandal::{
+            net::{Net, NetBuilder},
+            polarity::{PolarityChecker, WiringError},
+        },
+    };
+
+    #[test]
+    fn test_well_typed() {
+        let mut net = Net::new();
+        let mut checker = PolarityChecker::new(&mut net);
+        let id = lambda::id(&mut checker);
+        let x = checker.var();
+        let arg = checker.lam(x.0, x.1);
+        let r = checker.var();
+        let app = checker.app(arg, r.0);
+        checker.eqn(id, app);
+        let (a, b) = (checker.var(), checker.var());
+        let dup = checker.dup(a.0, b.0);
+        checker.eqn(r.1, dup);
+        checker.head(a.1);
+        checker.head(b.1);
+        assert_eq!(checker.finish(), Ok(()));
+    }
+
+    #[test]
+    fn test_pair_gadgets() {
+        let mut net = Net::new();
+        let mut checker = PolarityChecker::new(&mut net);
+        let (a, b) = (lambda::id(&mut checker), lambda::id(&mut checker));
+        let p = lambda::pair(&mut checker, a, b);
+        let first = lambda::fst(&mut checker, p);
+        checker.head(first);
+        let (a, b) = (lambda::id(&mut checker), lambda::id(&mut checker));
+        let p = lambda::pair(&mut checker, a, b);
+        let (first, second) = lambda::unpair(&mut checker, p);
+        checker.head(first);
+        checker.head(second);
+        assert_eq!(checker.finish(), Ok(()));
+    }
+
+    #[test]
+    fn test_lam_to_lam() {
+        let mut net = Net::new();
+        let mut checker = PolarityChecker::new(&mut net);
+        let x = checker.var();
+        let y = checker.var();
+        let id_x = checker.lam(x.0, x.1);
+        let id_y = checker.lam(y.0, y.1);
+        let id_y_ptr = id_y.ptr();
+        let w = checker.var();
+        checker.eqn(w.0, id_x);
+        checker.eqn(w.1, id_y);
+        let unused = checker.var();
+        checker.head(unused.0);
+        assert_eq!(
+            checker.finish(),
+            Err(vec![
+                // the second lambda meets the first through `w`
+                WiringError::Polarity(id_y_ptr),
+                WiringError::UnpairedVar(unused.1.ptr())
+            ])
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Recursive programs on numbers, built from Rust. The same programs are
+//! written as source in `tests/fixtures`: `factorial`, `fibonacci` and
+//! `tree_sum`.
+//!
+//! Each recursive call goes through a switch branch holding a reference, so
+//! it is only expanded when the switch takes that branch.
+
+use super::{
+    ast::{self, Term},
+    net::{Net, NetBuilder},
+    num::Op,
+    template::Template,
+    term::{CellUse, TermPtr},
+    var::VarUse,
+};
+
+// declare every definition first, so they can reference each other
+fn define(net: &mut Net, defs: Vec<(&str, ast::Net)>) {
+    for (name, _) in &defs {
+        net.declare_ref(name);
+    }
+    for (name, def) in defs {
+        let template =
+            Template::from_ast_with_refs(&def, net.refs()).expect("the definitions are closed");
+        net.define_ref(name, template)
+            .expect("the definitions have a single port");
+    }
+}
+
+// a definition `λbinding. result` wired by `body`
+fn function(binding: &str, result: &str, body: Vec<(Term, Term)>) -> ast::Net {
+    ast::Net {
+        head: vec![Term::lam(Term::var(binding), Term::var(result))],
+        body,
+    }
+}
+
+// apply the definition `name` to `arg`, returning the result
+fn call<T: Into<TermPtr>>(net: &mut Net, name: &str, arg: T) -> VarUse {
+    let id = net.refs().id(name).expect("the definition is declared");
+    let r = net.var();
+    let app = net.app(arg, r.0);
+    let f = net.reference(id);
+    net.eqn(f, app);
+    r.1
+}
+
+/// `n!`, defining `fact` and `fact_s` in `net`:
+///
+/// ```text
+/// fact = λn. switch n { 0 => 1, _ => @fact_s }
+/// fact_s = λp. (p + 1) * @fact p
+/// ```
+pub fn factorial(net: &mut Net, n: u32) -> VarUse {
+    let branches = Term::lam(Term::Num(1), Term::reference("fact_s"));
+    let fact = function(
+        "n",
+        "r",
+        vec![(Term::var("n"), Term::swi(branches, Term::var("r")))],
+    );
+    let fact_s = function(
+        "p",
+        "s",
+        vec![
+            (Term::var("p"), Term::dup(Term::var("p1"), Term::var("p2"))),
+            (
+                Term::var("p1"),
+                Term::op(Op::Add, Term::Num(1), Term::var("m")),
+            ),
+            (
+                Term::reference("fact"),
+                Term::app(Term::var("p2"), Term::var("f")),
+            ),
+            (
+                Term::var("m"),
+                Term::op(Op::Mul, Term::var("f"), Term::var("s")),
+            ),
+        ],
+    );
+    define(net, vec![("fact", fact), ("fact_s", fact_s)]);
+    let n = net.num(n);
+    call(net, "fact", n)
+}
+
+/// The `n`th Fibonacci number, defining `fib`, `fib_s` and `fib_ss` in
+/// `net`:
+///
+/// ```text
+/// fib = λn. switch n { 0 => 0, _ => @fib_s }
+/// fib_s = λp. switch p { 0 => 1, _ => @fib_ss }
+/// fib_ss = λq. @fib (q + 1) + @fib q
+/// ```
+pub fn fibonacci(net: &mut Net, n: u32) -> VarUse {
+    let branches = Term::lam(Term::Num(0), Term::reference("fib_s"));
+    let fib = function(
+        "n",
+        "r",
+        vec![(Term::var("n"), Term::swi(branches, Term::var("r")))],
+    );
+    let branches = Term::lam(Term::Num(1), Term::reference("fib_ss"));
+    let fib_s = function(
+        "p",
+        "s",
+        vec![(Term::var("p"), Term::swi(branches, Term::var("s")))],
+    );
+    let fib_ss = function(
+        "q",
+        "t",
+        vec![
+            (Term::var("q"), Term::dup(Term::var("q1"), Term::var("q2"))),
+            (
+                Term::var("q1"),
+                Term::op(Op::Add, Term::Num(1), Term::var("a")),
+            ),
+            (
+                Term::reference("fib"),
+                Term::app(Term::var("a"), Term::var("x")),
+            ),
+            (
+                Term::reference("fib"),
+                Term::app(Term::var("q2"), Term::var("y")),
+            ),
+            (
+                Term::var("x"),
+                Term::op(Op::Add, Term::var("y"), Term::var("t")),
+            ),
+        ],
+    );
+    define(
+        net,
+        vec![("fib", fib), ("fib_s", fib_s), ("fib_ss", fib_ss)],
+    );
+    let n = net.num(n);
+    call(net, "fib", n)
+}
+
+// a tree is a function of a leaf and a node handler, split in halves down
+// to the leaves
+fn tree(net: &mut Net, leaves: &[u32]) -> CellUse {
+    let (handler, w) = (net.var(), net.var());
+    match leaves {
+        [value] => {
+            // λl. λn. l v
+            let value = net.num(*value);
+            let apply = net.app(value, w.0);
+            net.eqn(handler.0, apply);
+            let inner = net.lam(TermPtr::Era, w.1);
+            net.lam(handler.1, inner)
+        }
+        _ => {
+            // λl. λn. n a b
+            let (left, right) = leaves.split_at(leaves.len() / 2);
+            let (left, right) = (tree(net, left), tree(net, right));
+            let apply_b = net.app(right, w.0);
+            let apply_a = net.app(left, apply_b);
+            net.eqn(handler.0, apply_a);
+            let inner = net.lam(handler.1, w.1);
+            net.lam(TermPtr::Era, inner)
+        }
+    }
+}
+
+/// The sum of the leaves of a balanced binary tree, defining `sum`,
+/// `sum_leaf` and `sum_node` in `net`:
+///
+/// ```text
+/// sum = λt. t @sum_leaf @sum_node
+/// sum_leaf = λv. v
+/// sum_node = λa. λb. @sum a + @sum b
+/// ```
+pub fn tree_sum(net: &mut Net, leaves: &[u32]) -> VarUse {
+    assert!(!leaves.is_empty(), "a tree has at least one leaf");
+    let sum = function(
+        "t",
+        "r",
+        vec![
+            (
+                Term::var("t"),
+                Term::app(Term::reference("sum_leaf"), Term::var("u")),
+            ),
+            (
+                Term::var("u"),
+                Term::app(Term::reference("sum_node"), Term::var("r")),
+            ),
+        ],
+    );
+    let sum_leaf = function("v", "v", vec![]);
+    let sum_node = ast::Net {
+        head: vec![Term::lam(
+            Term::var("a"),
+            Term::lam(Term::var("b"), Term::var("c")),
+        )],
+        body: vec![
+            (
+                Term::reference("sum"),
+                Term::app(Term::var("a"), Term::var("x")),
+            ),
+            (
+                Term::reference("sum"),
+                Term::app(Term::var("b"), Term::var("y")),
+            ),
+            (
+                Term::var("x"),
+                Term::op(Op::Add, Term::var("y"), Term::var("c")),
+            ),
+        ],
+    };
+    define(
+        net,
+        vec![("sum", sum), ("sum_leaf", sum_leaf), ("sum_node", sum_node)],
+    );
+    let tree = tree(net, leaves);
+    call(net, "sum", tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        readback::OwnedTerm,
+        runtime::Runtime,
+        var::VarUse,
+    };
+
+    use super::{factorial, fibonacci, tree_sum};
+
+    fn eval(build: impl FnOnce(&mut Net) -> VarUse) -> OwnedTerm {
+        let mut net = Net::new();
+        let result = build(&mut net);
+        net.head(result);
+        Runtime::new().eval(&mut net).unwrap();
+        assert!(net.leaks().is_empty(), "{}", net.leaks());
+        net.normal_form().unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_factorial() {
+        assert_eq!(eval(|net| factorial(net, 0)), OwnedTerm::Num(1));
+        assert_eq!(eval(|net| factorial(net, 5)), OwnedTerm::Num(120));
+        assert_eq!(eval(|net| factorial(net, 10)), OwnedTerm::Num(3628800));
+    }
+
+    #[test]
+    fn test_fibonacci() {
+        let fibs: Vec<_> = (0..12).map(|n| eval(|net| fibonacci(net, n))).collect();
+        let expected = [0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89];
+        assert_eq!(fibs, expected.map(OwnedTerm::Num));
+    }
+
+    #[test]
+    fn test_tree_sum() {
+        assert_eq!(eval(|net| tree_sum(net, &[7])), OwnedTerm::Num(7));
+        assert_eq!(eval(|net| tree_sum(net, &[1, 2, 3, 4])), OwnedTerm::Num(10));
+        let leaves: Vec<u32> = (1..=100).collect();
+        assert_eq!(eval(|net| tree_sum(net, &leaves)), OwnedTerm::Num(5050));
+    }
+}
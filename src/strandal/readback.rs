@@ -0,0 +1,440 @@
+use std::{collections::HashSet, fmt::Display};
+
+use super::{
+    ast,
+    display::CellDisplay,
+    net::Net,
+    num::Op,
+    store::{Index, Ptr},
+    term::{Cell, Label, TermPtr, TermRef},
+    var::VarValue,
+};
+
+/// A term copied out of the store, owning its children.
+pub type OwnedTerm = ast::Term;
+
+/// A term read back by [`Net::readback_partial`], where the positions not
+/// read are left as holes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartialTerm {
+    Era,
+    Var(String),
+    Lam(Box<PartialTerm>, Box<PartialTerm>),
+    App(Box<PartialTerm>, Box<PartialTerm>),
+    Dup(Box<PartialTerm>, Box<PartialTerm>, Option<Label>),
+    Num(u32),
+    Op(Op, bool, Box<PartialTerm>, Box<PartialTerm>),
+    Swi(Box<PartialTerm>, Box<PartialTerm>),
+    Ref(String),
+    /// A cell past the depth read, or a position still waiting on a redex.
+    Hole,
+}
+
+impl PartialTerm {
+    /// Whether the whole term was read, with no hole left.
+    pub fn is_complete(&self) -> bool {
+        match self {
+            PartialTerm::Era | PartialTerm::Var(_) | PartialTerm::Num(_) | PartialTerm::Ref(_) => {
+                true
+            }
+            PartialTerm::Lam(p0, p1)
+            | PartialTerm::App(p0, p1)
+            | PartialTerm::Dup(p0, p1, _)
+            | PartialTerm::Op(_, _, p0, p1)
+            | PartialTerm::Swi(p0, p1) => p0.is_complete() && p1.is_complete(),
+            PartialTerm::Hole => false,
+        }
+    }
+}
+
+impl Display for PartialTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PartialTerm::Era => write!(f, "{}", CellDisplay::ERA_SYMBOL),
+            PartialTerm::Var(name) => write!(f, "{}", name),
+            PartialTerm::Lam(p0, p1) => write!(f, "({} {} {})", CellDisplay::LAM_SYMBOL, p0, p1),
+            PartialTerm::App(p0, p1) => write!(f, "({} {} {})", CellDisplay::APP_SYMBOL, p0, p1),
+            PartialTerm::Dup(p0, p1, None) => {
+                write!(f, "({} {} {})", CellDisplay::DUP_SYMBOL, p0, p1)
+            }
+            PartialTerm::Dup(p0, p1, Some(lbl)) => {
+                write!(f, "({} {} {} {{{}}})", CellDisplay::DUP_SYMBOL, p0, p1, lbl)
+            }
+            PartialTerm::Num(value) => write!(f, "{}", value),
+            PartialTerm::Op(op, flipped, p0, p1) => {
+                write!(
+                    f,
+                    "({} {} {})",
+                    CellDisplay::op_symbol(*op, *flipped),
+                    p0,
+                    p1
+                )
+            }
+            PartialTerm::Swi(p0, p1) => write!(f, "({} {} {})", CellDisplay::SWI_SYMBOL, p0, p1),
+            PartialTerm::Ref(name) => write!(f, "{}{}", CellDisplay::REF_SYMBOL, name),
+            PartialTerm::Hole => write!(f, "_"),
+        }
+    }
+}
+
+/// Why [`Net::normal_form`] could not read the net back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotNormal {
+    /// Equations are still waiting in the body: the net was not evaluated.
+    PendingEquations(usize),
+    /// A head reaches a store slot that was already freed.
+    Dangling(Ptr),
+    /// A cell is reachable from one of its own ports.
+    Cycle(Ptr),
+    /// A cell lost its ports, so the term cannot be copied out.
+    Disconnected(Ptr),
+}
+
+impl Display for NotNormal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotNormal::PendingEquations(count) => {
+                write!(f, "net is not normal: {} pending equations", count)
+            }
+            NotNormal::Dangling(ptr) => write!(f, "net is not normal: {} was freed", ptr),
+            NotNormal::Cycle(ptr) => write!(f, "net is not normal: cell {} is cyclic", ptr),
+            NotNormal::Disconnected(ptr) => {
+                write!(f, "net is not normal: cell {} has no ports", ptr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NotNormal {}
+
+impl Net {
+    /// Read each head back as an owned term, resolving vars through their
+    /// links and bindings.
+    pub fn normal_form(&self) -> Result<Vec<OwnedTerm>, NotNormal> {
+        if !self.body.is_empty() {
+            return Err(NotNormal::PendingEquations(self.body.len()));
+        }
+        let mut path = Vec::new();
+        self.head
+            .iter()
+            .map(|head| self.read_term(*head, &mut path))
+            .collect()
+    }
+
+    /// Read back the head port added with [`Net::named_head`], or declared as
+    /// `out name` in a definition head. `None` if no head has this name.
+    pub fn head_by_name(&self, name: &str) -> Option<Result<OwnedTerm, NotNormal>> {
+        let idx = *self.head_names.get(name)?;
+        if !self.body.is_empty() {
+            return Some(Err(NotNormal::PendingEquations(self.body.len())));
+        }
+        Some(self.read_term(self.head[idx], &mut Vec::new()))
+    }
+
+    /// Read each head back down to `depth` nested cells, like
+    /// [`Net::normal_form`] but without waiting for the net to be normal:
+    /// deeper cells, cells whose ports are being rewritten and wires ending
+    /// in a pending equation are read as [`PartialTerm::Hole`].
+    pub fn readback_partial(&self, depth: usize) -> Result<Vec<PartialTerm>, NotNormal> {
+        let pending = self.pending_vars()?;
+        let mut path = Vec::new();
+        self.head
+            .iter()
+            .map(|head| self.read_partial(*head, depth, &pending, &mut path))
+            .collect()
+    }
+
+    fn read_partial(
+        &self,
+        term_ptr: TermPtr,
+        depth: usize,
+        pending: &HashSet<Index>,
+        path: &mut Vec<Ptr>,
+    ) -> Result<PartialTerm, NotNormal> {
+        let ptr = match self.resolve(term_ptr)? {
+            Resolved::Era => return Ok(PartialTerm::Era),
+            Resolved::Var(index) if pending.contains(&index) => return Ok(PartialTerm::Hole),
+            Resolved::Var(index) => return Ok(PartialTerm::Var(format!("x{}", index))),
+            Resolved::Cell(ptr) => ptr,
+        };
+        if depth == 0 {
+            return Ok(PartialTerm::Hole);
+        }
+        if path.contains(&ptr) {
+            return Err(NotNormal::Cycle(ptr));
+        }
+        let Some(TermRef::Cell(cell)) = self.store.get(ptr) else {
+            return Err(NotNormal::Dangling(ptr));
+        };
+
+        path.push(ptr);
+        let mut read = |port: &TermPtr| -> Result<Box<PartialTerm>, NotNormal> {
+            Ok(Box::new(self.read_partial(
+                *port,
+                depth - 1,
+                pending,
+                path,
+            )?))
+        };
+        let term = match &cell {
+            Cell::Lam(Some((p0, p1))) => PartialTerm::Lam(read(p0)?, read(p1)?),
+            Cell::App(Some((p0, p1))) => PartialTerm::App(read(p0)?, read(p1)?),
+            Cell::Dup(Some((p0, p1)), lbl) => {
+                PartialTerm::Dup(read(p0)?, read(p1)?, *lbl)
+            }
+            Cell::Num(value) => PartialTerm::Num(*value),
+            Cell::Op(Some((p0, p1)), op, flipped) => {
+                PartialTerm::Op(*op, *flipped, read(p0)?, read(p1)?)
+            }
+            Cell::Swi(Some((p0, p1))) => PartialTerm::Swi(read(p0)?, read(p1)?),
+            Cell::Ref(id) => PartialTerm::Ref(self.refs.name(*id).to_string()),
+            Cell::Lam(None)
+            | Cell::App(None)
+            | Cell::Dup(None, _)
+            | Cell::Op(None, _, _)
+            | Cell::Swi(None) => PartialTerm::Hole,
+        };
+        path.pop();
+        Ok(term)
+    }
+
+    // the free wires reaching a pending equation, named as `resolve` does
+    fn pending_vars(&self) -> Result<HashSet<Index>, NotNormal> {
+        let mut stack: Vec<TermPtr> = self
+            .body
+            .iter()
+            .flat_map(|(eqn, _)| [eqn.left(), eqn.right()])
+            .collect();
+        let mut reached = HashSet::new();
+        let mut pending = HashSet::new();
+        while let Some(term_ptr) = stack.pop() {
+            match self.resolve(term_ptr)? {
+                Resolved::Era => {}
+                Resolved::Var(index) => {
+                    pending.insert(index);
+                }
+                Resolved::Cell(ptr) => {
+                    if !reached.insert(ptr) {
+                        continue;
+                    }
+                    if let Some(TermRef::Cell(cell)) = self.store.get(ptr) {
+                        stack.extend(cell.ports().iter().flat_map(|(p0, p1)| [*p0, *p1]));
+                    }
+                }
+            }
+        }
+        Ok(pending)
+    }
+
+    // `path` holds the cells being copied, to detect cycles
+    pub(crate) fn read_term(
+        &self,
+        term_ptr: TermPtr,
+        path: &mut Vec<Ptr>,
+    ) -> Result<OwnedTerm, NotNormal> {
+        let ptr = match self.resolve(term_ptr)? {
+            Resolved::Era => return Ok(OwnedTerm::Era),
+            Resolved::Var(index) => return Ok(OwnedTerm::Var(format!("x{}", index))),
+            Resolved::Cell(ptr) => ptr,
+        };
+        if path.contains(&ptr) {
+            return Err(NotNormal::Cycle(ptr));
+        }
+        let Some(TermRef::Cell(cell)) = self.store.get(ptr) else {
+            return Err(NotNormal::Dangling(ptr));
+        };
+
+        path.push(ptr);
+        let term = match &cell {
+            Cell::Lam(Some((p0, p1))) => {
+                OwnedTerm::lam(self.read_term(*p0, path)?, self.read_term(*p1, path)?)
+            }
+            Cell::App(Some((p0, p1))) => {
+                OwnedTerm::app(self.read_term(*p0, path)?, self.read_term(*p1, path)?)
+            }
+            Cell::Dup(Some((p0, p1)), lbl) => OwnedTerm::Dup(
+                Box::new(self.read_term(*p0, path)?),
+                Box::new(self.read_term(*p1, path)?),
+                *lbl,
+            ),
+            Cell::Num(value) => OwnedTerm::Num(*value),
+            Cell::Op(Some((p0, p1)), op, flipped) => OwnedTerm::Op(
+                *op,
+                *flipped,
+                Box::new(self.read_term(*p0, path)?),
+                Box::new(self.read_term(*p1, path)?),
+            ),
+            Cell::Swi(Some((p0, p1))) => OwnedTerm::Swi(
+                Box::new(self.read_term(*p0, path)?),
+                Box::new(self.read_term(*p1, path)?),
+            ),
+            Cell::Ref(id) => OwnedTerm::Ref(self.refs.name(*id).to_string()),
+            Cell::Lam(None)
+            | Cell::App(None)
+            | Cell::Dup(None, _)
+            | Cell::Op(None, _, _)
+            | Cell::Swi(None) => return Err(NotNormal::Disconnected(ptr)),
+        };
+        path.pop();
+        Ok(term)
+    }
+
+    /// Follow var links until reaching a cell, an eraser or a free wire.
+    fn resolve(&self, term_ptr: TermPtr) -> Result<Resolved, NotNormal> {
+        let mut ptr = match term_ptr {
+            TermPtr::Era => return Ok(Resolved::Era),
+            TermPtr::Ptr(ptr) => ptr,
+        };
+        // vars walked so far, a free wire is named after where it ends: the
+        // unbound var, or the smallest of the vars linked to each other, so
+        // both its ends get the same name
+        let mut chain: Vec<Ptr> = Vec::new();
+        let end = loop {
+            if !self.store.handed_out(ptr) {
+                return Err(NotNormal::Dangling(ptr));
+            }
+            let var = match self.store.get(ptr) {
+                None => return Err(NotNormal::Dangling(ptr)),
+                Some(TermRef::Cell(_)) => return Ok(Resolved::Cell(ptr)),
+                Some(TermRef::Var(var)) => var,
+            };
+            chain.push(ptr);
+            match var.read() {
+                None => break chain.len() - 1,
+                Some(VarValue::Era) => return Ok(Resolved::Era),
+                Some(VarValue::Cell(cell_ptr)) => return Ok(Resolved::Cell(cell_ptr)),
+                // linked vars point at each other: the wire ends here
+                Some(VarValue::Var(other)) if chain.contains(&other) => {
+                    break chain.iter().position(|ptr| *ptr == other).unwrap()
+                }
+                Some(VarValue::Var(other)) => ptr = other,
+            }
+        };
+        let name = chain[end..].iter().map(|ptr| ptr.index()).min().unwrap();
+        Ok(Resolved::Var(name))
+    }
+}
+
+enum Resolved {
+    Era,
+    Var(Index),
+    Cell(Ptr),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        parser::parse,
+        readback::{NotNormal, OwnedTerm, PartialTerm},
+        runtime::Runtime,
+        term::TermPtr,
+    };
+
+    #[test]
+    fn test_normal_form() {
+        let mut net = Net::new();
+        let r = net.var();
+        let i1_var = net.var();
+        let i1 = net.lam(i1_var.0, i1_var.1);
+        let i2_var = net.var();
+        let i2 = net.lam(i2_var.0, i2_var.1);
+        let app = net.app(r.0, i2);
+        net.head(r.1);
+        net.head(TermPtr::Era);
+        net.eqn(i1, app);
+
+        assert_eq!(net.normal_form(), Err(NotNormal::PendingEquations(1)));
+
+        Runtime::new().eval(&mut net).unwrap();
+        let normal_form = net.normal_form().unwrap();
+        let OwnedTerm::Lam(p0, p1) = &normal_form[0] else {
+            panic!("expected a lambda, got {}", normal_form[0]);
+        };
+        assert!(matches!(**p0, OwnedTerm::Var(_)));
+        assert_eq!(p0, p1);
+        assert_eq!(normal_form[1], OwnedTerm::Era);
+    }
+
+    #[test]
+    fn test_readback_partial() {
+        // r ~ (λx.(λy.y)) applied to ε, and (λε.(λε.ε)) already normal
+        let mut net = Net::new();
+        let r = net.var();
+        let y = net.var();
+        let inner = net.lam(y.0, y.1);
+        let x = net.var();
+        let id = net.lam(x.0, inner);
+        let app = net.app(TermPtr::Era, r.0);
+        net.head(r.1);
+        let inner = net.lam(TermPtr::Era, TermPtr::Era);
+        let outer = net.lam(TermPtr::Era, inner);
+        net.head(outer);
+        net.eqn(id, app);
+
+        let partial = net.readback_partial(1).unwrap();
+        assert_eq!(partial[0], PartialTerm::Hole);
+        assert_eq!(partial[1].to_string(), "(λ ε _)");
+        assert!(!partial[1].is_complete());
+        let partial = net.readback_partial(2).unwrap();
+        assert_eq!(partial[1].to_string(), "(λ ε (λ ε ε))");
+        assert!(partial[1].is_complete());
+
+        Runtime::new().eval(&mut net).unwrap();
+        let partial = net.readback_partial(0).unwrap();
+        assert_eq!(partial, vec![PartialTerm::Hole, PartialTerm::Hole]);
+        let partial = net.readback_partial(8).unwrap();
+        let normal_form = net.normal_form().unwrap();
+        assert_eq!(partial[0].to_string(), normal_form[0].to_string());
+    }
+
+    #[test]
+    fn test_free_wire() {
+        let mut net = Net::new();
+        let x = net.var();
+        let lam = net.lam(x.0, x.1);
+        let y = net.var();
+        let dup = net.dup(y.0, TermPtr::Era);
+        net.head(lam);
+        net.head(dup);
+        net.head(y.1);
+
+        let normal_form = net.normal_form().unwrap();
+        assert_eq!(normal_form[0].to_string(), "(λ x0 x0)");
+        assert_eq!(normal_form[1].to_string(), "(δ x2 ε)");
+        assert_eq!(normal_form[2], OwnedTerm::var("x2"));
+    }
+
+    #[test]
+    fn test_linked_heads() {
+        // the beta rule links the heads one way only, and both their ends
+        // still read back as the same wire
+        let mut net = Net::new();
+        assert!(parse("def main(a, b) = (a c) ~ [b c]", &mut net));
+        Runtime::new().eval(&mut net).unwrap();
+        let normal_form = net.normal_form().unwrap();
+        assert!(matches!(normal_form[0], OwnedTerm::Var(_)));
+        assert_eq!(normal_form[0], normal_form[1]);
+    }
+
+    #[test]
+    fn test_head_by_name() {
+        let mut net = Net::new();
+        assert!(parse(
+            "def main(*, out result) = (x x) ~ [result (y y)]",
+            &mut net
+        ));
+        assert_eq!(
+            net.head_by_name("result"),
+            Some(Err(NotNormal::PendingEquations(1)))
+        );
+
+        Runtime::new().eval(&mut net).unwrap();
+        let result = net.head_by_name("result").unwrap().unwrap();
+        let OwnedTerm::Lam(p0, p1) = &result else {
+            panic!("expected a lambda, got {}", result);
+        };
+        assert_eq!(p0, p1);
+        assert_eq!(net.head_by_name("missing"), None);
+    }
+}
@@ -0,0 +1,226 @@
+//! Recursive definitions, as in HVM: `@name` is a reference cell standing
+//! for a copy of the definition `name`, so definitions can refer to
+//! themselves or to each other.
+//!
+//! A reference is only expanded when it meets another cell on its principal
+//! port: it is replaced by a fresh copy of the definition, whose single port
+//! meets the cell instead. Erasing a reference drops it and a duplicator
+//! copies it to both of its ports, without expanding it. A recursive call
+//! left in an untaken branch is never expanded, so a recursive program stops
+//! when its base case is reached. A reference in a body equation meets its
+//! cell right away, so a definition recurring there expands forever: the
+//! recursion must go through a branch, as in `(? (λ base @step) r)`.
+//!
+//! [`Net::load`] declares every definition referenced in a book before
+//! compiling any of them, so references can point forward or form cycles.
+
+use std::{collections::HashMap, sync::Arc};
+
+use super::{ast::AstError, net::Net, template::Template};
+
+/// The index of a definition in [`Refs`].
+pub type RefId = u32;
+
+/// The definitions that reference cells expand to, see the
+/// [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct Refs {
+    names: Vec<String>,
+    ids: HashMap<String, RefId>,
+    // `None` until the declared definition is defined
+    templates: Vec<Option<Template>>,
+}
+
+impl Refs {
+    /// Number of declared definitions.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn id(&self, name: &str) -> Option<RefId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: RefId) -> &str {
+        &self.names[id as usize]
+    }
+
+    /// The definition `id` expands to, `None` if it was only declared.
+    #[inline]
+    pub fn template(&self, id: RefId) -> Option<&Template> {
+        self.templates[id as usize].as_ref()
+    }
+
+    /// The first declared definition that was never defined.
+    pub fn undefined(&self) -> Option<&str> {
+        self.templates
+            .iter()
+            .position(Option::is_none)
+            .map(|idx| self.names[idx].as_str())
+    }
+}
+
+impl Net {
+    /// The id of the definition `name`, declaring it the first time. A
+    /// declared definition can be referenced before it is defined.
+    pub fn declare_ref(&mut self, name: &str) -> RefId {
+        if let Some(id) = self.refs.id(name) {
+            return id;
+        }
+        let refs = Arc::make_mut(&mut self.refs);
+        let id = refs.names.len() as RefId;
+        refs.names.push(name.to_string());
+        refs.ids.insert(name.to_string(), id);
+        refs.templates.push(None);
+        id
+    }
+
+    /// Define `name` as `template`, declaring it if needed. The template
+    /// must have a single port: the one a reference expands to.
+    pub fn define_ref(&mut self, name: &str, template: Template) -> Result<RefId, AstError> {
+        if template.ports() != 1 {
+            return Err(AstError::RefPorts(name.to_string(), template.ports()));
+        }
+        let id = self.declare_ref(name);
+        Arc::make_mut(&mut self.refs).templates[id as usize] = Some(template);
+        Ok(id)
+    }
+
+    pub fn refs(&self) -> &Refs {
+        &self.refs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        ast::{self, AstError, Term},
+        net::{Net, NetBuilder},
+        parser::{parse, parse_ast},
+        readback::OwnedTerm,
+        runtime::{EvalError, Runtime},
+        template::Template,
+        term::TermPtr,
+    };
+
+    fn id() -> Template {
+        Template::from_ast(&ast::Net {
+            head: vec![Term::lam(Term::var("x"), Term::var("x"))],
+            body: vec![],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_countdown() {
+        // count = λn. switch n { 0 => 0, _ => @step }
+        // step = λp. @count p
+        let mut net = Net::new();
+        let (count, step) = (net.declare_ref("count"), net.declare_ref("step"));
+        let branches = Term::lam(Term::Num(0), Term::Ref("step".to_string()));
+        let count_body = ast::Net {
+            head: vec![Term::lam(Term::var("n"), Term::var("r"))],
+            body: vec![(
+                Term::var("n"),
+                Term::Swi(Box::new(branches), Box::new(Term::var("r"))),
+            )],
+        };
+        let step_body = ast::Net {
+            head: vec![Term::lam(Term::var("p"), Term::var("s"))],
+            body: vec![(
+                Term::Ref("count".to_string()),
+                Term::app(Term::var("p"), Term::var("s")),
+            )],
+        };
+        let template = Template::from_ast_with_refs(&count_body, net.refs()).unwrap();
+        assert_eq!(net.define_ref("count", template), Ok(count));
+        let template = Template::from_ast_with_refs(&step_body, net.refs()).unwrap();
+        assert_eq!(net.define_ref("step", template), Ok(step));
+
+        let r = net.var();
+        let n = net.num(5);
+        let app = net.app(n, r.0);
+        let f = net.reference(count);
+        net.eqn(f, app);
+        net.head(r.1);
+        let mut runtime = Runtime::new();
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(net.normal_form().unwrap(), vec![OwnedTerm::Num(0)]);
+        // `step` is erased instead of expanded once `count` reaches zero
+        assert_eq!(runtime.stats.expand_ref(), 6 + 5);
+        assert!(net.leaks().is_empty(), "{}", net.leaks());
+    }
+
+    #[test]
+    fn test_load_refs() {
+        // `loop` refers to itself, and is only expanded once
+        let mut net = Net::new();
+        assert!(parse(
+            "
+            def main(out r) = @loop ~ [* r] ;
+            def loop((* @loop))
+            ",
+            &mut net
+        ));
+        assert_eq!(net.refs().len(), 1);
+        let mut runtime = Runtime::new();
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(
+            net.head_by_name("r").unwrap().unwrap(),
+            Term::Ref("loop".to_string())
+        );
+        assert_eq!(runtime.stats.expand_ref(), 1);
+
+        let book = parse_ast("def main(r) = @missing ~ r").unwrap();
+        assert_eq!(
+            Net::new().load(&book),
+            Err(AstError::UnknownDef("missing".to_string()))
+        );
+        let book = parse_ast("def main(r) = @pair ~ r ; def pair(*, *)").unwrap();
+        assert_eq!(
+            Net::new().load(&book),
+            Err(AstError::RefPorts("pair".to_string(), 2))
+        );
+    }
+
+    #[test]
+    fn test_copy_ref() {
+        // a reference copied by a dup and erased is never expanded
+        let mut net = Net::new();
+        let f = net.declare_ref("id");
+        let (a, b) = (net.var(), net.var());
+        let dup = net.dup(a.0, b.0);
+        let f = net.reference(f);
+        net.eqn(f, dup);
+        net.eqn(b.1, TermPtr::Era);
+        net.head(a.1);
+        assert_eq!(
+            Runtime::new()
+                .eval(&mut net)
+                .err()
+                .map(|err| err.to_string()),
+            Some(EvalError::UndefinedRef("id".to_string()).to_string())
+        );
+
+        net.define_ref("id", id()).unwrap();
+        let mut runtime = Runtime::new();
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(
+            net.normal_form().unwrap(),
+            vec![Term::Ref("id".to_string())]
+        );
+        assert_eq!(
+            (
+                runtime.stats.comm_ref(),
+                runtime.stats.comm_era_ref(),
+                runtime.stats.expand_ref()
+            ),
+            (1, 1, 0)
+        );
+        assert!(net.leaks().is_empty(), "{}", net.leaks());
+    }
+}
@@ -0,0 +1,1127 @@
+//! A generic interaction net machine, where agents and interaction rules
+//! are data loaded from a rule file instead of the built-in Lam/App/Dup
+//! system:
+//!
+//! ```text
+//! agent Z 0 ;
+//! agent S 1 ;
+//! agent Add 2 ;
+//! rule Add(r, y) >< Z = r ~ y ;
+//! rule Add(r, y) >< S(x) = Add(s, y) ~ x & r ~ S(s) ;
+//! net (r) = Add(r, S(Z)) ~ S(Z) ;
+//! ```
+//!
+//! An `agent` declares a name and its number of auxiliary ports. A `rule`
+//! rewrites two agents connected by their principal ports: the vars of the
+//! left hand side name the auxiliary ports, and the right hand side is a
+//! list of equations wiring them to new agents. A `net` is a program to
+//! reduce, with heads and equations as in a book. Names declared as agents
+//! are agents, any other name is a var.
+//!
+//! Classic specifications in Lafont's notation load with
+//! [`RuleTable::parse_lafont`]: an agent signature gives its arity after a
+//! colon, a rule rewrites a pair into comma separated equations, and a net
+//! is a list of equations whose free vars, in order of first use, are its
+//! interface:
+//!
+//! ```text
+//! Z : 0 ; S : 1 ; Add : 2 ;
+//! Add(r, y) >< Z => r ~ y ;
+//! Add(r, y) >< S(x) => Add(s, y) ~ x, r ~ S(s) ;
+//! Add(r, S(Z)) ~ S(Z) ;
+//! ```
+//!
+//! Reduction is sequential: this machine trades the parallel runtime for
+//! the freedom to define any system.
+
+use std::{collections::HashMap, fmt::Display};
+
+use chumsky::{prelude::*, text::keyword, Parser};
+
+use super::plugin::{self, InteractFn};
+
+pub type AgentId = usize;
+
+/// A term of a rule file, before agent names are resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentTerm {
+    Var(String),
+    Agent(String, Vec<AgentTerm>),
+}
+
+impl Display for AgentTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentTerm::Var(name) => write!(f, "{}", name),
+            AgentTerm::Agent(name, ports) if ports.is_empty() => write!(f, "{}", name),
+            AgentTerm::Agent(name, ports) => {
+                write!(f, "{}(", name)?;
+                for (idx, port) in ports.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", port)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+pub type AgentEqn = (AgentTerm, AgentTerm);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentDef {
+    pub name: String,
+    pub arity: usize,
+}
+
+/// The rewrite of an active pair: the auxiliary ports of both agents, named
+/// by the left hand side, are wired by the equations of the right hand side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub body: Vec<AgentEqn>,
+}
+
+/// A program declared with `net` in a rule file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentProgram {
+    pub head: Vec<AgentTerm>,
+    pub body: Vec<AgentEqn>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleError {
+    Parse(String),
+    DuplicateAgent(String),
+    UnknownAgent(String),
+    Arity {
+        agent: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A left hand side port is not a distinct var.
+    NotAPort(String),
+    DuplicateRule(String, String),
+    /// A var is not used exactly twice in a rule, counting the left hand side.
+    VarUses(String),
+    /// Two agents met, but no rule rewrites them.
+    NoRule(String, String),
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleError::Parse(err) => write!(f, "invalid rule file: {}", err),
+            RuleError::DuplicateAgent(name) => write!(f, "agent `{}` is declared twice", name),
+            RuleError::UnknownAgent(name) => write!(f, "agent `{}` is not declared", name),
+            RuleError::Arity {
+                agent,
+                expected,
+                found,
+            } => write!(
+                f,
+                "agent `{}` has {} ports, found {}",
+                agent, expected, found
+            ),
+            RuleError::NotAPort(term) => {
+                write!(f, "`{}` is not a var in a rule left hand side", term)
+            }
+            RuleError::DuplicateRule(left, right) => {
+                write!(f, "rule `{} >< {}` is defined twice", left, right)
+            }
+            RuleError::VarUses(name) => write!(f, "var `{}` is not used exactly twice", name),
+            RuleError::NoRule(left, right) => write!(f, "no rule for `{} >< {}`", left, right),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// Agents and the rules between them.
+#[derive(Debug, Clone, Default)]
+pub struct RuleTable {
+    agents: Vec<AgentDef>,
+    names: HashMap<String, AgentId>,
+    rules: HashMap<(AgentId, AgentId), Rule>,
+    // rewrites implemented by plugins, see `add_native`
+    natives: HashMap<(AgentId, AgentId), InteractFn>,
+}
+
+enum Item {
+    Agent(AgentDef),
+    Rule(AgentTerm, AgentTerm, Vec<AgentEqn>),
+    Net(AgentProgram),
+    // a net whose heads are its free vars, once agents are resolved
+    FreeNet(Vec<AgentEqn>),
+}
+
+fn parse_term<'a>() -> impl Parser<'a, &'a str, AgentTerm> {
+    return recursive::<'a, &'a str, AgentTerm, extra::Default, _, _>(|term| {
+        text::ident()
+            .padded()
+            .then(
+                term.separated_by(just(',').padded())
+                    .collect::<Vec<_>>()
+                    .delimited_by(just('(').padded(), just(')').padded())
+                    .or_not(),
+            )
+            .map(|(name, ports): (&str, _)| match ports {
+                Some(ports) => AgentTerm::Agent(name.to_string(), ports),
+                None => AgentTerm::Var(name.to_string()),
+            })
+    });
+}
+
+fn parse_eqns<'a>() -> impl Parser<'a, &'a str, Vec<AgentEqn>> {
+    return parse_term()
+        .then_ignore(just('~').padded())
+        .then(parse_term())
+        .separated_by(just('&').padded())
+        .collect();
+}
+
+fn parse_items<'a>() -> impl Parser<'a, &'a str, Vec<Item>> {
+    let agent = keyword("agent")
+        .padded()
+        .ignore_then(text::ident().padded())
+        .then(text::int(10).padded())
+        .map(|(name, arity): (&str, &str)| {
+            Item::Agent(AgentDef {
+                name: name.to_string(),
+                arity: arity.parse().unwrap(),
+            })
+        });
+    let rule = keyword("rule")
+        .padded()
+        .ignore_then(parse_term())
+        .then_ignore(just("><").padded())
+        .then(parse_term())
+        .then_ignore(just('=').padded())
+        .then(parse_eqns())
+        .map(|((left, right), body)| Item::Rule(left, right, body));
+    let net = keyword("net")
+        .padded()
+        .ignore_then(
+            parse_term()
+                .separated_by(just(',').padded())
+                .collect::<Vec<_>>()
+                .delimited_by(just('(').padded(), just(')').padded()),
+        )
+        .then(just('=').padded().ignore_then(parse_eqns()).or_not())
+        .map(|(head, body)| {
+            Item::Net(AgentProgram {
+                head,
+                body: body.unwrap_or_default(),
+            })
+        });
+    return choice((agent, rule, net))
+        .separated_by(just(';').padded())
+        .allow_trailing()
+        .collect();
+}
+
+// the Lafont notation: `Name : arity`, `left >< right => eqns` and `eqns`,
+// with equations separated by commas
+fn parse_lafont_items<'a>() -> impl Parser<'a, &'a str, Vec<Item>> {
+    let eqns = |min| {
+        parse_term()
+            .then_ignore(just('~').padded())
+            .then(parse_term())
+            .separated_by(just(',').padded())
+            .at_least(min)
+            .collect::<Vec<_>>()
+    };
+    let agent = text::ident()
+        .padded()
+        .then_ignore(just(':').padded())
+        .then(text::int(10).padded())
+        .map(|(name, arity): (&str, &str)| {
+            Item::Agent(AgentDef {
+                name: name.to_string(),
+                arity: arity.parse().unwrap(),
+            })
+        });
+    let rule = parse_term()
+        .then_ignore(just("><").padded())
+        .then(parse_term())
+        .then_ignore(just("=>").padded())
+        .then(eqns(0))
+        .map(|((left, right), body)| Item::Rule(left, right, body));
+    let net = eqns(1).map(Item::FreeNet);
+    return choice((agent, rule, net))
+        .separated_by(just(';').padded())
+        .allow_trailing()
+        .collect();
+}
+
+impl RuleTable {
+    /// Load the agents and rules of a rule file, and the programs it
+    /// declares with `net`, with agent names resolved.
+    pub fn parse(src: &str) -> Result<(Self, Vec<AgentProgram>), RuleError> {
+        let mut table = RuleTable::default();
+        let programs = table.load(src)?;
+        Ok((table, programs))
+    }
+
+    /// Like [`Self::parse`], adding to the agents and rules of this table,
+    /// such as the ones registered by a [plugin](super::plugin).
+    pub fn load(&mut self, src: &str) -> Result<Vec<AgentProgram>, RuleError> {
+        let items = parse_items().parse(src.trim()).into_result();
+        self.load_items(items.map_err(parse_error)?)
+    }
+
+    /// Load a specification in Lafont's notation, see the
+    /// [module docs](self), with agent names resolved.
+    pub fn parse_lafont(src: &str) -> Result<(Self, Vec<AgentProgram>), RuleError> {
+        let mut table = RuleTable::default();
+        let programs = table.load_lafont(src)?;
+        Ok((table, programs))
+    }
+
+    /// Like [`Self::parse_lafont`], adding to the agents and rules of this
+    /// table.
+    pub fn load_lafont(&mut self, src: &str) -> Result<Vec<AgentProgram>, RuleError> {
+        let items = parse_lafont_items().parse(src.trim()).into_result();
+        self.load_items(items.map_err(parse_error)?)
+    }
+
+    fn load_items(&mut self, items: Vec<Item>) -> Result<Vec<AgentProgram>, RuleError> {
+        for item in &items {
+            if let Item::Agent(agent) = item {
+                self.add_agent(agent.clone())?;
+            }
+        }
+        let mut programs = Vec::new();
+        for item in items {
+            match item {
+                Item::Agent(_) => {}
+                Item::Rule(left, right, body) => self.add_rule(left, right, body)?,
+                Item::Net(program) => programs.push(AgentProgram {
+                    head: self.resolve_all(program.head)?,
+                    body: self.resolve_eqns(program.body)?,
+                }),
+                Item::FreeNet(body) => {
+                    let body = self.resolve_eqns(body)?;
+                    programs.push(AgentProgram {
+                        head: free_vars(&body),
+                        body,
+                    });
+                }
+            }
+        }
+        Ok(programs)
+    }
+
+    pub fn add_agent(&mut self, agent: AgentDef) -> Result<AgentId, RuleError> {
+        if self.names.contains_key(&agent.name) {
+            return Err(RuleError::DuplicateAgent(agent.name));
+        }
+        self.names.insert(agent.name.clone(), self.agents.len());
+        self.agents.push(agent);
+        Ok(self.agents.len() - 1)
+    }
+
+    /// Add the rule `left >< right = body`. Both sides of the pair must be
+    /// agents whose ports are distinct vars.
+    pub fn add_rule(
+        &mut self,
+        left: AgentTerm,
+        right: AgentTerm,
+        body: Vec<AgentEqn>,
+    ) -> Result<(), RuleError> {
+        let (left_id, left_ports) = self.pattern(left)?;
+        let (right_id, right_ports) = self.pattern(right)?;
+        self.check_new_rule(left_id, right_id)?;
+
+        let body = self.resolve_eqns(body)?;
+        let mut uses: HashMap<&str, usize> = HashMap::new();
+        for port in left_ports.iter().chain(&right_ports) {
+            *uses.entry(port).or_default() += 1;
+        }
+        for (left, right) in &body {
+            count_vars(left, &mut uses);
+            count_vars(right, &mut uses);
+        }
+        if let Some((name, _)) = uses.iter().filter(|(_, count)| **count != 2).min() {
+            return Err(RuleError::VarUses(name.to_string()));
+        }
+
+        self.rules.insert(
+            (left_id, right_id),
+            Rule {
+                left: left_ports,
+                right: right_ports,
+                body,
+            },
+        );
+        Ok(())
+    }
+
+    /// Add a rewrite of `left >< right` implemented by `interact`, which is
+    /// called with the node of the `left` agent first.
+    pub fn add_native(
+        &mut self,
+        left: AgentId,
+        right: AgentId,
+        interact: InteractFn,
+    ) -> Result<(), RuleError> {
+        self.check_new_rule(left, right)?;
+        self.natives.insert((left, right), interact);
+        Ok(())
+    }
+
+    fn check_new_rule(&self, left: AgentId, right: AgentId) -> Result<(), RuleError> {
+        let pairs = [(left, right), (right, left)];
+        if pairs
+            .iter()
+            .any(|pair| self.rules.contains_key(pair) || self.natives.contains_key(pair))
+        {
+            return Err(RuleError::DuplicateRule(
+                self.agents[left].name.clone(),
+                self.agents[right].name.clone(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn agent(&self, id: AgentId) -> &AgentDef {
+        &self.agents[id]
+    }
+
+    pub fn agent_id(&self, name: &str) -> Option<AgentId> {
+        self.names.get(name).copied()
+    }
+
+    pub fn agents(&self) -> usize {
+        self.agents.len()
+    }
+
+    pub fn rules(&self) -> usize {
+        self.rules.len() + self.natives.len()
+    }
+
+    // the rule for an active pair, and whether its sides are swapped
+    fn rule(&self, left: AgentId, right: AgentId) -> Option<(&Rule, bool)> {
+        match self.rules.get(&(left, right)) {
+            Some(rule) => Some((rule, false)),
+            None => self.rules.get(&(right, left)).map(|rule| (rule, true)),
+        }
+    }
+
+    fn native(&self, left: AgentId, right: AgentId) -> Option<(InteractFn, bool)> {
+        match self.natives.get(&(left, right)) {
+            Some(interact) => Some((*interact, false)),
+            None => self
+                .natives
+                .get(&(right, left))
+                .map(|interact| (*interact, true)),
+        }
+    }
+
+    fn pattern(&self, term: AgentTerm) -> Result<(AgentId, Vec<String>), RuleError> {
+        let (name, ports) = match self.resolve(term)? {
+            AgentTerm::Agent(name, ports) => (name, ports),
+            AgentTerm::Var(name) => return Err(RuleError::UnknownAgent(name)),
+        };
+        let ports = ports
+            .into_iter()
+            .map(|port| match port {
+                AgentTerm::Var(name) => Ok(name),
+                agent => Err(RuleError::NotAPort(agent.to_string())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok((self.names[&name], ports))
+    }
+
+    // bare names of declared agents are nullary agents; arities are checked
+    fn resolve(&self, term: AgentTerm) -> Result<AgentTerm, RuleError> {
+        match term {
+            AgentTerm::Var(name) => match self.names.get(&name) {
+                Some(_) => self.resolve(AgentTerm::Agent(name, Vec::new())),
+                None => Ok(AgentTerm::Var(name)),
+            },
+            AgentTerm::Agent(name, ports) => {
+                let Some(id) = self.names.get(&name) else {
+                    return Err(RuleError::UnknownAgent(name));
+                };
+                let expected = self.agents[*id].arity;
+                if ports.len() != expected {
+                    return Err(RuleError::Arity {
+                        agent: name,
+                        expected,
+                        found: ports.len(),
+                    });
+                }
+                Ok(AgentTerm::Agent(name, self.resolve_all(ports)?))
+            }
+        }
+    }
+
+    fn resolve_all(&self, terms: Vec<AgentTerm>) -> Result<Vec<AgentTerm>, RuleError> {
+        terms.into_iter().map(|term| self.resolve(term)).collect()
+    }
+
+    fn resolve_eqns(&self, eqns: Vec<AgentEqn>) -> Result<Vec<AgentEqn>, RuleError> {
+        eqns.into_iter()
+            .map(|(left, right)| Ok((self.resolve(left)?, self.resolve(right)?)))
+            .collect()
+    }
+}
+
+fn parse_error<E: Display>(errs: Vec<E>) -> RuleError {
+    RuleError::Parse(
+        errs.iter()
+            .map(|err| err.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+    )
+}
+
+// the vars used once in `eqns`, in order of first use
+fn free_vars(eqns: &[AgentEqn]) -> Vec<AgentTerm> {
+    let mut uses = HashMap::new();
+    for (left, right) in eqns {
+        count_vars(left, &mut uses);
+        count_vars(right, &mut uses);
+    }
+    let mut order = Vec::new();
+    eqns.iter()
+        .flat_map(|(left, right)| [left, right])
+        .for_each(|term| first_uses(term, &mut order));
+    order
+        .into_iter()
+        .filter(|name| uses[name] == 1)
+        .map(|name| AgentTerm::Var(name.to_string()))
+        .collect()
+}
+
+fn first_uses<'a>(term: &'a AgentTerm, order: &mut Vec<&'a str>) {
+    match term {
+        AgentTerm::Var(name) if !order.contains(&name.as_str()) => order.push(name),
+        AgentTerm::Var(_) => {}
+        AgentTerm::Agent(_, ports) => ports.iter().for_each(|port| first_uses(port, order)),
+    }
+}
+
+fn count_vars<'a>(term: &'a AgentTerm, uses: &mut HashMap<&'a str, usize>) {
+    match term {
+        AgentTerm::Var(name) => *uses.entry(name).or_default() += 1,
+        AgentTerm::Agent(_, ports) => ports.iter().for_each(|port| count_vars(port, uses)),
+    }
+}
+
+/// One end of a wire: a port of a node (0 is the principal port) or a head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Port {
+    Node(usize, usize),
+    Head(usize),
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    agent: AgentId,
+    // where each port is wired to
+    ports: Vec<Port>,
+}
+
+// the other end of a var seen once
+enum Pending<P> {
+    Port(P),
+    // wired straight to another var, whose other end is not known yet
+    Var(String),
+}
+
+/// Wires terms to ports, either in an [`AgentNet`] or as generated code.
+trait Wiring {
+    type Port: Copy;
+
+    fn table(&self) -> &RuleTable;
+
+    // allocate an agent, returning its ports with the principal one first
+    fn alloc(&mut self, agent: AgentId, arity: usize) -> Vec<Self::Port>;
+
+    fn link(&mut self, left: Self::Port, right: Self::Port);
+
+    // allocate the agent and its children, returning its principal port
+    fn node(
+        &mut self,
+        term: &AgentTerm,
+        pending: &mut HashMap<String, Pending<Self::Port>>,
+    ) -> Self::Port {
+        let AgentTerm::Agent(name, children) = term else {
+            unreachable!("vars have no node");
+        };
+        let agent = self.table().names[name];
+        let ports = self.alloc(agent, children.len());
+        for (child, port) in children.iter().zip(&ports[1..]) {
+            self.place(child, *port, pending);
+        }
+        ports[0]
+    }
+
+    // wire `term` to `at`
+    fn place(
+        &mut self,
+        term: &AgentTerm,
+        at: Self::Port,
+        pending: &mut HashMap<String, Pending<Self::Port>>,
+    ) {
+        match term {
+            AgentTerm::Agent(_, _) => {
+                let port = self.node(term, pending);
+                self.link(port, at);
+            }
+            AgentTerm::Var(name) => match pending.remove(name) {
+                Some(Pending::Port(other)) => self.link(other, at),
+                Some(Pending::Var(other)) => {
+                    pending.insert(other, Pending::Port(at));
+                }
+                None => {
+                    pending.insert(name.clone(), Pending::Port(at));
+                }
+            },
+        }
+    }
+
+    fn eqn(
+        &mut self,
+        left: &AgentTerm,
+        right: &AgentTerm,
+        pending: &mut HashMap<String, Pending<Self::Port>>,
+    ) {
+        match (left, right) {
+            (AgentTerm::Agent(_, _), _) => {
+                let port = self.node(left, pending);
+                self.place(right, port, pending);
+            }
+            (_, AgentTerm::Agent(_, _)) => self.eqn(right, left, pending),
+            (AgentTerm::Var(left), AgentTerm::Var(right)) => self.eqn_vars(left, right, pending),
+        }
+    }
+
+    fn eqn_vars(
+        &mut self,
+        left: &str,
+        right: &str,
+        pending: &mut HashMap<String, Pending<Self::Port>>,
+    ) {
+        match pending.remove(left) {
+            Some(Pending::Port(port)) => {
+                self.place(&AgentTerm::Var(right.to_string()), port, pending)
+            }
+            // `left` was wired to `other`: `right` takes its place
+            Some(Pending::Var(other)) => {
+                pending.remove(&other);
+                self.eqn_vars(&other, right, pending)
+            }
+            None => match pending.remove(right) {
+                Some(Pending::Port(port)) => {
+                    self.place(&AgentTerm::Var(left.to_string()), port, pending)
+                }
+                Some(Pending::Var(other)) => {
+                    pending.remove(&other);
+                    self.eqn_vars(left, &other, pending)
+                }
+                None => {
+                    pending.insert(left.to_string(), Pending::Var(right.to_string()));
+                    pending.insert(right.to_string(), Pending::Var(left.to_string()));
+                }
+            },
+        }
+    }
+}
+
+/// Specialized rewrites generated by [`RuleTable::codegen`]. Returns false
+/// for the pairs it does not handle, which are left to the rule table.
+pub type CompiledRules = fn(&mut AgentNet, usize, usize) -> bool;
+
+/// A net of agents from a [`RuleTable`], reduced one active pair at a time.
+pub struct AgentNet<'t> {
+    table: &'t RuleTable,
+    nodes: Vec<Option<Node>>,
+    heads: Vec<Port>,
+    active: Vec<(usize, usize)>,
+    /// Rewrites applied so far.
+    pub interactions: usize,
+}
+
+impl<'t> Wiring for AgentNet<'t> {
+    type Port = Port;
+
+    fn table(&self) -> &RuleTable {
+        self.table
+    }
+
+    fn alloc(&mut self, agent: AgentId, arity: usize) -> Vec<Port> {
+        let node = self.alloc_node(agent, arity);
+        (0..=arity).map(|port| Port::Node(node, port)).collect()
+    }
+
+    fn link(&mut self, left: Port, right: Port) {
+        AgentNet::link(self, left, right)
+    }
+}
+
+impl<'t> AgentNet<'t> {
+    pub fn new(table: &'t RuleTable, program: &AgentProgram) -> Self {
+        let mut net = AgentNet {
+            table,
+            nodes: Vec::new(),
+            heads: vec![Port::Head(0); program.head.len()],
+            active: Vec::new(),
+            interactions: 0,
+        };
+        let mut pending = HashMap::new();
+        for (idx, head) in program.head.iter().enumerate() {
+            net.place(head, Port::Head(idx), &mut pending);
+        }
+        for (left, right) in &program.body {
+            net.eqn(left, right, &mut pending);
+        }
+        net
+    }
+
+    /// Apply rules until no active pair is left, returning the number of
+    /// rewrites.
+    pub fn reduce(&mut self) -> Result<usize, RuleError> {
+        let start = self.interactions;
+        while let Some((left, right)) = self.active.pop() {
+            if self.is_active(left, right) {
+                self.interact(left, right)?;
+            }
+        }
+        Ok(self.interactions - start)
+    }
+
+    /// Like [`Self::reduce`], stopping after at most `max` rewrites for nets
+    /// that might not terminate. Returns whether no active pair is left.
+    pub fn reduce_bounded(&mut self, max: usize) -> Result<bool, RuleError> {
+        let end = self.interactions + max;
+        while let Some((left, right)) = self.active.pop() {
+            if !self.is_active(left, right) {
+                continue;
+            }
+            if self.interactions == end {
+                self.active.push((left, right));
+                return Ok(false);
+            }
+            self.interact(left, right)?;
+        }
+        Ok(true)
+    }
+
+    /// Like [`Self::reduce`], trying the `compiled` rewrites first.
+    pub fn reduce_with(&mut self, compiled: CompiledRules) -> Result<usize, RuleError> {
+        let start = self.interactions;
+        while let Some((left, right)) = self.active.pop() {
+            if !self.is_active(left, right) {
+                continue;
+            }
+            if compiled(self, left, right) {
+                self.interactions += 1;
+            } else {
+                self.interact(left, right)?;
+            }
+        }
+        Ok(self.interactions - start)
+    }
+
+    /// Read each head back as a term. Wires between auxiliary ports are
+    /// named `x<n>` in order of discovery.
+    pub fn read(&self) -> Vec<AgentTerm> {
+        let mut names = HashMap::new();
+        (0..self.heads.len())
+            .map(|idx| self.read_port(Port::Head(idx), &mut names, &mut Vec::new()))
+            .collect()
+    }
+
+    fn read_port(
+        &self,
+        from: Port,
+        names: &mut HashMap<(Port, Port), String>,
+        path: &mut Vec<usize>,
+    ) -> AgentTerm {
+        let to = self.target(from);
+        match to {
+            Port::Node(node, 0) if !path.contains(&node) => {
+                let agent = self.nodes[node].as_ref().unwrap();
+                path.push(node);
+                let ports = (1..agent.ports.len())
+                    .map(|port| self.read_port(Port::Node(node, port), names, path))
+                    .collect();
+                path.pop();
+                AgentTerm::Agent(self.table.agent(agent.agent).name.clone(), ports)
+            }
+            _ => {
+                let wire = (from.min(to), from.max(to));
+                let next = names.len();
+                AgentTerm::Var(names.entry(wire).or_insert(format!("x{}", next)).clone())
+            }
+        }
+    }
+
+    pub fn table(&self) -> &'t RuleTable {
+        self.table
+    }
+
+    pub fn agent(&self, node: usize) -> AgentId {
+        self.nodes[node].as_ref().unwrap().agent
+    }
+
+    /// Whether `port` is a head or a port of a node not removed yet.
+    pub fn has_port(&self, port: Port) -> bool {
+        match port {
+            Port::Node(node, port) => self
+                .nodes
+                .get(node)
+                .and_then(Option::as_ref)
+                .is_some_and(|node| port < node.ports.len()),
+            Port::Head(idx) => idx < self.heads.len(),
+        }
+    }
+
+    /// The port wired to `port`.
+    pub fn target(&self, port: Port) -> Port {
+        match port {
+            Port::Node(node, port) => self.nodes[node].as_ref().unwrap().ports[port],
+            Port::Head(idx) => self.heads[idx],
+        }
+    }
+
+    fn set_target(&mut self, port: Port, target: Port) {
+        match port {
+            Port::Node(node, port) => self.nodes[node].as_mut().unwrap().ports[port] = target,
+            Port::Head(idx) => self.heads[idx] = target,
+        }
+    }
+
+    /// Add an agent with all its ports unwired.
+    pub fn alloc_node(&mut self, agent: AgentId, arity: usize) -> usize {
+        let node = self.nodes.len();
+        self.nodes.push(Some(Node {
+            agent,
+            ports: (0..=arity).map(|port| Port::Node(node, port)).collect(),
+        }));
+        node
+    }
+
+    pub fn remove(&mut self, node: usize) {
+        self.nodes[node] = None;
+    }
+
+    /// Wire two ports, recording an active pair when both are principal.
+    pub fn link(&mut self, left: Port, right: Port) {
+        self.set_target(left, right);
+        self.set_target(right, left);
+        if let (Port::Node(left, 0), Port::Node(right, 0)) = (left, right) {
+            self.active.push((left, right));
+        }
+    }
+
+    // the pair may have been rewired since it was found
+    fn is_active(&self, left: usize, right: usize) -> bool {
+        self.nodes[left].is_some()
+            && self.nodes[right].is_some()
+            && self.target(Port::Node(left, 0)) == Port::Node(right, 0)
+    }
+
+    fn interact(&mut self, left: usize, right: usize) -> Result<(), RuleError> {
+        let (left_agent, right_agent) = (self.agent(left), self.agent(right));
+        if let Some((interact, swapped)) = self.table.native(left_agent, right_agent) {
+            let (left, right) = if swapped {
+                (right, left)
+            } else {
+                (left, right)
+            };
+            self.interactions += 1;
+            plugin::interact(self, interact, left, right);
+            return Ok(());
+        }
+        let Some((rule, swapped)) = self.table.rule(left_agent, right_agent) else {
+            return Err(RuleError::NoRule(
+                self.table.agent(left_agent).name.clone(),
+                self.table.agent(right_agent).name.clone(),
+            ));
+        };
+        let (left, right) = if swapped {
+            (right, left)
+        } else {
+            (left, right)
+        };
+        self.interactions += 1;
+
+        // the rule vars start out wired to what the aux ports were wired to
+        let mut interface: HashMap<Port, &str> = HashMap::new();
+        for (node, names) in [(left, &rule.left), (right, &rule.right)] {
+            for (idx, name) in names.iter().enumerate() {
+                interface.insert(Port::Node(node, idx + 1), name);
+            }
+        }
+        let mut pending = HashMap::new();
+        for (port, name) in &interface {
+            let target = self.target(*port);
+            let value = match interface.get(&target) {
+                // aux ports of the pair wired to each other
+                Some(other) => Pending::Var(other.to_string()),
+                None => Pending::Port(target),
+            };
+            pending.insert(name.to_string(), value);
+        }
+        self.remove(left);
+        self.remove(right);
+        for (left, right) in &rule.body {
+            self.eqn(left, right, &mut pending);
+        }
+        Ok(())
+    }
+}
+
+// ------------------- CODEGEN --------------------------
+
+#[derive(Debug, Clone, Copy)]
+enum Sym {
+    // the port an aux port of the pair was wired to
+    Target(usize),
+    Node(usize, usize),
+}
+
+impl Display for Sym {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sym::Target(idx) => write!(f, "t{}", idx),
+            Sym::Node(node, port) => write!(f, "Port::Node(n{}, {})", node, port),
+        }
+    }
+}
+
+/// Runs the rule wiring once, writing down the calls it makes.
+struct Emitter<'a> {
+    table: &'a RuleTable,
+    nodes: usize,
+    out: String,
+}
+
+impl<'a> Wiring for Emitter<'a> {
+    type Port = Sym;
+
+    fn table(&self) -> &RuleTable {
+        self.table
+    }
+
+    fn alloc(&mut self, agent: AgentId, arity: usize) -> Vec<Sym> {
+        let node = self.nodes;
+        self.nodes += 1;
+        self.out.push_str(&format!(
+            "    let n{} = net.alloc_node({}, {}); // {}\n",
+            node,
+            agent,
+            arity,
+            self.table.agent(agent).name
+        ));
+        (0..=arity).map(|port| Sym::Node(node, port)).collect()
+    }
+
+    fn link(&mut self, left: Sym, right: Sym) {
+        self.out
+            .push_str(&format!("    net.link({}, {});\n", left, right));
+    }
+}
+
+impl RuleTable {
+    /// Generate Rust source for a [`CompiledRules`] function named
+    /// `interact`, with one specialized rewrite per rule, to be written by a
+    /// build script and `include!`d. `path` is the module path of this
+    /// module in the including crate. The generated code refers to agents by
+    /// id, so it only works with nets of this table.
+    pub fn codegen(&self, path: &str) -> String {
+        let mut pairs: Vec<_> = self.rules.keys().copied().collect();
+        pairs.sort();
+
+        let mut out = String::new();
+        out.push_str("// Generated by RuleTable::codegen, do not edit.\n\n");
+        out.push_str(&format!("use {}::{{AgentNet, Port}};\n\n", path));
+        out.push_str("pub fn interact(net: &mut AgentNet, left: usize, right: usize) -> bool {\n");
+        out.push_str("    match (net.agent(left), net.agent(right)) {\n");
+        for (idx, (left, right)) in pairs.iter().enumerate() {
+            out.push_str(&format!(
+                "        ({}, {}) => rule_{}(net, left, right),\n",
+                left, right, idx
+            ));
+            if left != right {
+                out.push_str(&format!(
+                    "        ({}, {}) => rule_{}(net, right, left),\n",
+                    right, left, idx
+                ));
+            }
+        }
+        out.push_str("        _ => false,\n    }\n}\n");
+
+        for (idx, pair) in pairs.iter().enumerate() {
+            out.push_str(&self.codegen_rule(idx, *pair));
+        }
+        out
+    }
+
+    fn codegen_rule(&self, idx: usize, pair: (AgentId, AgentId)) -> String {
+        let rule = &self.rules[&pair];
+        let mut out = format!(
+            "\n// {} >< {}\nfn rule_{}(net: &mut AgentNet, left: usize, right: usize) -> bool {{\n",
+            self.agent(pair.0).name,
+            self.agent(pair.1).name,
+            idx
+        );
+        let mut pending = HashMap::new();
+        let mut targets = Vec::new();
+        for (node, names) in [("left", &rule.left), ("right", &rule.right)] {
+            for (port, name) in names.iter().enumerate() {
+                out.push_str(&format!(
+                    "    let t{} = net.target(Port::Node({}, {}));\n",
+                    targets.len(),
+                    node,
+                    port + 1
+                ));
+                pending.insert(name.clone(), Pending::Port(Sym::Target(targets.len())));
+                targets.push(format!("t{}", targets.len()));
+            }
+        }
+        if !targets.is_empty() {
+            out.push_str(&format!(
+                "    // aux ports wired to each other are left to the rule table\n    if [{}]\n        .iter()\n        .any(|t| matches!(t, Port::Node(n, _) if *n == left || *n == right))\n    {{\n        return false;\n    }}\n",
+                targets.join(", ")
+            ));
+        }
+        out.push_str("    net.remove(left);\n    net.remove(right);\n");
+
+        let mut emitter = Emitter {
+            table: self,
+            nodes: 0,
+            out,
+        };
+        for (left, right) in &rule.body {
+            emitter.eqn(left, right, &mut pending);
+        }
+        emitter.out.push_str("    true\n}\n");
+        emitter.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::rules::{AgentNet, RuleError, RuleTable};
+
+    mod nat {
+        include!("rules/nat.rs");
+    }
+
+    const NAT: &str = "
+        agent Z 0 ;
+        agent S 1 ;
+        agent Add 2 ;
+        rule Add(r, y) >< Z = r ~ y ;
+        rule Add(r, y) >< S(x) = Add(s, y) ~ x & r ~ S(s) ;
+    ";
+
+    #[test]
+    fn test_add() {
+        let src = format!("{} net (r) = Add(r, S(S(Z))) ~ S(S(S(Z))) ;", NAT);
+        let (table, programs) = RuleTable::parse(&src).unwrap();
+        assert_eq!(table.rules(), 2);
+
+        let mut net = AgentNet::new(&table, &programs[0]);
+        assert_eq!(net.reduce().unwrap(), 4);
+        assert_eq!(net.read()[0].to_string(), "S(S(S(S(S(Z)))))");
+
+        let mut net = AgentNet::new(&table, &programs[0]);
+        assert_eq!(net.reduce_bounded(3), Ok(false));
+        assert_eq!(net.reduce_bounded(1), Ok(true));
+        assert_eq!(net.interactions, 4);
+        assert_eq!(net.read()[0].to_string(), "S(S(S(S(S(Z)))))");
+    }
+
+    #[test]
+    fn test_codegen() {
+        let src = format!("{} net (r) = Add(r, S(S(Z))) ~ S(S(S(Z))) ;", NAT);
+        let (table, programs) = RuleTable::parse(&src).unwrap();
+        // regenerate with: table.codegen("crate::strandal::rules")
+        assert_eq!(
+            table.codegen("crate::strandal::rules"),
+            include_str!("rules/nat.rs")
+        );
+
+        let mut net = AgentNet::new(&table, &programs[0]);
+        assert_eq!(net.reduce_with(nat::interact).unwrap(), 4);
+        assert_eq!(net.read()[0].to_string(), "S(S(S(S(S(Z)))))");
+    }
+
+    #[test]
+    fn test_free_wires() {
+        let src = format!("{} net (r, a) = Add(r, a) ~ S(Z) ;", NAT);
+        let (table, programs) = RuleTable::parse(&src).unwrap();
+        let mut net = AgentNet::new(&table, &programs[0]);
+        net.reduce().unwrap();
+        let heads: Vec<_> = net.read().iter().map(|head| head.to_string()).collect();
+        assert_eq!(heads, vec!["S(x0)", "x0"]);
+    }
+
+    #[test]
+    fn test_lafont() {
+        let src = "
+            Z : 0 ; S : 1 ; Add : 2 ;
+            Add(r, y) >< Z => r ~ y ;
+            Add(r, y) >< S(x) => Add(s, y) ~ x, r ~ S(s) ;
+            Add(r, S(S(Z))) ~ S(S(S(Z))) ;
+            Add(a, b) ~ S(Z), c ~ Z ;
+        ";
+        let (table, programs) = RuleTable::parse_lafont(src).unwrap();
+        assert_eq!((table.agents(), table.rules()), (3, 2));
+        assert_eq!(programs.len(), 2);
+
+        let mut net = AgentNet::new(&table, &programs[0]);
+        assert_eq!(net.reduce().unwrap(), 4);
+        assert_eq!(net.read()[0].to_string(), "S(S(S(S(S(Z)))))");
+        // the free vars are the heads, in order of first use
+        assert_eq!(
+            programs[1]
+                .head
+                .iter()
+                .map(|head| head.to_string())
+                .collect::<Vec<_>>(),
+            vec!["a", "b", "c"]
+        );
+
+        assert!(matches!(
+            RuleTable::parse_lafont("Z : 0 ; Z >< Z"),
+            Err(RuleError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_rule_errors() {
+        let error = |src: &str| RuleTable::parse(src).err().unwrap();
+        assert_eq!(
+            error("agent Z 0 ; rule Z >< Z = x ~ Z"),
+            RuleError::VarUses("x".to_string())
+        );
+        assert_eq!(
+            error("agent S 1 ; rule S(x) >< S = x ~ x"),
+            RuleError::Arity {
+                agent: "S".to_string(),
+                expected: 1,
+                found: 0
+            }
+        );
+        assert_eq!(
+            error("agent Z 0 ; rule Z >< Y = "),
+            RuleError::UnknownAgent("Y".to_string())
+        );
+
+        let (table, programs) = RuleTable::parse("agent Z 0 ; net () = Z ~ Z").unwrap();
+        assert_eq!(
+            AgentNet::new(&table, &programs[0]).reduce(),
+            Err(RuleError::NoRule("Z".to_string(), "Z".to_string()))
+        );
+    }
+}
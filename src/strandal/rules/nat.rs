@@ -0,0 +1,53 @@
+// Generated by RuleTable::codegen, do not edit.
+
+use crate::strandal::rules::{AgentNet, Port};
+
+pub fn interact(net: &mut AgentNet, left: usize, right: usize) -> bool {
+    match (net.agent(left), net.agent(right)) {
+        (2, 0) => rule_0(net, left, right),
+        (0, 2) => rule_0(net, right, left),
+        (2, 1) => rule_1(net, left, right),
+        (1, 2) => rule_1(net, right, left),
+        _ => false,
+    }
+}
+
+// Add >< Z
+fn rule_0(net: &mut AgentNet, left: usize, right: usize) -> bool {
+    let t0 = net.target(Port::Node(left, 1));
+    let t1 = net.target(Port::Node(left, 2));
+    // aux ports wired to each other are left to the rule table
+    if [t0, t1]
+        .iter()
+        .any(|t| matches!(t, Port::Node(n, _) if *n == left || *n == right))
+    {
+        return false;
+    }
+    net.remove(left);
+    net.remove(right);
+    net.link(t1, t0);
+    true
+}
+
+// Add >< S
+fn rule_1(net: &mut AgentNet, left: usize, right: usize) -> bool {
+    let t0 = net.target(Port::Node(left, 1));
+    let t1 = net.target(Port::Node(left, 2));
+    let t2 = net.target(Port::Node(right, 1));
+    // aux ports wired to each other are left to the rule table
+    if [t0, t1, t2]
+        .iter()
+        .any(|t| matches!(t, Port::Node(n, _) if *n == left || *n == right))
+    {
+        return false;
+    }
+    net.remove(left);
+    net.remove(right);
+    let n0 = net.alloc_node(2, 2); // Add
+    net.link(t1, Port::Node(n0, 2));
+    net.link(t2, Port::Node(n0, 0));
+    let n1 = net.alloc_node(1, 1); // S
+    net.link(Port::Node(n0, 1), Port::Node(n1, 1));
+    net.link(t0, Port::Node(n1, 0));
+    true
+}
@@ -1,79 +1,942 @@
-use std::time::Instant;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-use tracing::{debug, info};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use tracing::{debug, info, warn};
 
-use crate::strandal::{display::CellDisplay, display::VarDisplay, stats::Stats, var::VarValue};
+use crate::strandal::{
+    display::CellDisplay, display::TermDisplay, display::VarDisplay, stats::Stats, var::VarValue,
+};
 
 use super::{
-    net::Net,
-    stats::{GlobalStats, LocalStats},
-    store::{FreePtrs, Ptr, Store},
-    term::{Cell, Term, TermPtr},
+    affinity::{self, Affinity},
+    debugger::{Agent, Debugger},
+    divergence::{DivergenceConfig, DivergenceDetector, DivergenceWarning},
+    intern::Interner,
+    logging::{JsonLog, Rule, RuleFilter, Sampler},
+    memo::{Memo, MEMO_MAX_CELLS},
+    net::{Equation, EquationKind, HeadCallback, Net},
+    num::Op,
+    refs::{RefId, Refs},
+    schedule::{Deferred, Overflow, Seeded, Spawner},
+    stats::{Concurrency, DefinitionStats, GlobalStats, LocalStats, PhaseTimes, TaskCounter},
+    store::{self, FreePtrs, Index, Ptr, Store},
+    taint::{self, Tainter, TraceEvent},
+    template::Template,
+    term::{Cell, Label, Term, TermPtr, TermRef},
+    timeline::{self, TaskEvent, Timeline},
     var::Var,
 };
 
+// log a rewrite at debug level, unless the runtime filters out its rule
+macro_rules! debug_rule {
+    ($runtime:expr, $rule:expr, $($arg:tt)+) => {
+        if $runtime.logs($rule) {
+            debug!($($arg)+);
+        }
+    };
+}
+
+/// How a task shares its free pointers with the subtasks it spawns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SplitPolicy {
+    /// Share the free pointers evenly between the task and its subtasks.
+    #[default]
+    Even,
+    /// Give each subtask up to this many free pointers and keep the rest.
+    Fixed(usize),
+    /// Keep every free pointer: subtasks allocate from the store.
+    Keep,
+}
+
+impl SplitPolicy {
+    /// How many of `available` free pointers go to the next spawned subtask,
+    /// when `ways` tasks (including the current one) still share them.
+    pub fn share(&self, available: usize, ways: u8) -> usize {
+        match self {
+            SplitPolicy::Even => available / ways as usize,
+            SplitPolicy::Fixed(count) => available.min(*count),
+            SplitPolicy::Keep => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeConfig {
+    /// Abort evaluation once the store holds more than this many live slots
+    /// (cells and vars).
+    pub max_cells: Option<Index>,
+    /// Abort evaluation after this many interactions, for nets that may
+    /// never reach a normal form without growing.
+    pub max_interactions: Option<usize>,
+    /// Log a [`LeakReport`](super::leaks::LeakReport) after evaluation.
+    pub detect_leaks: bool,
+    /// Log the [`Fragmentation`](super::store::Fragmentation) of the store
+    /// after evaluation.
+    pub report_fragmentation: bool,
+    pub split_policy: SplitPolicy,
+    /// Reduce small closed redexes once and splice their memoized reduced
+    /// form wherever the same redex shows up again.
+    pub memoize: bool,
+    /// Count rewrites per definition, see [`DefinitionStats`].
+    pub attribute_definitions: bool,
+    /// Run one task at a time, in an order drawn from this seed instead of
+    /// rayon's: the same seed replays the same interleaving.
+    pub seed: Option<u64>,
+    /// Record when each task was spawned, started and ended, see [`Timeline`].
+    pub record_timeline: bool,
+    /// Also time the frees and stat merges that end every task, see
+    /// [`PhaseTimes::teardown`].
+    pub time_phases: bool,
+    /// Count the queued and in-flight tasks, see [`Concurrency`].
+    pub track_concurrency: bool,
+    /// Only log these rules at debug level, and none of the other events.
+    pub trace_rules: Option<RuleFilter>,
+    /// Only log every Nth rewrite of each rule at debug level, see
+    /// [`Sampler`].
+    pub log_every: Option<usize>,
+    /// Also write every rewrite to this log, whatever the rules logged at
+    /// debug level, see [`JsonLog`].
+    pub json_log: Option<Arc<JsonLog>>,
+    /// Warn when the eval looks divergent, see [`DivergenceConfig`].
+    pub detect_divergence: Option<DivergenceConfig>,
+    /// Evaluate on a pool of this many threads owned by the runtime and
+    /// kept across evals, instead of rayon's global pool.
+    pub threads: Option<usize>,
+    /// Run a task right away on the thread that would have spawned it while
+    /// fewer than this many tasks per thread are pending, see
+    /// [`Runtime::inlined_tasks`].
+    pub sequential_below: Option<usize>,
+    /// Spawn at most this many tasks at once, deferring the others to the
+    /// tasks already spawned, see [`Runtime::deferred_tasks`].
+    pub max_tasks: Option<usize>,
+    /// Pin the threads of a pool owned by the runtime to cores, one thread
+    /// per core unless [`RuntimeConfig::threads`] says otherwise.
+    pub affinity: Option<Affinity>,
+}
+
+impl RuntimeConfig {
+    pub fn detect_leaks(mut self) -> Self {
+        self.detect_leaks = true;
+        self
+    }
+
+    pub fn report_fragmentation(mut self) -> Self {
+        self.report_fragmentation = true;
+        self
+    }
+
+    pub fn attribute_definitions(mut self) -> Self {
+        self.attribute_definitions = true;
+        self
+    }
+
+    pub fn record_timeline(mut self) -> Self {
+        self.record_timeline = true;
+        self
+    }
+
+    pub fn time_phases(mut self) -> Self {
+        self.time_phases = true;
+        self
+    }
+
+    pub fn track_concurrency(mut self) -> Self {
+        self.track_concurrency = true;
+        self
+    }
+
+    /// Only log the rules named as in [`Rule::name`], such as
+    /// `&["comm_app_dup", "bind"]`.
+    ///
+    /// # Panics
+    ///
+    /// If a name is not the name of a rule.
+    pub fn trace_rules(mut self, rules: &[&str]) -> Self {
+        let filter = rules
+            .iter()
+            .map(|name| name.parse::<Rule>())
+            .collect::<Result<RuleFilter, _>>();
+        self.trace_rules = Some(filter.unwrap_or_else(|err| panic!("{}", err)));
+        self
+    }
+
+    /// Only log every `every`th rewrite of each rule.
+    pub fn sample_logs(mut self, every: usize) -> Self {
+        self.log_every = Some(every);
+        self
+    }
+
+    /// Also write every rewrite to `log`, one JSON object per line.
+    pub fn json_log(mut self, log: Arc<JsonLog>) -> Self {
+        self.json_log = Some(log);
+        self
+    }
+
+    pub fn detect_divergence(mut self, config: DivergenceConfig) -> Self {
+        self.detect_divergence = Some(config);
+        self
+    }
+
+    pub fn seeded(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn memoize(mut self) -> Self {
+        self.memoize = true;
+        self
+    }
+
+    pub fn max_cells(mut self, max_cells: Index) -> Self {
+        self.max_cells = Some(max_cells);
+        self
+    }
+
+    pub fn max_interactions(mut self, max_interactions: usize) -> Self {
+        self.max_interactions = Some(max_interactions);
+        self
+    }
+
+    pub fn split_policy(mut self, split_policy: SplitPolicy) -> Self {
+        self.split_policy = split_policy;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    pub fn sequential_below(mut self, tasks_per_thread: usize) -> Self {
+        self.sequential_below = Some(tasks_per_thread);
+        self
+    }
+
+    pub fn max_tasks(mut self, max_tasks: usize) -> Self {
+        self.max_tasks = Some(max_tasks);
+        self
+    }
+
+    pub fn affinity(mut self, affinity: Affinity) -> Self {
+        self.affinity = Some(affinity);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// The net outgrew `RuntimeConfig::max_cells`.
+    MaxCellsExceeded {
+        max_cells: Index,
+        live: Index,
+        /// Counts of the rules that grow the net: commutations and expansions,
+        /// largest first.
+        growth: Vec<(&'static str, usize)>,
+        alloc_cells: usize,
+        alloc_vars: usize,
+    },
+    /// The net did not reach a normal form within
+    /// `RuntimeConfig::max_interactions`.
+    MaxInteractionsExceeded(usize),
+    /// The eval was stopped by `DivergenceConfig::abort`.
+    LikelyDivergent(DivergenceWarning),
+    /// The net references a definition that was declared but not defined.
+    UndefinedRef(String),
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::MaxCellsExceeded {
+                max_cells,
+                live,
+                growth,
+                alloc_cells,
+                alloc_vars,
+            } => {
+                write!(
+                    f,
+                    "net exceeded {} live cells ({} live), growth driven by ",
+                    max_cells, live
+                )?;
+                for (idx, (rule, count)) in growth.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", rule, count)?;
+                }
+                write!(f, " (allocated {} cells, {} vars)", alloc_cells, alloc_vars)
+            }
+            EvalError::MaxInteractionsExceeded(max_interactions) => write!(
+                f,
+                "net did not reach a normal form within {} interactions",
+                max_interactions
+            ),
+            EvalError::LikelyDivergent(warning) => write!(f, "{}", warning),
+            EvalError::UndefinedRef(name) => write!(f, "definition `{}` is not defined", name),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// What [`Runtime::eval_all`] found evaluating one of its nets.
+pub struct EvalReport {
+    pub result: Result<(), EvalError>,
+    pub stats: GlobalStats,
+    /// Wall time of this net's eval, which overlapped the others.
+    pub elapsed: Duration,
+}
+
 pub struct Runtime {
     pub stats: GlobalStats,
+    definition_stats: Option<DefinitionStats>,
+    tainter: Option<Tainter>,
+    timeline: Option<Timeline>,
+    // the task picked at each step of the last seeded eval
+    schedule: Vec<usize>,
+    config: RuntimeConfig,
+    debugger: Option<Debugger>,
+    memo: Option<Memo>,
+    // the constants of the net being evaluated, if it interned any
+    interner: Option<Arc<Interner>>,
+    // the definitions of the net being evaluated, if it references any
+    refs: Option<Arc<Refs>>,
+    divergence: Option<DivergenceDetector>,
+    // callbacks of the net being evaluated, by head var
+    head_callbacks: HashMap<Ptr, Vec<HeadCallback>>,
+    // set when evaluation must stop early: pending tasks skip their work
+    aborted: AtomicBool,
+    // the interactions of the current eval, only counted when limited
+    interactions: AtomicUsize,
+    elapsed: Duration,
+    phases: PhaseTimes,
+    // time the tasks spent ending, when timing phases
+    task_teardown: AtomicU64,
+    // the tasks of the last eval, when tracking concurrency
+    tasks: Option<TaskCounter>,
+    // the rewrites to log, when sampling them
+    sampler: Option<Sampler>,
+    // the tasks spawned or run inline and not ended yet
+    pending: AtomicUsize,
+    // the tasks of the last eval run inline
+    inlined: AtomicUsize,
+    // the tasks held back, when capped
+    overflow: Option<Overflow>,
+    threads: usize,
+    // the pool of `RuntimeConfig::threads`, built once
+    pool: Option<ThreadPool>,
+    // set during `Runtime::eval_in`, which keeps the slots its tasks free
+    warm: bool,
+    // the slots freed by the tasks of the last warm eval
+    spare: Mutex<Vec<Ptr>>,
+    // the spare slots still free in the store of this eval, handed out
+    // before growing it, and how many were asked for
+    reusable: Vec<Ptr>,
+    reused: AtomicUsize,
 }
 impl Runtime {
     pub fn new() -> Self {
+        Self::with_config(RuntimeConfig::default())
+    }
+
+    /// # Panics
+    ///
+    /// If the thread pool of [`RuntimeConfig::threads`] or
+    /// [`RuntimeConfig::affinity`] cannot be built.
+    pub fn with_config(config: RuntimeConfig) -> Self {
+        let pool = match (config.threads, config.affinity) {
+            (None, None) => None,
+            (Some(threads), None) => Some(ThreadPoolBuilder::new().num_threads(threads)),
+            (threads, Some(affinity)) => {
+                let cores = affinity.cores(threads);
+                Some(
+                    ThreadPoolBuilder::new()
+                        .num_threads(cores.len())
+                        .start_handler(move |idx| affinity::pin(cores[idx])),
+                )
+            }
+        };
+        let pool = pool.map(|pool| pool.build().expect("Could not build the thread pool"));
         Runtime {
             stats: GlobalStats::new(),
+            definition_stats: None,
+            tainter: None,
+            timeline: None,
+            schedule: Vec::new(),
+            memo: config.memoize.then(Memo::new),
+            interner: None,
+            refs: None,
+            divergence: None,
+            config,
+            debugger: None,
+            head_callbacks: HashMap::new(),
+            aborted: AtomicBool::new(false),
+            interactions: AtomicUsize::new(0),
+            elapsed: Duration::ZERO,
+            phases: PhaseTimes::default(),
+            task_teardown: AtomicU64::new(0),
+            tasks: None,
+            pending: AtomicUsize::new(0),
+            inlined: AtomicUsize::new(0),
+            overflow: None,
+            threads: 0,
+            sampler: None,
+            pool,
+            warm: false,
+            spare: Mutex::new(Vec::new()),
+            reusable: Vec::new(),
+            reused: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_debugger(debugger: Debugger) -> Self {
+        Runtime {
+            debugger: Some(debugger),
+            ..Self::new()
+        }
+    }
+
+    /// Reduced forms memoized so far, when memoization is enabled.
+    pub fn memo(&self) -> Option<&Memo> {
+        self.memo.as_ref()
+    }
+
+    /// Rewrites per definition of the last evaluated net, when enabled with
+    /// [`RuntimeConfig::attribute_definitions`].
+    pub fn definition_stats(&self) -> Option<&DefinitionStats> {
+        self.definition_stats.as_ref()
+    }
+
+    /// The rewrites of traced wires in the last eval, in the order they
+    /// happened, see [`Net::trace`].
+    pub fn trace_events(&self) -> Vec<TraceEvent> {
+        self.tainter
+            .as_ref()
+            .map_or_else(Vec::new, |tainter| tainter.events())
+    }
+
+    /// The tasks of the last eval, when enabled with
+    /// [`RuntimeConfig::record_timeline`].
+    pub fn timeline(&self) -> Option<&Timeline> {
+        self.timeline.as_ref()
+    }
+
+    /// The index picked among the pending tasks at each step of the last
+    /// eval, when seeded with [`RuntimeConfig::seeded`].
+    pub fn schedule(&self) -> &[usize] {
+        &self.schedule
+    }
+
+    /// Wall time of the last [`Runtime::eval`].
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// What made the last eval look divergent, when enabled with
+    /// [`RuntimeConfig::detect_divergence`].
+    pub fn divergence_warnings(&self) -> Vec<DivergenceWarning> {
+        self.divergence
+            .as_ref()
+            .map_or_else(Vec::new, DivergenceDetector::warnings)
+    }
+
+    /// Where the last [`Runtime::eval`] spent its time.
+    pub fn phases(&self) -> &PhaseTimes {
+        &self.phases
+    }
+
+    /// How many tasks the last [`Runtime::eval`] kept queued and in flight,
+    /// when enabled with [`RuntimeConfig::track_concurrency`].
+    pub fn concurrency(&self) -> Option<Concurrency> {
+        self.tasks
+            .as_ref()
+            .map(|tasks| tasks.concurrency(self.threads))
+    }
+
+    /// How many tasks of the last [`Runtime::eval`] ran inline, within the
+    /// task that would have spawned them, when enabled with
+    /// [`RuntimeConfig::sequential_below`].
+    ///
+    /// A task runs inline while fewer than `threads × k` tasks are pending,
+    /// counting the ones running inline, which stay pending until they end.
+    /// A small net then reduces as a plain recursion without paying for
+    /// rayon's jobs, and once the recursion nests `threads × k` tasks deep
+    /// the next ones are spawned again. This also bounds the depth of the
+    /// recursion.
+    pub fn inlined_tasks(&self) -> usize {
+        self.inlined.load(Ordering::Relaxed)
+    }
+
+    /// How many slots of the last eval were handed out again after an
+    /// earlier [`Runtime::eval_in`] freed them, instead of growing the store.
+    pub fn reused_slots(&self) -> usize {
+        self.reused.load(Ordering::Relaxed).min(self.reusable.len())
+    }
+
+    /// How many tasks of the last [`Runtime::eval`] were deferred, when
+    /// capped with [`RuntimeConfig::max_tasks`].
+    ///
+    /// Once that many spawned tasks are not ended yet, the next tasks are
+    /// queued instead of spawned, and the task that queued one runs the
+    /// queued tasks left before it ends. A net whose rewrites fan out wide
+    /// then keeps a bounded number of rayon jobs, and a queue of packed
+    /// equations, instead of millions of jobs.
+    pub fn deferred_tasks(&self) -> usize {
+        self.overflow.as_ref().map_or(0, Overflow::total)
+    }
+
+    /// Forget the stats of the evals so far.
+    pub fn reset_stats(&mut self) {
+        self.stats = GlobalStats::new();
+    }
+
+    /// Evaluate `net` as the only eval of this runtime: like
+    /// [`Runtime::eval`] once the stats are reset, so they count this eval
+    /// alone. The thread pool, the memoized forms and the slots the tasks
+    /// free are kept from one eval to the next, so evaluating many small
+    /// nets this way pays for them once: the next eval hands out the slots
+    /// still free in its net's store, such as those of a net evaluated
+    /// again, before growing the store.
+    pub fn eval_in(&mut self, net: &mut Net) -> Result<(), EvalError> {
+        self.reset_stats();
+        self.warm = true;
+        let result = self.eval(net);
+        self.warm = false;
+        result
+    }
+
+    /// Evaluate independent nets at once on the thread pool of this runtime,
+    /// each with a runtime of its own sharing this one's config, and report
+    /// on each net in order. The stats of this runtime are left alone, and
+    /// [`Runtime::elapsed`] is the wall time of the whole batch.
+    pub fn eval_all(&mut self, nets: &mut [Net]) -> Vec<EvalReport> {
+        // the nets share the pool of this runtime
+        let config = RuntimeConfig {
+            threads: None,
+            affinity: None,
+            ..self.config.clone()
+        };
+        let mut runtimes: Vec<_> = nets
+            .iter()
+            .map(|_| Runtime::with_config(config.clone()))
+            .collect();
+        let mut results: Vec<_> = nets.iter().map(|_| Ok(())).collect();
+        let now = Instant::now();
+        let mut eval = || {
+            rayon::scope(|scope| {
+                let evals = nets.iter_mut().zip(&mut runtimes).zip(&mut results);
+                for ((net, runtime), result) in evals {
+                    scope.spawn(move |_| *result = runtime.eval(net));
+                }
+            })
+        };
+        match &self.pool {
+            Some(pool) => pool.install(eval),
+            None => eval(),
         }
+        self.elapsed = now.elapsed();
+        runtimes
+            .into_iter()
+            .zip(results)
+            .map(|(runtime, result)| EvalReport {
+                result,
+                elapsed: runtime.elapsed,
+                stats: runtime.stats,
+            })
+            .collect()
     }
 
     fn free_ptrs<'scope>(&'scope self, store: &'scope Store, free_ptrs: &mut FreePtrs) {
+        // a warm eval keeps them for the next one
+        let mut spare = (self.warm && !free_ptrs.is_empty()).then(|| self.spare.lock().unwrap());
         while let Some(ptr) = free_ptrs.pop() {
             store.free(ptr);
+            if let Some(spare) = spare.as_mut() {
+                spare.push(ptr);
+            }
         }
     }
 
-    pub fn eval(&mut self, net: &mut Net) {
+    #[inline]
+    fn split_free_ptrs(&self, free_ptrs: &mut FreePtrs, ways: u8) -> FreePtrs {
+        let share = self.config.split_policy.share(free_ptrs.len(), ways);
+        free_ptrs.take(share)
+    }
+
+    pub fn eval(&mut self, net: &mut Net) -> Result<(), EvalError> {
+        if let Some(name) = net.refs.undefined() {
+            return Err(EvalError::UndefinedRef(name.to_string()));
+        }
+        if let Some(debugger) = self.debugger.as_mut() {
+            debugger.resolve(&net.definitions, &net.refs);
+        }
+        self.definition_stats = self
+            .config
+            .attribute_definitions
+            .then(|| DefinitionStats::new(&net.definitions));
+        self.tainter = (!net.traced.is_empty()).then(|| Tainter::new(net.traced.drain(..)));
+        self.watch_heads(net);
+        self.interner = (!net.interner.is_empty()).then(|| net.interner.clone());
+        self.refs = (!net.refs.is_empty()).then(|| net.refs.clone());
+        self.timeline = self.config.record_timeline.then(Timeline::new);
+        self.reusable = self.reusable_slots(&net.store);
+        self.reused.store(0, Ordering::Relaxed);
+        self.aborted.store(false, Ordering::Relaxed);
+        self.interactions.store(0, Ordering::Relaxed);
+        self.task_teardown.store(0, Ordering::Relaxed);
+        self.pending.store(0, Ordering::Relaxed);
+        self.inlined.store(0, Ordering::Relaxed);
+        self.overflow = self.config.max_tasks.map(Overflow::new);
+        self.tasks = self.config.track_concurrency.then(TaskCounter::new);
+        self.sampler = self.config.log_every.map(Sampler::new);
+        self.threads = match (self.config.seed, &self.pool) {
+            (Some(_), _) => 1,
+            (None, Some(pool)) => pool.current_num_threads(),
+            (None, None) => rayon::current_num_threads(),
+        };
+        let (allocs, live) = (self.stats.allocs(), net.store.len());
+        // the tasks of this thread only count what they run into
+        store::take_contention();
+        self.divergence = self
+            .config
+            .detect_divergence
+            .clone()
+            .map(|config| DivergenceDetector::new(config, live));
         let now = Instant::now();
-        rayon::scope(|scope| {
-            net.body.drain(..).for_each(|eqn| {
-                // eval this equation
-                self.spawn_eval_equation(scope, &net.store, eqn.left, eqn.right, None);
-            });
-        });
+        let mut setup = Duration::ZERO;
+        self.schedule = match self.config.seed {
+            None => {
+                let mut eval = || {
+                    rayon::scope(|scope| {
+                        net.body.drain(..).for_each(|(eqn, kind)| {
+                            // eval this equation
+                            let scope = Spawner::Rayon(scope);
+                            self.spawn_body_equation(&scope, &net.store, eqn, kind);
+                        });
+                        setup = now.elapsed();
+                        self.run_deferred_root(&Spawner::Rayon(scope), &net.store);
+                    })
+                };
+                match &self.pool {
+                    Some(pool) => pool.install(eval),
+                    None => eval(),
+                }
+                Vec::new()
+            }
+            Some(seed) => {
+                let seeded = Seeded::new(seed);
+                let scope = Spawner::Seeded(&seeded);
+                net.body.drain(..).for_each(|(eqn, kind)| {
+                    self.spawn_body_equation(&scope, &net.store, eqn, kind);
+                });
+                setup = now.elapsed();
+                self.run_deferred_root(&scope, &net.store);
+                seeded.run();
+                seeded.picks()
+            }
+        };
+        self.elapsed = now.elapsed();
+        let after = Instant::now();
+        if self.warm {
+            let unused = self.reusable.len().min(self.reused.load(Ordering::Relaxed));
+            let unused = self.reusable[unused..].iter();
+            self.spare.get_mut().unwrap().extend(unused);
+        }
+        self.phases = PhaseTimes {
+            setup,
+            reduction: self.elapsed.saturating_sub(setup),
+            teardown: Duration::from_nanos(self.task_teardown.load(Ordering::Relaxed)),
+        };
+        // every slot allocated and no longer live was freed
+        let allocated = (self.stats.allocs() - allocs) as i64;
+        let grown = net.store.len() as i64 - live as i64;
+        self.stats
+            .record_eval(self.elapsed, (allocated - grown).max(0) as usize);
         info!(
             "Net evaluated in {:0.0} microseconds",
-            now.elapsed().as_nanos() / 1000
+            self.elapsed.as_nanos() / 1000
         );
+
+        if let (Some(warning), true) = (self.divergence_abort(), self.is_aborted()) {
+            return Err(EvalError::LikelyDivergent(warning));
+        }
+        if let Some(max_interactions) = self.config.max_interactions {
+            if self.interactions.load(Ordering::Relaxed) > max_interactions {
+                return Err(EvalError::MaxInteractionsExceeded(max_interactions));
+            }
+        }
+        if let (Some(max_cells), true) = (self.config.max_cells, self.is_aborted()) {
+            return Err(self.max_cells_exceeded(max_cells, net.store.len()));
+        }
+        if self.config.detect_leaks {
+            let report = net.leaks();
+            if !report.is_empty() {
+                warn!("{}", report);
+            }
+        }
+        if self.config.report_fragmentation {
+            info!("{}", net.store.fragmentation());
+            if net.store.shards() > 1 {
+                for shard in 0..net.store.shards() {
+                    info!("shard {}: {}", shard, net.store.shard_fragmentation(shard));
+                }
+            }
+        }
+        self.phases.teardown += after.elapsed();
+        Ok(())
+    }
+
+    // the spare slots of the last warm eval that are free in `store`, once
+    // each: a slot may have been handed out again since, by a cleared store
+    // or in another net, and freed again
+    fn reusable_slots(&mut self, store: &Store) -> Vec<Ptr> {
+        let spare = std::mem::take(self.spare.get_mut().unwrap());
+        if !self.warm {
+            return Vec::new();
+        }
+        let mut reusable: Vec<_> = spare.into_iter().filter(|ptr| store.is_free(*ptr)).collect();
+        reusable.sort_unstable_by_key(|ptr| ptr.index());
+        reusable.dedup_by_key(|ptr| ptr.index());
+        reusable
+    }
+
+    fn watch_heads(&mut self, net: &mut Net) {
+        self.head_callbacks.clear();
+        for (idx, callback) in net.head_callbacks.drain(..) {
+            let Some(head) = net.head.get(idx) else {
+                warn!("No head {} to watch", idx);
+                continue;
+            };
+            match head {
+                TermPtr::Ptr(ptr) => match net.store.get(*ptr) {
+                    Some(TermRef::Var(var)) => match var.read() {
+                        None | Some(VarValue::Var(_)) => {
+                            self.head_callbacks.entry(*ptr).or_default().push(callback)
+                        }
+                        Some(VarValue::Era) => (callback.0)(&net.store, TermPtr::Era),
+                        Some(VarValue::Cell(cell_ptr)) => {
+                            (callback.0)(&net.store, TermPtr::Ptr(cell_ptr))
+                        }
+                    },
+                    _ => (callback.0)(&net.store, *head),
+                },
+                TermPtr::Era => (callback.0)(&net.store, *head),
+            }
+        }
+    }
+
+    // a bound head var is never read again by the runtime, so it is safe to
+    // read its value here
+    fn head_bound(&self, store: &Store, var_ptr: Ptr) {
+        if self.head_callbacks.is_empty() {
+            return;
+        }
+        if let Some(callbacks) = self.head_callbacks.get(&var_ptr) {
+            let value = match self.get_var(store, var_ptr).read() {
+                Some(VarValue::Era) => TermPtr::Era,
+                Some(VarValue::Cell(cell_ptr)) => TermPtr::Ptr(cell_ptr),
+                _ => return,
+            };
+            for callback in callbacks {
+                (callback.0)(store, value);
+            }
+        }
+    }
+
+    // the counts of the rules that grow the net, largest first
+    fn growth(&self) -> Vec<(&'static str, usize)> {
+        let mut growth = vec![
+            ("APP-DUP", self.stats.comm_app_dup()),
+            ("LAM-DUP", self.stats.comm_lam_dup()),
+            ("DUP-DUP", self.stats.comm_dup_dup()),
+            ("OPS", self.stats.comm_ops()),
+            ("REF", self.stats.expand_ref()),
+        ];
+        growth.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        growth
+    }
+
+    fn max_cells_exceeded(&self, max_cells: Index, live: Index) -> EvalError {
+        EvalError::MaxCellsExceeded {
+            max_cells,
+            live,
+            growth: self.growth(),
+            alloc_cells: self.stats.alloc_cells(),
+            alloc_vars: self.stats.alloc_vars(),
+        }
+    }
+
+    #[inline]
+    fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn count_interaction(&self) {
+        if let Some(max_interactions) = self.config.max_interactions {
+            if self.interactions.fetch_add(1, Ordering::Relaxed) >= max_interactions {
+                self.aborted.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[inline]
+    fn check_capacity(&self, store: &Store) {
+        if let Some(max_cells) = self.config.max_cells {
+            if store.len() > max_cells {
+                self.aborted.store(true, Ordering::Relaxed);
+            }
+        }
+        if let Some(divergence) = self.divergence.as_ref() {
+            let warning = divergence.check_growth(store.len(), || self.growth());
+            self.diverging(divergence, warning);
+        }
+    }
+
+    #[inline]
+    fn record_dup_label(&self, label: Option<Label>) {
+        if let Some(divergence) = self.divergence.as_ref() {
+            let warning = divergence.record_label(label);
+            self.diverging(divergence, warning);
+        }
+    }
+
+    fn diverging(&self, divergence: &DivergenceDetector, warning: Option<DivergenceWarning>) {
+        if let Some(warning) = warning {
+            warn!("{}", warning);
+            if divergence.aborts() {
+                self.aborted.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // the first divergence warning, if it stopped the eval
+    fn divergence_abort(&self) -> Option<DivergenceWarning> {
+        let divergence = self.divergence.as_ref()?;
+        match divergence.aborts() {
+            true => divergence.warnings().into_iter().next(),
+            false => None,
+        }
     }
 
     fn spawn_eval_equation<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         left: TermPtr,
         right: TermPtr,
         free_ptrs: Option<FreePtrs>,
     ) {
-        scope.spawn(move |scope| {
+        self.spawn_equation(scope, store, left, right, None, free_ptrs);
+    }
+
+    // spawn an equation of the body, dispatched on its kind
+    fn spawn_body_equation<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        eqn: Equation,
+        kind: EquationKind,
+    ) {
+        self.spawn_equation(scope, store, eqn.left(), eqn.right(), Some(kind), None);
+    }
+
+    fn spawn_equation<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        left: TermPtr,
+        right: TermPtr,
+        kind: Option<EquationKind>,
+        free_ptrs: Option<FreePtrs>,
+    ) {
+        let Some(inline) = self.launch(scope) else {
+            self.defer(store, Deferred::Equation(Equation::new(left, right)), free_ptrs);
+            return;
+        };
+        let pending = self.debugger.as_ref().map(|debugger| {
+            debugger.push_pending(format!(
+                "{} ~ {}",
+                TermDisplay(store, &left),
+                TermDisplay(store, &right)
+            ))
+        });
+        let spawned = self.timeline.as_ref().map(|timeline| timeline.now());
+        if let Some(tasks) = &self.tasks {
+            tasks.spawned();
+        }
+        // the task carries the packed equation
+        let eqn = Equation::new(left, right);
+        let task = move |scope: &Spawner<'_, 'scope>| {
+            let (left, right) = (eqn.left(), eqn.right());
+            self.start_pending(pending);
+            if let Some(tasks) = &self.tasks {
+                tasks.started();
+            }
+            let task = self.start_task(spawned, || timeline::rule(store, left, right));
             let mut free_ptrs = free_ptrs.unwrap_or_else(|| FreePtrs::new());
             let mut stats = LocalStats::new();
             // eval this equation
-            self.eval_equation(scope, store, left, right, &mut free_ptrs, &mut stats);
+            match kind {
+                Some(kind) => self.eval_kind(scope, store, kind, eqn, &mut free_ptrs, &mut stats),
+                None => self.eval_equation(scope, store, left, right, &mut free_ptrs, &mut stats),
+            }
+            self.run_deferred(scope, store, &mut free_ptrs, &mut stats);
 
-            // free all unused free ptrs
-            self.free_ptrs(store, &mut free_ptrs);
-            // update global stats
-            self.stats.update(stats);
-        })
+            self.finish_task(store, &mut free_ptrs, stats);
+            self.end_task(task);
+            self.task_ended(inline);
+            if let Some(tasks) = &self.tasks {
+                tasks.ended();
+            }
+        };
+        match inline {
+            true => task(scope),
+            false => scope.spawn(task),
+        }
     }
 
     #[inline]
     fn spawn_eval_cell_term<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         cell_ptr: Option<Ptr>,
         cell: Cell,
         term_ptr: TermPtr,
         mut free_ptrs: FreePtrs,
     ) {
-        scope.spawn(move |scope| {
+        let Some(inline) = self.launch(scope) else {
+            let deferred = Deferred::CellTerm(cell_ptr, cell, term_ptr);
+            self.defer(store, deferred, Some(free_ptrs));
+            return;
+        };
+        let pending = self.debugger.as_ref().map(|debugger| {
+            debugger.push_pending(format!(
+                "{} ~ {}",
+                CellDisplay(store, cell_ptr, &cell),
+                TermDisplay(store, &term_ptr)
+            ))
+        });
+        let spawned = self.timeline.as_ref().map(|timeline| timeline.now());
+        if let Some(tasks) = &self.tasks {
+            tasks.spawned();
+        }
+        let mut task = move |scope: &Spawner<'_, 'scope>| {
+            self.start_pending(pending);
+            if let Some(tasks) = &self.tasks {
+                tasks.started();
+            }
+            let task = self.start_task(spawned, || timeline::cell_rule(store, &cell, term_ptr));
             let mut stats = LocalStats::new();
             self.eval_cell_term(
                 scope,
@@ -84,30 +947,332 @@ impl Runtime {
                 &mut free_ptrs,
                 &mut stats,
             );
-            self.stats.update(stats);
+            self.run_deferred(scope, store, &mut free_ptrs, &mut stats);
+            self.finish_task(store, &mut free_ptrs, stats);
+            self.end_task(task);
+            self.task_ended(inline);
+            if let Some(tasks) = &self.tasks {
+                tasks.ended();
+            }
+        };
+        match inline {
+            true => task(scope),
+            false => scope.spawn(task),
+        }
+    }
+
+    // whether the task about to be spawned runs inline, see
+    // `Runtime::inlined_tasks`, or none when it is deferred, see
+    // `Runtime::deferred_tasks`
+    #[inline]
+    fn launch(&self, scope: &Spawner) -> Option<bool> {
+        if self.runs_inline(scope) {
+            return Some(true);
+        }
+        match &self.overflow {
+            Some(overflow) if !overflow.reserve() => {
+                // the deferred task runs within a pending one
+                if self.config.sequential_below.is_some() {
+                    self.pending.fetch_sub(1, Ordering::Relaxed);
+                }
+                None
+            }
+            _ => Some(false),
+        }
+    }
+
+    // whether the task about to be spawned runs inline instead, counting it
+    // as pending until it ends
+    #[inline]
+    fn runs_inline(&self, scope: &Spawner) -> bool {
+        let Some(tasks_per_thread) = self.config.sequential_below else {
+            return false;
+        };
+        let pending = self.pending.fetch_add(1, Ordering::Relaxed);
+        // a seeded eval keeps every task in its queue, to replay it
+        let inline = matches!(scope, Spawner::Rayon(_)) && pending < self.threads * tasks_per_thread;
+        if inline {
+            self.inlined.fetch_add(1, Ordering::Relaxed);
+        }
+        inline
+    }
+
+    // a task launched by `launch` ended
+    #[inline]
+    fn task_ended(&self, inline: bool) {
+        if self.config.sequential_below.is_some() {
+            self.pending.fetch_sub(1, Ordering::Relaxed);
+        }
+        if let (Some(overflow), false) = (&self.overflow, inline) {
+            overflow.release();
+        }
+    }
+
+    fn defer(&self, store: &Store, deferred: Deferred, free_ptrs: Option<FreePtrs>) {
+        if let Some(mut free_ptrs) = free_ptrs {
             self.free_ptrs(store, &mut free_ptrs);
-        });
+        }
+        self.overflow
+            .as_ref()
+            .expect("only capped evals defer tasks")
+            .push(deferred);
     }
 
+    // run the deferred tasks left, including the ones they defer: a task
+    // deferring one runs this before it ends
     #[inline]
-    fn spawn_eval_era_term<'scope>(
+    fn run_deferred<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
-        term_ptr: TermPtr,
-        mut free_ptrs: FreePtrs,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
     ) {
-        scope.spawn(move |scope| {
-            let mut stats = LocalStats::new();
-            self.eval_era_term(scope, store, term_ptr, &mut free_ptrs, &mut stats);
-            self.stats.update(stats);
-        });
+        let Some(overflow) = &self.overflow else {
+            return;
+        };
+        while let Some(deferred) = overflow.pop() {
+            match deferred {
+                Deferred::Equation(eqn) => {
+                    self.eval_equation(scope, store, eqn.left(), eqn.right(), free_ptrs, stats)
+                }
+                Deferred::CellTerm(cell_ptr, cell, term_ptr) => {
+                    self.eval_cell_term(scope, store, cell_ptr, cell, term_ptr, free_ptrs, stats)
+                }
+            }
+        }
+    }
+
+    // run the tasks deferred while spawning the equations of the net
+    fn run_deferred_root<'scope>(&'scope self, scope: &Spawner<'_, 'scope>, store: &'scope Store) {
+        if self.overflow.is_none() {
+            return;
+        }
+        let (mut free_ptrs, mut stats) = (FreePtrs::new(), LocalStats::new());
+        self.run_deferred(scope, store, &mut free_ptrs, &mut stats);
+        self.finish_task(store, &mut free_ptrs, stats);
+    }
+
+    // free the unused free ptrs and merge the stats of a task
+    #[inline]
+    fn finish_task(&self, store: &Store, free_ptrs: &mut FreePtrs, mut stats: LocalStats) {
+        let started = self.config.time_phases.then(Instant::now);
+        self.free_ptrs(store, free_ptrs);
+        stats.record_contention(store::take_contention());
+        self.stats.update(stats);
+        if let Some(started) = started {
+            self.task_teardown
+                .fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        }
+    }
+
+    // the task spawned at `spawned` starts now, when recording a timeline
+    #[inline]
+    fn start_task(
+        &self,
+        spawned: Option<Duration>,
+        rule: impl FnOnce() -> &'static str,
+    ) -> Option<TaskEvent> {
+        let (timeline, spawned) = self.timeline.as_ref().zip(spawned)?;
+        let started = timeline.now();
+        Some(TaskEvent {
+            rule: rule(),
+            thread: self.thread_id(),
+            spawned,
+            started,
+            ended: started,
+        })
+    }
+
+    #[inline]
+    fn end_task(&self, task: Option<TaskEvent>) {
+        if let (Some(timeline), Some(mut task)) = (&self.timeline, task) {
+            task.ended = timeline.now();
+            timeline.record(task);
+        }
+    }
+
+    #[inline]
+    fn start_pending(&self, pending: Option<u64>) {
+        if let (Some(debugger), Some(id)) = (&self.debugger, pending) {
+            debugger.pop_pending(id);
+        }
+    }
+
+    // ------------------- MEMO --------------------------
+
+    /// Splice in the reduced form of the redex `left ~ right` if it is small
+    /// and closed enough to be memoized, returning whether it was.
+    fn eval_memoized<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        memo: &Memo,
+        left: (Option<Ptr>, &Cell),
+        right: (Option<Ptr>, &Cell),
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) -> bool {
+        let Some(captured) = Template::capture(store, left, right, MEMO_MAX_CELLS) else {
+            return false;
+        };
+        let (Some(reduced), known) = memo.reduce(&captured.template) else {
+            return false;
+        };
+        if known {
+            stats.inc_memo_hits();
+        } else {
+            stats.inc_memo_misses();
+        }
+
+        debug_rule!(
+            self,
+            None,
+            "({:02}) memo {:>7}: {} ⋈ {} ({} slots → {})",
+            self.thread_id(),
+            if known { "HIT" } else { "MISS" },
+            CellDisplay(store, left.0, left.1),
+            CellDisplay(store, right.0, right.1),
+            captured.template.size(),
+            reduced.size()
+        );
+
+        captured
+            .owned
+            .into_iter()
+            .for_each(|ptr| free_ptrs.push(ptr));
+        let (ports, _) = reduced.alloc_in(store);
+        (0..reduced.vars()).for_each(|_| stats.inc_alloc_vars());
+        (0..reduced.cells()).for_each(|_| stats.inc_alloc_cells());
+        self.check_capacity(store);
+
+        for (port, var_ptr) in ports.into_iter().zip(captured.boundary) {
+            self.eval_equation(scope, store, port, TermPtr::Ptr(var_ptr), free_ptrs, stats);
+        }
+        true
+    }
+
+    // ------------------- INTERN --------------------------
+
+    /// Rewrite `left ⋈ right` when either side is an interned constant,
+    /// returning whether one was. A duplicator shares a constant without
+    /// duplicators; any other cell gets a copy of it to rewrite.
+    fn eval_interned<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        interner: &Interner,
+        left_ptr: Option<Ptr>,
+        left: Cell,
+        right_ptr: Option<Ptr>,
+        right: Cell,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) -> bool {
+        let (left_constant, right_constant) =
+            (interner.template(left_ptr), interner.template(right_ptr));
+        match (left_constant, right_constant) {
+            (None, None) => return false,
+            (Some(constant), None) if matches!(right, Cell::Dup(..)) && !constant.has_dups() => {
+                self.share_constant(
+                    scope,
+                    store,
+                    left_ptr.unwrap(),
+                    right_ptr,
+                    right,
+                    free_ptrs,
+                    stats,
+                )
+            }
+            (None, Some(constant)) if matches!(left, Cell::Dup(..)) && !constant.has_dups() => self
+                .share_constant(
+                    scope,
+                    store,
+                    right_ptr.unwrap(),
+                    left_ptr,
+                    left,
+                    free_ptrs,
+                    stats,
+                ),
+            _ => {
+                let (left_ptr, left) = match left_constant {
+                    Some(constant) => self.copy_constant(store, constant, stats),
+                    None => (left_ptr, left),
+                };
+                let (right_ptr, right) = match right_constant {
+                    Some(constant) => self.copy_constant(store, constant, stats),
+                    None => (right_ptr, right),
+                };
+                self.eval_cell_cell(
+                    scope, store, left_ptr, left, right_ptr, right, free_ptrs, stats,
+                );
+            }
+        }
+        true
+    }
+
+    // a duplicator hands the constant rooted at `root` to both its ports
+    fn share_constant<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        root: Ptr,
+        dup_ptr: Option<Ptr>,
+        dup: Cell,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        debug_rule!(
+            self,
+            None,
+            "({:02}) share DUP    : {} ⋈ {}",
+            self.thread_id(),
+            TermDisplay(store, &TermPtr::Ptr(root)),
+            CellDisplay(store, dup_ptr, &dup)
+        );
+        dup_ptr.map(|ptr| free_ptrs.push(ptr));
+        let Cell::Dup(ports, _) = dup else {
+            unreachable!("only duplicators share constants");
+        };
+        let root = TermPtr::Ptr(root);
+        match ports {
+            Some((p0, p1)) => {
+                self.spawn_eval_equation(
+                    scope,
+                    store,
+                    p0,
+                    root,
+                    self.split_free_ptrs(free_ptrs, 2).into(),
+                );
+                self.eval_equation(scope, store, p1, root, free_ptrs, stats);
+            }
+            None => {
+                // the aux ports were wired to each other: the copies meet
+                self.eval_equation(scope, store, root, root, free_ptrs, stats);
+            }
+        }
+    }
+
+    fn copy_constant(
+        &self,
+        store: &Store,
+        constant: &Template,
+        stats: &mut LocalStats,
+    ) -> (Option<Ptr>, Cell) {
+        let (ports, _) = constant.alloc_in(store);
+        (0..constant.vars()).for_each(|_| stats.inc_alloc_vars());
+        (0..constant.cells()).for_each(|_| stats.inc_alloc_cells());
+        self.check_capacity(store);
+        let TermPtr::Ptr(ptr) = ports[0] else {
+            unreachable!("a constant is rooted at a cell");
+        };
+        (Some(ptr), self.get_cell(store, ptr))
     }
 
     // ------------------- CONNECT --------------------------
     fn connect_vars<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         left_ptr: Ptr,
         left: &Var,
@@ -117,8 +1282,20 @@ impl Runtime {
         stats: &mut LocalStats,
     ) {
         stats.inc_connects();
+        store::rule_context("CONNECT");
+        self.log_json("CONNECT", [Some(left_ptr), Some(right_ptr)]);
+        if let Some(tainter) = &self.tainter {
+            tainter.rewrite("CONNECT", [Some(left_ptr), Some(right_ptr)], [], || {
+                (
+                    VarDisplay(left_ptr, left).to_string(),
+                    VarDisplay(right_ptr, right).to_string(),
+                )
+            });
+        }
 
-        debug!(
+        debug_rule!(
+            self,
+            Rule::Connect,
             "({:02}) eval CONNECT : {} ↔ {}",
             self.thread_id(),
             VarDisplay(left_ptr, left),
@@ -131,6 +1308,7 @@ impl Runtime {
             right_ptr,
             right,
             None,
+            0,
             free_ptrs,
             stats,
             |var, _| var.link(left_ptr),
@@ -148,57 +1326,16 @@ impl Runtime {
                     left_ptr,
                     left,
                     Some(cell_ptr),
-                    *cell,
+                    cell,
                     free_ptrs,
                     stats,
                 );
             }
-            VarValue::Var(right_ptr_set) => {
-                // now right var is set to link to the left one: we need set the left var to link to the right one too
-                match self.walk_var(
-                    scope,
-                    store,
-                    left_ptr,
-                    left,
-                    None,
-                    free_ptrs,
-                    stats,
-                    |var, _| var.link(right_ptr_set),
-                ) {
-                    VarValue::Var(_) => {
-                        // TODO set completed?
-                    }
-                    VarValue::Era => {
-                        // what if walking updated a different var?
-                        // TODO if diff, we are loading the var twice: could we return the var reference instead?
-                        let right_set = if right_ptr_set != right_ptr {
-                            self.get_var(store, right_ptr_set)
-                        } else {
-                            right
-                        };
-                        self.bind_era(scope, store, right_ptr_set, right_set, free_ptrs, stats)
-                    }
-                    VarValue::Cell(cell_ptr) => {
-                        // what if walking updated a different var?
-                        // TODO if diff, we are loading the var twice: could we return the var reference instead?
-                        let right_set = if right_ptr_set != right_ptr {
-                            self.get_var(store, right_ptr_set)
-                        } else {
-                            right
-                        };
-                        let cell = self.get_cell(store, cell_ptr);
-                        self.bind_cell(
-                            scope,
-                            store,
-                            right_ptr_set,
-                            right_set,
-                            Some(cell_ptr),
-                            *cell,
-                            free_ptrs,
-                            stats,
-                        )
-                    }
-                }
+            VarValue::Var(_) => {
+                // the right var now links to the left one, which takes the
+                // value of the right var's other use when it arrives. The left
+                // var is not linked back: its own other use still has to
+                // arrive, and a var takes exactly two.
             }
         }
     }
@@ -207,7 +1344,7 @@ impl Runtime {
 
     fn bind_era<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         var_ptr: Ptr,
         var: &Var,
@@ -215,8 +1352,20 @@ impl Runtime {
         stats: &mut LocalStats,
     ) {
         stats.inc_binds();
+        store::rule_context("BIND");
+        self.log_json("BIND", [Some(var_ptr), None]);
+        if let Some(tainter) = &self.tainter {
+            tainter.rewrite("BIND", [Some(var_ptr), None], [], || {
+                (
+                    VarDisplay(var_ptr, var).to_string(),
+                    CellDisplay::ERA_SYMBOL.to_string(),
+                )
+            });
+        }
 
-        debug!(
+        debug_rule!(
+            self,
+            Rule::Bind,
             "({:02}) eval BIND    : {} ← {}",
             self.thread_id(),
             VarDisplay(var_ptr, var),
@@ -229,24 +1378,26 @@ impl Runtime {
             var_ptr,
             var,
             None,
+            0,
             free_ptrs,
             stats,
             |var, _| var.assign_era(),
         ) {
             VarValue::Era => self.anni_era_era(scope, store, free_ptrs, stats),
             VarValue::Cell(cell_ptr) => {
-                let cell: &Cell = store.get(cell_ptr).as_ref().unwrap().try_into().unwrap();
-                self.eval_era_cell(scope, store, Some(cell_ptr), *cell, free_ptrs, stats)
+                let cell: Cell = store.get(cell_ptr).unwrap().try_into().unwrap();
+                self.eval_era_cell(scope, store, Some(cell_ptr), cell, free_ptrs, stats)
             }
-            VarValue::Var(_) => {
+            VarValue::Var(bound_ptr) => {
                 // done
+                self.head_bound(store, bound_ptr);
             }
         }
     }
 
     fn bind_cell<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         var_ptr: Ptr,
         var: &Var,
@@ -256,48 +1407,69 @@ impl Runtime {
         stats: &mut LocalStats,
     ) {
         stats.inc_binds();
+        store::rule_context("BIND");
+        self.log_json("BIND", [Some(var_ptr), cell_ptr]);
+        if let Some(tainter) = &self.tainter {
+            // a cell not allocated yet is followed through its ports
+            let reached: Vec<_> = match cell_ptr {
+                Some(_) => vec![],
+                None => taint::ports(&cell).collect(),
+            };
+            tainter.rewrite("BIND", [Some(var_ptr), cell_ptr], reached, || {
+                (
+                    VarDisplay(var_ptr, var).to_string(),
+                    CellDisplay(store, cell_ptr, &cell).to_string(),
+                )
+            });
+        }
 
-        debug!(
+        debug_rule!(
+            self,
+            Rule::Bind,
             "({:02}) eval BIND    : {} ← {}",
             self.thread_id(),
             VarDisplay(var_ptr, var),
             CellDisplay(store, cell_ptr, &cell)
         );
 
+        // a stack cell is allocated once, even when walking a chain of vars,
+        // and the rule it meets frees it when the var was already set
+        let allocated = std::cell::Cell::new(cell_ptr);
         let right_value = self.walk_var(
             scope,
             store,
             var_ptr,
             var,
             None,
+            0,
             free_ptrs,
             stats,
             |var, stats| {
-                let cell_ptr =
-                    cell_ptr.map_or_else(|| self.alloc_cell(store, cell.into(), stats), |ptr| ptr);
+                let cell_ptr = allocated.get().unwrap_or_else(|| {
+                    let ptr = self.alloc_cell(store, cell.into(), stats);
+                    allocated.set(Some(ptr));
+                    ptr
+                });
                 var.assign_cell(cell_ptr)
             },
         );
+        let cell_ptr = allocated.get();
 
         match right_value {
-            VarValue::Var(_) => {
+            VarValue::Var(bound_ptr) => {
                 // var set
+                self.head_bound(store, bound_ptr);
             }
             VarValue::Era => self.eval_era_cell(scope, store, cell_ptr, cell, free_ptrs, stats),
             VarValue::Cell(other_cell_ptr) => {
-                let other_cell: &Cell = store
-                    .get(other_cell_ptr)
-                    .as_ref()
-                    .unwrap()
-                    .try_into()
-                    .unwrap();
+                let other_cell: Cell = store.get(other_cell_ptr).unwrap().try_into().unwrap();
                 self.eval_cell_cell(
                     scope,
                     store,
                     cell_ptr,
                     cell,
                     Some(other_cell_ptr),
-                    *other_cell,
+                    other_cell,
                     free_ptrs,
                     stats,
                 )
@@ -307,12 +1479,14 @@ impl Runtime {
 
     fn walk_var<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         var_ptr: Ptr,
         var: &Var,
         // need to avoid loops when traversing linked vars
         previous_ptr: Option<Ptr>,
+        // vars walked through so far
+        hops: usize,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
         assign_var: impl Fn(&Var, &mut LocalStats) -> Option<VarValue>,
@@ -322,6 +1496,7 @@ impl Runtime {
         // let cell_ptr = cell_ptr.map_or_else(|| self.alloc_cell(store, cell.into()), |ptr| ptr);
         match assign_var(var, stats) {
             None => {
+                stats.record_var_hops(hops);
                 return VarValue::Var(var_ptr);
             }
             Some(VarValue::Var(other_var_ptr)) => {
@@ -329,6 +1504,9 @@ impl Runtime {
                 // TODO: this will only check for direct cycles: could there be an indirect cycle?
                 if Some(other_var_ptr) != previous_ptr {
                     // was linked
+                    if let Some(tainter) = &self.tainter {
+                        tainter.follow(var_ptr, other_var_ptr);
+                    }
                     store.free(var_ptr);
                     let other_var = self.get_var(store, other_var_ptr);
                     // walk to the next var
@@ -338,22 +1516,26 @@ impl Runtime {
                         other_var_ptr,
                         other_var,
                         Some(var_ptr),
+                        hops + 1,
                         free_ptrs,
                         stats,
                         assign_var,
                     );
                 } else {
                     // var already set : in its final state
+                    stats.record_var_hops(hops);
                     return VarValue::Var(var_ptr);
                 }
             }
             Some(val @ VarValue::Era) => {
                 // var already set : in its final state
+                stats.record_var_hops(hops);
                 free_ptrs.push(var_ptr);
                 return val;
             }
             Some(val @ VarValue::Cell(_)) => {
                 // var already set : in its final state
+                stats.record_var_hops(hops);
                 free_ptrs.push(var_ptr);
                 return val;
             }
@@ -364,7 +1546,7 @@ impl Runtime {
 
     fn eval_equation<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         left: TermPtr,
         right: TermPtr,
@@ -373,37 +1555,64 @@ impl Runtime {
     ) {
         return match left {
             TermPtr::Era => self.eval_era_term(scope, store, right, free_ptrs, stats),
-            TermPtr::Ptr(ptr) => match store.get(ptr).as_ref().unwrap() {
-                Term::Cell(cell) => {
-                    // copy cell to the stack
-                    self.eval_cell_term(scope, store, Some(ptr), *cell, right, free_ptrs, stats)
+            TermPtr::Ptr(ptr) => match store.get(ptr).unwrap() {
+                TermRef::Cell(cell) => {
+                    self.eval_cell_term(scope, store, Some(ptr), cell, right, free_ptrs, stats)
                 }
-                Term::Var(var) => {
+                TermRef::Var(var) => {
                     self.eval_var_term(scope, store, ptr, var, right, free_ptrs, stats)
                 }
             },
         };
     }
 
+    // eval an equation of a known kind, reading only the terms it rewrites
+    fn eval_kind<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        kind: EquationKind,
+        eqn: Equation,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        let (left, right) = (eqn.left(), eqn.right());
+        let var = |term_ptr| match term_ptr {
+            TermPtr::Ptr(ptr) => match store.get(ptr) {
+                Some(TermRef::Var(var)) => (ptr, var),
+                _ => unreachable!("{} is not a var", ptr),
+            },
+            TermPtr::Era => unreachable!("an eraser is not a var"),
+        };
+        match kind {
+            EquationKind::Redex => self.eval_equation(scope, store, left, right, free_ptrs, stats),
+            EquationKind::Bind => {
+                let (var_ptr, var) = var(left);
+                self.eval_var_term(scope, store, var_ptr, var, right, free_ptrs, stats)
+            }
+            EquationKind::Connect => {
+                let ((left_ptr, left), (right_ptr, right)) = (var(left), var(right));
+                self.connect_vars(scope, store, left_ptr, left, right_ptr, right, free_ptrs, stats)
+            }
+        }
+    }
+
     #[inline]
     fn eval_era_term<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         term_ptr: TermPtr,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
-        match term_ptr {
-            TermPtr::Era => self.anni_era_era(scope, store, free_ptrs, stats),
-            TermPtr::Ptr(ptr) => self.eval_era_ptr(scope, store, ptr, free_ptrs, stats),
-        }
+        self.erase(scope, store, vec![term_ptr], free_ptrs, stats);
     }
 
     #[inline]
     fn eval_cell_term<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         cell_ptr: Option<Ptr>,
         cell: Cell,
@@ -413,21 +1622,20 @@ impl Runtime {
     ) {
         match term_ptr {
             TermPtr::Era => self.eval_era_cell(scope, store, cell_ptr, cell, free_ptrs, stats),
-            TermPtr::Ptr(ptr) => match store.get(ptr).as_ref().unwrap() {
-                Term::Cell(other_cell) => {
-                    // copy other_cell to the stack
+            TermPtr::Ptr(ptr) => match store.get(ptr).unwrap() {
+                TermRef::Cell(other_cell) => {
                     self.eval_cell_cell(
                         scope,
                         store,
                         cell_ptr,
                         cell,
                         Some(ptr),
-                        *other_cell,
+                        other_cell,
                         free_ptrs,
                         stats,
                     )
                 }
-                Term::Var(var) => {
+                TermRef::Var(var) => {
                     self.bind_cell(scope, store, ptr, var, cell_ptr, cell, free_ptrs, stats)
                 }
             },
@@ -437,7 +1645,7 @@ impl Runtime {
     #[inline]
     fn eval_var_term<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         var_ptr: Ptr,
         var: &Var,
@@ -447,21 +1655,20 @@ impl Runtime {
     ) {
         match term_ptr {
             TermPtr::Era => self.bind_era(scope, store, var_ptr, var, free_ptrs, stats),
-            TermPtr::Ptr(ptr) => match store.get(ptr).as_ref().unwrap() {
-                Term::Cell(cell) => {
-                    // copy cell to the stack
+            TermPtr::Ptr(ptr) => match store.get(ptr).unwrap() {
+                TermRef::Cell(cell) => {
                     self.bind_cell(
                         scope,
                         store,
                         var_ptr,
                         var,
                         Some(ptr),
-                        *cell,
+                        cell,
                         free_ptrs,
                         stats,
                     )
                 }
-                Term::Var(other_var) => {
+                TermRef::Var(other_var) => {
                     self.connect_vars(scope, store, var_ptr, var, ptr, other_var, free_ptrs, stats)
                 }
             },
@@ -469,46 +1676,112 @@ impl Runtime {
     }
 
     #[inline]
-    fn eval_era_ptr<'scope>(
+    fn eval_era_cell<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
-        ptr: Ptr,
+        cell_ptr: Option<Ptr>,
+        cell: Cell,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
-        match store.get(ptr).as_ref().unwrap() {
-            Term::Var(var) => self.bind_era(scope, store, ptr, var, free_ptrs, stats),
-            Term::Cell(cell) => {
-                // copy Cell to the stack
-                self.eval_era_cell(scope, store, Some(ptr), *cell, free_ptrs, stats)
+        let mut erasing = Vec::new();
+        self.erase_cell(store, cell_ptr, cell, &mut erasing, free_ptrs, stats);
+        self.erase(scope, store, erasing, free_ptrs, stats);
+    }
+
+    /// Propagate an erasure through every term in `erasing` without leaving
+    /// the current task.
+    ///
+    /// ERA interactions only shrink the net, so instead of spawning a task per
+    /// erased port we walk the erased subtrees depth first with a local work
+    /// list. Cells are recycled as soon as they are reached and the list only
+    /// holds the ports still to erase.
+    fn erase<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        mut erasing: Vec<TermPtr>,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        while let Some(term_ptr) = erasing.pop() {
+            match term_ptr {
+                TermPtr::Era => self.anni_era_era(scope, store, free_ptrs, stats),
+                TermPtr::Ptr(ptr) => match store.get(ptr).unwrap() {
+                    TermRef::Var(var) => self.bind_era(scope, store, ptr, var, free_ptrs, stats),
+                    TermRef::Cell(cell) => {
+                        self.erase_cell(store, Some(ptr), cell, &mut erasing, free_ptrs, stats)
+                    }
+                },
             }
         }
     }
 
     #[inline]
-    fn eval_era_cell<'scope>(
+    fn erase_cell<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
         store: &'scope Store,
         cell_ptr: Option<Ptr>,
         cell: Cell,
+        erasing: &mut Vec<TermPtr>,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
+        if self.is_aborted() {
+            return;
+        }
+        self.count_interaction();
+        if let Some(interner) = &self.interner {
+            if interner.template(cell_ptr).is_some() {
+                // dropping a reference to a constant leaves it to the others
+                return;
+            }
+        }
+        if let Some(debugger) = &self.debugger {
+            debugger.on_interaction(store, &self.stats, Agent::Era, Agent::Cell(cell_ptr, cell));
+        }
+        if let Some(definition_stats) = &self.definition_stats {
+            definition_stats.record(cell_ptr, None);
+        }
+        store::rule_context(taint::erase_rule(&cell));
+        self.log_json(taint::erase_rule(&cell), [cell_ptr, None]);
+        if let Some(tainter) = &self.tainter {
+            let rule = taint::erase_rule(&cell);
+            tainter.rewrite(rule, [cell_ptr, None], taint::ports(&cell), || {
+                (
+                    CellDisplay::ERA_SYMBOL.to_string(),
+                    CellDisplay(store, cell_ptr, &cell).to_string(),
+                )
+            });
+        }
+        // a large subtree recycles more pointers than a task can reuse
+        if free_ptrs.is_spilled() {
+            free_ptrs.spill(store);
+        }
+
         match cell {
             Cell::Dup(ports, lbl) => {
-                self.commute_era_dup(scope, store, cell_ptr, ports, lbl, free_ptrs, stats);
+                self.commute_era_dup(store, cell_ptr, ports, lbl, erasing, free_ptrs, stats);
+            }
+            Cell::App(ports) => {
+                self.comm_era_app(store, cell_ptr, ports, erasing, free_ptrs, stats)
+            }
+            Cell::Lam(ports) => {
+                self.comm_era_lam(store, cell_ptr, ports, erasing, free_ptrs, stats)
+            }
+            Cell::Num(value) => self.comm_era_num(store, cell_ptr, value, free_ptrs, stats),
+            Cell::Op(..) | Cell::Swi(..) => {
+                self.comm_era_ops(store, cell_ptr, cell, erasing, free_ptrs, stats)
             }
-            Cell::App(ports) => self.comm_era_app(scope, store, cell_ptr, ports, free_ptrs, stats),
-            Cell::Lam(ports) => self.comm_era_lam(scope, store, cell_ptr, ports, free_ptrs, stats),
+            Cell::Ref(id) => self.comm_era_ref(store, cell_ptr, id, free_ptrs, stats),
         }
     }
 
     #[inline]
     fn eval_cell_cell<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         left_ptr: Option<Ptr>,
         left: Cell,
@@ -517,6 +1790,51 @@ impl Runtime {
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
+        if self.is_aborted() {
+            return;
+        }
+        self.count_interaction();
+        if let Some(interner) = &self.interner {
+            if self.eval_interned(
+                scope, store, interner, left_ptr, left, right_ptr, right, free_ptrs, stats,
+            ) {
+                return;
+            }
+        }
+        if let Some(debugger) = &self.debugger {
+            debugger.on_interaction(
+                store,
+                &self.stats,
+                Agent::Cell(left_ptr, left),
+                Agent::Cell(right_ptr, right),
+            );
+        }
+        if let Some(definition_stats) = &self.definition_stats {
+            definition_stats.record(left_ptr, right_ptr);
+        }
+        store::rule_context(taint::rule(&left, &right));
+        self.log_json(taint::rule(&left, &right), [left_ptr, right_ptr]);
+        if let Some(tainter) = &self.tainter {
+            let reached = taint::ports(&left).chain(taint::ports(&right));
+            tainter.rewrite(
+                taint::rule(&left, &right),
+                [left_ptr, right_ptr],
+                reached,
+                || {
+                    (
+                        CellDisplay(store, left_ptr, &left).to_string(),
+                        CellDisplay(store, right_ptr, &right).to_string(),
+                    )
+                },
+            );
+        }
+        if let Some(memo) = &self.memo {
+            let (left, right) = ((left_ptr, &left), (right_ptr, &right));
+            if self.eval_memoized(scope, store, memo, left, right, free_ptrs, stats) {
+                return;
+            }
+        }
+
         match (left, right) {
             // ANNIHILATE APP-APP
             (Cell::App(left_ports), Cell::App(right_ports)) => {
@@ -568,9 +1886,48 @@ impl Runtime {
             | (Cell::Lam(lam_ports), Cell::App(app_ports)) => self.commute_app_lam(
                 scope, store, right_ptr, app_ports, left_ptr, lam_ports, free_ptrs, stats,
             ),
-            (Cell::Dup(dup_ports, dup_lbl), Cell::Lam(lam_ports))
-            | (Cell::Lam(lam_ports), Cell::Dup(dup_ports, dup_lbl)) => self.commute_lam_dup(
-                scope, store, left_ptr, lam_ports, right_ptr, dup_ports, dup_lbl, free_ptrs, stats,
+            (Cell::Dup(dup_ports, dup_lbl), Cell::Lam(lam_ports))
+            | (Cell::Lam(lam_ports), Cell::Dup(dup_ports, dup_lbl)) => self.commute_lam_dup(
+                scope, store, left_ptr, lam_ports, right_ptr, dup_ports, dup_lbl, free_ptrs, stats,
+            ),
+            // COPY a REF
+            (Cell::Ref(id), Cell::Dup(..)) => self.comm_ref(
+                scope, store, left_ptr, id, right_ptr, right, free_ptrs, stats,
+            ),
+            (Cell::Dup(..), Cell::Ref(id)) => self.comm_ref(
+                scope, store, right_ptr, id, left_ptr, left, free_ptrs, stats,
+            ),
+            // EXPAND a REF
+            (Cell::Ref(id), _) => self.expand_ref(
+                scope, store, left_ptr, id, right_ptr, right, free_ptrs, stats,
+            ),
+            (_, Cell::Ref(id)) => self.expand_ref(
+                scope, store, right_ptr, id, left_ptr, left, free_ptrs, stats,
+            ),
+            // OPERATE on a NUM
+            (Cell::Num(value), Cell::Op(ports, op, flipped)) => self.oper_num(
+                scope, store, left_ptr, value, right_ptr, ports, op, flipped, free_ptrs, stats,
+            ),
+            (Cell::Op(ports, op, flipped), Cell::Num(value)) => self.oper_num(
+                scope, store, right_ptr, value, left_ptr, ports, op, flipped, free_ptrs, stats,
+            ),
+            // SWITCH on a NUM
+            (Cell::Num(value), Cell::Swi(ports)) => self.swi_num(
+                scope, store, left_ptr, value, right_ptr, ports, free_ptrs, stats,
+            ),
+            (Cell::Swi(ports), Cell::Num(value)) => self.swi_num(
+                scope, store, right_ptr, value, left_ptr, ports, free_ptrs, stats,
+            ),
+            // COPY a NUM
+            (Cell::Num(value), other) => self.comm_num(
+                scope, store, left_ptr, value, right_ptr, other, free_ptrs, stats,
+            ),
+            (other, Cell::Num(value)) => self.comm_num(
+                scope, store, right_ptr, value, left_ptr, other, free_ptrs, stats,
+            ),
+            // COMMUTE an OP or a SWI
+            (Cell::Op(..) | Cell::Swi(..), _) | (_, Cell::Op(..) | Cell::Swi(..)) => self.comm_ops(
+                scope, store, left_ptr, left, right_ptr, right, free_ptrs, stats,
             ),
         }
     }
@@ -580,14 +1937,16 @@ impl Runtime {
     #[inline]
     fn anni_era_era<'scope>(
         &'scope self,
-        _scope: &rayon::Scope<'scope>,
+        _scope: &Spawner<'_, 'scope>,
         _store: &'scope Store,
         _free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
         stats.inc_anni_era_era();
 
-        debug!(
+        debug_rule!(
+            self,
+            Rule::AnniEraEra,
             "({:02}) anni ERA-ERA : {} <- {}",
             self.thread_id(),
             CellDisplay::ERA_SYMBOL,
@@ -598,7 +1957,7 @@ impl Runtime {
     #[inline]
     fn anni_lam_lam<'scope>(
         &'scope self,
-        _scope: &rayon::Scope<'scope>,
+        _scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         left_ptr: Option<Ptr>,
         left_ports: Option<(TermPtr, TermPtr)>,
@@ -609,7 +1968,9 @@ impl Runtime {
     ) {
         stats.inc_anni_lam_lam();
 
-        debug!(
+        debug_rule!(
+            self,
+            Rule::AnniLamLam,
             "({:02}) anni LAM-LAM : {} ⋈ {}",
             self.thread_id(),
             CellDisplay(store, left_ptr, &Cell::Lam(left_ports)),
@@ -623,7 +1984,7 @@ impl Runtime {
     #[inline]
     fn anni_app_app<'scope>(
         &'scope self,
-        _scope: &rayon::Scope<'scope>,
+        _scope: &Spawner<'_, 'scope>,
         _store: &'scope Store,
         left_ptr: Option<Ptr>,
         _left_ports: Option<(TermPtr, TermPtr)>,
@@ -634,7 +1995,9 @@ impl Runtime {
     ) {
         stats.inc_anni_app_app();
 
-        debug!(
+        debug_rule!(
+            self,
+            Rule::AnniAppApp,
             "({:02}) anni APP-APP : {} ⋈ {}",
             self.thread_id(),
             CellDisplay::LAM_SYMBOL,
@@ -653,17 +2016,19 @@ impl Runtime {
     #[inline]
     fn reduce_dup_dup<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         left_ptr: Option<Ptr>,
         left_ports: Option<(TermPtr, TermPtr)>,
-        left_lbl: Option<Ptr>,
+        left_lbl: Option<Label>,
         right_ptr: Option<Ptr>,
         right_ports: Option<(TermPtr, TermPtr)>,
-        right_lbl: Option<Ptr>,
+        right_lbl: Option<Label>,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
+        self.record_dup_label(left_lbl);
+        self.record_dup_label(right_lbl);
         if left_lbl == right_lbl {
             stats.inc_anni_dup_dup();
             self.anni_dup_dup(
@@ -697,21 +2062,23 @@ impl Runtime {
 
     fn anni_dup_dup<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         left_ptr: Option<Ptr>,
         left_ports: Option<(TermPtr, TermPtr)>,
-        left_lbl: Option<Ptr>,
+        left_lbl: Option<Label>,
         right_ptr: Option<Ptr>,
         right_ports: Option<(TermPtr, TermPtr)>,
-        right_lbl: Option<Ptr>,
+        right_lbl: Option<Label>,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
         left_ptr.map(|ptr| free_ptrs.push(ptr));
         right_ptr.map(|ptr| free_ptrs.push(ptr));
 
-        debug!(
+        debug_rule!(
+            self,
+            Rule::AnniDupDup,
             "({:02}) anni DUP-DUP : {} ⋈ {}",
             self.thread_id(),
             CellDisplay(store, left_ptr, &Cell::Dup(None, left_lbl)),
@@ -732,7 +2099,7 @@ impl Runtime {
                     store,
                     left_p0,
                     right_p0,
-                    free_ptrs.split(2).into(),
+                    self.split_free_ptrs(free_ptrs, 2).into(),
                 );
                 self.eval_equation(scope, store, left_p1, right_p1, free_ptrs, stats);
             }
@@ -741,49 +2108,47 @@ impl Runtime {
 
     fn comm_dup_dup<'scope>(
         &'scope self,
-        _scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         left_ptr: Option<Ptr>,
         left_ports: Option<(TermPtr, TermPtr)>,
-        left_lbl: Option<Ptr>,
+        left_lbl: Option<Label>,
         right_ptr: Option<Ptr>,
         right_ports: Option<(TermPtr, TermPtr)>,
-        right_lbl: Option<Ptr>,
+        right_lbl: Option<Label>,
         free_ptrs: &mut FreePtrs,
-        _stats: &mut LocalStats,
+        stats: &mut LocalStats,
     ) {
-        left_ptr.map(|ptr| free_ptrs.push(ptr));
-        right_ptr.map(|ptr| free_ptrs.push(ptr));
-
-        debug!(
+        debug_rule!(
+            self,
+            Rule::CommDupDup,
             "({:02}) comm DUP-DUP : {} ⋈ {}",
             self.thread_id(),
             CellDisplay(store, left_ptr, &Cell::Dup(None, left_lbl)),
             CellDisplay(store, right_ptr, &Cell::Dup(None, right_lbl))
         );
 
-        match (left_ports, right_ports) {
-            // Disconnected NET: (Dup a a ?) ⋈ (Dup b b ?)
-            (None, None) => {}
-            // (Dup a a ?) ⋈ (Dup b c ?)
-            (Some((_p0, _p1)), None) | (None, Some((_p0, _p1))) => {
-                // COMMUTE
-                todo!("comm dup-dup not yet implemented")
-            }
-            (Some((_left_p0, _left_p1)), Some((_right_p0, _right_p1))) => {
-                // COMMUTE
-                todo!("comm dup-dup not yet implemented")
-            }
-        }
+        self.commute(
+            scope,
+            store,
+            left_ptr,
+            left_ports,
+            |ports| Cell::Dup(ports, left_lbl),
+            right_ptr,
+            right_ports,
+            |ports| Cell::Dup(ports, right_lbl),
+            free_ptrs,
+            stats,
+        )
     }
 
     #[inline]
     fn comm_era_app<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
         store: &'scope Store,
         app_ptr: Option<Ptr>,
         app_ports: Option<(TermPtr, TermPtr)>,
+        erasing: &mut Vec<TermPtr>,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
@@ -791,203 +2156,588 @@ impl Runtime {
 
         app_ptr.map(|ptr| free_ptrs.push(ptr));
 
-        debug!(
+        debug_rule!(
+            self,
+            Rule::CommEraApp,
+            "({:02}) comm ERA-APP : {} ⋈ {}",
+            self.thread_id(),
+            CellDisplay::ERA_SYMBOL,
+            CellDisplay(store, app_ptr, &Cell::App(app_ports))
+        );
+
+        match app_ports {
+            Some((p0, p1)) => {
+                erasing.push(p0);
+                erasing.push(p1);
+            }
+            None => {
+                // the aux ports were wired to each other
+                erasing.push(TermPtr::Era);
+            }
+        }
+    }
+
+    #[inline]
+    fn comm_era_lam<'scope>(
+        &'scope self,
+        store: &'scope Store,
+        lam_ptr: Option<Ptr>,
+        lam_ports: Option<(TermPtr, TermPtr)>,
+        erasing: &mut Vec<TermPtr>,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        stats.inc_comm_era_lam();
+
+        lam_ptr.map(|ptr| free_ptrs.push(ptr));
+
+        debug_rule!(
+            self,
+            Rule::CommEraLam,
+            "({:02}) comm ERA-LAM : {} ⋈ {}",
+            self.thread_id(),
+            CellDisplay::ERA_SYMBOL,
+            CellDisplay(store, lam_ptr, &Cell::Lam(lam_ports))
+        );
+
+        match lam_ports {
+            Some((p0, p1)) => {
+                erasing.push(p0);
+                erasing.push(p1);
+            }
+            None => {
+                // the aux ports were wired to each other
+                erasing.push(TermPtr::Era);
+            }
+        }
+    }
+
+    #[inline]
+    fn commute_era_dup<'scope>(
+        &'scope self,
+        store: &'scope Store,
+        dup_ptr: Option<Ptr>,
+        dup_ports: Option<(TermPtr, TermPtr)>,
+        dup_lbl: Option<Label>,
+        erasing: &mut Vec<TermPtr>,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        stats.inc_commute_era_dup();
+        dup_ptr.map(|ptr| free_ptrs.push(ptr));
+
+        debug_rule!(
+            self,
+            Rule::CommEraDup,
             "({:02}) comm ERA-DUP : {} ⋈ {}",
             self.thread_id(),
-            CellDisplay::ERA_SYMBOL,
-            CellDisplay(store, app_ptr, &Cell::App(app_ports))
+            CellDisplay::ERA_SYMBOL,
+            CellDisplay(store, dup_ptr, &Cell::Dup(dup_ports, dup_lbl))
+        );
+
+        match dup_ports {
+            Some((p0, p1)) => {
+                erasing.push(p0);
+                erasing.push(p1);
+            }
+            None => {
+                // the aux ports were wired to each other
+                erasing.push(TermPtr::Era);
+            }
+        }
+    }
+
+    #[inline]
+    fn commute_app_lam<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        app_ptr: Option<Ptr>,
+        app_ports: Option<(TermPtr, TermPtr)>,
+        lam_ptr: Option<Ptr>,
+        lam_ports: Option<(TermPtr, TermPtr)>,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        stats.inc_comm_app_lam();
+        lam_ptr.map(|ptr| free_ptrs.push(ptr));
+        app_ptr.map(|ptr| free_ptrs.push(ptr));
+
+        debug_rule!(
+            self,
+            Rule::CommAppLam,
+            "({:02}) comm APP-LAM : {} ⋈ {}",
+            self.thread_id(),
+            CellDisplay(store, app_ptr, &Cell::App(app_ports)),
+            CellDisplay(store, lam_ptr, &Cell::Lam(lam_ports))
+        );
+
+        match (app_ports, lam_ports) {
+            (Some((p0, p1)), Some((q0, q1))) => {
+                self.spawn_eval_equation(
+                    scope,
+                    store,
+                    p0,
+                    q0,
+                    self.split_free_ptrs(free_ptrs, 2).into(),
+                );
+                self.eval_equation(scope, store, p1, q1, free_ptrs, stats);
+            }
+            (Some((p0, p1)), None) => {
+                self.spawn_eval_equation(
+                    scope,
+                    store,
+                    p0,
+                    TermPtr::Era,
+                    self.split_free_ptrs(free_ptrs, 2).into(),
+                );
+                self.eval_equation(scope, store, p1, TermPtr::Era, free_ptrs, stats);
+            }
+            (None, Some((q0, q1))) => {
+                self.spawn_eval_equation(
+                    scope,
+                    store,
+                    TermPtr::Era,
+                    q0,
+                    self.split_free_ptrs(free_ptrs, 2).into(),
+                );
+                self.eval_equation(scope, store, TermPtr::Era, q1, free_ptrs, stats);
+            }
+            (None, None) => {
+                self.eval_equation(scope, store, TermPtr::Era, TermPtr::Era, free_ptrs, stats);
+            }
+        }
+    }
+
+    #[inline]
+    fn commute_app_dup<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        app_ptr: Option<Ptr>,
+        app_ports: Option<(TermPtr, TermPtr)>,
+        dup_ptr: Option<Ptr>,
+        dup_ports: Option<(TermPtr, TermPtr)>,
+        dup_lbl: Option<Label>,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        stats.inc_comm_app_dup();
+        self.record_dup_label(dup_lbl);
+
+        debug_rule!(
+            self,
+            Rule::CommAppDup,
+            "({:02}) comm APP-DUP : {} ⋈ {}",
+            self.thread_id(),
+            CellDisplay(store, app_ptr, &Cell::App(app_ports)),
+            CellDisplay(store, dup_ptr, &Cell::Dup(dup_ports, dup_lbl))
+        );
+
+        self.commute(
+            scope,
+            store,
+            app_ptr,
+            app_ports,
+            Cell::App,
+            dup_ptr,
+            dup_ports,
+            |dup_ports| Cell::Dup(dup_ports, dup_lbl),
+            free_ptrs,
+            stats,
+        )
+    }
+
+    #[inline]
+    fn commute_lam_dup<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        lam_ptr: Option<Ptr>,
+        lam_ports: Option<(TermPtr, TermPtr)>,
+        dup_ptr: Option<Ptr>,
+        dup_ports: Option<(TermPtr, TermPtr)>,
+        dup_lbl: Option<Label>,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        stats.inc_comm_lam_dup();
+        self.record_dup_label(dup_lbl);
+
+        debug_rule!(
+            self,
+            Rule::CommLamDup,
+            "({:02}) comm LAM-DUP : {} ⋈ {}",
+            self.thread_id(),
+            CellDisplay(store, lam_ptr, &Cell::Lam(lam_ports)),
+            CellDisplay(store, dup_ptr, &Cell::Dup(dup_ports, dup_lbl))
+        );
+
+        self.commute(
+            scope,
+            store,
+            lam_ptr,
+            lam_ports,
+            Cell::Lam,
+            dup_ptr,
+            dup_ports,
+            |ports| Cell::Dup(ports, dup_lbl),
+            free_ptrs,
+            stats,
+        )
+    }
+
+    #[inline]
+    fn comm_era_num<'scope>(
+        &'scope self,
+        store: &'scope Store,
+        num_ptr: Option<Ptr>,
+        value: u32,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        stats.inc_comm_era_num();
+        num_ptr.map(|ptr| free_ptrs.push(ptr));
+
+        debug_rule!(
+            self,
+            Rule::CommEraNum,
+            "({:02}) comm ERA-NUM : {} ⋈ {}",
+            self.thread_id(),
+            CellDisplay::ERA_SYMBOL,
+            CellDisplay(store, num_ptr, &Cell::Num(value))
+        );
+    }
+
+    #[inline]
+    fn comm_era_ops<'scope>(
+        &'scope self,
+        store: &'scope Store,
+        cell_ptr: Option<Ptr>,
+        cell: Cell,
+        erasing: &mut Vec<TermPtr>,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        stats.inc_comm_era_ops();
+        cell_ptr.map(|ptr| free_ptrs.push(ptr));
+
+        debug_rule!(
+            self,
+            Rule::CommEraOps,
+            "({:02}) comm ERA-OPS : {} ⋈ {}",
+            self.thread_id(),
+            CellDisplay::ERA_SYMBOL,
+            CellDisplay(store, cell_ptr, &cell)
+        );
+
+        match cell.ports() {
+            Some((p0, p1)) => {
+                erasing.push(p0);
+                erasing.push(p1);
+            }
+            None => {
+                // the aux ports were wired to each other
+                erasing.push(TermPtr::Era);
+            }
+        }
+    }
+
+    #[inline]
+    fn comm_era_ref<'scope>(
+        &'scope self,
+        store: &'scope Store,
+        ref_ptr: Option<Ptr>,
+        id: RefId,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        stats.inc_comm_era_ref();
+        ref_ptr.map(|ptr| free_ptrs.push(ptr));
+
+        debug_rule!(
+            self,
+            Rule::CommEraRef,
+            "({:02}) comm ERA-REF : {} ⋈ {}",
+            self.thread_id(),
+            CellDisplay::ERA_SYMBOL,
+            CellDisplay(store, ref_ptr, &Cell::Ref(id))
+        );
+    }
+
+    /// Copy a reference to both ports of a duplicator, without expanding it.
+    #[inline]
+    fn comm_ref<'scope>(
+        &'scope self,
+        scope: &Spawner<'_, 'scope>,
+        store: &'scope Store,
+        ref_ptr: Option<Ptr>,
+        id: RefId,
+        dup_ptr: Option<Ptr>,
+        dup: Cell,
+        free_ptrs: &mut FreePtrs,
+        stats: &mut LocalStats,
+    ) {
+        stats.inc_comm_ref();
+        ref_ptr.map(|ptr| free_ptrs.push(ptr));
+        dup_ptr.map(|ptr| free_ptrs.push(ptr));
+
+        debug_rule!(
+            self,
+            Rule::CommRef,
+            "({:02}) comm REF     : {} ⋈ {}",
+            self.thread_id(),
+            CellDisplay(store, ref_ptr, &Cell::Ref(id)),
+            CellDisplay(store, dup_ptr, &dup)
         );
 
-        // TODO inc ERA-APP
-        match app_ports {
-            Some((p0, p1)) => {
-                self.spawn_eval_era_term(scope, store, p0, free_ptrs.split(2).into());
-                self.eval_era_term(scope, store, p1, free_ptrs, stats);
-            }
-            None => {
-                self.anni_era_era(scope, store, free_ptrs, stats);
-            }
+        if let Some((p0, p1)) = dup.ports() {
+            // the copies live on the stack until bound to a var
+            self.spawn_eval_cell_term(
+                scope,
+                store,
+                None,
+                Cell::Ref(id),
+                p0,
+                self.split_free_ptrs(free_ptrs, 2),
+            );
+            self.eval_cell_term(scope, store, None, Cell::Ref(id), p1, free_ptrs, stats);
         }
     }
 
+    /// Replace a reference by a fresh copy of its definition, whose port
+    /// meets the other cell instead.
     #[inline]
-    fn comm_era_lam<'scope>(
+    fn expand_ref<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
-        lam_ptr: Option<Ptr>,
-        lam_ports: Option<(TermPtr, TermPtr)>,
+        ref_ptr: Option<Ptr>,
+        id: RefId,
+        other_ptr: Option<Ptr>,
+        other: Cell,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
-        stats.inc_comm_era_lam();
-
-        lam_ptr.map(|ptr| free_ptrs.push(ptr));
+        stats.inc_expand_ref();
+        ref_ptr.map(|ptr| free_ptrs.push(ptr));
 
-        debug!(
-            "({:02}) comm ERA-LAM : {} ⋈ {}",
+        debug_rule!(
+            self,
+            Rule::ExpandRef,
+            "({:02}) expand REF   : {} ⋈ {}",
             self.thread_id(),
-            CellDisplay::ERA_SYMBOL,
-            CellDisplay(store, lam_ptr, &Cell::Lam(lam_ports))
+            CellDisplay(store, ref_ptr, &Cell::Ref(id)),
+            CellDisplay(store, other_ptr, &other)
         );
 
-        // TODO inc ERA-LAM
-        match lam_ports {
-            Some((p0, p1)) => {
-                self.spawn_eval_era_term(scope, store, p0, free_ptrs.split(2).into());
-                self.eval_era_term(scope, store, p1, free_ptrs, stats);
-            }
-            None => {
-                self.anni_era_era(scope, store, free_ptrs, stats);
-            }
+        let template = self
+            .refs
+            .as_ref()
+            .and_then(|refs| refs.template(id))
+            .expect("references are defined before eval");
+        let (ports, body) = template.alloc_in(store);
+        (0..template.vars()).for_each(|_| stats.inc_alloc_vars());
+        (0..template.cells()).for_each(|_| stats.inc_alloc_cells());
+        self.check_capacity(store);
+
+        for (left, right) in body {
+            self.spawn_eval_equation(
+                scope,
+                store,
+                left,
+                right,
+                Some(self.split_free_ptrs(free_ptrs, 2)),
+            );
         }
+        self.eval_cell_term(scope, store, other_ptr, other, ports[0], free_ptrs, stats);
     }
 
+    /// Copy a number to both ports of a lambda, an application or a
+    /// duplicator, or drop it with another number.
     #[inline]
-    fn commute_era_dup<'scope>(
+    fn comm_num<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
-        dup_ptr: Option<Ptr>,
-        dup_ports: Option<(TermPtr, TermPtr)>,
-        dup_lbl: Option<Ptr>,
+        num_ptr: Option<Ptr>,
+        value: u32,
+        other_ptr: Option<Ptr>,
+        other: Cell,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
-        stats.inc_commute_era_dup();
-        dup_ptr.map(|ptr| free_ptrs.push(ptr));
-
-        debug!(
-            "({:02}) comm ERA-DUP : {} ⋈ {}",
+        stats.inc_comm_num();
+        num_ptr.map(|ptr| free_ptrs.push(ptr));
+        other_ptr.map(|ptr| free_ptrs.push(ptr));
+
+        debug_rule!(
+            self,
+            Rule::CommNum,
+            "({:02}) comm NUM     : {} ⋈ {}",
             self.thread_id(),
-            CellDisplay::ERA_SYMBOL,
-            CellDisplay(store, dup_ptr, &Cell::Dup(dup_ports, dup_lbl))
+            CellDisplay(store, num_ptr, &Cell::Num(value)),
+            CellDisplay(store, other_ptr, &other)
         );
 
-        match dup_ports {
-            Some((p0, p1)) => {
-                self.spawn_eval_era_term(scope, store, p0, free_ptrs.split(2).into());
-                self.eval_era_term(scope, store, p1, free_ptrs, stats);
-            }
-            None => {
-                self.anni_era_era(scope, store, free_ptrs, stats);
-            }
+        if let Some((p0, p1)) = other.ports() {
+            // the copies live on the stack until bound to a var
+            self.spawn_eval_cell_term(
+                scope,
+                store,
+                None,
+                Cell::Num(value),
+                p0,
+                self.split_free_ptrs(free_ptrs, 2),
+            );
+            self.eval_cell_term(scope, store, None, Cell::Num(value), p1, free_ptrs, stats);
         }
     }
 
+    /// Apply an operator to the number meeting it. If the other operand is
+    /// not a number yet, the operator flips to wait for it instead.
     #[inline]
-    fn commute_app_lam<'scope>(
+    fn oper_num<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
-        app_ptr: Option<Ptr>,
-        app_ports: Option<(TermPtr, TermPtr)>,
-        lam_ptr: Option<Ptr>,
-        lam_ports: Option<(TermPtr, TermPtr)>,
+        num_ptr: Option<Ptr>,
+        value: u32,
+        op_ptr: Option<Ptr>,
+        op_ports: Option<(TermPtr, TermPtr)>,
+        op: Op,
+        flipped: bool,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
-        stats.inc_comm_app_lam();
-        lam_ptr.map(|ptr| free_ptrs.push(ptr));
-        app_ptr.map(|ptr| free_ptrs.push(ptr));
+        stats.inc_oper_num();
+        op_ptr.map(|ptr| free_ptrs.push(ptr));
 
-        debug!(
-            "({:02}) comm APP-LAM : {} ⋈ {}",
+        debug_rule!(
+            self,
+            Rule::OperNum,
+            "({:02}) oper NUM     : {} ⋈ {}",
             self.thread_id(),
-            CellDisplay(store, app_ptr, &Cell::App(app_ports)),
-            CellDisplay(store, lam_ptr, &Cell::Lam(lam_ports))
+            CellDisplay(store, num_ptr, &Cell::Num(value)),
+            CellDisplay(store, op_ptr, &Cell::Op(op_ports, op, flipped))
         );
 
-        match (app_ports, lam_ports) {
-            (Some((p0, p1)), Some((q0, q1))) => {
-                self.spawn_eval_equation(scope, store, p0, q0, free_ptrs.split(2).into());
-                self.eval_equation(scope, store, p1, q1, free_ptrs, stats);
-            }
-            (Some((p0, p1)), None) => {
-                self.spawn_eval_equation(scope, store, p0, TermPtr::Era, free_ptrs.split(2).into());
-                self.eval_equation(scope, store, p1, TermPtr::Era, free_ptrs, stats);
-            }
-            (None, Some((q0, q1))) => {
-                self.spawn_eval_equation(scope, store, TermPtr::Era, q0, free_ptrs.split(2).into());
-                self.eval_equation(scope, store, TermPtr::Era, q1, free_ptrs, stats);
+        let Some((operand, result)) = op_ports else {
+            // the operand is the result: nothing to compute
+            num_ptr.map(|ptr| free_ptrs.push(ptr));
+            return;
+        };
+        match self.get_num(store, operand) {
+            Some((operand_ptr, other)) => {
+                num_ptr.map(|ptr| free_ptrs.push(ptr));
+                free_ptrs.push(operand_ptr);
+                let value = match flipped {
+                    false => op.apply(value, other),
+                    true => op.apply(other, value),
+                };
+                self.eval_cell_term(
+                    scope,
+                    store,
+                    None,
+                    Cell::Num(value),
+                    result,
+                    free_ptrs,
+                    stats,
+                );
             }
-            (None, None) => {
-                self.eval_equation(scope, store, TermPtr::Era, TermPtr::Era, free_ptrs, stats);
+            None => {
+                let num_ptr = num_ptr
+                    .unwrap_or_else(|| self.alloc_cell(store, Some(Cell::Num(value)), stats));
+                let op = Cell::Op(Some((TermPtr::Ptr(num_ptr), result)), op, !flipped);
+                self.eval_cell_term(scope, store, None, op, operand, free_ptrs, stats);
             }
         }
     }
 
+    /// Pick the zero branch of a switch for 0, or apply the successor branch
+    /// to the predecessor of any other number.
     #[inline]
-    fn commute_app_dup<'scope>(
+    fn swi_num<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
-        app_ptr: Option<Ptr>,
-        app_ports: Option<(TermPtr, TermPtr)>,
-        dup_ptr: Option<Ptr>,
-        dup_ports: Option<(TermPtr, TermPtr)>,
-        dup_lbl: Option<Ptr>,
+        num_ptr: Option<Ptr>,
+        value: u32,
+        swi_ptr: Option<Ptr>,
+        swi_ports: Option<(TermPtr, TermPtr)>,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
-        stats.inc_comm_app_dup();
+        stats.inc_swi_num();
+        swi_ptr.map(|ptr| free_ptrs.push(ptr));
 
-        debug!(
-            "({:02}) comm APP-DUP : {} ⋈ {}",
+        debug_rule!(
+            self,
+            Rule::SwiNum,
+            "({:02}) swi NUM      : {} ⋈ {}",
             self.thread_id(),
-            CellDisplay(store, app_ptr, &Cell::App(app_ports)),
-            CellDisplay(store, dup_ptr, &Cell::Dup(dup_ports, dup_lbl))
+            CellDisplay(store, num_ptr, &Cell::Num(value)),
+            CellDisplay(store, swi_ptr, &Cell::Swi(swi_ports))
         );
 
-        self.commute(
-            scope,
-            store,
-            app_ptr,
-            app_ports,
-            |app_ports, _| Cell::App(app_ports),
-            false,
-            dup_ptr,
-            dup_ports,
-            |dup_ports, lbl| Cell::Dup(dup_ports, lbl),
-            true,
-            free_ptrs,
-            stats,
-        )
+        let Some((branches, result)) = swi_ports else {
+            // the branches are the result: nothing to pick
+            num_ptr.map(|ptr| free_ptrs.push(ptr));
+            return;
+        };
+        // the branches meet an application taking the zero branch first
+        let pick = if value == 0 {
+            num_ptr.map(|ptr| free_ptrs.push(ptr));
+            Cell::App(Some((result, TermPtr::Era)))
+        } else {
+            let pred = Cell::Num(value - 1);
+            let pred_ptr = match num_ptr {
+                Some(ptr) => {
+                    self.reuse_cell(store, ptr, pred);
+                    ptr
+                }
+                None => self.alloc_cell(store, Some(pred), stats),
+            };
+            let succ = Cell::App(Some((TermPtr::Ptr(pred_ptr), result)));
+            let succ_ptr = self.alloc_cell(store, Some(succ), stats);
+            Cell::App(Some((TermPtr::Era, TermPtr::Ptr(succ_ptr))))
+        };
+        self.eval_cell_term(scope, store, None, pick, branches, free_ptrs, stats);
     }
 
+    /// Commute an operator or a switch with a cell that is not a number.
     #[inline]
-    fn commute_lam_dup<'scope>(
+    fn comm_ops<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
-        lam_ptr: Option<Ptr>,
-        lam_ports: Option<(TermPtr, TermPtr)>,
-        dup_ptr: Option<Ptr>,
-        dup_ports: Option<(TermPtr, TermPtr)>,
-        dup_lbl: Option<Ptr>,
+        left_ptr: Option<Ptr>,
+        left: Cell,
+        right_ptr: Option<Ptr>,
+        right: Cell,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
-        stats.inc_comm_lam_dup();
+        stats.inc_comm_ops();
 
-        debug!(
-            "({:02}) comm LAM-DUP : {} ⋈ {}",
+        debug_rule!(
+            self,
+            Rule::CommOps,
+            "({:02}) comm OPS     : {} ⋈ {}",
             self.thread_id(),
-            CellDisplay(store, lam_ptr, &Cell::Lam(lam_ports)),
-            CellDisplay(store, dup_ptr, &Cell::Dup(dup_ports, dup_lbl))
+            CellDisplay(store, left_ptr, &left),
+            CellDisplay(store, right_ptr, &right)
         );
 
         self.commute(
             scope,
             store,
-            lam_ptr,
-            lam_ports,
-            |ports, _| Cell::App(ports),
-            false,
-            dup_ptr,
-            dup_ports,
-            |ports, lbl| Cell::Dup(ports, lbl),
-            true,
+            left_ptr,
+            left.ports(),
+            |ports| left.with_ports(ports),
+            right_ptr,
+            right.ports(),
+            |ports| right.with_ports(ports),
             free_ptrs,
             stats,
         )
@@ -996,28 +2746,56 @@ impl Runtime {
     #[inline]
     fn alloc_var(&self, store: &Store, stats: &mut LocalStats) -> Ptr {
         stats.inc_alloc_vars();
-        return store.alloc(Some(Term::Var(Var::new())));
+        let ptr = self.alloc(store, Some(Term::Var(Var::new())));
+        self.check_capacity(store);
+        return ptr;
+    }
+
+    // a slot freed by an earlier warm eval, while any is left, or a new one
+    #[inline]
+    fn alloc(&self, store: &Store, term: Option<Term>) -> Ptr {
+        if !self.reusable.is_empty() {
+            let reused = self.reused.fetch_add(1, Ordering::Relaxed);
+            if let Some(ptr) = self.reusable.get(reused) {
+                return store.reuse(*ptr, term);
+            }
+        }
+        store.alloc(term)
     }
 
     #[inline]
     fn alloc_cell(&self, store: &Store, cell: Option<Cell>, stats: &mut LocalStats) -> Ptr {
         stats.inc_alloc_cells();
-        return store.alloc(cell.map(|c| Term::Cell(c)));
+        let ptr = self.alloc(store, cell.map(Term::Cell));
+        self.check_capacity(store);
+        return ptr;
+    }
+
+    #[inline]
+    fn get_cell(&self, store: &Store, cell_ptr: Ptr) -> Cell {
+        match store.get(cell_ptr).unwrap() {
+            TermRef::Var(_) => panic!("Expected Cell, found Var"),
+            TermRef::Cell(cell) => cell,
+        }
     }
 
+    // the number at `term_ptr`, if it is one already
     #[inline]
-    fn get_cell<'scope>(&'scope self, store: &'scope Store, cell_ptr: Ptr) -> &Cell {
-        match store.get(cell_ptr).as_ref().unwrap() {
-            Term::Var(_) => panic!("Expected Cell, found Var"),
-            Term::Cell(cell) => cell,
+    fn get_num(&self, store: &Store, term_ptr: TermPtr) -> Option<(Ptr, u32)> {
+        let TermPtr::Ptr(ptr) = term_ptr else {
+            return None;
+        };
+        match store.get(ptr) {
+            Some(TermRef::Cell(Cell::Num(value))) => Some((ptr, value)),
+            _ => None,
         }
     }
 
     #[inline]
     fn get_var<'scope>(&'scope self, store: &'scope Store, var_ptr: Ptr) -> &Var {
-        match store.get(var_ptr).as_ref().unwrap() {
-            Term::Var(var) => var,
-            Term::Cell(_) => panic!("Expected Var, found Cell"),
+        match store.get(var_ptr).unwrap() {
+            TermRef::Var(var) => var,
+            TermRef::Cell(_) => panic!("Expected Var, found Cell"),
         }
     }
 
@@ -1034,16 +2812,14 @@ impl Runtime {
     #[inline]
     fn commute<'scope>(
         &'scope self,
-        scope: &rayon::Scope<'scope>,
+        scope: &Spawner<'_, 'scope>,
         store: &'scope Store,
         left_ptr: Option<Ptr>,
         left_ports: Option<(TermPtr, TermPtr)>,
-        left_fn: impl Fn(Option<(TermPtr, TermPtr)>, Option<Ptr>) -> Cell,
-        left_alloc: bool,
+        left_fn: impl Fn(Option<(TermPtr, TermPtr)>) -> Cell,
         right_ptr: Option<Ptr>,
         right_ports: Option<(TermPtr, TermPtr)>,
-        right_fn: impl Fn(Option<(TermPtr, TermPtr)>, Option<Ptr>) -> Cell,
-        right_alloc: bool,
+        right_fn: impl Fn(Option<(TermPtr, TermPtr)>) -> Cell,
         free_ptrs: &mut FreePtrs,
         stats: &mut LocalStats,
     ) {
@@ -1059,33 +2835,13 @@ impl Runtime {
             let x3 = TermPtr::Ptr(self.alloc_var(store, stats));
             let x4 = TermPtr::Ptr(self.alloc_var(store, stats));
 
-            // duplicate left cell (we dont allocate it in the store)
-            let left_1_ptr = if left_alloc {
-                Some(free_ptrs.pop().unwrap()) // we know we have at least two (see above)
-            } else {
-                None
-            };
-            let left_0 = left_fn(Some((x1, x3)), left_1_ptr);
-            let left_0_ptr = if left_alloc {
-                Some(self.alloc_cell(store, Some(left_0), stats))
-            } else {
-                None
-            };
-            let left_1 = left_fn(Some((x4, x2)), left_0_ptr);
-
-            // duplicate right cell (we dont allocate it in the store)
-            let right_1_ptr = if right_alloc {
-                Some(free_ptrs.pop().unwrap()) // we know we have at least two (see above)
-            } else {
-                None
-            };
-            let right_0 = right_fn(Some((x4, x1)), left_1_ptr);
-            let right_0_ptr = if right_alloc {
-                Some(self.alloc_cell(store, Some(right_0), stats))
-            } else {
-                None
-            };
-            let right_1 = left_fn(Some((x2, x3)), left_0_ptr);
+            // duplicate both cells on the stack, they are only allocated
+            // in the store when bound to a var. Port `j` of the left copy
+            // `i` meets port `i` of the right copy `j`.
+            let left_0 = left_fn(Some((x1, x3)));
+            let left_1 = left_fn(Some((x4, x2)));
+            let right_0 = right_fn(Some((x1, x4)));
+            let right_1 = right_fn(Some((x3, x2)));
 
             match (left_ports, right_ports) {
                 (None, None) => unreachable!(),
@@ -1098,7 +2854,7 @@ impl Runtime {
                         None,
                         left_0,
                         right_p0,
-                        free_ptrs.split(3).into(),
+                        self.split_free_ptrs(free_ptrs, 3),
                     );
                     self.spawn_eval_cell_term(
                         scope,
@@ -1106,7 +2862,7 @@ impl Runtime {
                         None,
                         left_1,
                         right_p1,
-                        free_ptrs.split(2).into(),
+                        self.split_free_ptrs(free_ptrs, 2),
                     );
 
                     self.eval_cell_cell(
@@ -1123,7 +2879,7 @@ impl Runtime {
                         None,
                         right_0,
                         left_p0,
-                        free_ptrs.split(3).into(),
+                        self.split_free_ptrs(free_ptrs, 3),
                     );
                     self.spawn_eval_cell_term(
                         scope,
@@ -1131,7 +2887,7 @@ impl Runtime {
                         None,
                         right_1,
                         left_p1,
-                        free_ptrs.split(2).into(),
+                        self.split_free_ptrs(free_ptrs, 2),
                     );
 
                     self.eval_cell_cell(
@@ -1147,7 +2903,7 @@ impl Runtime {
                         None,
                         right_0,
                         left_p0,
-                        free_ptrs.split(4).into(),
+                        self.split_free_ptrs(free_ptrs, 4),
                     );
                     self.spawn_eval_cell_term(
                         scope,
@@ -1155,7 +2911,7 @@ impl Runtime {
                         None,
                         right_1,
                         left_p1,
-                        free_ptrs.split(3).into(),
+                        self.split_free_ptrs(free_ptrs, 3),
                     );
 
                     self.spawn_eval_cell_term(
@@ -1164,7 +2920,7 @@ impl Runtime {
                         None,
                         left_0,
                         right_p0,
-                        free_ptrs.split(2).into(),
+                        self.split_free_ptrs(free_ptrs, 2),
                     );
                     self.eval_cell_term(scope, store, None, left_1, right_p1, free_ptrs, stats);
                 }
@@ -1172,8 +2928,709 @@ impl Runtime {
         }
     }
 
+    // whether to log an event of `rule`, or of no rule when `None`: checked
+    // before building the arguments of the log, which are not cheap
+    #[inline]
+    fn logs(&self, rule: impl Into<Option<Rule>>) -> bool {
+        if !tracing::enabled!(tracing::Level::DEBUG) {
+            return false;
+        }
+        let rule = rule.into();
+        self.traces(rule)
+            && self
+                .sampler
+                .as_ref()
+                .is_none_or(|sampler| sampler.sample(rule))
+    }
+
+    #[inline]
+    fn log_json(&self, rule: &'static str, ptrs: [Option<Ptr>; 2]) {
+        if let Some(log) = &self.config.json_log {
+            log.rewrite(rule, self.thread_id(), ptrs);
+        }
+    }
+
+    // whether the rule filter keeps `rule`
+    #[inline]
+    fn traces(&self, rule: Option<Rule>) -> bool {
+        match (self.config.trace_rules, rule) {
+            (None, _) => true,
+            (Some(filter), Some(rule)) => filter.contains(rule),
+            (Some(_), None) => false,
+        }
+    }
+
     #[inline]
     fn thread_id(&self) -> usize {
-        return rayon::current_thread_index().unwrap();
+        // seeded evals run on the calling thread
+        return rayon::current_thread_index().unwrap_or(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use crate::strandal::{
+        ast::{self, Term as AstTerm},
+        divergence::{DivergenceConfig, DivergenceWarning},
+        logging::Rule,
+        net::{Net, NetBuilder},
+        num::Op,
+        parser::parse,
+        runtime::{EvalError, Runtime, RuntimeConfig, SplitPolicy},
+        store::Store,
+        term::{Cell, Label, TermPtr, TermRef},
+    };
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_max_cells() {
+        let mut net = copied_id(false);
+        let live = net.store.len();
+
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().max_cells(live));
+        match runtime.eval(&mut net) {
+            Err(EvalError::MaxCellsExceeded {
+                max_cells, growth, ..
+            }) => {
+                assert_eq!(max_cells, live);
+                assert_eq!(growth[0], ("LAM-DUP", 1));
+            }
+            result => panic!("expected max cells to be exceeded, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_max_interactions() {
+        // f = λa.(f a) applied to ε expands f forever without growing
+        let src = "def main(r) = @f ~ [* r];\ndef f(r) = r ~ (a b) & @f ~ [a b]";
+        let mut net = Net::new();
+        assert!(parse(src, &mut net));
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().max_interactions(1_000));
+        assert_eq!(
+            runtime.eval(&mut net),
+            Err(EvalError::MaxInteractionsExceeded(1_000))
+        );
+
+        // a net normal within the limit is left alone
+        let mut net = Net::new();
+        assert!(parse("def main(r) = (x x) ~ [(y y) r]", &mut net));
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().max_interactions(1));
+        assert_eq!(runtime.eval(&mut net), Ok(()));
+    }
+
+    #[test]
+    fn test_detect_divergence() {
+        let mut net = copied_id(true);
+        let live = net.store.len();
+        let config = DivergenceConfig {
+            growth_factor: 1,
+            min_live: 0,
+            abort: true,
+            ..Default::default()
+        };
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().detect_divergence(config));
+        match runtime.eval(&mut net) {
+            Err(EvalError::LikelyDivergent(DivergenceWarning::Growth { initial, .. })) => {
+                assert_eq!(initial, live)
+            }
+            result => panic!("expected a growth warning, got {:?}", result),
+        }
+
+        // without aborting, the eval ends and keeps the warnings
+        let mut net = copied_id(true);
+        let config = DivergenceConfig {
+            label_share: 0.5,
+            min_label_interactions: 1,
+            ..Default::default()
+        };
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().detect_divergence(config));
+        runtime.eval(&mut net).unwrap();
+        let warnings = runtime.divergence_warnings();
+        assert!(matches!(
+            warnings.as_slice(),
+            [DivergenceWarning::DominantLabel {
+                interactions: 1,
+                total: 1,
+                ..
+            }]
+        ));
+        assert!(net.normal_form().is_ok());
+    }
+
+    #[test]
+    fn test_leaks_after_eval() {
+        let mut net = Net::new();
+        let r = net.var();
+        let i1_var = net.var();
+        let i1 = net.lam(i1_var.0, i1_var.1);
+        let i2_var = net.var();
+        let i2 = net.lam(i2_var.0, i2_var.1);
+        let app = net.app(r.0, i2);
+        net.head(r.1);
+        net.eqn(i1, app);
+
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().detect_leaks());
+        runtime.eval(&mut net).unwrap();
+        assert!(net.leaks().is_empty());
+    }
+
+    #[test]
+    fn test_max_cells_not_exceeded() {
+        let mut net = copied_id(false);
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().max_cells(1_000));
+        assert_eq!(runtime.eval(&mut net), Ok(()));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_erase_subtree() {
+        let mut net = Net::new();
+        // a deep spine of lambdas erasing their bodies: λ*.λ*. ... λ*.*
+        let mut tree = TermPtr::Era;
+        for _ in 0..1_000 {
+            tree = net.lam(TermPtr::Era, tree).into();
+        }
+        net.eqn(TermPtr::Era, tree);
+
+        let mut runtime = Runtime::new();
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(runtime.stats.comm_era_lam(), 1_000);
+        assert_eq!(runtime.stats.anni_era_era(), 1_001);
+        assert!(net.leaks().is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_era_rules() {
+        // the rules counted erasing a term
+        fn erase(term: impl FnOnce(&mut Net) -> TermPtr) -> Vec<(&'static str, usize)> {
+            let mut net = Net::new();
+            let term = term(&mut net);
+            net.eqn(term, TermPtr::Era);
+            let mut runtime = Runtime::new();
+            runtime.eval(&mut net).unwrap();
+            let rules = runtime.stats.rules().into_iter();
+            rules.filter(|(_, count)| *count > 0).collect()
+        }
+        // a cell is erased, then the erasers on its ports
+        let (era, era_era) = (TermPtr::Era, ("ANNI ERA-ERA", 2));
+        assert_eq!(
+            erase(|net| net.lam(era, era).into()),
+            [era_era, ("COMM ERA-LAM", 1)]
+        );
+        assert_eq!(
+            erase(|net| net.app(era, era).into()),
+            [era_era, ("COMM ERA-APP", 1)]
+        );
+        assert_eq!(
+            erase(|net| net.dup(era, era).into()),
+            [era_era, ("COMM ERA-DUP", 1)]
+        );
+        assert_eq!(
+            erase(|net| net.op(Op::Add, era, era).into()),
+            [era_era, ("COMM ERA-OPS", 1)]
+        );
+        assert_eq!(erase(|net| net.num(7).into()), [("COMM ERA-NUM", 1)]);
+        assert_eq!(erase(|_| era), [("ANNI ERA-ERA", 1)]);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_split_policy() {
+        assert_eq!(SplitPolicy::Even.share(9, 3), 3);
+        assert_eq!(SplitPolicy::Fixed(2).share(9, 3), 2);
+        assert_eq!(SplitPolicy::Fixed(2).share(1, 3), 1);
+        assert_eq!(SplitPolicy::Keep.share(9, 3), 0);
+
+        for policy in [SplitPolicy::Even, SplitPolicy::Fixed(1), SplitPolicy::Keep] {
+            let mut net = copied_id(false);
+            let mut runtime = Runtime::with_config(RuntimeConfig::default().split_policy(policy));
+            runtime.eval(&mut net).unwrap();
+            assert_eq!(runtime.stats.comm_lam_dup(), 1);
+        }
+    }
+
+    #[test]
+    fn test_phases() {
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().time_phases());
+        // a coarse clock may round any phase down to zero, but the phases
+        // before the tasks are done never add up to more than the eval
+        for n in [100, 10] {
+            runtime.eval(&mut erased_ids(n)).unwrap();
+            let phases = runtime.phases();
+            assert!(phases.setup + phases.reduction <= runtime.elapsed());
+            let display = phases.to_string();
+            assert!(display.starts_with("PHASES  | setup: "));
+            assert!(display.contains(", reduction: ") && display.contains(", teardown: "));
+        }
+    }
+
+    #[test]
+    fn test_concurrency() {
+        assert_eq!(Runtime::new().concurrency(), None);
+
+        // a seeded eval queues every equation before running one at a time
+        let mut runtime =
+            Runtime::with_config(RuntimeConfig::default().seeded(7).track_concurrency());
+        runtime.eval(&mut erased_ids(100)).unwrap();
+        let concurrency = runtime.concurrency().unwrap();
+        assert!(concurrency.tasks >= 100);
+        assert_eq!((concurrency.threads, concurrency.peak_in_flight), (1, 1));
+        assert_eq!(concurrency.mean_in_flight, 1.0);
+        assert!(concurrency.peak_queued >= 100);
+        assert!(concurrency.mean_queued > 1.0);
+        assert!(concurrency.to_string().starts_with("TASKS   | tasks: "));
+
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().track_concurrency());
+        runtime.eval(&mut erased_ids(100)).unwrap();
+        let concurrency = runtime.concurrency().unwrap();
+        assert!(concurrency.tasks >= 100);
+        assert!((1..=concurrency.threads).contains(&concurrency.peak_in_flight));
+    }
+
+    #[test]
+    fn test_trace_rules() {
+        let runtime = Runtime::new();
+        assert!(Rule::ALL.into_iter().all(|rule| runtime.traces(Some(rule))));
+        assert!(runtime.traces(None));
+        // nothing is logged without a subscriber
+        assert!(!runtime.logs(Rule::Bind));
+
+        let config = RuntimeConfig::default().trace_rules(&["comm_app_dup", "bind"]);
+        let runtime = Runtime::with_config(config);
+        assert!(runtime.traces(Some(Rule::CommAppDup)) && runtime.traces(Some(Rule::Bind)));
+        assert!(!runtime.traces(Some(Rule::Connect)));
+        assert!(!runtime.traces(None));
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown rule `comm_app`")]
+    fn test_trace_unknown_rule() {
+        RuntimeConfig::default().trace_rules(&["comm_app"]);
+    }
+
+    #[test]
+    fn test_seeded() {
+        let run = |seed| {
+            let mut runtime = Runtime::with_config(RuntimeConfig::default().seeded(seed));
+            let heads = eval_src(THREE_DEFS, Net::new(), &mut runtime);
+            (runtime.schedule().to_vec(), heads)
+        };
+        let (schedule, heads) = run(7);
+        assert!(!schedule.is_empty());
+        assert_eq!(heads, eval_src(THREE_DEFS, Net::new(), &mut Runtime::new()));
+        for _ in 0..3 {
+            assert_eq!(run(7), (schedule.clone(), heads.clone()));
+        }
+        // other interleavings reach the same normal form
+        let schedules: Vec<_> = (0..8)
+            .map(run)
+            .map(|(schedule, other)| {
+                assert_eq!(other, heads);
+                schedule
+            })
+            .collect();
+        assert!(schedules.iter().any(|other| *other != schedule));
+    }
+
+    #[test]
+    fn test_sharded() {
+        // every dup commutes with a lambda, allocating from the shards of the
+        // workers running it
+        let src = "
+            def first(a, b) = (x (y (x y))) ~ {a b};
+            def second(c, d) = (z (* z)) ~ {c d};
+            def third(e, f) = (u (v (v u))) ~ {e f};
+        ";
+        let run = |mut net: Net| {
+            assert!(parse(src, &mut net));
+            let loaded = net.store.next();
+            Runtime::new().eval(&mut net).unwrap();
+            assert!(net.store.next() > loaded);
+            assert!(net.leaks().is_empty());
+            normal_form(&net)
+        };
+        assert_eq!(run(Net::with_shards(1 << 12, 4)), run(Net::new()));
+    }
+
+    #[test]
+    fn test_checked_store() {
+        let src = "
+            def first(a) = (x x) ~ [(y y) a];
+            def second(b) = {c d} ~ (e e) & [c [d b]] ~ (* (f f));
+            def third(r) = 2 ~ (+ 3 s) & s ~ (? (0 (t t)) r);
+        ";
+        let run = |net| eval_src(src, net, &mut Runtime::new());
+        let checked = Net::with_store(Store::with_capacity(1 << 12).checked());
+        assert!(checked.store.is_checked());
+        assert_eq!(run(checked), run(Net::new()));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_memoize() {
+        let mut net = Net::new();
+        let heads: Vec<_> = (0..8)
+            .map(|_| {
+                let r = net.var();
+                let x = net.var();
+                let id_x = net.lam(x.0, x.1);
+                let y = net.var();
+                let id_y = net.lam(y.0, y.1);
+                let app = net.app(r.0, id_y);
+                net.eqn(id_x, app);
+                r.1
+            })
+            .collect();
+        heads.into_iter().for_each(|head| net.head(head));
+
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().memoize());
+        runtime.eval(&mut net).unwrap();
+        let stats = &runtime.stats;
+        assert_eq!(stats.memo_hits() + stats.memo_misses(), 8);
+        assert!(stats.memo_misses() >= 1);
+        assert_eq!(runtime.memo().unwrap().len(), 1);
+        for head in net.normal_form().unwrap() {
+            let head = head.to_string();
+            assert!(head.starts_with("(λ x") && !head.contains('@'), "{}", head);
+        }
+        assert!(net.leaks().is_empty());
+    }
+
+    #[test]
+    fn test_on_head_bound() {
+        let mut net = Net::new();
+        assert!(parse("def main(r, *) = (x x) ~ [r (y y)]", &mut net));
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        net.on_head_bound(0, move |store, value| {
+            let TermPtr::Ptr(ptr) = value else {
+                panic!("expected a cell, got {:?}", value);
+            };
+            assert!(matches!(store.get(ptr), Some(TermRef::Cell(Cell::Lam(_)))));
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        let erased = hits.clone();
+        net.on_head_bound(1, move |_, value| {
+            assert_eq!(value, TermPtr::Era);
+            erased.fetch_add(1, Ordering::Relaxed);
+        });
+
+        Runtime::new().eval(&mut net).unwrap();
+        assert_eq!(hits.load(Ordering::Relaxed), 2);
+        assert!(net.head_callbacks.is_empty());
+    }
+
+    // evaluate on a single thread, which runs the spawned equations last
+    // first, so the body is evaluated in a known order
+    fn eval_sequential(body: Vec<(AstTerm, AstTerm)>, heads: &[&str]) -> (Net, Runtime) {
+        let head = heads.iter().map(|name| AstTerm::var(name)).collect();
+        let mut net = Net::from_ast(&ast::Net { head, body }).unwrap();
+        let mut runtime = Runtime::new();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        pool.install(|| runtime.eval(&mut net)).unwrap();
+        (net, runtime)
+    }
+
+    fn normal_form(net: &Net) -> Vec<String> {
+        let heads = net.normal_form().unwrap();
+        heads.iter().map(|head| head.to_string()).collect()
+    }
+
+    #[test]
+    fn test_connect_then_bind() {
+        let id = |name| AstTerm::lam(AstTerm::var(name), AstTerm::var(name));
+        // `a ~ b` runs first, while neither var is bound yet
+        let (net, runtime) = eval_sequential(
+            vec![
+                (AstTerm::var("a"), id("x")),
+                (AstTerm::var("b"), AstTerm::app(AstTerm::var("r"), id("y"))),
+                (AstTerm::var("a"), AstTerm::var("b")),
+            ],
+            &["r"],
+        );
+        let stats = &runtime.stats;
+        assert_eq!(
+            (stats.connects(), stats.binds(), stats.comm_app_lam()),
+            (2, 4, 1)
+        );
+        assert_eq!(normal_form(&net), ["(λ x5 x5)"]);
+    }
+
+    #[test]
+    fn test_comm_lam_dup() {
+        let lam = AstTerm::lam(AstTerm::var("x"), AstTerm::var("x"));
+        let dup = AstTerm::dup(AstTerm::var("a"), AstTerm::var("b"));
+        let (net, runtime) = eval_sequential(vec![(lam, dup)], &["a", "b"]);
+        let stats = &runtime.stats;
+        assert_eq!((stats.comm_lam_dup(), stats.anni_dup_dup()), (1, 1));
+        assert_eq!(normal_form(&net), ["(λ x5 x5)", "(λ x8 x8)"]);
+    }
+
+    #[test]
+    fn test_comm_dup_dup() {
+        let dup = |p0, p1, lbl| {
+            AstTerm::Dup(
+                Box::new(AstTerm::var(p0)),
+                Box::new(AstTerm::var(p1)),
+                Some(Label::User(lbl)),
+            )
+        };
+        let (net, runtime) = eval_sequential(
+            vec![(dup("a", "b", 1), dup("c", "d", 2))],
+            &["a", "b", "c", "d"],
+        );
+        assert_eq!(
+            (runtime.stats.comm_dup_dup(), runtime.stats.anni_dup_dup()),
+            (1, 0)
+        );
+        // each copy of a duplicator meets one port of each copy of the other
+        assert_eq!(
+            normal_form(&net),
+            [
+                "(δ x6 x9 {2})",
+                "(δ x8 x7 {2})",
+                "(δ x6 x8 {1})",
+                "(δ x9 x7 {1})"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fresh_dup_label() {
+        let mut net = Net::new();
+        let (a, b, c, d) = (net.var(), net.var(), net.var(), net.var());
+        let dup = net.fresh_dup(a.0, b.0);
+        // a superposition labelled after the slot the fresh dup landed in
+        let sup = net.sup(dup.ptr().index(), c.0, d.0);
+        for head in [a.1, b.1, c.1, d.1] {
+            net.head(head);
+        }
+        net.eqn(dup, sup);
+
+        let mut runtime = Runtime::new();
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(
+            (runtime.stats.comm_dup_dup(), runtime.stats.anni_dup_dup()),
+            (1, 0)
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_eval_in() {
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().threads(2));
+        for n in 1..=3 {
+            let mut net = erased_ids(n);
+            runtime.eval_in(&mut net).unwrap();
+            // only this eval is counted
+            assert_eq!(runtime.stats.comm_era_lam(), n);
+            assert_eq!(runtime.threads, 2);
+            assert!(net.leaks().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_eval_in_reuses_slots() {
+        let mut runtime = Runtime::new();
+        let mut net = erased_ids(100);
+        runtime.eval_in(&mut net).unwrap();
+        assert_eq!(runtime.reused_slots(), 0);
+
+        // λx.x copied by a dup in the same net: the copies take the slots
+        // the erased identities left free instead of growing the store
+        let x = net.var();
+        let lam = net.lam(x.0, x.1);
+        let (a, b) = (net.var(), net.var());
+        let dup = net.dup(a.0, b.0);
+        net.head(a.1);
+        net.head(b.1);
+        net.eqn(lam, dup);
+        let next = net.store.next();
+        runtime.eval_in(&mut net).unwrap();
+        assert!(runtime.reused_slots() > 0);
+        assert_eq!(net.store.next(), next);
+        assert_eq!(normal_form(&net).len(), 2);
+        assert!(net.leaks().is_empty());
+
+        // a plain eval grows the store instead
+        let mut net = copied_id(false);
+        let next = net.store.next();
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(runtime.reused_slots(), 0);
+        assert!(net.store.next() > next);
+    }
+
+    // a full tree of applications of `depth` against one of lambdas
+    fn tree_redex(depth: usize) -> Net {
+        fn tree(net: &mut Net, depth: usize, lam: bool) -> TermPtr {
+            if depth == 0 {
+                return TermPtr::Era;
+            }
+            let (p0, p1) = (tree(net, depth - 1, lam), tree(net, depth - 1, lam));
+            match lam {
+                true => net.lam(p0, p1).into(),
+                false => net.app(p0, p1).into(),
+            }
+        }
+        let mut net = Net::new();
+        let (lams, apps) = (tree(&mut net, depth, true), tree(&mut net, depth, false));
+        net.eqn(apps, lams);
+        net
+    }
+
+    // `n` identities each erased by its own equation
+    fn erased_ids(n: usize) -> Net {
+        let mut net = Net::new();
+        for _ in 0..n {
+            let x = net.var();
+            let id = net.lam(x.0, x.1);
+            net.eqn(id, TermPtr::Era);
+        }
+        net
+    }
+
+    // λx.x copied by a dup, growing the net, with a fresh label or none
+    fn copied_id(fresh: bool) -> Net {
+        let mut net = Net::new();
+        let x = net.var();
+        let lam = net.lam(x.0, x.1);
+        let (a, b) = (net.var(), net.var());
+        let dup = match fresh {
+            true => net.fresh_dup(a.0, b.0),
+            false => net.dup(a.0, b.0),
+        };
+        net.head(a.1);
+        net.head(b.1);
+        net.eqn(lam, dup);
+        net
+    }
+
+    // three independent definitions, whose equations can be run in any order
+    const THREE_DEFS: &str = "
+        def first(a) = (x x) ~ [(y y) a];
+        def second(b) = (t (* t)) ~ [(c c) [(d (* d)) b]];
+        def third(e) = (* (f f)) ~ [(g g) [(h (* h)) e]];
+    ";
+
+    // parse `src` into `net` and evaluate it, returning its normal form
+    fn eval_src(src: &str, mut net: Net, runtime: &mut Runtime) -> Vec<String> {
+        assert!(parse(src, &mut net));
+        runtime.eval(&mut net).unwrap();
+        assert!(net.leaks().is_empty());
+        normal_form(&net)
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_sequential_below() {
+        let config = RuntimeConfig::default()
+            .threads(2)
+            .sequential_below(4)
+            .track_concurrency();
+        // few pending tasks at a time: every task runs inline
+        let mut net = erased_ids(100);
+        let mut runtime = Runtime::with_config(config);
+        runtime.eval(&mut net).unwrap();
+        let tasks = runtime.concurrency().unwrap().tasks;
+        assert!(tasks >= 100);
+        assert_eq!(runtime.inlined_tasks(), tasks);
+        assert!(net.leaks().is_empty());
+
+        // its rewrites nest deeper than 2 × 4 tasks
+        let mut net = tree_redex(12);
+        runtime.eval_in(&mut net).unwrap();
+        assert_eq!(runtime.stats.comm_app_lam(), (1 << 12) - 1);
+        let tasks = runtime.concurrency().unwrap().tasks;
+        assert!((1..tasks).contains(&runtime.inlined_tasks()));
+        assert!(net.leaks().is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_max_tasks() {
+        let config = RuntimeConfig::default().max_tasks(4).track_concurrency();
+        let mut runtime = Runtime::with_config(config.clone());
+        let mut net = tree_redex(12);
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(runtime.stats.comm_app_lam(), (1 << 12) - 1);
+        assert!(runtime.deferred_tasks() > 0);
+        // the deferred tasks were never queued in rayon
+        assert!(runtime.concurrency().unwrap().peak_queued <= 4);
+        assert!(net.leaks().is_empty());
+
+        // the roots past the cap are deferred too, and so are seeded tasks
+        let mut net = erased_ids(100);
+        let mut runtime = Runtime::with_config(config.seeded(7));
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(runtime.stats.comm_era_lam(), 100);
+        assert!(runtime.deferred_tasks() >= 96);
+        assert!(net.leaks().is_empty());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_affinity() {
+        use crate::strandal::affinity::{allowed_cores, Affinity};
+
+        let config = RuntimeConfig::default().threads(2).affinity(Affinity::Pinned);
+        let mut runtime = Runtime::with_config(config);
+        let pinned = runtime
+            .pool
+            .as_ref()
+            .unwrap()
+            .broadcast(|_| allowed_cores());
+        let cores = Affinity::Pinned.cores(Some(2));
+        assert_eq!(pinned, cores.iter().map(|core| vec![*core]).collect::<Vec<_>>());
+
+        let mut net = tree_redex(4);
+        runtime.eval(&mut net).unwrap();
+        assert_eq!(runtime.threads, 2);
+        assert!(net.leaks().is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_eval_all() {
+        let srcs = ["def main(r) = (x x) ~ [r (y y)]", "def main(r) = 2 ~ (+ 3 r)"];
+        let mut nets: Vec<_> = srcs
+            .iter()
+            .map(|src| {
+                let mut net = Net::new();
+                assert!(parse(src, &mut net));
+                net
+            })
+            .collect();
+        // a reference that was declared and never defined
+        let mut net = Net::new();
+        let missing = net.declare_ref("missing");
+        let r = net.var();
+        let reference = net.reference(missing);
+        net.head(r.1);
+        net.eqn(reference, r.0);
+        nets.push(net);
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().threads(2));
+        let reports = runtime.eval_all(&mut nets);
+        assert_eq!(reports.len(), 3);
+        assert!(reports[0].result.is_ok());
+        assert!(reports[0].stats.rewrites() > 0);
+        assert_eq!(reports[1].stats.oper_num(), 1);
+        assert_eq!(nets[1].normal_form().unwrap()[0].to_string(), "5");
+        assert!(matches!(
+            reports[2].result,
+            Err(EvalError::UndefinedRef(ref name)) if name == "missing"
+        ));
+        assert_eq!(runtime.stats.rewrites(), 0);
     }
 }
@@ -0,0 +1,147 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use super::{
+    net::Equation,
+    store::Ptr,
+    term::{Cell, TermPtr},
+};
+
+type Task<'scope> = Box<dyn for<'a> FnOnce(&Spawner<'a, 'scope>) + Send + 'scope>;
+
+/// Where the runtime spawns the tasks of an evaluation: onto rayon's thread
+/// pool, or into a [`Seeded`] queue that runs them one at a time.
+pub(crate) enum Spawner<'a, 'scope> {
+    Rayon(&'a rayon::Scope<'scope>),
+    Seeded(&'a Seeded<'scope>),
+}
+
+impl<'a, 'scope> Spawner<'a, 'scope> {
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: for<'b> FnOnce(&Spawner<'b, 'scope>) + Send + 'scope,
+    {
+        match self {
+            Spawner::Rayon(scope) => scope.spawn(move |scope| task(&Spawner::Rayon(scope))),
+            Spawner::Seeded(seeded) => seeded.tasks.lock().unwrap().push(Box::new(task)),
+        }
+    }
+}
+
+/// A scheduler running one task at a time, picking the next among the
+/// pending ones with a PRNG. The same seed replays the same interleaving,
+/// and the picks are recorded to compare runs.
+pub(crate) struct Seeded<'scope> {
+    state: Mutex<u64>,
+    tasks: Mutex<Vec<Task<'scope>>>,
+    picks: Mutex<Vec<usize>>,
+}
+
+impl<'scope> Seeded<'scope> {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Mutex::new(seed),
+            tasks: Mutex::new(Vec::new()),
+            picks: Mutex::new(Vec::new()),
+        }
+    }
+
+    // splitmix64
+    fn next(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        *state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Run pending tasks, and the ones they spawn, until none is left.
+    pub fn run(&self) {
+        loop {
+            let task = {
+                let mut tasks = self.tasks.lock().unwrap();
+                if tasks.is_empty() {
+                    break;
+                }
+                let pick = (self.next() % tasks.len() as u64) as usize;
+                self.picks.lock().unwrap().push(pick);
+                tasks.swap_remove(pick)
+            };
+            task(&Spawner::Seeded(self));
+        }
+    }
+
+    /// The index picked among the pending tasks at each step.
+    pub fn picks(&self) -> Vec<usize> {
+        self.picks.lock().unwrap().clone()
+    }
+}
+
+/// A task held back by [`Overflow`], as the arguments it would have been
+/// spawned with.
+pub(crate) enum Deferred {
+    Equation(Equation),
+    CellTerm(Option<Ptr>, Cell, TermPtr),
+}
+
+/// Caps the spawned tasks not ended yet. Past the cap, a task is pushed
+/// here instead, and whoever pushed it runs the ones left before ending:
+/// the queue is empty once every task ended.
+pub(crate) struct Overflow {
+    max_tasks: usize,
+    outstanding: AtomicUsize,
+    deferred: Mutex<Vec<Deferred>>,
+    len: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl Overflow {
+    pub fn new(max_tasks: usize) -> Self {
+        Self {
+            max_tasks,
+            outstanding: AtomicUsize::new(0),
+            deferred: Mutex::new(Vec::new()),
+            len: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+        }
+    }
+
+    /// Count a task about to be spawned, returning false when it must be
+    /// deferred instead.
+    pub fn reserve(&self) -> bool {
+        if self.outstanding.fetch_add(1, Ordering::Relaxed) < self.max_tasks {
+            return true;
+        }
+        self.outstanding.fetch_sub(1, Ordering::Relaxed);
+        false
+    }
+
+    /// A task counted by [`Overflow::reserve`] ended.
+    pub fn release(&self) {
+        self.outstanding.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn push(&self, task: Deferred) {
+        self.deferred.lock().unwrap().push(task);
+        self.len.fetch_add(1, Ordering::Relaxed);
+        self.total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn pop(&self) -> Option<Deferred> {
+        // a task only misses the ones pushed by others, who run them
+        if self.len.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+        let task = self.deferred.lock().unwrap().pop()?;
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(task)
+    }
+
+    /// How many tasks were deferred so far.
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+}
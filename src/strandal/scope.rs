@@ -0,0 +1,331 @@
+use std::{collections::HashMap, fmt::Display};
+
+use super::{
+    net::NetBuilder,
+    num::Op,
+    refs::RefId,
+    store::{Index, Ptr},
+    term::{CellUse, TermPtr},
+    var::VarUse,
+};
+
+/// The free ports a scoped fragment hands back to its caller, see
+/// [`NetBuilder::scope`].
+pub trait Interface {
+    /// Push the pointer of every var use and cell this interface exposes.
+    fn ports(&self, ports: &mut Vec<Ptr>);
+}
+
+impl Interface for () {
+    fn ports(&self, _: &mut Vec<Ptr>) {}
+}
+
+impl Interface for VarUse {
+    fn ports(&self, ports: &mut Vec<Ptr>) {
+        ports.push(self.ptr());
+    }
+}
+
+impl Interface for CellUse {
+    fn ports(&self, ports: &mut Vec<Ptr>) {
+        ports.push(self.ptr());
+    }
+}
+
+impl Interface for TermPtr {
+    fn ports(&self, ports: &mut Vec<Ptr>) {
+        if let TermPtr::Ptr(ptr) = self {
+            ports.push(*ptr);
+        }
+    }
+}
+
+impl<T: Interface, const N: usize> Interface for [T; N] {
+    fn ports(&self, ports: &mut Vec<Ptr>) {
+        self.iter().for_each(|port| port.ports(ports));
+    }
+}
+
+impl<T: Interface> Interface for Vec<T> {
+    fn ports(&self, ports: &mut Vec<Ptr>) {
+        self.iter().for_each(|port| port.ports(ports));
+    }
+}
+
+impl<A: Interface, B: Interface> Interface for (A, B) {
+    fn ports(&self, ports: &mut Vec<Ptr>) {
+        self.0.ports(ports);
+        self.1.ports(ports);
+    }
+}
+
+impl<A: Interface, B: Interface, C: Interface> Interface for (A, B, C) {
+    fn ports(&self, ports: &mut Vec<Ptr>) {
+        self.0.ports(ports);
+        self.1.ports(ports);
+        self.2.ports(ports);
+    }
+}
+
+/// A fragment that does not close over its internal wiring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopeError {
+    /// The var is neither wired twice in the scope nor exposed.
+    UnpairedVar(Ptr),
+    /// The var is wired or exposed more than twice.
+    OverusedVar(Ptr),
+    /// The cell is neither wired in the scope nor exposed.
+    UnwiredCell(Ptr),
+    /// The cell is wired or exposed more than once.
+    OverusedCell(Ptr),
+}
+
+impl Display for ScopeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScopeError::UnpairedVar(ptr) => write!(f, "var {} is used only once", ptr),
+            ScopeError::OverusedVar(ptr) => write!(f, "var {} is used more than twice", ptr),
+            ScopeError::UnwiredCell(ptr) => write!(f, "cell {} is never wired", ptr),
+            ScopeError::OverusedCell(ptr) => write!(f, "cell {} is wired more than once", ptr),
+        }
+    }
+}
+
+impl std::error::Error for ScopeError {}
+
+/// A [`NetBuilder`] for a fragment of a net, counting how the vars and
+/// cells allocated in it are wired. Ports built outside the scope pass
+/// through unchecked.
+pub struct Scope<'a, B: NetBuilder> {
+    builder: &'a mut B,
+    // wirings of each var or cell allocated in the scope
+    vars: HashMap<Ptr, u8>,
+    cells: HashMap<Ptr, u8>,
+}
+
+impl<'a, B: NetBuilder> Scope<'a, B> {
+    pub(crate) fn new(builder: &'a mut B) -> Self {
+        Self {
+            builder,
+            vars: HashMap::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    fn wire(&mut self, term_ptr: TermPtr) {
+        let TermPtr::Ptr(ptr) = term_ptr else {
+            return;
+        };
+        if let Some(uses) = self.vars.get_mut(&ptr) {
+            *uses += 1;
+        } else if let Some(uses) = self.cells.get_mut(&ptr) {
+            *uses += 1;
+        }
+    }
+
+    fn cell<const N: usize>(&mut self, cell: CellUse, ports: [TermPtr; N]) -> CellUse {
+        ports.into_iter().for_each(|port| self.wire(port));
+        self.cells.insert(cell.ptr(), 0);
+        cell
+    }
+
+    // wire the interface ports, then check every var and cell of the scope
+    pub(crate) fn close<I: Interface>(mut self, interface: I) -> Result<I, ScopeError> {
+        let mut ports = Vec::new();
+        interface.ports(&mut ports);
+        ports
+            .into_iter()
+            .for_each(|ptr| self.wire(TermPtr::Ptr(ptr)));
+        let mut errors: Vec<_> = self
+            .vars
+            .iter()
+            .filter_map(|(ptr, uses)| match uses {
+                2 => None,
+                0 | 1 => Some(ScopeError::UnpairedVar(*ptr)),
+                _ => Some(ScopeError::OverusedVar(*ptr)),
+            })
+            .chain(self.cells.iter().filter_map(|(ptr, uses)| match uses {
+                1 => None,
+                0 => Some(ScopeError::UnwiredCell(*ptr)),
+                _ => Some(ScopeError::OverusedCell(*ptr)),
+            }))
+            .collect();
+        errors.sort_by_key(|err| match err {
+            ScopeError::UnpairedVar(ptr)
+            | ScopeError::OverusedVar(ptr)
+            | ScopeError::UnwiredCell(ptr)
+            | ScopeError::OverusedCell(ptr) => ptr.index(),
+        });
+        match errors.into_iter().next() {
+            None => Ok(interface),
+            Some(err) => Err(err),
+        }
+    }
+}
+
+impl<'a, B: NetBuilder> NetBuilder for Scope<'a, B> {
+    fn head<T: Into<TermPtr>>(&mut self, term_ptr: T) {
+        let term_ptr = term_ptr.into();
+        self.wire(term_ptr);
+        self.builder.head(term_ptr);
+    }
+
+    fn var(&mut self) -> (VarUse, VarUse) {
+        let var = self.builder.var();
+        self.vars.insert(var.0.ptr(), 0);
+        var
+    }
+
+    fn lam<T1, T2>(&mut self, binding: T1, body: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (binding, body) = (binding.into(), body.into());
+        let lam = self.builder.lam(binding, body);
+        self.cell(lam, [binding, body])
+    }
+
+    fn app<T1, T2>(&mut self, arg: T1, result: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (arg, result) = (arg.into(), result.into());
+        let app = self.builder.app(arg, result);
+        self.cell(app, [arg, result])
+    }
+
+    fn dup<T1, T2>(&mut self, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (left, right) = (left.into(), right.into());
+        let dup = self.builder.dup(left, right);
+        self.cell(dup, [left, right])
+    }
+
+    fn fresh_dup<T1, T2>(&mut self, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (left, right) = (left.into(), right.into());
+        let dup = self.builder.fresh_dup(left, right);
+        self.cell(dup, [left, right])
+    }
+
+    fn labeled_dup<T1, T2>(&mut self, label: Index, left: T1, right: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (left, right) = (left.into(), right.into());
+        let dup = self.builder.labeled_dup(label, left, right);
+        self.cell(dup, [left, right])
+    }
+
+    fn num(&mut self, value: u32) -> CellUse {
+        let num = self.builder.num(value);
+        self.cell(num, [])
+    }
+
+    fn op<T1, T2>(&mut self, op: Op, right: T1, result: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (right, result) = (right.into(), result.into());
+        let op = self.builder.op(op, right, result);
+        self.cell(op, [right, result])
+    }
+
+    fn swi<T1, T2>(&mut self, branches: T1, result: T2) -> CellUse
+    where
+        T1: Into<TermPtr>,
+        T2: Into<TermPtr>,
+    {
+        let (branches, result) = (branches.into(), result.into());
+        let swi = self.builder.swi(branches, result);
+        self.cell(swi, [branches, result])
+    }
+
+    fn reference(&mut self, id: RefId) -> CellUse {
+        let reference = self.builder.reference(id);
+        self.cell(reference, [])
+    }
+
+    fn era(&mut self) -> TermPtr {
+        self.builder.era()
+    }
+
+    fn eqn<T1: Into<TermPtr>, T2: Into<TermPtr>>(&mut self, left: T1, right: T2) {
+        let (left, right) = (left.into(), right.into());
+        self.wire(left);
+        self.wire(right);
+        self.builder.eqn(left, right);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        runtime::Runtime,
+        scope::ScopeError,
+        term::{CellUse, TermPtr},
+        var::VarUse,
+    };
+
+    // the identity function, exposed as its unwired cell
+    fn id(b: &mut impl NetBuilder) -> CellUse {
+        b.scope(|b| {
+            let x = b.var();
+            b.lam(x.0, x.1)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_scope() {
+        let mut net = Net::new();
+        // an application waiting for its function, exposing the result
+        let (app, result): (CellUse, VarUse) = net
+            .scope(|b| {
+                let r = b.var();
+                let arg = id(b);
+                (b.app(r.0, arg), r.1)
+            })
+            .unwrap();
+        let f = id(&mut net);
+        net.eqn(f, app);
+        net.head(result);
+
+        Runtime::new().eval(&mut net).unwrap();
+        assert_eq!(net.normal_form().unwrap()[0].to_string(), "(λ x1 x1)");
+    }
+
+    #[test]
+    fn test_scope_errors() {
+        let mut net = Net::new();
+        let mut unpaired = None;
+        let err = net
+            .scope(|b| {
+                let x = b.var();
+                unpaired = Some(x.0.ptr());
+                b.lam(x.0, TermPtr::Era)
+            })
+            .unwrap_err();
+        assert_eq!(err, ScopeError::UnpairedVar(unpaired.unwrap()));
+
+        let mut unwired = None;
+        let err = net
+            .scope(|b| {
+                let lam = b.lam(TermPtr::Era, TermPtr::Era);
+                unwired = Some(lam.ptr());
+            })
+            .unwrap_err();
+        assert_eq!(err, ScopeError::UnwiredCell(unwired.unwrap()));
+    }
+}
@@ -0,0 +1,169 @@
+//! Build and evaluate nets from [rhai](https://rhai.rs) scripts, to generate
+//! families of nets without writing Rust:
+//!
+//! ```text
+//! // `n` identities, each applied to another one
+//! let net = net();
+//! for i in 0..n {
+//!     let x = net.var();
+//!     let y = net.var();
+//!     let r = net.var();
+//!     net.eqn(net.lam(x[0], x[1]), net.app(net.lam(y[0], y[1]), r[0]));
+//!     net.head(r[1]);
+//! }
+//! net.eval();
+//! print(net.normal_form());
+//! ```
+//!
+//! A net is built with the [`NetBuilder`] methods, where `var` returns the
+//! two uses of the var as an array. `parse` loads a book into the net,
+//! `eval` reduces it and returns the number of interactions, and
+//! `normal_form` reads its heads back as strings.
+
+use std::{cell::RefCell, rc::Rc};
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Scope};
+
+use super::{
+    net::{Net, NetBuilder},
+    parser::parse,
+    runtime::Runtime,
+    term::TermPtr,
+};
+
+/// A net shared between the script values referring to it.
+pub type ScriptNet = Rc<RefCell<Net>>;
+
+/// An engine with the net construction functions registered.
+pub fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .register_type_with_name::<ScriptNet>("Net")
+        .register_type_with_name::<TermPtr>("Term")
+        .register_fn("net", || Rc::new(RefCell::new(Net::new())))
+        .register_fn("var", |net: &mut ScriptNet| -> Array {
+            let (left, right) = net.borrow_mut().var();
+            vec![
+                Dynamic::from(TermPtr::from(left)),
+                Dynamic::from(TermPtr::from(right)),
+            ]
+        })
+        .register_fn(
+            "lam",
+            |net: &mut ScriptNet, binding: TermPtr, body: TermPtr| {
+                TermPtr::from(net.borrow_mut().lam(binding, body))
+            },
+        )
+        .register_fn(
+            "app",
+            |net: &mut ScriptNet, arg: TermPtr, result: TermPtr| {
+                TermPtr::from(net.borrow_mut().app(arg, result))
+            },
+        )
+        .register_fn(
+            "dup",
+            |net: &mut ScriptNet, left: TermPtr, right: TermPtr| {
+                TermPtr::from(net.borrow_mut().dup(left, right))
+            },
+        )
+        .register_fn(
+            "fresh_dup",
+            |net: &mut ScriptNet, left: TermPtr, right: TermPtr| {
+                TermPtr::from(net.borrow_mut().fresh_dup(left, right))
+            },
+        )
+        .register_fn("era", |net: &mut ScriptNet| net.borrow_mut().era())
+        .register_fn(
+            "eqn",
+            |net: &mut ScriptNet, left: TermPtr, right: TermPtr| net.borrow_mut().eqn(left, right),
+        )
+        .register_fn("head", |net: &mut ScriptNet, term: TermPtr| {
+            net.borrow_mut().head(term)
+        })
+        .register_fn(
+            "parse",
+            |net: &mut ScriptNet, src: &str| -> Result<(), Box<EvalAltResult>> {
+                match parse(src, &mut net.borrow_mut()) {
+                    true => Ok(()),
+                    false => Err("could not parse the book".into()),
+                }
+            },
+        )
+        .register_fn(
+            "eval",
+            |net: &mut ScriptNet| -> Result<i64, Box<EvalAltResult>> {
+                let mut runtime = Runtime::new();
+                runtime
+                    .eval(&mut net.borrow_mut())
+                    .map_err(|err| err.to_string())?;
+                Ok(runtime.stats.interactions() as i64)
+            },
+        )
+        .register_fn(
+            "normal_form",
+            |net: &mut ScriptNet| -> Result<Array, Box<EvalAltResult>> {
+                let heads = net.borrow().normal_form().map_err(|err| err.to_string())?;
+                Ok(heads
+                    .iter()
+                    .map(|head| Dynamic::from(head.to_string()))
+                    .collect())
+            },
+        );
+    engine
+}
+
+/// Run `script` with `vars` defined as constants, returning its value.
+pub fn run(script: &str, vars: Vec<(String, Dynamic)>) -> Result<Dynamic, Box<EvalAltResult>> {
+    let mut scope = Scope::new();
+    for (name, value) in vars {
+        scope.push_constant_dynamic(name, value);
+    }
+    engine().eval_with_scope(&mut scope, script)
+}
+
+/// Run `script` and take the net it evaluates to.
+pub fn build(script: &str, vars: Vec<(String, Dynamic)>) -> Result<Net, Box<EvalAltResult>> {
+    let value = run(script, vars)?;
+    let type_name = value.type_name().to_string();
+    let net = value
+        .try_cast::<ScriptNet>()
+        .ok_or_else(|| format!("the script returned a {}, not a net", type_name))?;
+    Rc::try_unwrap(net)
+        .map(RefCell::into_inner)
+        .map_err(|_| "the net is still referenced by the script".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use rhai::Dynamic;
+
+    use super::{build, run};
+
+    #[test]
+    fn test_script() {
+        let script = "
+            let net = net();
+            for i in 0..n {
+                let x = net.var();
+                let r = net.var();
+                let y = net.var();
+                net.eqn(net.lam(x[0], x[1]), net.app(net.lam(y[0], y[1]), r[0]));
+                net.head(r[1]);
+            }
+            net.eval();
+            net.normal_form()
+        ";
+        let heads = run(script, vec![("n".to_string(), Dynamic::from(3_i64))])
+            .unwrap()
+            .into_array()
+            .unwrap();
+        assert_eq!(heads.len(), 3);
+
+        let net = build(
+            "let net = net(); net.parse(\"def id(x) = (y y) ~ x;\"); net",
+            vec![],
+        )
+        .unwrap();
+        assert!(!net.head.is_empty());
+    }
+}
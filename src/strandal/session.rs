@@ -0,0 +1,299 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+};
+
+use super::{
+    ast::{AstError, Book},
+    net::Net,
+    parser::parse_ast,
+    readback::{NotNormal, OwnedTerm},
+    runtime::{EvalError, Runtime, RuntimeConfig},
+    term::TermPtr,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionError {
+    Parse,
+    Ast(AstError),
+    Eval(EvalError),
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::Parse => write!(f, "could not parse the definitions"),
+            SessionError::Ast(err) => write!(f, "{}", err),
+            SessionError::Eval(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl From<AstError> for SessionError {
+    fn from(err: AstError) -> Self {
+        SessionError::Ast(err)
+    }
+}
+
+impl From<EvalError> for SessionError {
+    fn from(err: EvalError) -> Self {
+        SessionError::Eval(err)
+    }
+}
+
+// definitions connected through shared vars, evaluated together
+struct Component {
+    defs: BTreeSet<String>,
+    // the head ports of each definition, in the session net
+    heads: HashMap<String, Vec<TermPtr>>,
+}
+
+/// A book kept evaluated across edits, for a REPL or an editor.
+///
+/// Definitions are added or replaced one at a time, and [`Session::eval`]
+/// only evaluates again the groups of definitions connected through shared
+/// vars that changed since the last eval. Every group is evaluated in the
+/// same net, whose store stays allocated for the life of the session: the
+/// slots of a group evaluated again are freed first.
+pub struct Session {
+    book: Book,
+    net: Net,
+    config: RuntimeConfig,
+    components: Vec<Component>,
+    // definitions added, replaced or removed since the last eval
+    dirty: HashSet<String>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::with_config(RuntimeConfig::default())
+    }
+
+    pub fn with_config(config: RuntimeConfig) -> Self {
+        Session {
+            book: Book::default(),
+            net: Net::new(),
+            config,
+            components: Vec::new(),
+            dirty: HashSet::new(),
+        }
+    }
+
+    pub fn book(&self) -> &Book {
+        &self.book
+    }
+
+    pub fn net(&self) -> &Net {
+        &self.net
+    }
+
+    /// Add the definitions of `src`, replacing the ones with the same name.
+    /// Returns their names.
+    pub fn define(&mut self, src: &str) -> Result<Vec<String>, SessionError> {
+        let book = parse_ast(src).ok_or(SessionError::Parse)?;
+        let mut names = Vec::new();
+        for def in book.defs {
+            names.push(def.name.clone());
+            self.dirty.insert(def.name.clone());
+            match self.book.defs.iter_mut().find(|old| old.name == def.name) {
+                Some(old) => *old = def,
+                None => self.book.defs.push(def),
+            }
+        }
+        Ok(names)
+    }
+
+    /// Remove a definition, returning whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.book.defs.len();
+        self.book.defs.retain(|def| def.name != name);
+        self.dirty.insert(name.to_string());
+        self.book.defs.len() < len
+    }
+
+    /// Evaluate the groups of definitions changed since the last eval, or
+    /// connected to a changed one. Returns the names of the definitions
+    /// evaluated, in book order.
+    pub fn eval(&mut self) -> Result<Vec<String>, SessionError> {
+        let current: Vec<BTreeSet<String>> = self
+            .book
+            .components()
+            .into_iter()
+            .map(|defs| {
+                defs.into_iter()
+                    .map(|idx| self.book.defs[idx].name.clone())
+                    .collect()
+            })
+            .collect();
+        let (kept, stale): (Vec<_>, Vec<_>) = self.components.drain(..).partition(|component| {
+            current.contains(&component.defs)
+                && component.defs.iter().all(|def| !self.dirty.contains(def))
+        });
+        stale
+            .into_iter()
+            .for_each(|component| self.release(component));
+        self.components = kept;
+        self.dirty.clear();
+
+        let fresh: Vec<_> = current
+            .into_iter()
+            .filter(|defs| !self.components.iter().any(|kept| kept.defs == *defs))
+            .collect();
+        let mut evaluated = Vec::new();
+        for defs in fresh {
+            let book = Book {
+                defs: self
+                    .book
+                    .defs
+                    .iter()
+                    .filter(|def| defs.contains(&def.name))
+                    .cloned()
+                    .collect(),
+            };
+            self.net.load(&book)?;
+            // take the heads out of the net, to keep each group's apart
+            let mut heads = self.net.head.drain(..);
+            let heads = book
+                .defs
+                .iter()
+                .map(|def| {
+                    (
+                        def.name.clone(),
+                        heads.by_ref().take(def.net.head.len()).collect(),
+                    )
+                })
+                .collect();
+            evaluated.extend(book.defs.into_iter().map(|def| def.name));
+            self.components.push(Component { defs, heads });
+        }
+        Runtime::with_config(self.config.clone()).eval(&mut self.net)?;
+        Ok(evaluated)
+    }
+
+    /// Read back the heads of a definition, as of the last eval. `None` if
+    /// the definition was not evaluated since it was last changed.
+    pub fn normal_form(&self, name: &str) -> Option<Result<Vec<OwnedTerm>, NotNormal>> {
+        if self.dirty.contains(name) {
+            return None;
+        }
+        let heads = self
+            .components
+            .iter()
+            .find_map(|component| component.heads.get(name))?;
+        let mut path = Vec::new();
+        Some(
+            heads
+                .iter()
+                .map(|head| self.net.read_term(*head, &mut path))
+                .collect(),
+        )
+    }
+
+    // free every slot reachable from the heads of a group: groups share no
+    // wire, so none of them is used by another group
+    fn release(&mut self, component: Component) {
+        let heads = component.heads.into_values().flatten().collect();
+        self.net.free_reachable(heads);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{net::Net, parser::parse};
+
+    use super::Session;
+
+    // name the vars of a read back term by order of appearance, as their
+    // slots differ between nets
+    fn rename(term: String) -> String {
+        let mut names: Vec<String> = Vec::new();
+        term.split(|c: char| c == ' ' || c == '(' || c == ')' || c == '[' || c == ']')
+            .filter(|word| word.starts_with('x') && word[1..].parse::<usize>().is_ok())
+            .for_each(|word| {
+                if !names.iter().any(|name| name == word) {
+                    names.push(word.to_string())
+                }
+            });
+        names.iter().enumerate().fold(term, |term, (idx, name)| {
+            replace_word(&term, name, &format!("v{}", idx))
+        })
+    }
+
+    fn replace_word(term: &str, from: &str, to: &str) -> String {
+        let mut out = String::new();
+        let mut word = String::new();
+        for c in term.chars().chain(std::iter::once(' ')) {
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else {
+                out.push_str(if word == from { to } else { &word });
+                word.clear();
+                out.push(c);
+            }
+        }
+        out.pop();
+        out
+    }
+
+    // the normal form of `src` evaluated from scratch
+    fn expected(src: &str) -> Vec<String> {
+        let mut net = Net::new();
+        assert!(parse(src, &mut net));
+        crate::strandal::runtime::Runtime::new()
+            .eval(&mut net)
+            .unwrap();
+        net.normal_form()
+            .unwrap()
+            .iter()
+            .map(|head| rename(head.to_string()))
+            .collect()
+    }
+
+    fn heads(session: &Session, name: &str) -> Vec<String> {
+        session
+            .normal_form(name)
+            .unwrap()
+            .unwrap()
+            .iter()
+            .map(|head| rename(head.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_session() {
+        let first = "def first(a) = (x x) ~ [(y y) a];";
+        let second = "def second(b) = (t (* t)) ~ [(c c) [(d (* d)) b]];";
+        let mut session = Session::new();
+        session.define(first).unwrap();
+        session.define(second).unwrap();
+        assert_eq!(session.eval().unwrap(), vec!["first", "second"]);
+        assert_eq!(heads(&session, "first"), expected(first));
+        assert_eq!(heads(&session, "second"), expected(second));
+
+        // only the replaced definition is evaluated again, in the same store
+        let replaced = "def second(b) = (* (f f)) ~ [(g g) [(h (* h)) b]];";
+        session.define(replaced).unwrap();
+        assert!(session.normal_form("second").is_none());
+        assert_eq!(session.eval().unwrap(), vec!["second"]);
+        assert_eq!(heads(&session, "second"), expected(replaced));
+        assert_eq!(heads(&session, "first"), expected(first));
+        // the slots of the replaced definition were freed
+        let mut fresh = Session::new();
+        fresh.define(first).unwrap();
+        fresh.define(replaced).unwrap();
+        fresh.eval().unwrap();
+        assert_eq!(session.net().store.len(), fresh.net().store.len());
+
+        // a definition sharing a var joins the group of the other one
+        session.define("def uses((k j));").unwrap();
+        assert_eq!(session.eval().unwrap(), vec!["uses"]);
+        session.define("def third(j, k);").unwrap();
+        assert_eq!(session.eval().unwrap(), vec!["uses", "third"]);
+
+        assert!(session.remove("uses"));
+        assert_eq!(session.eval().unwrap(), vec!["third"]);
+        assert!(session.eval().unwrap().is_empty());
+    }
+}
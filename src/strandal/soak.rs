@@ -0,0 +1,270 @@
+//! Soak runs: a workload loaded and evaluated over and over in the same
+//! store, as a long-running host would, until a time or memory limit. A
+//! slot that an iteration frees but leaves live, or a rule that forgets to
+//! recycle a pointer, leaks a few slots per eval: too few to show in a
+//! single run, but every iteration must end with as many live slots as the
+//! first one did.
+//!
+//! The slots an iteration frees after its eval are not handed out again, so
+//! the store grows by every iteration until it is full, which stops the
+//! run: its high water mark tells how much memory a host evaluating in the
+//! same store for as long would hold.
+
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use super::{
+    ast::AstError,
+    leaks::LeakReport,
+    net::Net,
+    runtime::{EvalError, Runtime},
+    store::Index,
+};
+
+#[derive(Debug, Clone)]
+pub struct SoakConfig {
+    /// Stop once the iterations took this long.
+    pub duration: Duration,
+    /// Stop once an eval leaves more than this many live slots.
+    pub max_live: Option<Index>,
+    /// Stop after this many iterations.
+    pub iterations: Option<usize>,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(10),
+            max_live: None,
+            iterations: None,
+        }
+    }
+}
+
+/// Why a soak run stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoakStop {
+    Duration,
+    Iterations,
+    /// An eval left this many live slots, past [`SoakConfig::max_live`].
+    MaxLive(Index),
+    /// The store could not hold another iteration.
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoakReport {
+    pub iterations: usize,
+    pub interactions: usize,
+    pub elapsed: Duration,
+    /// The live slots left by the first iteration, and by every other one.
+    pub baseline: Index,
+    /// The most live slots an eval left, its normal form included.
+    pub peak_live: Index,
+    /// The slots of the store handed out so far, see
+    /// [`Store::next`](super::store::Store::next).
+    pub high_water: Index,
+    pub stop: SoakStop,
+}
+
+impl Display for SoakReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stop = match self.stop {
+            SoakStop::Duration => "duration".to_string(),
+            SoakStop::Iterations => "iterations".to_string(),
+            SoakStop::MaxLive(live) => format!("{} live slots", live),
+            SoakStop::Full => "full store".to_string(),
+        };
+        write!(
+            f,
+            "SOAK    | iterations: {}, interactions: {}, time: {:.3} s, baseline: {}, peak live: {}, high water: {}, stopped on: {}",
+            self.iterations,
+            self.interactions,
+            self.elapsed.as_secs_f64(),
+            self.baseline,
+            self.peak_live,
+            self.high_water,
+            stop
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SoakError {
+    Load(AstError),
+    /// The eval of an iteration, counted from 0, failed.
+    Eval(usize, EvalError),
+    /// An iteration left more live slots than the first one.
+    Leak {
+        iteration: usize,
+        baseline: Index,
+        live: Index,
+        leaks: LeakReport,
+    },
+}
+
+impl Display for SoakError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SoakError::Load(err) => write!(f, "{}", err),
+            SoakError::Eval(iteration, err) => write!(f, "iteration {}: {}", iteration, err),
+            SoakError::Leak {
+                iteration,
+                baseline,
+                live,
+                leaks,
+            } => write!(
+                f,
+                "iteration {} left {} live slots instead of {}\n{}",
+                iteration, live, baseline, leaks
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SoakError {}
+
+impl From<AstError> for SoakError {
+    fn from(err: AstError) -> Self {
+        SoakError::Load(err)
+    }
+}
+
+/// Load a workload into `net` with `load`, evaluate it and release it, over
+/// and over until `config` says to stop. The first iteration sets the
+/// baseline of live slots, the interned constants and whatever the net
+/// held before, and every later one must release back to it.
+pub fn soak(
+    net: &mut Net,
+    runtime: &mut Runtime,
+    config: &SoakConfig,
+    mut load: impl FnMut(&mut Net) -> Result<(), AstError>,
+) -> Result<SoakReport, SoakError> {
+    let start = Instant::now();
+    let mut report = SoakReport {
+        iterations: 0,
+        interactions: 0,
+        elapsed: Duration::ZERO,
+        baseline: 0,
+        peak_live: 0,
+        high_water: 0,
+        stop: SoakStop::Duration,
+    };
+    loop {
+        let high_water = net.store().next();
+        load(net)?;
+        runtime
+            .eval_in(net)
+            .map_err(|err| SoakError::Eval(report.iterations, err))?;
+        let live = net.store().len();
+        report.interactions += runtime.stats.interactions();
+        report.peak_live = report.peak_live.max(live);
+
+        net.release();
+        let released = net.store().len();
+        match report.iterations {
+            0 => report.baseline = released,
+            iteration if released != report.baseline => {
+                return Err(SoakError::Leak {
+                    iteration,
+                    baseline: report.baseline,
+                    live: released,
+                    leaks: net.leaks(),
+                })
+            }
+            _ => {}
+        }
+        report.iterations += 1;
+        report.high_water = net.store().next();
+        let growth = report.high_water - high_water;
+
+        let stop = match config.max_live {
+            Some(max_live) if live > max_live => Some(SoakStop::MaxLive(live)),
+            _ if config.iterations.is_some_and(|max| report.iterations >= max) => {
+                Some(SoakStop::Iterations)
+            }
+            _ if start.elapsed() >= config.duration => Some(SoakStop::Duration),
+            _ if report.high_water + growth > net.store().capacity => Some(SoakStop::Full),
+            _ => None,
+        };
+        if let Some(stop) = stop {
+            report.stop = stop;
+            report.elapsed = start.elapsed();
+            return Ok(report);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        parser::parse_ast,
+        runtime::Runtime,
+        store::Store,
+    };
+
+    use super::{soak, SoakConfig, SoakError, SoakStop};
+
+    #[test]
+    fn test_soak() {
+        let book = parse_ast(include_str!("../../tests/fixtures/fibonacci.strandal")).unwrap();
+        let mut net = Net::with_store(Store::new_checked());
+        let config = SoakConfig {
+            duration: Duration::from_secs(60),
+            iterations: Some(20),
+            ..Default::default()
+        };
+        let report = soak(&mut net, &mut Runtime::new(), &config, |net| net.load(&book)).unwrap();
+        assert_eq!(report.iterations, 20);
+        assert_eq!(report.stop, SoakStop::Iterations);
+        assert_eq!(net.store().len(), report.baseline);
+        assert!(report.peak_live > report.baseline);
+        let report_line = report.to_string();
+        assert!(report_line.starts_with("SOAK    | iterations: 20, interactions: "));
+        assert!(report_line.ends_with(&format!(
+            "baseline: {}, peak live: {}, high water: {}, stopped on: iterations",
+            report.baseline, report.peak_live, report.high_water
+        )));
+
+        let limited = SoakConfig {
+            max_live: Some(report.baseline),
+            ..config.clone()
+        };
+        let report = soak(&mut net, &mut Runtime::new(), &limited, |net| net.load(&book)).unwrap();
+        assert_eq!((report.iterations, report.stop), (1, SoakStop::MaxLive(report.peak_live)));
+
+        let mut net = Net::with_capacity(1 << 16);
+        let report = soak(&mut net, &mut Runtime::new(), &config, |net| net.load(&book)).unwrap();
+        assert_eq!(report.stop, SoakStop::Full);
+        assert!(report.iterations > 1);
+    }
+
+    #[test]
+    fn test_soak_leak() {
+        let book = parse_ast("def main(r) = (x x) ~ [(y y) r]").unwrap();
+        let mut net = Net::new();
+        // a var no head or equation reaches, left live by every iteration
+        let leaky = |net: &mut Net| {
+            net.load(&book)?;
+            net.var();
+            Ok(())
+        };
+        let err = soak(&mut net, &mut Runtime::new(), &SoakConfig::default(), leaky).unwrap_err();
+        let SoakError::Leak {
+            iteration,
+            baseline,
+            live,
+            leaks,
+        } = err
+        else {
+            panic!("{:?} is not a leak", err);
+        };
+        assert_eq!((iteration, live), (1, baseline + 1));
+        assert_eq!(leaks.total(), 2);
+    }
+}
@@ -1,8 +1,30 @@
 use std::{
+    collections::HashMap,
     fmt::{Display, Formatter},
-    sync::atomic::{AtomicUsize, Ordering},
+    ops::Range,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
 };
 
+use super::store::{Contention, Index, Ptr};
+
+/// Buckets of the var-chain histogram: 0 hops, 1, 2-3, 4-7 and so on, with
+/// the last one open-ended.
+pub const VAR_HOPS_BUCKETS: usize = 8;
+
+#[cfg_attr(feature = "no-stats", allow(dead_code))]
+fn var_hops_bucket(hops: usize) -> usize {
+    ((usize::BITS - hops.leading_zeros()) as usize).min(VAR_HOPS_BUCKETS - 1)
+}
+
+fn var_hops_label(bucket: usize) -> String {
+    match bucket {
+        0 | 1 => bucket.to_string(),
+        _ if bucket == VAR_HOPS_BUCKETS - 1 => format!("{}+", 1 << (bucket - 1)),
+        _ => format!("{}-{}", 1 << (bucket - 1), (1 << bucket) - 1),
+    }
+}
+
 pub trait Stats {
     fn inc_anni_era_era(&mut self);
 
@@ -26,6 +48,29 @@ pub trait Stats {
 
     fn inc_comm_lam_dup(&mut self);
 
+    fn inc_oper_num(&mut self);
+
+    fn inc_swi_num(&mut self);
+
+    /// A number copied to the ports of a cell.
+    fn inc_comm_num(&mut self);
+
+    /// An operator or a switch commuting with a cell that is not a number.
+    fn inc_comm_ops(&mut self);
+
+    /// A reference copied by a duplicator.
+    fn inc_comm_ref(&mut self);
+
+    fn inc_comm_era_num(&mut self);
+
+    /// An operator or a switch erased.
+    fn inc_comm_era_ops(&mut self);
+
+    fn inc_comm_era_ref(&mut self);
+
+    /// A reference replaced by a copy of its definition.
+    fn inc_expand_ref(&mut self);
+
     fn inc_binds(&mut self);
 
     fn inc_connects(&mut self);
@@ -33,9 +78,26 @@ pub trait Stats {
     fn inc_alloc_cells(&mut self);
 
     fn inc_alloc_vars(&mut self);
+
+    fn inc_memo_hits(&mut self);
+
+    fn inc_memo_misses(&mut self);
+
+    /// Record a bind or connect that walked through `hops` linked vars
+    /// before reaching the end of the chain.
+    fn record_var_hops(&mut self, hops: usize);
+
+    fn record_contention(&mut self, contention: Contention);
 }
 
-pub struct GlobalStats {
+/// The counters of [`GlobalStats`] merged by the tasks of a thread.
+///
+/// Every task ending merges all of its counters at once, so a single set of
+/// counters would bounce between the cores. Each thread merges into its own
+/// stripe instead, aligned to a pair of cache lines since some cores fetch
+/// lines in pairs, and the reads sum the stripes.
+#[repr(align(128))]
+struct Stripe {
     anni_era_era: AtomicUsize,
     anni_app_app: AtomicUsize,
     anni_lam_lam: AtomicUsize,
@@ -47,14 +109,29 @@ pub struct GlobalStats {
     comm_app_lam: AtomicUsize,
     comm_app_dup: AtomicUsize,
     comm_lam_dup: AtomicUsize,
+    oper_num: AtomicUsize,
+    swi_num: AtomicUsize,
+    comm_num: AtomicUsize,
+    comm_ops: AtomicUsize,
+    comm_ref: AtomicUsize,
+    comm_era_num: AtomicUsize,
+    comm_era_ops: AtomicUsize,
+    comm_era_ref: AtomicUsize,
+    expand_ref: AtomicUsize,
     binds: AtomicUsize,
     connects: AtomicUsize,
     alloc_vars: AtomicUsize,
     alloc_cells: AtomicUsize,
+    memo_hits: AtomicUsize,
+    memo_misses: AtomicUsize,
+    var_hops: [AtomicUsize; VAR_HOPS_BUCKETS],
+    alloc_retries: AtomicUsize,
+    shard_misses: AtomicUsize,
+    remote_frees: AtomicUsize,
 }
 
-impl GlobalStats {
-    pub fn new() -> Self {
+impl Stripe {
+    fn new() -> Self {
         Self {
             anni_era_era: AtomicUsize::new(0),
             anni_app_app: AtomicUsize::new(0),
@@ -67,14 +144,63 @@ impl GlobalStats {
             comm_app_lam: AtomicUsize::new(0),
             comm_app_dup: AtomicUsize::new(0),
             comm_lam_dup: AtomicUsize::new(0),
+            oper_num: AtomicUsize::new(0),
+            swi_num: AtomicUsize::new(0),
+            comm_num: AtomicUsize::new(0),
+            comm_ops: AtomicUsize::new(0),
+            comm_ref: AtomicUsize::new(0),
+            comm_era_num: AtomicUsize::new(0),
+            comm_era_ops: AtomicUsize::new(0),
+            comm_era_ref: AtomicUsize::new(0),
+            expand_ref: AtomicUsize::new(0),
             binds: AtomicUsize::new(0),
             connects: AtomicUsize::new(0),
             alloc_vars: AtomicUsize::new(0),
             alloc_cells: AtomicUsize::new(0),
+            memo_hits: AtomicUsize::new(0),
+            memo_misses: AtomicUsize::new(0),
+            var_hops: std::array::from_fn(|_| AtomicUsize::new(0)),
+            alloc_retries: AtomicUsize::new(0),
+            shard_misses: AtomicUsize::new(0),
+            remote_frees: AtomicUsize::new(0),
         }
     }
 }
 
+pub struct GlobalStats {
+    stripes: Box<[Stripe]>,
+    frees: AtomicUsize,
+    elapsed_nanos: AtomicU64,
+}
+
+impl GlobalStats {
+    pub fn new() -> Self {
+        Self {
+            stripes: (0..rayon::current_num_threads().max(1))
+                .map(|_| Stripe::new())
+                .collect(),
+            frees: AtomicUsize::new(0),
+            elapsed_nanos: AtomicU64::new(0),
+        }
+    }
+
+    // the stripe of the current thread
+    #[inline]
+    fn stripe(&self) -> &Stripe {
+        let thread = rayon::current_thread_index().unwrap_or(0);
+        &self.stripes[thread % self.stripes.len()]
+    }
+
+    // a counter summed over the stripes
+    #[inline]
+    fn sum(&self, counter: impl Fn(&Stripe) -> &AtomicUsize) -> usize {
+        self.stripes
+            .iter()
+            .map(|stripe| counter(stripe).load(Ordering::Relaxed))
+            .sum()
+    }
+}
+
 impl GlobalStats {
     pub fn annihilations(&self) -> usize {
         self.anni_era_era() + self.anni_app_app() + self.anni_lam_lam() + self.anni_dup_dup()
@@ -85,104 +211,271 @@ impl GlobalStats {
             + self.comm_era_app()
             + self.comm_era_lam()
             + self.comm_era_dup()
+            + self.comm_era_num()
+            + self.comm_era_ops()
+            + self.comm_era_ref()
             + self.comm_app_lam()
             + self.comm_app_dup()
             + self.comm_lam_dup()
+            + self.comm_num()
+            + self.comm_ops()
+            + self.comm_ref()
+    }
+
+    /// Operators and switches meeting a number.
+    pub fn operations(&self) -> usize {
+        self.oper_num() + self.swi_num()
+    }
+
+    /// Interactions as HVM counts them: every annihilation and commutation,
+    /// but not the binds and connects that only move a value through a var.
+    pub fn interactions(&self) -> usize {
+        self.annihilations() + self.commutations() + self.operations() + self.expand_ref()
     }
 
     pub fn allocs(&self) -> usize {
         self.alloc_vars() + self.alloc_cells()
     }
 
+    /// Every rewrite: the interactions, plus the binds and connects.
+    pub fn rewrites(&self) -> usize {
+        self.interactions() + self.binds() + self.connects()
+    }
+
+    /// The count of each rewrite rule, named as in traces.
+    pub fn rules(&self) -> [(&'static str, usize); 22] {
+        [
+            ("ANNI ERA-ERA", self.anni_era_era()),
+            ("ANNI LAM-LAM", self.anni_lam_lam()),
+            ("ANNI APP-APP", self.anni_app_app()),
+            ("ANNI DUP-DUP", self.anni_dup_dup()),
+            ("COMM ERA-APP", self.comm_era_app()),
+            ("COMM ERA-LAM", self.comm_era_lam()),
+            ("COMM ERA-DUP", self.comm_era_dup()),
+            ("COMM ERA-NUM", self.comm_era_num()),
+            ("COMM ERA-OPS", self.comm_era_ops()),
+            ("COMM ERA-REF", self.comm_era_ref()),
+            ("COMM APP-LAM", self.comm_app_lam()),
+            ("COMM APP-DUP", self.comm_app_dup()),
+            ("COMM LAM-DUP", self.comm_lam_dup()),
+            ("COMM DUP-DUP", self.comm_dup_dup()),
+            ("COMM NUM", self.comm_num()),
+            ("COMM OPS", self.comm_ops()),
+            ("COMM REF", self.comm_ref()),
+            ("OPER NUM", self.oper_num()),
+            ("SWI NUM", self.swi_num()),
+            ("EXPAND REF", self.expand_ref()),
+            ("BIND", self.binds()),
+            ("CONNECT", self.connects()),
+        ]
+    }
+
+    /// Record an eval that took `elapsed` and freed `frees` store slots.
+    pub fn record_eval(&self, elapsed: Duration, frees: usize) {
+        self.elapsed_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        if cfg!(feature = "no-stats") {
+            return;
+        }
+        self.frees.fetch_add(frees, Ordering::Relaxed);
+    }
+
+    /// Wall time of the evals recorded so far.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed_nanos.load(Ordering::Relaxed))
+    }
+
+    /// `None` until an eval that took some time is recorded.
+    pub fn rewrites_per_sec(&self) -> Option<f64> {
+        let elapsed = self.elapsed().as_secs_f64();
+        (elapsed > 0.0).then(|| self.rewrites() as f64 / elapsed)
+    }
+
+    pub fn frees(&self) -> usize {
+        self.frees.load(Ordering::Relaxed)
+    }
+
+    /// Slots allocated and not freed: what the evals added to the store.
+    pub fn balance(&self) -> i64 {
+        self.allocs() as i64 - self.frees() as i64
+    }
+
     pub fn update(&self, stats: LocalStats) {
-        self.anni_era_era
-            .fetch_add(stats.anni_era_era, Ordering::Relaxed);
-        self.anni_app_app
-            .fetch_add(stats.anni_app_app, Ordering::Relaxed);
-        self.anni_lam_lam
-            .fetch_add(stats.anni_lam_lam, Ordering::Relaxed);
-        self.anni_dup_dup
-            .fetch_add(stats.anni_dup_dup, Ordering::Relaxed);
-        self.comm_dup_dup
-            .fetch_add(stats.comm_dup_dup, Ordering::Relaxed);
-        self.comm_era_app
-            .fetch_add(stats.comm_era_app, Ordering::Relaxed);
-        self.comm_era_lam
-            .fetch_add(stats.comm_era_lam, Ordering::Relaxed);
-        self.comm_era_dup
-            .fetch_add(stats.comm_era_dup, Ordering::Relaxed);
-        self.comm_app_lam
-            .fetch_add(stats.comm_app_lam, Ordering::Relaxed);
-        self.comm_app_dup
-            .fetch_add(stats.comm_app_dup, Ordering::Relaxed);
-        self.comm_lam_dup
-            .fetch_add(stats.comm_lam_dup, Ordering::Relaxed);
-        self.binds.fetch_add(stats.binds, Ordering::Relaxed);
-        self.connects.fetch_add(stats.connects, Ordering::Relaxed);
-        self.alloc_cells
-            .fetch_add(stats.alloc_cells, Ordering::Relaxed);
-        self.alloc_vars
-            .fetch_add(stats.alloc_vars, Ordering::Relaxed);
+        if cfg!(feature = "no-stats") {
+            return;
+        }
+        let stripe = self.stripe();
+        stripe.anni_era_era.fetch_add(stats.anni_era_era, Ordering::Relaxed);
+        stripe.anni_app_app.fetch_add(stats.anni_app_app, Ordering::Relaxed);
+        stripe.anni_lam_lam.fetch_add(stats.anni_lam_lam, Ordering::Relaxed);
+        stripe.anni_dup_dup.fetch_add(stats.anni_dup_dup, Ordering::Relaxed);
+        stripe.comm_dup_dup.fetch_add(stats.comm_dup_dup, Ordering::Relaxed);
+        stripe.comm_era_app.fetch_add(stats.comm_era_app, Ordering::Relaxed);
+        stripe.comm_era_lam.fetch_add(stats.comm_era_lam, Ordering::Relaxed);
+        stripe.comm_era_dup.fetch_add(stats.comm_era_dup, Ordering::Relaxed);
+        stripe.comm_app_lam.fetch_add(stats.comm_app_lam, Ordering::Relaxed);
+        stripe.comm_app_dup.fetch_add(stats.comm_app_dup, Ordering::Relaxed);
+        stripe.comm_lam_dup.fetch_add(stats.comm_lam_dup, Ordering::Relaxed);
+        stripe.oper_num.fetch_add(stats.oper_num, Ordering::Relaxed);
+        stripe.swi_num.fetch_add(stats.swi_num, Ordering::Relaxed);
+        stripe.comm_num.fetch_add(stats.comm_num, Ordering::Relaxed);
+        stripe.comm_ops.fetch_add(stats.comm_ops, Ordering::Relaxed);
+        stripe.comm_ref.fetch_add(stats.comm_ref, Ordering::Relaxed);
+        stripe.comm_era_num.fetch_add(stats.comm_era_num, Ordering::Relaxed);
+        stripe.comm_era_ops.fetch_add(stats.comm_era_ops, Ordering::Relaxed);
+        stripe.comm_era_ref.fetch_add(stats.comm_era_ref, Ordering::Relaxed);
+        stripe.expand_ref.fetch_add(stats.expand_ref, Ordering::Relaxed);
+        stripe.binds.fetch_add(stats.binds, Ordering::Relaxed);
+        stripe.connects.fetch_add(stats.connects, Ordering::Relaxed);
+        stripe.alloc_cells.fetch_add(stats.alloc_cells, Ordering::Relaxed);
+        stripe.alloc_vars.fetch_add(stats.alloc_vars, Ordering::Relaxed);
+        stripe.memo_hits.fetch_add(stats.memo_hits, Ordering::Relaxed);
+        stripe.memo_misses.fetch_add(stats.memo_misses, Ordering::Relaxed);
+        for (global, local) in stripe.var_hops.iter().zip(stats.var_hops) {
+            global.fetch_add(local, Ordering::Relaxed);
+        }
+        let contention = stats.contention;
+        stripe
+            .alloc_retries
+            .fetch_add(contention.alloc_retries, Ordering::Relaxed);
+        stripe
+            .shard_misses
+            .fetch_add(contention.shard_misses, Ordering::Relaxed);
+        stripe
+            .remote_frees
+            .fetch_add(contention.remote_frees, Ordering::Relaxed);
     }
 
     pub fn anni_era_era(&self) -> usize {
-        self.anni_era_era.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.anni_era_era)
     }
 
     pub fn anni_app_app(&self) -> usize {
-        self.anni_app_app.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.anni_app_app)
     }
 
     pub fn anni_lam_lam(&self) -> usize {
-        self.anni_lam_lam.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.anni_lam_lam)
     }
 
     pub fn anni_dup_dup(&self) -> usize {
-        self.anni_dup_dup.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.anni_dup_dup)
     }
 
     pub fn comm_dup_dup(&self) -> usize {
-        self.comm_dup_dup.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.comm_dup_dup)
     }
 
     pub fn comm_era_app(&self) -> usize {
-        self.comm_era_app.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.comm_era_app)
     }
 
     pub fn comm_era_lam(&self) -> usize {
-        self.comm_era_lam.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.comm_era_lam)
     }
 
     pub fn comm_era_dup(&self) -> usize {
-        self.comm_era_dup.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.comm_era_dup)
     }
 
     pub fn comm_app_lam(&self) -> usize {
-        self.comm_app_lam.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.comm_app_lam)
     }
 
     pub fn comm_app_dup(&self) -> usize {
-        self.comm_app_dup.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.comm_app_dup)
     }
 
     pub fn comm_lam_dup(&self) -> usize {
-        self.comm_lam_dup.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.comm_lam_dup)
+    }
+
+    pub fn oper_num(&self) -> usize {
+        self.sum(|stripe| &stripe.oper_num)
+    }
+
+    pub fn swi_num(&self) -> usize {
+        self.sum(|stripe| &stripe.swi_num)
+    }
+
+    pub fn comm_num(&self) -> usize {
+        self.sum(|stripe| &stripe.comm_num)
+    }
+
+    pub fn comm_ops(&self) -> usize {
+        self.sum(|stripe| &stripe.comm_ops)
+    }
+
+    pub fn comm_ref(&self) -> usize {
+        self.sum(|stripe| &stripe.comm_ref)
+    }
+
+    pub fn comm_era_num(&self) -> usize {
+        self.sum(|stripe| &stripe.comm_era_num)
+    }
+
+    pub fn comm_era_ops(&self) -> usize {
+        self.sum(|stripe| &stripe.comm_era_ops)
+    }
+
+    pub fn comm_era_ref(&self) -> usize {
+        self.sum(|stripe| &stripe.comm_era_ref)
+    }
+
+    pub fn expand_ref(&self) -> usize {
+        self.sum(|stripe| &stripe.expand_ref)
     }
 
     pub fn binds(&self) -> usize {
-        self.binds.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.binds)
     }
 
     pub fn connects(&self) -> usize {
-        self.connects.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.connects)
     }
 
     pub fn alloc_cells(&self) -> usize {
-        self.alloc_cells.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.alloc_cells)
     }
 
     pub fn alloc_vars(&self) -> usize {
-        self.alloc_vars.load(Ordering::Relaxed)
+        self.sum(|stripe| &stripe.alloc_vars)
+    }
+
+    pub fn memo_hits(&self) -> usize {
+        self.sum(|stripe| &stripe.memo_hits)
+    }
+
+    pub fn memo_misses(&self) -> usize {
+        self.sum(|stripe| &stripe.memo_misses)
+    }
+
+    /// How many var walks took each bucket's number of hops, see
+    /// [`VAR_HOPS_BUCKETS`].
+    pub fn var_hops(&self) -> [usize; VAR_HOPS_BUCKETS] {
+        std::array::from_fn(|bucket| self.sum(|stripe| &stripe.var_hops[bucket]))
+    }
+
+    /// The contention the store ran into, see [`Contention`].
+    pub fn contention(&self) -> Contention {
+        Contention {
+            alloc_retries: self.sum(|stripe| &stripe.alloc_retries),
+            shard_misses: self.sum(|stripe| &stripe.shard_misses),
+            remote_frees: self.sum(|stripe| &stripe.remote_frees),
+        }
+    }
+
+    /// The contention of each stripe, which holds the counts of the threads
+    /// whose index is the stripe's modulo the number of stripes.
+    pub fn contention_by_thread(&self) -> Vec<Contention> {
+        self.stripes
+            .iter()
+            .map(|stripe| Contention {
+                alloc_retries: stripe.alloc_retries.load(Ordering::Relaxed),
+                shard_misses: stripe.shard_misses.load(Ordering::Relaxed),
+                remote_frees: stripe.remote_frees.load(Ordering::Relaxed),
+            })
+            .collect()
     }
 }
 
@@ -198,10 +491,23 @@ pub struct LocalStats {
     comm_app_lam: usize,
     comm_app_dup: usize,
     comm_lam_dup: usize,
+    oper_num: usize,
+    swi_num: usize,
+    comm_num: usize,
+    comm_ops: usize,
+    comm_ref: usize,
+    comm_era_num: usize,
+    comm_era_ops: usize,
+    comm_era_ref: usize,
+    expand_ref: usize,
     binds: usize,
     connects: usize,
     alloc_cells: usize,
     alloc_vars: usize,
+    memo_hits: usize,
+    memo_misses: usize,
+    var_hops: [usize; VAR_HOPS_BUCKETS],
+    contention: Contention,
 }
 impl LocalStats {
     pub fn new() -> Self {
@@ -217,14 +523,28 @@ impl LocalStats {
             comm_app_lam: 0,
             comm_app_dup: 0,
             comm_lam_dup: 0,
+            oper_num: 0,
+            swi_num: 0,
+            comm_num: 0,
+            comm_ops: 0,
+            comm_ref: 0,
+            comm_era_num: 0,
+            comm_era_ops: 0,
+            comm_era_ref: 0,
+            expand_ref: 0,
             binds: 0,
             connects: 0,
             alloc_cells: 0,
             alloc_vars: 0,
+            memo_hits: 0,
+            memo_misses: 0,
+            var_hops: [0; VAR_HOPS_BUCKETS],
+            contention: Contention::default(),
         }
     }
 }
 
+#[cfg(not(feature = "no-stats"))]
 impl Stats for LocalStats {
     fn inc_anni_era_era(&mut self) {
         self.anni_era_era += 1;
@@ -270,6 +590,42 @@ impl Stats for LocalStats {
         self.comm_lam_dup += 1;
     }
 
+    fn inc_oper_num(&mut self) {
+        self.oper_num += 1;
+    }
+
+    fn inc_swi_num(&mut self) {
+        self.swi_num += 1;
+    }
+
+    fn inc_comm_num(&mut self) {
+        self.comm_num += 1;
+    }
+
+    fn inc_comm_ops(&mut self) {
+        self.comm_ops += 1;
+    }
+
+    fn inc_comm_ref(&mut self) {
+        self.comm_ref += 1;
+    }
+
+    fn inc_comm_era_num(&mut self) {
+        self.comm_era_num += 1;
+    }
+
+    fn inc_comm_era_ops(&mut self) {
+        self.comm_era_ops += 1;
+    }
+
+    fn inc_comm_era_ref(&mut self) {
+        self.comm_era_ref += 1;
+    }
+
+    fn inc_expand_ref(&mut self) {
+        self.expand_ref += 1;
+    }
+
     fn inc_binds(&mut self) {
         self.binds += 1;
     }
@@ -285,39 +641,443 @@ impl Stats for LocalStats {
     fn inc_alloc_vars(&mut self) {
         self.alloc_vars += 1;
     }
+
+    fn inc_memo_hits(&mut self) {
+        self.memo_hits += 1;
+    }
+
+    fn inc_memo_misses(&mut self) {
+        self.memo_misses += 1;
+    }
+
+    fn record_var_hops(&mut self, hops: usize) {
+        self.var_hops[var_hops_bucket(hops)] += 1;
+    }
+
+    fn record_contention(&mut self, contention: Contention) {
+        self.contention.alloc_retries += contention.alloc_retries;
+        self.contention.shard_misses += contention.shard_misses;
+        self.contention.remote_frees += contention.remote_frees;
+    }
+}
+
+// with the `no-stats` feature every counter compiles away
+#[cfg(feature = "no-stats")]
+impl Stats for LocalStats {
+    #[inline(always)]
+    fn inc_anni_era_era(&mut self) {}
+
+    #[inline(always)]
+    fn inc_anni_app_app(&mut self) {}
+
+    #[inline(always)]
+    fn inc_anni_lam_lam(&mut self) {}
+
+    #[inline(always)]
+    fn inc_anni_dup_dup(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_dup_dup(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_era_app(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_era_lam(&mut self) {}
+
+    #[inline(always)]
+    fn inc_commute_era_dup(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_app_lam(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_app_dup(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_lam_dup(&mut self) {}
+
+    #[inline(always)]
+    fn inc_oper_num(&mut self) {}
+
+    #[inline(always)]
+    fn inc_swi_num(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_num(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_ops(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_ref(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_era_num(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_era_ops(&mut self) {}
+
+    #[inline(always)]
+    fn inc_comm_era_ref(&mut self) {}
+
+    #[inline(always)]
+    fn inc_expand_ref(&mut self) {}
+
+    #[inline(always)]
+    fn inc_binds(&mut self) {}
+
+    #[inline(always)]
+    fn inc_connects(&mut self) {}
+
+    #[inline(always)]
+    fn inc_alloc_cells(&mut self) {}
+
+    #[inline(always)]
+    fn inc_alloc_vars(&mut self) {}
+
+    #[inline(always)]
+    fn inc_memo_hits(&mut self) {}
+
+    #[inline(always)]
+    fn inc_memo_misses(&mut self) {}
+
+    #[inline(always)]
+    fn record_var_hops(&mut self, _: usize) {}
+
+    #[inline(always)]
+    fn record_contention(&mut self, _: Contention) {}
 }
 
 impl Display for GlobalStats {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let rows = self.rules();
+        let total = self.rewrites();
+        let width = total.to_string().len().max("COUNT".len());
+        let share = |count: usize| match total {
+            0 => 0.0,
+            _ => count as f64 * 100.0 / total as f64,
+        };
+        write!(f, "{:<12} | {:>width$} | {:>6}", "RULE", "COUNT", "%")?;
+        for (rule, count) in rows {
+            write!(
+                f,
+                "\n{:<12} | {:>width$} | {:>5.1}%",
+                rule,
+                count,
+                share(count)
+            )?;
+        }
         write!(
             f,
-            "SUMMARY | annis: {}, comms: {}, binds: {}, connects: {}, allocs: {}\nANNIS   | ERA-ERA: {}, LAM-LAM: {}, APP-APP: {}, DUP-DUP: {}\nCOMMS   | ERA-APP: {}, ERA-LAM: {}, ERA-DUP: {}, APP-LAM: {}, APP-DUP: {}, LAM-DUP: {}",
-            self.annihilations(),
-            self.commutations(),
-            self.binds(),
-            self.connects(),
-            self.allocs(),
-            self.anni_era_era(),
-            self.anni_lam_lam(),
-            self.anni_app_app(),
-            self.anni_dup_dup(),
-            self.comm_era_app(),
-            self.comm_era_lam(),
-            self.comm_era_dup(),
-            self.comm_app_lam(),
-            self.comm_app_dup(),
-            self.comm_lam_dup(),
+            "\n{:<12} | {:>width$} | {:>5.1}%",
+            "TOTAL",
+            total,
+            share(total)
+        )?;
+        if let Some(rate) = self.rewrites_per_sec() {
+            write!(
+                f,
+                "\n{:<12} | {:.2} M rewrites/s over {:.3} s",
+                "RATE",
+                rate / 1e6,
+                self.elapsed().as_secs_f64()
+            )?;
+        }
+        write!(
+            f,
+            "\n{:<12} | cells: {}, vars: {}, frees: {}, balance: {:+}",
+            "ALLOCS",
+            self.alloc_cells(),
+            self.alloc_vars(),
+            self.frees(),
+            self.balance()
+        )?;
+        if self.memo_hits() + self.memo_misses() > 0 {
+            write!(
+                f,
+                "\nMEMO    | hits: {}, misses: {}",
+                self.memo_hits(),
+                self.memo_misses()
+            )?;
+        }
+        let var_hops = self.var_hops();
+        if var_hops.iter().any(|count| *count > 0) {
+            write!(f, "\nCHAINS  |")?;
+            for (bucket, count) in var_hops.iter().enumerate() {
+                let sep = if bucket > 0 { "," } else { "" };
+                write!(f, "{} {}: {}", sep, var_hops_label(bucket), count)?;
+            }
+        }
+        let contention = self.contention();
+        if contention != Contention::default() {
+            write!(f, "\n{}", contention)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where an eval spent its time, see [`Runtime::phases`](super::runtime::Runtime::phases).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTimes {
+    /// Draining the net body and spawning a task per equation.
+    pub setup: Duration,
+    /// From the last initial spawn until every task is done.
+    pub reduction: Duration,
+    /// Reporting after the tasks are done. With
+    /// [`RuntimeConfig::time_phases`](super::runtime::RuntimeConfig::time_phases)
+    /// it also counts the time every task spent freeing its unused pointers
+    /// and merging its stats, summed over the tasks.
+    pub teardown: Duration,
+}
+
+impl Display for PhaseTimes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1e3;
+        write!(
+            f,
+            "PHASES  | setup: {:.3} ms, reduction: {:.3} ms, teardown: {:.3} ms",
+            ms(self.setup),
+            ms(self.reduction),
+            ms(self.teardown)
         )
     }
 }
 
+/// How many tasks an eval kept busy, see
+/// [`Runtime::concurrency`](super::runtime::Runtime::concurrency).
+///
+/// A task is queued from the time it is spawned until a thread starts it,
+/// and in flight until it ends. The means are sampled whenever a task
+/// starts, so they weigh the eval by task rather than by time: a peak in
+/// flight well under the thread count means the workload cannot keep every
+/// core busy, and a deep queue means it could use more of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Concurrency {
+    pub tasks: usize,
+    pub threads: usize,
+    pub peak_in_flight: usize,
+    pub peak_queued: usize,
+    pub mean_in_flight: f64,
+    pub mean_queued: f64,
+}
+
+impl Display for Concurrency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "TASKS   | tasks: {}, threads: {}, in flight: {} peak, {:.1} mean, queued: {} peak, {:.1} mean",
+            self.tasks,
+            self.threads,
+            self.peak_in_flight,
+            self.mean_in_flight,
+            self.peak_queued,
+            self.mean_queued
+        )
+    }
+}
+
+/// Counts the queued and in-flight tasks of an eval as they come and go,
+/// keeping their peaks and the sums behind their means.
+#[derive(Debug, Default)]
+pub struct TaskCounter {
+    queued: AtomicUsize,
+    in_flight: AtomicUsize,
+    peak_queued: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+    started: AtomicUsize,
+    queued_sum: AtomicUsize,
+    in_flight_sum: AtomicUsize,
+}
+
+impl TaskCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawned(&self) {
+        let queued = self.queued.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_queued.fetch_max(queued, Ordering::Relaxed);
+    }
+
+    pub fn started(&self) {
+        let queued = self.queued.fetch_sub(1, Ordering::Relaxed) - 1;
+        let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_in_flight.fetch_max(in_flight, Ordering::Relaxed);
+        self.started.fetch_add(1, Ordering::Relaxed);
+        self.queued_sum.fetch_add(queued, Ordering::Relaxed);
+        self.in_flight_sum.fetch_add(in_flight, Ordering::Relaxed);
+    }
+
+    pub fn ended(&self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// The peaks and means so far, with `threads` running the tasks.
+    pub fn concurrency(&self, threads: usize) -> Concurrency {
+        let tasks = self.started.load(Ordering::Relaxed);
+        let mean = |sum: &AtomicUsize| match tasks {
+            0 => 0.0,
+            _ => sum.load(Ordering::Relaxed) as f64 / tasks as f64,
+        };
+        Concurrency {
+            tasks,
+            threads,
+            peak_in_flight: self.peak_in_flight.load(Ordering::Relaxed),
+            peak_queued: self.peak_queued.load(Ordering::Relaxed),
+            mean_in_flight: mean(&self.in_flight_sum),
+            mean_queued: mean(&self.queued_sum),
+        }
+    }
+}
+
+/// Rewrites attributed to the definition that loaded the cells taking part
+/// in them.
+///
+/// Definitions are loaded up front, so only the cells allocated while
+/// loading one carry its name: a rewrite counts for the definition of its
+/// first cell, or of its second one, and is unattributed when neither was
+/// loaded by a definition (such as copies made by commutations).
+pub struct DefinitionStats {
+    // sorted by start, definitions never overlap; each range points into
+    // `names`, as a definition may span several shards
+    ranges: Vec<(Range<Index>, usize)>,
+    names: Vec<String>,
+    rewrites: Vec<AtomicUsize>,
+    unattributed: AtomicUsize,
+}
+
+impl DefinitionStats {
+    pub fn new(definitions: &HashMap<String, Vec<Range<Index>>>) -> Self {
+        let names: Vec<_> = definitions.keys().cloned().collect();
+        let mut ranges: Vec<_> = definitions
+            .values()
+            .enumerate()
+            .flat_map(|(idx, ranges)| ranges.iter().map(move |range| (range.clone(), idx)))
+            .collect();
+        ranges.sort_by_key(|(range, _)| range.start);
+        Self {
+            rewrites: names.iter().map(|_| AtomicUsize::new(0)).collect(),
+            names,
+            ranges,
+            unattributed: AtomicUsize::new(0),
+        }
+    }
+
+    fn definition(&self, ptr: Ptr) -> Option<usize> {
+        let idx = self
+            .ranges
+            .partition_point(|(range, _)| range.start <= ptr.index())
+            .checked_sub(1)?;
+        let (range, def) = &self.ranges[idx];
+        range.contains(&ptr.index()).then_some(*def)
+    }
+
+    pub fn record(&self, left: Option<Ptr>, right: Option<Ptr>) {
+        let def = left
+            .and_then(|ptr| self.definition(ptr))
+            .or_else(|| right.and_then(|ptr| self.definition(ptr)));
+        match def {
+            Some(idx) => self.rewrites[idx].fetch_add(1, Ordering::Relaxed),
+            None => self.unattributed.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    /// Rewrites per definition, most rewrites first.
+    pub fn rewrites(&self) -> Vec<(&str, usize)> {
+        let mut rewrites: Vec<_> = self
+            .names
+            .iter()
+            .zip(&self.rewrites)
+            .map(|(name, count)| (name.as_str(), count.load(Ordering::Relaxed)))
+            .collect();
+        rewrites.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        rewrites
+    }
+
+    pub fn unattributed(&self) -> usize {
+        self.unattributed.load(Ordering::Relaxed)
+    }
+}
+
+impl Display for DefinitionStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DEFS    |")?;
+        for (name, count) in self.rewrites() {
+            write!(f, " {}: {},", name, count)?;
+        }
+        write!(f, " (unattributed): {}", self.unattributed())
+    }
+}
+
+/// How the count of each rewrite rule changed from one eval to the next, as
+/// listed by [`GlobalStats::rules`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsDiff {
+    /// The rules whose count changed, before and after.
+    pub rules: Vec<(&'static str, usize, usize)>,
+    pub before: usize,
+    pub after: usize,
+}
+
+impl StatsDiff {
+    pub fn new(before: &[(&'static str, usize)], after: &[(&'static str, usize)]) -> Self {
+        let rules = before
+            .iter()
+            .zip(after)
+            .filter(|((_, before), (_, after))| before != after)
+            .map(|((rule, before), (_, after))| (*rule, *before, *after))
+            .collect();
+        StatsDiff {
+            rules,
+            before: before.iter().map(|(_, count)| count).sum(),
+            after: after.iter().map(|(_, count)| count).sum(),
+        }
+    }
+}
+
+impl Display for StatsDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .before
+            .max(self.after)
+            .to_string()
+            .len()
+            .max("BEFORE".len())
+            + 1;
+        let row = |f: &mut Formatter<'_>, rule: &str, before: usize, after: usize| {
+            let delta = after as i64 - before as i64;
+            write!(
+                f,
+                "\n{:<12} | {:>width$} | {:>width$} | {:>+width$}",
+                rule, before, after, delta
+            )
+        };
+        write!(
+            f,
+            "{:<12} | {:>width$} | {:>width$} | {:>width$}",
+            "RULE", "BEFORE", "AFTER", "DELTA"
+        )?;
+        for (rule, before, after) in &self.rules {
+            row(f, rule, *before, *after)?;
+        }
+        row(f, "TOTAL", self.before, self.after)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::strandal::stats::{GlobalStats, LocalStats, Stats};
+    use std::{collections::HashMap, time::Duration};
+
+    use crate::strandal::{
+        stats::{DefinitionStats, GlobalStats, LocalStats, Stats, StatsDiff, VAR_HOPS_BUCKETS},
+        store::{Contention, Ptr},
+    };
 
     // use super::*;
 
     #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
     fn test_stats() {
         let global_stats = GlobalStats::new();
         assert_eq!(global_stats.annihilations(), 0);
@@ -343,10 +1103,101 @@ mod tests {
         global_stats.update(stats);
         assert_eq!(global_stats.annihilations(), 4);
         assert_eq!(global_stats.commutations(), 7);
+        assert_eq!(global_stats.interactions(), 11);
         assert_eq!(global_stats.binds(), 1);
         assert_eq!(global_stats.connects(), 1);
         assert_eq!(global_stats.allocs(), 1);
 
         println!("{}", global_stats);
     }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_stats_table() {
+        let global_stats = GlobalStats::new();
+        let mut stats = LocalStats::new();
+        stats.inc_comm_app_lam();
+        stats.inc_binds();
+        stats.inc_binds();
+        stats.inc_connects();
+        (0..3).for_each(|_| stats.inc_alloc_vars());
+        global_stats.update(stats);
+        global_stats.record_eval(Duration::from_millis(2), 1);
+
+        assert_eq!(global_stats.rewrites(), 4);
+        assert_eq!(global_stats.rewrites_per_sec(), Some(2000.0));
+        assert_eq!(global_stats.balance(), 2);
+        let table = global_stats.to_string();
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines[0], "RULE         | COUNT |      %");
+        assert!(lines.contains(&"COMM APP-LAM |     1 |  25.0%"));
+        assert!(lines.contains(&"BIND         |     2 |  50.0%"));
+        assert!(lines.contains(&"TOTAL        |     4 | 100.0%"));
+        assert!(lines.contains(&"RATE         | 0.00 M rewrites/s over 0.002 s"));
+        assert!(lines.contains(&"ALLOCS       | cells: 0, vars: 3, frees: 1, balance: +2"));
+        assert!(!table.contains("CONTEND"));
+
+        let mut stats = LocalStats::new();
+        stats.record_contention(Contention {
+            alloc_retries: 3,
+            shard_misses: 0,
+            remote_frees: 1,
+        });
+        global_stats.update(stats);
+        assert_eq!(global_stats.contention().alloc_retries, 3);
+        assert!(global_stats
+            .to_string()
+            .ends_with("CONTEND | alloc retries: 3, shard misses: 0, remote frees: 1"));
+    }
+
+    #[test]
+    fn test_stats_diff() {
+        let before = [("COMM APP-LAM", 1), ("BIND", 2), ("CONNECT", 1)];
+        let after = [("COMM APP-LAM", 3), ("BIND", 2), ("CONNECT", 0)];
+        let diff = StatsDiff::new(&before, &after);
+        assert_eq!(diff.rules, vec![("COMM APP-LAM", 1, 3), ("CONNECT", 1, 0)]);
+        assert_eq!(
+            diff.to_string(),
+            "RULE         |  BEFORE |   AFTER |   DELTA\n\
+             COMM APP-LAM |       1 |       3 |      +2\n\
+             CONNECT      |       1 |       0 |      -1\n\
+             TOTAL        |       4 |       5 |      +1"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "no-stats", ignore = "stats are compiled out")]
+    fn test_var_hops() {
+        let global_stats = GlobalStats::new();
+        let mut stats = LocalStats::new();
+        for hops in [0, 0, 1, 2, 3, 4, 1000] {
+            stats.record_var_hops(hops);
+        }
+        global_stats.update(stats);
+        let mut expected = [0; VAR_HOPS_BUCKETS];
+        expected[..4].copy_from_slice(&[2, 1, 2, 1]);
+        expected[VAR_HOPS_BUCKETS - 1] = 1;
+        assert_eq!(global_stats.var_hops(), expected);
+        assert!(global_stats.to_string().ends_with(
+            "CHAINS  | 0: 2, 1: 1, 2-3: 2, 4-7: 1, 8-15: 0, 16-31: 0, 32-63: 0, 64+: 1"
+        ));
+    }
+
+    #[test]
+    fn test_definition_stats() {
+        let definitions = HashMap::from([
+            ("a".to_string(), vec![0..4, 4096..4097]),
+            ("b".to_string(), vec![6..8, 4098..4100]),
+        ]);
+        let stats = DefinitionStats::new(&definitions);
+        stats.record(Some(Ptr::new(7)), Some(Ptr::new(0)));
+        stats.record(Some(Ptr::new(5)), Some(Ptr::new(3)));
+        stats.record(None, Some(Ptr::new(6)));
+        stats.record(Some(Ptr::new(8)), None);
+        stats.record(Some(Ptr::new(4096)), None);
+        stats.record(None, Some(Ptr::new(4099)));
+        assert_eq!(stats.rewrites(), vec![("b", 3), ("a", 2)]);
+        assert_eq!(stats.unattributed(), 1);
+        assert_eq!(stats.to_string(), "DEFS    | b: 3, a: 2, (unattributed): 1");
+    }
 }
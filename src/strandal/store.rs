@@ -1,133 +1,885 @@
 use std::{
-    alloc::{alloc, Layout},
+    collections::BTreeMap,
     fmt::Display,
-    sync::atomic::{AtomicU32, Ordering},
+    ops::Range,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
 };
 
-use super::term::Term;
+use super::{
+    codec,
+    num::Op,
+    refs::RefId,
+    term::{Cell, Label, Term, TermPtr, TermRef},
+    var::Var,
+};
+
+/// The type of store indices: `u32` by default, or `u64` with the
+/// `wide-ptrs` feature for nets beyond 4G slots.
+#[cfg(not(feature = "wide-ptrs"))]
+pub type Index = u32;
+#[cfg(not(feature = "wide-ptrs"))]
+type AtomicIndex = std::sync::atomic::AtomicU32;
+
+#[cfg(feature = "wide-ptrs")]
+pub type Index = u64;
+#[cfg(feature = "wide-ptrs")]
+type AtomicIndex = std::sync::atomic::AtomicU64;
+
+/// The index of a store slot.
+///
+/// In debug builds a pointer also carries the generation of its slot when it
+/// was handed out. Freeing a slot bumps its generation, so any later access
+/// through an older pointer panics instead of reading whatever reused the
+/// slot. Pointers built from a bare index with [`Ptr::new`] are not checked.
+#[derive(Clone, Copy)]
+pub struct Ptr {
+    idx: Index,
+    #[cfg(debug_assertions)]
+    gen: u32,
+}
+
+// generations fit the 30 bits a var value leaves above the index, with the
+// largest one meaning unchecked, see `codec`
+#[cfg(debug_assertions)]
+const UNCHECKED: u32 = codec::GEN_MASK;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Ptr(u32);
 impl Ptr {
     #[inline]
-    pub fn new(value: u32) -> Self {
-        Ptr(value)
+    pub fn new(value: Index) -> Self {
+        Ptr {
+            idx: value,
+            #[cfg(debug_assertions)]
+            gen: UNCHECKED,
+        }
+    }
+
+    #[inline]
+    pub fn index(&self) -> Index {
+        self.idx
     }
 
+    /// Pack the pointer in the low 62 bits, for var values, see
+    /// [`codec::pack_ptr`]. Wide indices leave no room for the generation,
+    /// which is then not checked.
     #[inline]
-    pub fn index(&self) -> u32 {
-        self.0
+    #[allow(clippy::useless_conversion)] // `Index` is already a u64 under wide-ptrs
+    pub(crate) fn to_bits(self) -> u64 {
+        #[cfg(debug_assertions)]
+        let gen = self.gen;
+        #[cfg(not(debug_assertions))]
+        let gen = 0;
+        codec::pack_ptr(u64::from(self.idx), gen)
     }
 
     #[inline]
-    pub fn as_u32(&self) -> u32 {
-        self.0
+    pub(crate) fn from_bits(bits: u64) -> Self {
+        #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+        let (idx, gen) = codec::unpack_ptr(bits);
+        Ptr {
+            idx: idx as Index,
+            #[cfg(debug_assertions)]
+            gen,
+        }
+    }
+}
+
+// pointers are equal when they point to the same slot, whatever their
+// generation
+impl PartialEq for Ptr {
+    fn eq(&self, other: &Self) -> bool {
+        self.idx == other.idx
+    }
+}
+
+impl Eq for Ptr {}
+
+impl std::hash::Hash for Ptr {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.idx.hash(state);
+    }
+}
+
+impl std::fmt::Debug for Ptr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Ptr({})", self.idx)
     }
 }
-unsafe impl Send for Ptr {}
-unsafe impl Sync for Ptr {}
 
 impl Display for Ptr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "#{}", self.0)
+        write!(f, "#{}", self.idx)
     }
 }
 
-#[derive(Debug)]
+/// The slots of a net, in a single arena split into shards.
+///
+/// Each shard is a region of the arena starting at a power of two boundary,
+/// so the high bits of a pointer select its shard. A thread allocates from
+/// its own shard, moving on to the next ones once it is full, so threads do
+/// not contend on the same `next` and `len` counters.
+///
+/// Every slot is a few atomic words, see [`Slot`], so threads read and write
+/// them without locks and without sharing anything but the words. The slots
+/// are allocated a segment at a time, when a slot of the segment is first
+/// used, so a store takes memory for the slots it uses, not its capacity.
+///
+/// A store built with [`Store::new_checked`] or [`Store::checked`] also
+/// checks every access, whatever the build: that the slot is in bounds and
+/// was handed out, that it holds a well-formed var or cell whose ports point
+/// to slots handed out, and that it is not freed twice. It panics on the
+/// first corrupt slot instead of reducing it, at the cost of a few loads per
+/// access.
 pub struct Store {
-    mem: *mut Option<Term>, // raw mutable pointer
-    pub capacity: u32,
-    next: AtomicU32,
-    len: AtomicU32,
+    segments: Box<[OnceLock<Box<[Slot]>>]>,
+    pub capacity: Index,
+    shards: Box<[Shard]>,
+    // the slots of each shard, and the bits of an index below its shard
+    shard_size: Index,
+    shard_bits: u32,
+    checked: bool,
+}
+
+impl std::fmt::Debug for Store {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Store")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .field("shards", &self.shards)
+            .field("checked", &self.checked)
+            .finish_non_exhaustive()
+    }
+}
+
+// each shard on its own pair of cache lines, since some cores fetch lines in
+// pairs, so threads allocating from different shards do not share them
+#[derive(Debug, Default)]
+#[repr(align(128))]
+struct Shard {
+    next: AtomicIndex,
+    len: AtomicIndex,
+    // the attempts to hand out a slot that lost a race for `next`
+    retries: AtomicU64,
+}
+
+/// A slot of the store: a head word telling what it holds, then the value
+/// of a var, or the ports and payload of a cell.
+///
+/// ```text
+/// head  | generation (debug builds) | flag | ports | kind |
+/// var   | var value, or the first port
+/// words | the second port           | number, operator, label or reference
+/// ```
+///
+/// The head is written last, with release ordering, so a thread that reads
+/// it with acquire ordering sees the rest of the slot.
+#[derive(Debug)]
+struct Slot {
+    head: AtomicU64,
+    var: Var,
+    words: [AtomicU64; 2],
 }
 
-impl Drop for Store {
-    fn drop(&mut self) {
-        let layout: Layout = Layout::array::<Option<Term>>(self.capacity as usize)
-            .expect("Could not deallocate Store");
-        unsafe {
-            std::alloc::dealloc(self.mem as *mut u8, layout);
+const _: () = assert!(std::mem::size_of::<Slot>() == 4 * 8);
+
+// the bits of an index below its segment of slots
+const SEGMENT_BITS: u32 = 14;
+
+// the kind of a slot, in the low bits of its head
+const EMPTY: u64 = 0;
+const VAR: u64 = 1;
+const DUP: u64 = 2;
+const APP: u64 = 3;
+const LAM: u64 = 4;
+const NUM: u64 = 5;
+const OP: u64 = 6;
+const SWI: u64 = 7;
+const REF: u64 = 8;
+const KIND_MASK: u64 = 0xf;
+// the cell holds its ports
+const PORTS: u64 = 1 << 4;
+// a labelled duplicator, or a flipped operator
+const FLAG: u64 = 1 << 5;
+// the generation of the slot, see `Ptr`
+const HEAD_GEN_SHIFT: u32 = 32;
+// the bits of a head no slot is written with
+const HEAD_UNUSED: u64 = !(KIND_MASK | PORTS | FLAG | (codec::GEN_MASK as u64) << HEAD_GEN_SHIFT);
+
+// a port of a cell, all ones for an eraser as no pointer packs to it
+const ERA_PORT: u64 = u64::MAX;
+
+#[inline]
+fn port_to_bits(port: TermPtr) -> u64 {
+    match port {
+        TermPtr::Era => ERA_PORT,
+        TermPtr::Ptr(ptr) => ptr.to_bits(),
+    }
+}
+
+#[inline]
+fn port_from_bits(bits: u64) -> TermPtr {
+    match bits {
+        ERA_PORT => TermPtr::Era,
+        bits => TermPtr::Ptr(Ptr::from_bits(bits)),
+    }
+}
+
+impl Slot {
+    // an empty slot of generation 0
+    fn new() -> Self {
+        Slot {
+            head: AtomicU64::new(EMPTY),
+            var: Var::new(),
+            words: [AtomicU64::new(0), AtomicU64::new(0)],
         }
     }
+
+    #[inline]
+    fn read(&self) -> Option<TermRef<'_>> {
+        let head = self.head.load(Ordering::Acquire);
+        let ports = || {
+            (head & PORTS != 0).then(|| {
+                (
+                    port_from_bits(self.var.word().load(Ordering::Relaxed)),
+                    port_from_bits(self.words[0].load(Ordering::Relaxed)),
+                )
+            })
+        };
+        let payload = || self.words[1].load(Ordering::Relaxed);
+        let flag = head & FLAG != 0;
+        let cell = match head & KIND_MASK {
+            EMPTY => return None,
+            VAR => return Some(TermRef::Var(&self.var)),
+            DUP => Cell::Dup(ports(), flag.then(|| Label::from_bits(payload()))),
+            APP => Cell::App(ports()),
+            LAM => Cell::Lam(ports()),
+            NUM => Cell::Num(payload() as u32),
+            OP => Cell::Op(ports(), Op::ALL[payload() as usize], flag),
+            SWI => Cell::Swi(ports()),
+            _ => Cell::Ref(payload() as RefId),
+        };
+        Some(TermRef::Cell(cell))
+    }
+
+    // write the term, then publish it in the head, keeping the generation
+    #[inline]
+    fn write(&self, term: Option<Term>) {
+        let kind = match term {
+            None => EMPTY,
+            Some(Term::Var(var)) => {
+                let value = var.word().load(Ordering::Relaxed);
+                self.var.word().store(value, Ordering::Relaxed);
+                VAR
+            }
+            Some(Term::Cell(cell)) => self.write_cell(cell),
+        };
+        let gen = self.head.load(Ordering::Relaxed) >> HEAD_GEN_SHIFT;
+        self.head
+            .store(gen << HEAD_GEN_SHIFT | kind, Ordering::Release);
+    }
+
+    // the words of a cell, and the bits of its head
+    #[inline]
+    fn write_cell(&self, cell: Cell) -> u64 {
+        let (kind, flag, payload) = match cell {
+            Cell::Dup(_, label) => (DUP, label.is_some(), label.map_or(0, Label::to_bits)),
+            Cell::App(_) => (APP, false, 0),
+            Cell::Lam(_) => (LAM, false, 0),
+            Cell::Num(value) => (NUM, false, value as u64),
+            Cell::Op(_, op, flipped) => (OP, flipped, op as u64),
+            Cell::Swi(_) => (SWI, false, 0),
+            Cell::Ref(id) => (REF, false, id as u64),
+        };
+        let mut head = kind;
+        if flag {
+            head |= FLAG;
+        }
+        if let Some((p0, p1)) = cell.ports() {
+            self.var.word().store(port_to_bits(p0), Ordering::Relaxed);
+            self.words[0].store(port_to_bits(p1), Ordering::Relaxed);
+            head |= PORTS;
+        }
+        self.words[1].store(payload, Ordering::Relaxed);
+        head
+    }
+
+    // empty the slot, bumping its generation in debug builds
+    #[inline]
+    fn empty(&self) {
+        let gen = match cfg!(debug_assertions) {
+            true => ((self.gen() + 1) % codec::GEN_MASK) as u64,
+            false => 0,
+        };
+        self.head
+            .store(gen << HEAD_GEN_SHIFT | EMPTY, Ordering::Release);
+    }
+
+    #[inline]
+    fn gen(&self) -> u32 {
+        (self.head.load(Ordering::Relaxed) >> HEAD_GEN_SHIFT) as u32
+    }
 }
+
 impl Store {
+    pub const DEFAULT_CAPACITY: Index = 2 << 24;
+
     #[inline]
     pub fn new() -> Self {
-        Self::with_capacity(2 << 24)
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: Index) -> Self {
+        Self::with_shards(capacity, 1)
     }
 
-    pub fn with_capacity(capacity: u32) -> Self {
-        let layout: Layout =
-            Layout::array::<Option<Term>>(capacity as usize).expect("Could not allocate Store");
-        let mem = unsafe { alloc(layout) } as *mut Option<Term>;
-        assert!(!mem.is_null(), "Could not allocate Store");
+    /// A store of the default capacity that checks every access, see
+    /// [`Store::checked`].
+    pub fn new_checked() -> Self {
+        Self::new().checked()
+    }
+
+    /// The same store, checking every access from now on: a slower backend
+    /// to switch to when chasing a corrupt net, see the [type docs](Store).
+    pub fn checked(mut self) -> Self {
+        self.checked = true;
+        self
+    }
+
+    /// Whether the store checks every access.
+    pub fn is_checked(&self) -> bool {
+        self.checked
+    }
+
+    /// A store of `capacity` slots split evenly into `shards`. Shards start
+    /// at a power of two boundary, so unless their size is a power of two
+    /// the arena is larger than `capacity`.
+    pub fn with_shards(capacity: Index, shards: usize) -> Self {
+        assert!(shards > 0, "a store needs at least one shard");
+        let shard_size = capacity.div_ceil(shards as Index);
+        let shard_bits = shard_size.next_power_of_two().trailing_zeros();
+        let capacity = ((shards as Index - 1) << shard_bits) + shard_size;
         Store {
-            mem,
+            segments: (0..(capacity as usize).div_ceil(1 << SEGMENT_BITS))
+                .map(|_| OnceLock::new())
+                .collect(),
             capacity,
-            next: AtomicU32::new(0),
-            len: AtomicU32::new(0),
+            shards: (0..shards).map(|_| Shard::default()).collect(),
+            shard_size,
+            shard_bits,
+            checked: false,
         }
     }
 
+    /// The number of live slots.
+    #[inline]
+    pub fn len(&self) -> Index {
+        return self
+            .shards
+            .iter()
+            .map(|shard| shard.len.load(Ordering::Relaxed))
+            .sum();
+    }
+
+    /// The number of slots handed out so far. With a single shard, these are
+    /// the slots below this index.
+    #[inline]
+    pub fn next(&self) -> Index {
+        return (0..self.shards.len())
+            .map(|shard| self.shard_next(shard))
+            .sum();
+    }
+
     #[inline]
-    pub fn len(&self) -> u32 {
-        return self.len.load(Ordering::Relaxed);
+    pub fn shards(&self) -> usize {
+        self.shards.len()
     }
 
+    /// The shard `ptr` points into.
     #[inline]
-    pub fn next(&self) -> u32 {
-        return self.next.load(Ordering::Relaxed);
+    pub fn shard(&self, ptr: Ptr) -> usize {
+        (ptr.idx >> self.shard_bits) as usize
+    }
+
+    #[inline]
+    fn shard_start(&self, shard: usize) -> Index {
+        (shard as Index) << self.shard_bits
+    }
+
+    #[inline]
+    fn shard_next(&self, shard: usize) -> Index {
+        self.shards[shard].next.load(Ordering::Relaxed)
+    }
+
+    /// How many times a thread lost a race to hand out a slot and tried
+    /// again, a measure of the contention on the allocation counters.
+    pub fn retries(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.retries.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Whether the slot `ptr` points to was handed out, live or freed since.
+    #[inline]
+    pub fn handed_out(&self, ptr: Ptr) -> bool {
+        let shard = self.shard(ptr);
+        shard < self.shards.len() && ptr.idx - self.shard_start(shard) < self.shard_next(shard)
+    }
+
+    /// The number of slots handed out so far by each shard, to pass to
+    /// [`Store::handed_out_since`].
+    pub fn marks(&self) -> Vec<Index> {
+        (0..self.shards.len())
+            .map(|shard| self.shard_next(shard))
+            .collect()
+    }
+
+    /// The slots handed out since `marks` were taken, as one range per shard
+    /// that handed out any. A single thread allocates from its own shard
+    /// until it is full, so this is usually a single range.
+    pub fn handed_out_since(&self, marks: &[Index]) -> Vec<Range<Index>> {
+        marks
+            .iter()
+            .enumerate()
+            .filter(|&(shard, &mark)| self.shard_next(shard) > mark)
+            .map(|(shard, &mark)| {
+                let start = self.shard_start(shard);
+                start + mark..start + self.shard_next(shard)
+            })
+            .collect()
+    }
+
+    /// Pointers to every slot handed out so far, shard by shard.
+    pub fn ptrs(&self) -> impl Iterator<Item = Ptr> + '_ {
+        (0..self.shards.len()).flat_map(move |shard| {
+            let start = self.shard_start(shard);
+            (start..start + self.shard_next(shard)).map(Ptr::new)
+        })
+    }
+
+    /// Every live slot, shard by shard, as [`Store::get`] reads it. The
+    /// slots are read one at a time, so a net being evaluated meanwhile may
+    /// be seen half rewritten.
+    pub fn iter(&self) -> impl Iterator<Item = (Ptr, TermRef<'_>)> + '_ {
+        self.ptrs()
+            .filter_map(|ptr| self.get(ptr).map(|term| (ptr, term)))
+    }
+
+    /// A copy of every live slot, which unlike [`Store::iter`] does not
+    /// borrow the store: its vars keep the values they had when copied, and
+    /// the store may be written to, cleared or dropped meanwhile. The store
+    /// is borrowed mutably so that no eval rewrites it while it is copied:
+    /// the copy is never half rewritten.
+    pub fn snapshot(&mut self) -> Vec<(Ptr, Term)> {
+        self.iter().map(|(ptr, term)| (ptr, term.into())).collect()
     }
 
     #[inline]
     pub fn alloc(&self, value: Option<Term>) -> Ptr {
-        let ptr = self.inc_next();
-        unsafe {
-            self.ptr(ptr).write(value);
-            self.len.fetch_add(1, Ordering::Relaxed);
-            return ptr;
+        #[allow(unused_mut)]
+        let (shard, mut ptr) = self.inc_next();
+        let slot = self.slot(ptr);
+        #[cfg(debug_assertions)]
+        {
+            ptr.gen = slot.gen();
         }
+        slot.write(value);
+        self.shards[shard].len.fetch_add(1, Ordering::Relaxed);
+        ptr
     }
+
+    /// Hand out again the slot `ptr` points to, which must have been freed
+    /// and be read and written by no one else meanwhile, see
+    /// [`Store::is_free`]: the store does not keep track of its freed slots,
+    /// so only whoever freed them can hand them out again.
     #[inline]
-    pub fn free(&self, ptr: Ptr) -> Option<Term> {
-        unsafe {
-            self.len.fetch_sub(1, Ordering::Relaxed);
-            return self.ptr(ptr).replace(None);
+    pub fn reuse(&self, ptr: Ptr, value: Option<Term>) -> Ptr {
+        #[allow(unused_mut)]
+        let mut ptr = Ptr::new(ptr.idx);
+        let slot = self.slot(ptr);
+        #[cfg(debug_assertions)]
+        {
+            ptr.gen = slot.gen();
         }
+        slot.write(value);
+        self.shards[self.shard(ptr)]
+            .len
+            .fetch_add(1, Ordering::Relaxed);
+        ptr
+    }
+
+    /// Whether the slot `ptr` points to was handed out and freed since.
+    pub fn is_free(&self, ptr: Ptr) -> bool {
+        self.handed_out(ptr) && self.slot(ptr).read().is_none()
     }
 
     #[inline]
-    pub fn get(&self, ptr: Ptr) -> &Option<Term> {
-        unsafe {
-            return self.ptr(ptr).as_ref().expect("Index out of bounds");
+    pub fn free(&self, ptr: Ptr) {
+        if cfg!(debug_assertions) || self.checked {
+            self.check_free(ptr);
+        }
+        let shard = self.shard(ptr);
+        if self.shards.len() > 1 && shard != self.home_shard() {
+            count_contention(|contention| contention.remote_frees += 1);
+        }
+        self.shards[shard].len.fetch_sub(1, Ordering::Relaxed);
+        self.slot(ptr).empty();
+    }
+
+    // panic on an access through a pointer to a slot freed since it was
+    // handed out
+    #[cfg(debug_assertions)]
+    #[track_caller]
+    fn check_gen(&self, ptr: Ptr) {
+        if ptr.gen == UNCHECKED || !self.handed_out(ptr) {
+            return;
         }
+        let gen = self.slot(ptr).gen();
+        assert!(
+            ptr.gen == gen,
+            "used {} after it was freed (generation {}, slot at {}, during {})",
+            ptr,
+            ptr.gen,
+            gen,
+            current_rule()
+        );
+    }
+
+    // panic on freeing a slot twice, or one never handed out, naming the
+    // rule the runtime was applying on this thread
+    fn check_free(&self, ptr: Ptr) {
+        let rule = current_rule();
+        assert!(
+            self.handed_out(ptr),
+            "freed {} which was never allocated (during {})",
+            ptr,
+            rule
+        );
+        assert!(
+            self.get(Ptr::new(ptr.idx)).is_some(),
+            "freed {} twice (during {})",
+            ptr,
+            rule
+        );
     }
 
     #[inline]
-    pub fn set(&self, ptr: Ptr, term: Term) -> Option<Term> {
-        unsafe {
-            return self.ptr(ptr).replace(Some(term));
+    #[track_caller]
+    pub fn get(&self, ptr: Ptr) -> Option<TermRef<'_>> {
+        #[cfg(debug_assertions)]
+        self.check_gen(ptr);
+        if self.checked {
+            self.check_slot(ptr);
+        }
+        self.slot(ptr).read()
+    }
+
+    // panic on a slot out of bounds, never handed out, or holding what no
+    // term is written as, for checked stores
+    #[track_caller]
+    fn check_slot(&self, ptr: Ptr) {
+        assert!(
+            ptr.idx < self.capacity,
+            "{} is out of the bounds of the store ({} slots)",
+            ptr,
+            self.capacity
+        );
+        assert!(self.handed_out(ptr), "read {} which was never allocated", ptr);
+        let slot = self.slot(ptr);
+        let head = slot.head.load(Ordering::Acquire);
+        let well_formed = head & HEAD_UNUSED == 0
+            && match head & KIND_MASK {
+                EMPTY | VAR | NUM | REF => head & (PORTS | FLAG) == 0,
+                APP | LAM | SWI => head & FLAG == 0,
+                DUP => true,
+                OP => slot.words[1].load(Ordering::Relaxed) < Op::ALL.len() as u64,
+                _ => false,
+            };
+        assert!(well_formed, "{} holds no term (head {:#x})", ptr, head);
+        if head & PORTS != 0 {
+            let ports = [slot.var.word(), &slot.words[0]];
+            for port in ports.map(|port| port.load(Ordering::Relaxed)) {
+                if let TermPtr::Ptr(port) = port_from_bits(port) {
+                    assert!(
+                        self.handed_out(port),
+                        "{} has a port to {}, which was never allocated",
+                        ptr,
+                        port
+                    );
+                }
+            }
         }
     }
 
+    /// Overwrite the slot `ptr` points to, which must not be read by another
+    /// thread meanwhile: its words are not written all at once.
+    #[inline]
+    #[track_caller]
+    pub fn set(&self, ptr: Ptr, term: Term) {
+        #[cfg(debug_assertions)]
+        self.check_gen(ptr);
+        if self.checked {
+            self.check_slot(ptr);
+        }
+        self.slot(ptr).write(Some(term));
+    }
+
+    /// Empty the store, live slots included, keeping its memory: the next
+    /// slots are handed out from the start of each shard again. The pointers
+    /// handed out before must not be used anymore, which debug builds check.
+    ///
+    /// Debug builds visit every slot handed out, to bump its generation, so
+    /// they take a time proportional to the slots handed out. Release builds
+    /// only reset the shards, leaving the slots as they are to be overwritten
+    /// as they are handed out again.
+    pub fn clear(&mut self) {
+        if cfg!(debug_assertions) {
+            for ptr in self.ptrs() {
+                self.slot(ptr).empty();
+            }
+        }
+        self.shards.iter_mut().for_each(|shard| *shard = Shard::default());
+    }
+
+    /// Scan the slots handed out so far for the ones freed and not reused.
+    pub fn fragmentation(&self) -> Fragmentation {
+        let mut frag = Fragmentation::default();
+        for shard in 0..self.shards.len() {
+            frag.merge(self.shard_fragmentation(shard));
+        }
+        frag
+    }
+
+    /// The [`Fragmentation`] of a single shard, whose slots can be compacted
+    /// independently of the others.
+    pub fn shard_fragmentation(&self, shard: usize) -> Fragmentation {
+        let start = self.shard_start(shard);
+        let next = self.shard_next(shard);
+        let mut frag = Fragmentation {
+            high_water: next,
+            live: self.shards[shard].len.load(Ordering::Relaxed),
+            ..Fragmentation::default()
+        };
+        let mut gap = 0;
+        for idx in start..start + next {
+            if self.get(Ptr::new(idx)).is_none() {
+                frag.holes += 1;
+                gap += 1;
+            } else if gap > 0 {
+                frag.add_gap(gap);
+                gap = 0;
+            }
+        }
+        if gap > 0 {
+            frag.add_gap(gap);
+        }
+        frag
+    }
+
+    #[inline]
+    #[track_caller]
+    fn slot(&self, ptr: Ptr) -> &Slot {
+        let idx = ptr.idx as usize;
+        let segment = idx >> SEGMENT_BITS;
+        let slots = self.segments[segment].get_or_init(|| {
+            let start = segment << SEGMENT_BITS;
+            let len = (self.capacity as usize - start).min(1 << SEGMENT_BITS);
+            (0..len).map(|_| Slot::new()).collect()
+        });
+        &slots[idx & ((1 << SEGMENT_BITS) - 1)]
+    }
+
+    // the shard of the current thread
     #[inline]
-    unsafe fn ptr(&self, index: Ptr) -> *mut Option<Term> {
-        self.mem.add(index.0 as usize)
+    fn home_shard(&self) -> usize {
+        match self.shards.len() {
+            1 => 0,
+            shards => rayon::current_thread_index().map_or(0, |thread| thread % shards),
+        }
     }
 
+    // hand out the next slot of the thread's shard, or of the following ones
+    // once it is full
     #[inline]
-    fn inc_next(&self) -> Ptr {
-        return Ptr(self.next.fetch_add(1, std::sync::atomic::Ordering::Relaxed));
+    fn inc_next(&self) -> (usize, Ptr) {
+        let home = self.home_shard();
+        for shard in (home..self.shards.len()).chain(0..home) {
+            let Shard { next, retries, .. } = &self.shards[shard];
+            let mut offset = next.load(Ordering::Relaxed);
+            while offset < self.shard_size {
+                match next.compare_exchange_weak(
+                    offset,
+                    offset + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        if shard != home {
+                            count_contention(|contention| contention.shard_misses += 1);
+                        }
+                        return (shard, Ptr::new(self.shard_start(shard) + offset));
+                    }
+                    Err(current) => {
+                        retries.fetch_add(1, Ordering::Relaxed);
+                        count_contention(|contention| contention.alloc_retries += 1);
+                        offset = current;
+                    }
+                }
+            }
+        }
+        panic!("the store is full ({} slots)", self.next());
+    }
+}
+
+#[cfg(debug_assertions)]
+thread_local! {
+    static RULE_CONTEXT: std::cell::Cell<&'static str> = const { std::cell::Cell::new("no rule") };
+}
+
+thread_local! {
+    static CONTENTION: std::cell::Cell<Contention> = const {
+        std::cell::Cell::new(Contention {
+            alloc_retries: 0,
+            shard_misses: 0,
+            remote_frees: 0,
+        })
+    };
+}
+
+/// The contention the allocations and frees of a thread ran into, counted
+/// apart from the rewrites so that a slow eval can be told to wait on the
+/// store rather than on the scheduler or the rules.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Contention {
+    /// Races for the next slot of a shard lost to another thread, each
+    /// followed by a retry.
+    pub alloc_retries: usize,
+    /// Slots handed out by another shard than the thread's own, once full.
+    pub shard_misses: usize,
+    /// Slots freed into another shard than the thread's own, whose counters
+    /// its threads keep using.
+    pub remote_frees: usize,
+}
+
+impl Display for Contention {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "CONTEND | alloc retries: {}, shard misses: {}, remote frees: {}",
+            self.alloc_retries, self.shard_misses, self.remote_frees
+        )
     }
 }
 
-unsafe impl Send for Store {}
-unsafe impl Sync for Store {}
+#[inline]
+fn count_contention(count: impl FnOnce(&mut Contention)) {
+    if cfg!(feature = "no-stats") {
+        return;
+    }
+    CONTENTION.with(|contention| {
+        let mut counts = contention.get();
+        count(&mut counts);
+        contention.set(counts);
+    });
+}
+
+/// The contention this thread ran into since the last call.
+pub fn take_contention() -> Contention {
+    CONTENTION.with(|contention| contention.take())
+}
+
+// the rule this thread is applying, for the panics of the checks
+fn current_rule() -> &'static str {
+    #[cfg(debug_assertions)]
+    return RULE_CONTEXT.with(|rule| rule.get());
+    #[cfg(not(debug_assertions))]
+    "a rule only named in debug builds"
+}
+
+/// Name the rule this thread is applying, for the double-free check of
+/// debug builds. A no-op in release builds.
+#[inline]
+pub fn rule_context(rule: &'static str) {
+    #[cfg(debug_assertions)]
+    RULE_CONTEXT.with(|context| context.set(rule));
+    #[cfg(not(debug_assertions))]
+    let _ = rule;
+}
+
+/// How the slots below the high-water mark of each shard are used, see
+/// [`Store::fragmentation`].
+///
+/// Allocation only ever bumps the high-water mark, so a freed slot comes
+/// back into use only through the [`FreePtrs`] of the task that freed it:
+/// every hole counted here was freed and never reused.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Fragmentation {
+    /// The slots handed out so far, over all shards.
+    pub high_water: Index,
+    pub live: Index,
+    pub holes: Index,
+    /// Runs of consecutive holes, by the power of two their length rounds
+    /// up to.
+    pub gaps: BTreeMap<Index, Index>,
+}
+
+impl Fragmentation {
+    fn add_gap(&mut self, len: Index) {
+        *self.gaps.entry(len.next_power_of_two()).or_default() += 1;
+    }
+
+    fn merge(&mut self, other: Fragmentation) {
+        self.high_water += other.high_water;
+        self.live += other.live;
+        self.holes += other.holes;
+        for (len, count) in other.gaps {
+            *self.gaps.entry(len).or_default() += count;
+        }
+    }
+
+    /// The share of the slots handed out that are still live.
+    pub fn occupancy(&self) -> f64 {
+        match self.high_water {
+            0 => 1.0,
+            high_water => self.live as f64 / high_water as f64,
+        }
+    }
+}
+
+impl Display for Fragmentation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "FRAG    | high water: {}, live: {}, holes: {}, occupancy: {:.1}%",
+            self.high_water,
+            self.live,
+            self.holes,
+            self.occupancy() * 100.0
+        )?;
+        if !self.gaps.is_empty() {
+            write!(f, "\nGAPS    |")?;
+            for (idx, (len, count)) in self.gaps.iter().enumerate() {
+                let sep = if idx > 0 { "," } else { "" };
+                write!(f, "{} <={}: {}", sep, len, count)?;
+            }
+        }
+        Ok(())
+    }
+}
 
 const FREE_PTRS_SIZE: usize = 20;
+
+/// Pointers freed by a task and kept around for reuse by the same task.
+///
+/// The first `N` pointers live inline; once a reduction frees more than that
+/// the rest overflow to the heap, and can be handed back to the store with
+/// [`FreePtrs::spill`].
 pub struct FreePtrs<const N: usize = FREE_PTRS_SIZE> {
     ptrs: [Option<Ptr>; N],
     len: usize,
+    spilled: Vec<Ptr>,
 }
 impl<const N: usize> FreePtrs<N> {
     #[inline]
@@ -135,41 +887,79 @@ impl<const N: usize> FreePtrs<N> {
         FreePtrs {
             ptrs: [None; N],
             len: 0,
+            spilled: Vec::new(),
         }
     }
 
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len + self.spilled.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether some pointers overflowed the inline slots.
+    #[inline]
+    pub fn is_spilled(&self) -> bool {
+        !self.spilled.is_empty()
+    }
+
     #[inline]
     pub fn push(&mut self, ptr: Ptr) {
-        self.ptrs[self.len] = Some(ptr);
-        self.len += 1;
+        if self.len < N {
+            self.ptrs[self.len] = Some(ptr);
+            self.len += 1;
+        } else {
+            self.spilled.push(ptr);
+        }
     }
 
     #[inline]
     pub fn pop(&mut self) -> Option<Ptr> {
-        if self.len == 0 {
+        if let Some(ptr) = self.spilled.pop() {
+            Some(ptr)
+        } else if self.len == 0 {
             None
         } else {
             self.len -= 1;
-            self.ptrs[self.len]
+            self.ptrs[self.len].take()
         }
     }
 
-    pub fn split(&mut self, n: u8) -> FreePtrs {
-        let split = self.len / n as usize;
-        let mut new = FreePtrs::new();
-        new.len = self.len - split;
-        for i in 0..new.len {
-            new.ptrs[i] = self.ptrs[split + i];
-            self.ptrs[split + i] = None;
+    /// Free the pointers that overflowed the inline slots back to the store,
+    /// keeping only the inline ones for reuse. Returns how many were freed.
+    pub fn spill(&mut self, store: &Store) -> usize {
+        let spilled = self.spilled.len();
+        for ptr in self.spilled.drain(..) {
+            store.free(ptr);
         }
-        self.len = split;
-        new
+        spilled
+    }
+
+    /// Move up to `count` pointers out into a new set.
+    pub fn take(&mut self, count: usize) -> FreePtrs<N> {
+        let mut taken = FreePtrs::new();
+        while taken.len() < count {
+            match self.pop() {
+                Some(ptr) => taken.push(ptr),
+                None => break,
+            }
+        }
+        taken
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::strandal::{store::Store, term::Term, var::Var};
+    use crate::strandal::{
+        num::Op,
+        store::{FreePtrs, Ptr, Store},
+        term::{Cell, Label, Term, TermPtr, TermRef},
+        var::{Var, VarValue},
+    };
 
     #[test]
     fn test_alloc() {
@@ -178,10 +968,322 @@ mod tests {
         assert_eq!(ptr.index(), 0);
         assert_eq!(store.len(), 1);
         assert_eq!(store.next(), 1);
-        assert_eq!(store.get(ptr), &Some(Term::Var(Var::new())));
-        assert_eq!(store.free(ptr), Some(Term::Var(Var::new())));
+        // a single thread never races for a slot
+        assert_eq!(store.retries(), 0);
+        assert_eq!(store.get(ptr), Some(TermRef::Var(&Var::new())));
+        store.free(ptr);
         assert_eq!(store.len(), 0);
         assert_eq!(store.next(), 1);
-        assert_eq!(store.get(ptr), &None);
+        // `ptr` itself is stale now
+        assert_eq!(store.get(Ptr::new(ptr.index())), None);
+    }
+
+    #[test]
+    fn test_slots() {
+        let store = Store::with_capacity(16);
+        let var = store.alloc(Some(Term::Var(Var::new())));
+        let ports = Some((TermPtr::Ptr(var), TermPtr::Era));
+        let cells = [
+            Cell::Dup(ports, Some(Label::User(7))),
+            Cell::Dup(ports, Some(Label::Fresh(7))),
+            Cell::Dup(None, None),
+            Cell::App(ports),
+            Cell::Lam(None),
+            Cell::Num(u32::MAX),
+            Cell::Op(ports, Op::Shr, true),
+            Cell::Op(None, Op::Add, false),
+            Cell::Swi(ports),
+            Cell::Ref(3),
+        ];
+        for cell in cells {
+            let ptr = store.alloc(Some(Term::Cell(cell)));
+            assert_eq!(store.get(ptr), Some(TermRef::Cell(cell)));
+            // the generation of the slot is kept as it is rewritten
+            store.set(ptr, Term::Cell(Cell::Num(1)));
+            assert_eq!(store.get(ptr), Some(TermRef::Cell(Cell::Num(1))));
+        }
+        // a var is read in place
+        let TermRef::Var(read) = store.get(var).unwrap() else {
+            panic!("expected a var");
+        };
+        read.assign_era();
+        assert_eq!(store.get(var).unwrap(), TermRef::Var(read));
+        assert_eq!(read.read(), Some(VarValue::Era));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "freed #0 twice (during ANNI LAM-LAM)")]
+    fn test_double_free() {
+        let store = Store::with_capacity(4);
+        let ptr = store.alloc(Some(Term::Var(Var::new())));
+        super::rule_context("ANNI LAM-LAM");
+        store.free(ptr);
+        store.free(ptr);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "freed #3 which was never allocated")]
+    fn test_free_unallocated() {
+        let store = Store::with_capacity(4);
+        store.free(Ptr::new(3));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "used #0 after it was freed")]
+    fn test_use_after_free() {
+        let store = Store::with_capacity(4);
+        let ptr = store.alloc(Some(Term::Var(Var::new())));
+        store.free(ptr);
+        // the slot is reused through an unchecked pointer
+        store.set(Ptr::new(ptr.index()), Term::Var(Var::new()));
+        store.get(ptr);
+    }
+
+    #[test]
+    #[should_panic(expected = "freed #0 twice")]
+    fn test_checked_double_free() {
+        // caught by a checked store in release builds too
+        let store = Store::with_capacity(4).checked();
+        let ptr = store.alloc(Some(Term::Var(Var::new())));
+        store.free(ptr);
+        store.free(ptr);
+    }
+
+    #[test]
+    #[should_panic(expected = "read #2 which was never allocated")]
+    fn test_checked_uninitialized() {
+        let store = Store::with_capacity(4).checked();
+        store.alloc(Some(Term::Var(Var::new())));
+        store.get(Ptr::new(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "#4 is out of the bounds of the store (4 slots)")]
+    fn test_checked_out_of_bounds() {
+        Store::with_capacity(4).checked().get(Ptr::new(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "#0 holds no term (head 0xf)")]
+    fn test_checked_corrupt() {
+        let store = Store::with_capacity(4).checked();
+        let ptr = store.alloc(Some(Term::Cell(Cell::Num(1))));
+        store.slot(ptr).head.store(0xf, std::sync::atomic::Ordering::Relaxed);
+        store.get(ptr);
+    }
+
+    #[test]
+    #[should_panic(expected = "#0 has a port to #3, which was never allocated")]
+    fn test_checked_dangling_port() {
+        let store = Store::with_capacity(4).checked();
+        let ports = Some((TermPtr::Era, TermPtr::Ptr(Ptr::new(3))));
+        let ptr = store.alloc(Some(Term::Cell(Cell::Lam(ports))));
+        store.get(ptr);
+    }
+
+    #[test]
+    fn test_fragmentation() {
+        let store = Store::with_capacity(16);
+        let ptrs: Vec<_> = (0..8)
+            .map(|_| store.alloc(Some(Term::Var(Var::new()))))
+            .collect();
+        // a gap of one and a trailing gap of three
+        for idx in [1, 5, 6, 7] {
+            store.free(ptrs[idx]);
+        }
+        let frag = store.fragmentation();
+        assert_eq!(frag.high_water, 8);
+        assert_eq!(frag.live, 4);
+        assert_eq!(frag.holes, 4);
+        assert_eq!(frag.gaps, [(1, 1), (4, 1)].into_iter().collect());
+        assert_eq!(frag.occupancy(), 0.5);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut store = Store::with_capacity(16);
+        let var = store.alloc(Some(Term::Var(Var::new())));
+        let num = store.alloc(Some(Term::Cell(Cell::Num(7))));
+        let freed = store.alloc(Some(Term::Var(Var::new())));
+        let ports = Some((TermPtr::Ptr(var), TermPtr::Era));
+        let lam = store.alloc(Some(Term::Cell(Cell::Lam(ports))));
+        store.free(freed);
+        let TermRef::Var(value) = store.get(var).unwrap() else {
+            panic!("{} is not a var", var);
+        };
+        value.assign_cell(num);
+
+        let ptrs: Vec<_> = store.iter().map(|(ptr, _)| ptr).collect();
+        assert_eq!(ptrs, [var, num, lam]);
+        let snapshot = store.snapshot();
+        // a copy, left as is by later writes
+        let TermRef::Var(value) = store.get(var).unwrap() else {
+            panic!("{} is not a var", var);
+        };
+        value.assign_era();
+        let expected = Var::new();
+        expected.assign_cell(num);
+        assert_eq!(
+            snapshot,
+            [
+                (var, Term::Var(expected)),
+                (num, Term::Cell(Cell::Num(7))),
+                (lam, Term::Cell(Cell::Lam(ports))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shards() {
+        // shards of 3 slots start every 4
+        let store = Store::with_shards(12, 4);
+        assert_eq!(store.shards(), 4);
+        assert_eq!(store.capacity, 15);
+        let marks = store.marks();
+        // the main thread allocates from the first shard, then the next ones
+        let ptrs: Vec<_> = (0..5)
+            .map(|_| store.alloc(Some(Term::Var(Var::new()))))
+            .collect();
+        let indices: Vec<_> = ptrs.iter().map(|ptr| ptr.index()).collect();
+        assert_eq!(indices, vec![0, 1, 2, 4, 5]);
+        assert_eq!(store.shard(ptrs[3]), 1);
+        assert_eq!(store.next(), 5);
+        assert!(!store.handed_out(Ptr::new(3)));
+        assert!(!store.handed_out(Ptr::new(6)));
+        assert_eq!(store.ptrs().collect::<Vec<_>>(), ptrs);
+        // the slots handed out are not the ones below `next`
+        assert_eq!(store.handed_out_since(&marks), vec![0..3, 4..6]);
+
+        store.free(ptrs[1]);
+        store.free(ptrs[4]);
+        // two slots were handed out by the second shard, and one freed there
+        #[cfg(not(feature = "no-stats"))]
+        {
+            let contention = super::take_contention();
+            assert_eq!(
+                (contention.shard_misses, contention.remote_frees),
+                (2, 1)
+            );
+        }
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.shard_fragmentation(0).holes, 1);
+        assert_eq!(store.shard_fragmentation(1).live, 1);
+        let frag = store.fragmentation();
+        assert_eq!((frag.high_water, frag.live, frag.holes), (5, 3, 2));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut store = Store::with_shards(4, 2);
+        let ptrs: Vec<_> = (0..3)
+            .map(|_| store.alloc(Some(Term::Var(Var::new()))))
+            .collect();
+        store.free(ptrs[0]);
+        store.clear();
+        assert_eq!((store.len(), store.next()), (0, 0));
+        assert_eq!(store.fragmentation().holes, 0);
+        // the slots are handed out again from the start
+        let ptr = store.alloc(Some(Term::Var(Var::new())));
+        assert_eq!(ptr.index(), 0);
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "used #1 after it was freed")]
+    fn test_use_after_clear() {
+        let mut store = Store::with_capacity(4);
+        let ptrs: Vec<_> = (0..2)
+            .map(|_| store.alloc(Some(Term::Var(Var::new()))))
+            .collect();
+        store.clear();
+        (0..2).for_each(|_| {
+            store.alloc(Some(Term::Var(Var::new())));
+        });
+        store.get(ptrs[1]);
+    }
+
+    #[test]
+    fn test_concurrent_alloc() {
+        let store = Store::with_capacity(4_000);
+        assert_eq!(std::mem::align_of::<super::Shard>(), 128);
+        let mut indices: Vec<_> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..1_000)
+                            .map(|_| store.alloc(Some(Term::Var(Var::new()))).index())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .flat_map(|thread| thread.join().unwrap())
+                .collect()
+        });
+        // no slot was handed out twice, however many races were lost
+        indices.sort();
+        indices.dedup();
+        assert_eq!(indices.len(), 4_000);
+        assert_eq!((store.next(), store.len()), (4_000, 4_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "the store is full")]
+    fn test_store_full() {
+        let store = Store::with_shards(4, 2);
+        for _ in 0..5 {
+            store.alloc(Some(Term::Var(Var::new())));
+        }
+    }
+
+    #[test]
+    fn test_free_ptrs_overflow() {
+        let mut free_ptrs = FreePtrs::<4>::new();
+        for idx in 0..10 {
+            free_ptrs.push(Ptr::new(idx));
+        }
+        assert_eq!(free_ptrs.len(), 10);
+        assert!(free_ptrs.is_spilled());
+        for idx in (0..10).rev() {
+            assert_eq!(free_ptrs.pop(), Some(Ptr::new(idx)));
+        }
+        assert_eq!(free_ptrs.pop(), None);
+        assert!(free_ptrs.is_empty());
+    }
+
+    #[test]
+    fn test_free_ptrs_spill() {
+        let store = Store::with_capacity(32);
+        let mut free_ptrs = FreePtrs::<4>::new();
+        for _ in 0..10 {
+            free_ptrs.push(store.alloc(Some(Term::Var(Var::new()))));
+        }
+        assert_eq!(store.len(), 10);
+
+        assert_eq!(free_ptrs.spill(&store), 6);
+        assert_eq!(store.len(), 4);
+        assert_eq!(free_ptrs.len(), 4);
+        assert!(!free_ptrs.is_spilled());
+        assert_eq!(store.get(Ptr::new(9)), None);
+        assert!(store.get(Ptr::new(3)).is_some());
+    }
+
+    #[test]
+    fn test_free_ptrs_take() {
+        let mut free_ptrs = FreePtrs::<4>::new();
+        for idx in 0..9 {
+            free_ptrs.push(Ptr::new(idx));
+        }
+        let taken = free_ptrs.take(6);
+        assert_eq!(free_ptrs.len(), 3);
+        assert_eq!(taken.len(), 6);
+        assert!(!free_ptrs.is_spilled());
+        assert_eq!(free_ptrs.take(10).len(), 3);
+        assert!(free_ptrs.is_empty());
     }
 }
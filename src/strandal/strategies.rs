@@ -0,0 +1,199 @@
+//! [`proptest`] strategies for λ-terms and nets, under the `testing`
+//! feature, so property tests need not each write their own generators.
+//!
+//! - [`lambda_term`] generates closed, simply typed [`Lambda`] terms of the
+//!   type of the Church numerals, `(o → o) → o → o`. Typed terms always
+//!   normalize, and applied to a successor and a zero they read back as a
+//!   number.
+//! - [`term`] and [`net`] generate nets of every kind of cell, well formed
+//!   but otherwise arbitrary, see [`ast::Net::closed`].
+
+use proptest::{collection::vec, prelude::*, sample::select};
+
+use super::{
+    ast::{self, Term},
+    expr::Lambda,
+    num::Op,
+    store::Index,
+    term::Label,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Type {
+    Base,
+    Arrow(Box<Type>, Box<Type>),
+}
+
+impl Type {
+    fn arrow(from: Type, to: Type) -> Type {
+        Type::Arrow(Box::new(from), Box::new(to))
+    }
+}
+
+/// Closed λ-terms of type `(o → o) → o → o`, with applications nested at
+/// most `depth` deep. Terms shrink towards vars.
+pub fn lambda_term(depth: u32) -> impl Strategy<Value = Lambda> {
+    let scope = vec![Type::arrow(Type::Base, Type::Base), Type::Base];
+    typed(Type::Base, scope, depth)
+        .prop_map(|body| Lambda::Lam(Box::new(Lambda::Lam(Box::new(body)))))
+}
+
+// the terms of type `ty` given the types of the binders in scope, innermost
+// last. A var of type `o` is always in scope, so every type is inhabited:
+// past `depth`, only by vars and by abstractions, which shrink the type.
+fn typed(ty: Type, scope: Vec<Type>, depth: u32) -> BoxedStrategy<Lambda> {
+    let vars: Vec<usize> = (0..scope.len())
+        .filter(|&idx| scope[scope.len() - 1 - idx] == ty)
+        .collect();
+    let lam = matches!(ty, Type::Arrow(..)) && (depth > 0 || vars.is_empty());
+    // the arguments are of type `o` or `o → o`
+    let args = match depth {
+        0 => vec![],
+        _ => vec![Type::Base, Type::arrow(Type::Base, Type::Base)],
+    };
+    let choices = vars.len() + lam as usize + args.len();
+    (0..choices)
+        .prop_flat_map(move |choice| match choice {
+            _ if choice < vars.len() => Just(Lambda::Var(vars[choice])).boxed(),
+            _ if choice == vars.len() && lam => {
+                let Type::Arrow(from, to) = &ty else {
+                    unreachable!()
+                };
+                let scope = [scope.clone(), vec![*from.clone()]].concat();
+                typed(*to.clone(), scope, depth.saturating_sub(1))
+                    .prop_map(|body| Lambda::Lam(Box::new(body)))
+                    .boxed()
+            }
+            _ => {
+                let arg = args[choice - vars.len() - lam as usize].clone();
+                let fun = typed(Type::arrow(arg.clone(), ty.clone()), scope.clone(), depth - 1);
+                (fun, typed(arg, scope.clone(), depth - 1))
+                    .prop_map(|(fun, arg)| Lambda::App(Box::new(fun), Box::new(arg)))
+                    .boxed()
+            }
+        })
+        .boxed()
+}
+
+// the names of the vars of a generated net, few enough for most to pair up
+const VARS: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "h"];
+
+/// Terms of every kind of cell nesting at most `depth` deep, but calls and
+/// references: their vars are named after a handful of names, and their dup
+/// labels and numbers are small.
+pub fn term(depth: u32) -> impl Strategy<Value = Term> {
+    let leaf = prop_oneof![
+        Just(Term::Era),
+        select(&VARS[..]).prop_map(Term::var),
+        (0..16u32).prop_map(Term::Num),
+    ];
+    leaf.prop_recursive(depth, 64, 2, |inner| {
+        let pair = (inner.clone(), inner);
+        let op = (select(&Op::ALL[..]), any::<bool>());
+        prop_oneof![
+            pair.clone().prop_map(|(p0, p1)| Term::lam(p0, p1)),
+            pair.clone().prop_map(|(p0, p1)| Term::App(Box::new(p0), Box::new(p1))),
+            (pair.clone(), proptest::option::of((0..4 as Index).prop_map(Label::User)))
+                .prop_map(|((p0, p1), label)| Term::Dup(Box::new(p0), Box::new(p1), label)),
+            (pair.clone(), op).prop_map(|((p0, p1), (op, flipped))| {
+                Term::Op(op, flipped, Box::new(p0), Box::new(p1))
+            }),
+            pair.clone().prop_map(|(p0, p1)| Term::swi(p0, p1)),
+            (pair, select(&Op::ALL[..])).prop_map(|((p0, p1), op)| Term::infix(op, p0, p1)),
+        ]
+    })
+}
+
+/// Nets of at most `eqns` equations and a few heads, their terms from
+/// [`term`], closed with [`ast::Net::closed`].
+pub fn net(eqns: usize, depth: u32) -> impl Strategy<Value = ast::Net> {
+    let head = vec(term(depth), 0..=3);
+    let body = vec((term(depth), term(depth)), 0..=eqns);
+    (head, body).prop_map(|(head, body)| ast::Net { head, body }.closed())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use proptest::prelude::*;
+
+    use crate::strandal::{
+        ast::Term,
+        expr::Lambda,
+        net::Net,
+        num::Op,
+        readback::OwnedTerm,
+        runtime::{EvalError, Runtime, RuntimeConfig},
+    };
+
+    use super::lambda_term;
+
+    #[derive(Clone)]
+    enum Value {
+        Num(u32),
+        Fun(Rc<dyn Fn(Value) -> Value>),
+    }
+
+    // the reference semantics: the term evaluated with closures
+    fn eval(term: &Lambda, env: &[Value]) -> Value {
+        match term {
+            Lambda::Var(idx) => env[env.len() - 1 - idx].clone(),
+            Lambda::Lam(body) => {
+                let (body, env) = (body.clone(), env.to_vec());
+                Value::Fun(Rc::new(move |arg| eval(&body, &[env.clone(), vec![arg]].concat())))
+            }
+            Lambda::App(fun, arg) => match eval(fun, env) {
+                Value::Fun(fun) => fun(eval(arg, env)),
+                Value::Num(_) => unreachable!("the terms are well typed"),
+            },
+            Lambda::Ref(_) | Lambda::Num(_) => unreachable!("the terms are closed λ-terms"),
+        }
+    }
+
+    fn apply(fun: &Value, arg: Value) -> Value {
+        match fun {
+            Value::Fun(fun) => fun(arg),
+            Value::Num(_) => unreachable!("the terms are well typed"),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_church_numerals(term in lambda_term(3)) {
+            // applied to `λy. y + 1` and 0
+            let succ = Value::Fun(Rc::new(|arg| match arg {
+                Value::Num(value) => Value::Num(value + 1),
+                Value::Fun(_) => unreachable!("the terms are well typed"),
+            }));
+            let applied = apply(&eval(&term, &[]), succ);
+            let Value::Num(expected) = apply(&applied, Value::Num(0)) else {
+                unreachable!("the terms are well typed");
+            };
+
+            let mut ast = term.to_net();
+            let fun = ast.head.pop().unwrap();
+            let succ = Term::lam(Term::var("y"), Term::var("z"));
+            ast.body.push((Term::var("y"), Term::op(Op::Add, Term::Num(1), Term::var("z"))));
+            let applied = Term::App(Box::new(succ), Box::new(Term::var("s")));
+            ast.body.push((fun, applied));
+            let result = Term::App(Box::new(Term::Num(0)), Box::new(Term::var("n")));
+            ast.body.push((Term::var("s"), result));
+            ast.head.push(Term::var("n"));
+
+            let mut net = Net::from_ast(&ast).unwrap();
+            Runtime::new().eval(&mut net).unwrap();
+            prop_assert_eq!(net.normal_form().unwrap(), vec![OwnedTerm::Num(expected)], "{}", term);
+        }
+
+        #[test]
+        fn test_nets(ast in super::net(4, 3)) {
+            let mut net = Net::from_ast(&ast).unwrap();
+            let config = RuntimeConfig::default().max_cells(1 << 12).max_interactions(1 << 12);
+            match Runtime::with_config(config).eval(&mut net) {
+                Ok(()) | Err(EvalError::MaxCellsExceeded { .. } | EvalError::MaxInteractionsExceeded(_)) => {}
+                Err(err) => panic!("{} fails to evaluate: {}", ast, err),
+            }
+        }
+    }
+}
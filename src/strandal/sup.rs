@@ -0,0 +1,195 @@
+//! Superpositions, as in HVM: `{a b}` with a label stands for either `a` or
+//! `b`, so a net can compute on several values at once.
+//!
+//! A superposition is a labelled duplicator whose principal port produces a
+//! value, built with [`NetBuilder::sup`](super::net::NetBuilder::sup).
+//! Duplicators work in either direction, so the duplicator rules are the
+//! superposition rules too:
+//!
+//! - a duplicator meeting a superposition of the same label annihilates
+//!   with it, its left copy taking `a` and its right copy `b`;
+//! - with another label, they commute: each copy gets a superposition of
+//!   copies, so every copy keeps both branches;
+//! - an application or a lambda meeting a superposition commutes with it,
+//!   applying both branches.
+//!
+//! After evaluation, [`Net::collapse`] reads the heads back once per way of
+//! picking a side of each label, a label picking the same side everywhere.
+
+use std::collections::HashMap;
+
+use super::{
+    net::Net,
+    readback::{NotNormal, OwnedTerm},
+    term,
+};
+
+impl Net {
+    /// Read the heads back once for each way of picking a side of the
+    /// superpositions left in them, see the [module docs](self). Picking a
+    /// side can leave other labels out of the heads, so only the labels
+    /// still reached are picked.
+    pub fn collapse(&self) -> Result<Vec<Vec<OwnedTerm>>, NotNormal> {
+        Ok(collapse(&self.normal_form()?))
+    }
+}
+
+/// The results superposed in `heads`, in order: the left sides first.
+pub fn collapse(heads: &[OwnedTerm]) -> Vec<Vec<OwnedTerm>> {
+    let mut results = Vec::new();
+    collapse_with(heads, &mut HashMap::new(), &mut results);
+    results
+}
+
+// a superposition without a label picks together with the other ones
+type Label = Option<term::Label>;
+
+fn collapse_with(
+    heads: &[OwnedTerm],
+    sides: &mut HashMap<Label, bool>,
+    results: &mut Vec<Vec<OwnedTerm>>,
+) {
+    let picked: Result<Vec<OwnedTerm>, Label> =
+        heads.iter().map(|head| pick(head, true, sides)).collect();
+    match picked {
+        Ok(result) => results.push(result),
+        Err(label) => {
+            for right in [false, true] {
+                sides.insert(label, right);
+                collapse_with(heads, sides, results);
+            }
+            sides.remove(&label);
+        }
+    }
+}
+
+// replace the superpositions of `term` by the side picked for their label,
+// failing with the first label not picked yet. `value` tells whether the
+// principal port of `term` produces a value: a lambda takes its binding and
+// gives its body, an application, an operator or a switch takes its
+// argument, operand or branches and gives its result, and the ports of a duplicator face the other way from its principal port
+fn pick(term: &OwnedTerm, value: bool, sides: &HashMap<Label, bool>) -> Result<OwnedTerm, Label> {
+    Ok(match term {
+        // readback builds no calls
+        OwnedTerm::Era
+        | OwnedTerm::Var(_)
+        | OwnedTerm::Num(_)
+        | OwnedTerm::Ref(_)
+        | OwnedTerm::Call(..)
+        | OwnedTerm::Infix(..) => term.clone(),
+        OwnedTerm::Lam(p0, p1) => OwnedTerm::lam(pick(p0, false, sides)?, pick(p1, true, sides)?),
+        OwnedTerm::App(p0, p1) => OwnedTerm::app(pick(p0, true, sides)?, pick(p1, false, sides)?),
+        OwnedTerm::Dup(p0, p1, label) if value => match sides.get(label) {
+            Some(false) => pick(p0, true, sides)?,
+            Some(true) => pick(p1, true, sides)?,
+            None => return Err(*label),
+        },
+        OwnedTerm::Dup(p0, p1, label) => OwnedTerm::Dup(
+            Box::new(pick(p0, false, sides)?),
+            Box::new(pick(p1, false, sides)?),
+            *label,
+        ),
+        OwnedTerm::Op(op, flipped, p0, p1) => OwnedTerm::Op(
+            *op,
+            *flipped,
+            Box::new(pick(p0, true, sides)?),
+            Box::new(pick(p1, false, sides)?),
+        ),
+        OwnedTerm::Swi(p0, p1) => OwnedTerm::Swi(
+            Box::new(pick(p0, true, sides)?),
+            Box::new(pick(p1, false, sides)?),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        polarity::PolarityChecker,
+        runtime::Runtime,
+        term::{CellUse, TermPtr},
+    };
+
+    fn id(net: &mut impl NetBuilder) -> CellUse {
+        let x = net.var();
+        net.lam(x.0, x.1)
+    }
+
+    // λa.λb.b
+    fn second(net: &mut impl NetBuilder) -> CellUse {
+        let b = net.var();
+        let inner = net.lam(b.0, b.1);
+        net.lam(TermPtr::Era, inner)
+    }
+
+    fn results(net: &Net) -> Vec<Vec<String>> {
+        net.collapse()
+            .unwrap()
+            .iter()
+            .map(|heads| heads.iter().map(|head| head.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_collapse() {
+        // r ~ {id second} applied to ε, and a copy of {ε id} by a dup of
+        // the same label picking a side for each copy
+        let mut net = Net::new();
+        let r = net.var();
+        let (f, g) = (id(&mut net), second(&mut net));
+        let sup = net.sup(1, f, g);
+        let app = net.app(TermPtr::Era, r.0);
+        net.eqn(sup, app);
+        net.head(r.1);
+        let (left, right) = (net.var(), net.var());
+        let dup = net.labeled_dup(1, left.0, right.0);
+        let i = id(&mut net);
+        let sup = net.sup(1, TermPtr::Era, i);
+        net.eqn(sup, dup);
+        net.head(left.1);
+        net.head(right.1);
+        Runtime::new().eval(&mut net).unwrap();
+
+        // the label picks the same side in the first head
+        let results = results(&net);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0][0], "ε");
+        assert!(results[1][0].starts_with("(λ x"));
+        // and the dup already picked both sides of the second superposition
+        for result in &results {
+            assert_eq!(result[1], "ε");
+            assert!(result[2].starts_with("(λ x"));
+        }
+
+        // two labels superposed in two heads give every combination
+        let mut net = Net::new();
+        for label in [1, 2] {
+            let (f, g) = (id(&mut net), second(&mut net));
+            let sup = net.sup(label, f, g);
+            net.head(sup);
+        }
+        assert_eq!(net.collapse().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_sup_polarity() {
+        let mut net = Net::new();
+        let mut checker = PolarityChecker::new(&mut net);
+        let (f, g) = (id(&mut checker), second(&mut checker));
+        let sup = checker.sup(1, f, g);
+        let r = checker.var();
+        let app = checker.app(TermPtr::Era, r.0);
+        checker.eqn(sup, app);
+        checker.head(r.1);
+        assert!(checker.finish().is_ok());
+
+        // a superposition cannot stand for a consumer
+        let mut net = Net::new();
+        let mut checker = PolarityChecker::new(&mut net);
+        let sup = checker.sup(1, TermPtr::Era, TermPtr::Era);
+        let f = id(&mut checker);
+        checker.eqn(sup, f);
+        assert!(checker.finish().is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Tracing the rewrites a value flows through, see
+//! [`Net::trace`](super::net::Net::trace), and exporting them for tools that
+//! play a reduction back with [`write_json`]:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "steps": [
+//!     {
+//!       "step": 0,
+//!       "rule": "BIND",
+//!       "left": "x.1",
+//!       "right": "(λ.4 x.3 x.3)",
+//!       "consumed": [1, 4],
+//!       "produced": [4]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! The steps are in the order the rewrites ran. `consumed` holds the store
+//! indices of the vars and cells the rewrite took apart, and `produced`
+//! those of the wires the traced value reached through it. `left` and
+//! `right` are the two sides as displayed in the logs.
+
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    io::Write,
+    sync::{Mutex, RwLock},
+};
+
+use serde_json::json;
+use tracing::info;
+
+use super::{
+    store::Ptr,
+    term::{Cell, TermPtr},
+};
+
+/// A rewrite that touched a traced wire, see [`Net::trace`](super::net::Net::trace).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// The rule, named as in the stats (`BIND`, `CONNECT`, `COMM LAM-DUP`...).
+    pub rule: &'static str,
+    pub left: String,
+    pub right: String,
+    /// The vars and cells the rewrite took apart.
+    pub consumed: Vec<Ptr>,
+    /// The wires the traced value reached through this rewrite, which are
+    /// traced from now on.
+    pub tainted: Vec<Ptr>,
+}
+
+impl Display for TraceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} : {} ⋈ {}", self.rule, self.left, self.right)?;
+        if !self.tainted.is_empty() {
+            write!(f, " →")?;
+            for ptr in &self.tainted {
+                write!(f, " {}", ptr)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the name of the rule reducing two cells
+pub(crate) fn rule(left: &Cell, right: &Cell) -> &'static str {
+    match (left, right) {
+        (Cell::App(_), Cell::App(_)) => "ANNI APP-APP",
+        (Cell::Lam(_), Cell::Lam(_)) => "ANNI LAM-LAM",
+        (Cell::Dup(_, left_lbl), Cell::Dup(_, right_lbl)) if left_lbl == right_lbl => {
+            "ANNI DUP-DUP"
+        }
+        (Cell::Dup(_, _), Cell::Dup(_, _)) => "COMM DUP-DUP",
+        (Cell::App(_), Cell::Lam(_)) | (Cell::Lam(_), Cell::App(_)) => "COMM APP-LAM",
+        (Cell::App(_), Cell::Dup(_, _)) | (Cell::Dup(_, _), Cell::App(_)) => "COMM APP-DUP",
+        (Cell::Lam(_), Cell::Dup(_, _)) | (Cell::Dup(_, _), Cell::Lam(_)) => "COMM LAM-DUP",
+        (Cell::Ref(_), Cell::Dup(..)) | (Cell::Dup(..), Cell::Ref(_)) => "COMM REF",
+        (Cell::Ref(_), _) | (_, Cell::Ref(_)) => "EXPAND REF",
+        (Cell::Num(_), Cell::Op(..)) | (Cell::Op(..), Cell::Num(_)) => "OPER NUM",
+        (Cell::Num(_), Cell::Swi(_)) | (Cell::Swi(_), Cell::Num(_)) => "SWI NUM",
+        (Cell::Num(_), _) | (_, Cell::Num(_)) => "COMM NUM",
+        (Cell::Op(..) | Cell::Swi(_), _) | (_, Cell::Op(..) | Cell::Swi(_)) => "COMM OPS",
+    }
+}
+
+pub(crate) fn erase_rule(cell: &Cell) -> &'static str {
+    match cell {
+        Cell::App(_) => "COMM ERA-APP",
+        Cell::Lam(_) => "COMM ERA-LAM",
+        Cell::Dup(_, _) => "COMM ERA-DUP",
+        Cell::Num(_) => "COMM ERA-NUM",
+        Cell::Op(..) | Cell::Swi(_) => "COMM ERA-OPS",
+        Cell::Ref(_) => "COMM ERA-REF",
+    }
+}
+
+pub(crate) fn ports(cell: &Cell) -> impl Iterator<Item = Ptr> {
+    cell.ports()
+        .into_iter()
+        .flat_map(|(p0, p1)| [p0, p1])
+        .filter_map(|port| match port {
+            TermPtr::Ptr(ptr) => Some(ptr),
+            TermPtr::Era => None,
+        })
+}
+
+/// The traced wires of the net being evaluated: the vars and cells holding
+/// a traced value, and every wire the value flows into as it is rewritten.
+pub(crate) struct Tainter {
+    traced: RwLock<HashSet<Ptr>>,
+    events: Mutex<Vec<TraceEvent>>,
+}
+
+impl Tainter {
+    pub fn new(traced: impl IntoIterator<Item = Ptr>) -> Self {
+        Self {
+            traced: RwLock::new(traced.into_iter().collect()),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_traced(&self, ptr: Option<Ptr>) -> bool {
+        ptr.is_some_and(|ptr| self.traced.read().unwrap().contains(&ptr))
+    }
+
+    /// Record a rewrite of `ptrs` if any of them is traced, tainting
+    /// `reached`. The terms are only displayed when the rewrite is traced.
+    pub fn rewrite(
+        &self,
+        rule: &'static str,
+        ptrs: [Option<Ptr>; 2],
+        reached: impl IntoIterator<Item = Ptr>,
+        terms: impl FnOnce() -> (String, String),
+    ) {
+        if !ptrs.iter().any(|ptr| self.is_traced(*ptr)) {
+            return;
+        }
+        let consumed: Vec<_> = ptrs.into_iter().flatten().collect();
+        let mut traced = self.traced.write().unwrap();
+        let tainted: Vec<_> = consumed
+            .iter()
+            .copied()
+            .chain(reached)
+            .filter(|ptr| traced.insert(*ptr))
+            .collect();
+        drop(traced);
+        let (left, right) = terms();
+        let event = TraceEvent {
+            rule,
+            left,
+            right,
+            consumed,
+            tainted,
+        };
+        info!(target: "strandal::taint", rule, left = %event.left, right = %event.right, tainted = ?event.tainted, "traced rewrite");
+        self.events.lock().unwrap().push(event);
+    }
+
+    /// Taint `to` if `from` is traced, as a value is forwarded through a link
+    /// between two vars.
+    pub fn follow(&self, from: Ptr, to: Ptr) {
+        if self.is_traced(Some(from)) {
+            self.traced.write().unwrap().insert(to);
+        }
+    }
+
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+/// Write `events` as JSON, in the schema of the [module docs](self).
+pub fn write_json(events: &[TraceEvent], out: &mut impl Write) -> std::io::Result<()> {
+    let indices = |ptrs: &[Ptr]| ptrs.iter().map(|ptr| ptr.index()).collect::<Vec<_>>();
+    let steps = events.iter().enumerate().map(|(step, event)| {
+        json!({
+            "step": step,
+            "rule": event.rule,
+            "left": event.left,
+            "right": event.right,
+            "consumed": indices(&event.consumed),
+            "produced": indices(&event.tainted),
+        })
+    });
+    let trace = json!({ "version": 1, "steps": steps.collect::<Vec<_>>() });
+    serde_json::to_writer(&mut *out, &trace)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        runtime::Runtime,
+    };
+
+    use super::write_json;
+
+    #[test]
+    fn test_trace() {
+        // (λx.x) (λy.y), tracing the function
+        let mut net = Net::new();
+        let x = net.var();
+        let x_ptr = x.0.ptr();
+        let id = net.lam(x.0, x.1);
+        let id_ptr = id.ptr();
+        let y = net.var();
+        let arg = net.lam(y.0, y.1);
+        let arg_ptr = arg.ptr();
+        let r = net.var();
+        let r_ptr = r.0.ptr();
+        let app = net.app(arg, r.0);
+        let app_ptr = app.ptr();
+        net.head(r.1);
+        net.trace(id_ptr);
+        net.eqn(id, app);
+        let mut runtime = Runtime::new();
+        runtime.eval(&mut net).unwrap();
+
+        let events = runtime.trace_events();
+        assert_eq!(events[0].rule, "COMM APP-LAM");
+        assert_eq!(events[0].tainted, vec![app_ptr, x_ptr, arg_ptr, r_ptr]);
+        let mut consumed = events[0].consumed.clone();
+        consumed.sort_by_key(|ptr| ptr.index());
+        assert_eq!(consumed, vec![id_ptr, app_ptr]);
+        // the two port equations run in either order
+        let mut rules: Vec<_> = events[1..].iter().map(|event| event.rule).collect();
+        rules.sort();
+        assert_eq!(rules, vec!["BIND", "CONNECT"]);
+
+        let mut out = Vec::new();
+        write_json(&events, &mut out).unwrap();
+        let trace: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(trace["version"], 1);
+        let steps = trace["steps"].as_array().unwrap();
+        assert_eq!(steps.len(), events.len());
+        assert_eq!(steps[1]["step"], 1);
+        assert_eq!(steps[0]["rule"], "COMM APP-LAM");
+        assert_eq!(steps[0]["produced"], json!([app_ptr.index(), x_ptr.index(), arg_ptr.index(), r_ptr.index()]));
+        assert_eq!(steps[0]["left"], events[0].left);
+    }
+}
@@ -0,0 +1,493 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use super::{
+    ast::{self, AstError},
+    net::{Net, NetBuilder},
+    num::Op,
+    refs::{RefId, Refs},
+    store::{Ptr, Store},
+    term::{Cell, Label, Term, TermPtr, TermRef},
+    var::Var,
+};
+
+/// A reference inside a [`Template`], relative to the template itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Slot {
+    Era,
+    Var(u32),
+    Cell(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TemplateCell {
+    Lam(Slot, Slot),
+    App(Slot, Slot),
+    Dup(Slot, Slot, Option<Label>),
+    Num(u32),
+    Op(Op, bool, Slot, Slot),
+    Swi(Slot, Slot),
+    Ref(RefId),
+}
+
+/// A sub-net that does not live in any store, and can be copied into nets
+/// any number of times with [`Net::instantiate`].
+///
+/// Cells only refer to cells that come before them, so they can be
+/// allocated in order. Vars are numbered by first use and cells in post
+/// order, so structurally equal sub-nets compile to equal templates.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Template {
+    vars: u32,
+    cells: Vec<TemplateCell>,
+    ports: Vec<Slot>,
+    body: Vec<(Slot, Slot)>,
+}
+
+/// The free ports of an instantiated [`Template`], in the order of the
+/// template heads. Each port must be wired exactly once (as a head, in an
+/// equation or as a cell port).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub ports: Vec<TermPtr>,
+}
+
+impl Template {
+    /// Compile an owned net: its heads become the template ports.
+    pub fn from_ast(ast: &ast::Net) -> Result<Self, AstError> {
+        Self::compile(ast, None)
+    }
+
+    /// Compile an owned net whose references are resolved in `refs`.
+    pub fn from_ast_with_refs(ast: &ast::Net, refs: &Refs) -> Result<Self, AstError> {
+        Self::compile(ast, Some(refs))
+    }
+
+    fn compile(ast: &ast::Net, refs: Option<&Refs>) -> Result<Self, AstError> {
+        let mut compiler = TemplateCompiler {
+            refs,
+            ..Default::default()
+        };
+        let ports = ast
+            .head
+            .iter()
+            .map(|head| compiler.slot(head))
+            .collect::<Result<_, _>>()?;
+        let mut body: Vec<_> = ast
+            .body
+            .iter()
+            .map(|(left, right)| Ok((compiler.slot(left)?, compiler.slot(right)?)))
+            .collect::<Result<_, _>>()?;
+        body.append(&mut compiler.eqns);
+        if let Some(name) = compiler.open.keys().min() {
+            return Err(AstError::UnpairedVar(name.to_string()));
+        }
+        Ok(Template {
+            vars: compiler.vars,
+            cells: compiler.cells,
+            ports,
+            body,
+        })
+    }
+
+    pub fn ports(&self) -> usize {
+        self.ports.len()
+    }
+
+    pub fn vars(&self) -> usize {
+        self.vars as usize
+    }
+
+    pub fn cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Whether the template is a single closed cell tree: one port that is a
+    /// cell, and no equations.
+    pub fn is_constant(&self) -> bool {
+        matches!(self.ports[..], [Slot::Cell(_)]) && self.body.is_empty()
+    }
+
+    pub fn has_dups(&self) -> bool {
+        self.cells
+            .iter()
+            .any(|cell| matches!(cell, TemplateCell::Dup(..)))
+    }
+
+    /// Store slots needed by every instantiation.
+    pub fn size(&self) -> usize {
+        self.vars() + self.cells()
+    }
+
+    /// A hash that only depends on the structure of the sub-net, not on where
+    /// its cells and vars live in a store.
+    pub fn canonical_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Copy the template into `store` with fresh pointers, returning its
+    /// ports and body equations.
+    pub(crate) fn alloc_in(&self, store: &Store) -> (Vec<TermPtr>, Vec<(TermPtr, TermPtr)>) {
+        let vars: Vec<Ptr> = (0..self.vars)
+            .map(|_| store.alloc(Some(Term::Var(Var::new()))))
+            .collect();
+        let mut cells: Vec<TermPtr> = Vec::with_capacity(self.cells.len());
+        let term_ptr = |cells: &[TermPtr], slot: &Slot| match slot {
+            Slot::Era => TermPtr::Era,
+            Slot::Var(idx) => TermPtr::Ptr(vars[*idx as usize]),
+            Slot::Cell(idx) => cells[*idx as usize],
+        };
+
+        for cell in &self.cells {
+            let cell = match cell {
+                TemplateCell::Lam(p0, p1) => {
+                    Cell::Lam(Some((term_ptr(&cells, p0), term_ptr(&cells, p1))))
+                }
+                TemplateCell::App(p0, p1) => {
+                    Cell::App(Some((term_ptr(&cells, p0), term_ptr(&cells, p1))))
+                }
+                TemplateCell::Dup(p0, p1, lbl) => Cell::Dup(
+                    Some((term_ptr(&cells, p0), term_ptr(&cells, p1))),
+                    *lbl,
+                ),
+                TemplateCell::Num(value) => Cell::Num(*value),
+                TemplateCell::Op(op, flipped, p0, p1) => Cell::Op(
+                    Some((term_ptr(&cells, p0), term_ptr(&cells, p1))),
+                    *op,
+                    *flipped,
+                ),
+                TemplateCell::Swi(p0, p1) => {
+                    Cell::Swi(Some((term_ptr(&cells, p0), term_ptr(&cells, p1))))
+                }
+                TemplateCell::Ref(id) => Cell::Ref(*id),
+            };
+            cells.push(TermPtr::Ptr(store.alloc(Some(Term::Cell(cell)))));
+        }
+        let ports = self
+            .ports
+            .iter()
+            .map(|port| term_ptr(&cells, port))
+            .collect();
+        let body = self
+            .body
+            .iter()
+            .map(|(left, right)| (term_ptr(&cells, left), term_ptr(&cells, right)))
+            .collect();
+        (ports, body)
+    }
+
+    /// Copy the template into `net` with fresh vars and cells, returning its
+    /// ports. Both uses of an internal var point to the same fresh var, so
+    /// the copy is wired like the template; its body equations are added to
+    /// the net body.
+    pub fn clone_into(&self, net: &mut Net) -> Vec<TermPtr> {
+        let (ports, body) = self.alloc_in(&net.store);
+        for (left, right) in body {
+            net.eqn(left, right);
+        }
+        ports
+    }
+
+    /// Capture the redex `left ~ right` and every cell hanging from it, up to
+    /// the first vars. Vars used twice are internal wires; vars used once
+    /// become the template ports, in order of first use.
+    ///
+    /// Gives up on sub-nets with more than `max_cells` cells, with vars that
+    /// were already bound, with cells that lost their ports, or with
+    /// references.
+    pub(crate) fn capture(
+        store: &Store,
+        left: (Option<Ptr>, &Cell),
+        right: (Option<Ptr>, &Cell),
+        max_cells: usize,
+    ) -> Option<Captured> {
+        let mut captor = Captor {
+            store,
+            max_cells,
+            vars: Vec::new(),
+            used: Vec::new(),
+            cells: Vec::new(),
+            owned: Vec::new(),
+        };
+        let left = captor.cell(left.0, left.1)?;
+        let right = captor.cell(right.0, right.1)?;
+
+        let mut boundary = Vec::new();
+        let mut ports = Vec::new();
+        for (idx, (ptr, used)) in captor.vars.iter().zip(captor.used).enumerate() {
+            if used {
+                captor.owned.push(*ptr);
+            } else {
+                boundary.push(*ptr);
+                ports.push(Slot::Var(idx as u32));
+            }
+        }
+        Some(Captured {
+            template: Template {
+                vars: captor.vars.len() as u32,
+                cells: captor.cells,
+                ports,
+                body: vec![(left, right)],
+            },
+            boundary,
+            owned: captor.owned,
+        })
+    }
+}
+
+/// A redex copied out of the store by [`Template::capture`].
+pub(crate) struct Captured {
+    pub template: Template,
+    /// The vars crossing the sub-net boundary, one per template port.
+    pub boundary: Vec<Ptr>,
+    /// Cells and internal vars that only the sub-net uses.
+    pub owned: Vec<Ptr>,
+}
+
+struct Captor<'a> {
+    store: &'a Store,
+    max_cells: usize,
+    // vars in order of first use, and whether the second use was seen
+    vars: Vec<Ptr>,
+    used: Vec<bool>,
+    cells: Vec<TemplateCell>,
+    owned: Vec<Ptr>,
+}
+
+impl<'a> Captor<'a> {
+    fn cell(&mut self, ptr: Option<Ptr>, cell: &Cell) -> Option<Slot> {
+        if self.cells.len() >= self.max_cells {
+            return None;
+        }
+        let cell = match cell {
+            Cell::Lam(Some((p0, p1))) => TemplateCell::Lam(self.slot(*p0)?, self.slot(*p1)?),
+            Cell::App(Some((p0, p1))) => TemplateCell::App(self.slot(*p0)?, self.slot(*p1)?),
+            Cell::Dup(Some((p0, p1)), lbl) => {
+                TemplateCell::Dup(self.slot(*p0)?, self.slot(*p1)?, *lbl)
+            }
+            Cell::Num(value) => TemplateCell::Num(*value),
+            Cell::Op(Some((p0, p1)), op, flipped) => {
+                TemplateCell::Op(*op, *flipped, self.slot(*p0)?, self.slot(*p1)?)
+            }
+            Cell::Swi(Some((p0, p1))) => TemplateCell::Swi(self.slot(*p0)?, self.slot(*p1)?),
+            // a reference expands in the net it belongs to
+            Cell::Ref(_)
+            | Cell::Lam(None)
+            | Cell::App(None)
+            | Cell::Dup(None, _)
+            | Cell::Op(None, _, _)
+            | Cell::Swi(None) => return None,
+        };
+        self.owned.extend(ptr);
+        self.cells.push(cell);
+        Some(Slot::Cell(self.cells.len() as u32 - 1))
+    }
+
+    fn slot(&mut self, term_ptr: TermPtr) -> Option<Slot> {
+        let ptr = match term_ptr {
+            TermPtr::Era => return Some(Slot::Era),
+            TermPtr::Ptr(ptr) if !self.store.handed_out(ptr) => return None,
+            TermPtr::Ptr(ptr) => ptr,
+        };
+        match self.store.get(ptr) {
+            Some(TermRef::Cell(cell)) => self.cell(Some(ptr), &cell),
+            Some(TermRef::Var(var)) if var.read().is_none() => {
+                match self.vars.iter().position(|var_ptr| *var_ptr == ptr) {
+                    Some(idx) if self.used[idx] => None,
+                    Some(idx) => {
+                        self.used[idx] = true;
+                        Some(Slot::Var(idx as u32))
+                    }
+                    None => {
+                        self.vars.push(ptr);
+                        self.used.push(false);
+                        Some(Slot::Var(self.vars.len() as u32 - 1))
+                    }
+                }
+            }
+            Some(TermRef::Var(_)) | None => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct TemplateCompiler<'a> {
+    vars: u32,
+    cells: Vec<TemplateCell>,
+    // vars seen once, waiting for their second use
+    open: HashMap<&'a str, u32>,
+    closed: Vec<&'a str>,
+    // the definitions references are resolved in, if any
+    refs: Option<&'a Refs>,
+    // the equations of the infix operators, see `ast::Term::Infix`
+    eqns: Vec<(Slot, Slot)>,
+}
+
+impl<'a> TemplateCompiler<'a> {
+    fn slot(&mut self, term: &'a ast::Term) -> Result<Slot, AstError> {
+        let cell = match term {
+            ast::Term::Era => return Ok(Slot::Era),
+            ast::Term::Var(name) => {
+                if let Some(var) = self.open.remove(name.as_str()) {
+                    self.closed.push(name);
+                    return Ok(Slot::Var(var));
+                } else if self.closed.contains(&name.as_str()) {
+                    return Err(AstError::OverusedVar(name.clone()));
+                }
+                self.vars += 1;
+                self.open.insert(name, self.vars - 1);
+                return Ok(Slot::Var(self.vars - 1));
+            }
+            ast::Term::Lam(p0, p1) => TemplateCell::Lam(self.slot(p0)?, self.slot(p1)?),
+            ast::Term::App(p0, p1) => TemplateCell::App(self.slot(p0)?, self.slot(p1)?),
+            ast::Term::Dup(p0, p1, lbl) => TemplateCell::Dup(self.slot(p0)?, self.slot(p1)?, *lbl),
+            ast::Term::Num(value) => TemplateCell::Num(*value),
+            ast::Term::Op(op, flipped, p0, p1) => {
+                TemplateCell::Op(*op, *flipped, self.slot(p0)?, self.slot(p1)?)
+            }
+            ast::Term::Swi(p0, p1) => TemplateCell::Swi(self.slot(p0)?, self.slot(p1)?),
+            ast::Term::Ref(name) => match self.refs.and_then(|refs| refs.id(name)) {
+                Some(id) => TemplateCell::Ref(id),
+                None => return Err(AstError::UnknownDef(name.clone())),
+            },
+            // the calls of a book are expanded before it is compiled
+            ast::Term::Call(name, _) => return Err(AstError::UnknownDef(name.clone())),
+            // the result is a third port, which a single cell cannot give
+            ast::Term::Infix(op, p0, p1) => {
+                let result = Slot::Var(self.vars);
+                self.vars += 1;
+                let (left, right) = (self.slot(p0)?, self.slot(p1)?);
+                let op = TemplateCell::Op(*op, false, right, result);
+                self.cells.push(op);
+                self.eqns.push((left, Slot::Cell(self.cells.len() as u32 - 1)));
+                return Ok(result);
+            }
+        };
+        self.cells.push(cell);
+        Ok(Slot::Cell(self.cells.len() as u32 - 1))
+    }
+}
+
+impl Net {
+    /// Copy `template` into this net with fresh pointers. The template body
+    /// equations are added to the net body, and its ports are returned for
+    /// the caller to wire.
+    pub fn instantiate(&mut self, template: &Template) -> Interface {
+        Interface {
+            ports: template.clone_into(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        ast::{self, AstError, Term},
+        net::{Net, NetBuilder},
+        runtime::Runtime,
+        template::Template,
+    };
+
+    fn id() -> Template {
+        Template::from_ast(&ast::Net {
+            head: vec![Term::lam(Term::var("x"), Term::var("x"))],
+            body: vec![],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_instantiate() {
+        let id = id();
+        assert_eq!((id.ports(), id.size()), (1, 2));
+        let renamed = Template::from_ast(&ast::Net {
+            head: vec![Term::lam(Term::var("y"), Term::var("y"))],
+            body: vec![],
+        })
+        .unwrap();
+        assert_eq!(id.canonical_hash(), renamed.canonical_hash());
+
+        let mut net = Net::new();
+        let i1 = net.instantiate(&id);
+        let i2 = net.instantiate(&id);
+        assert_ne!(i1.ports, i2.ports);
+
+        let r = net.var();
+        let app = net.app(r.0, i2.ports[0]);
+        net.head(r.1);
+        net.eqn(i1.ports[0], app);
+
+        Runtime::new().eval(&mut net).unwrap();
+        let normal_form = net.normal_form().unwrap();
+        let Term::Lam(p0, p1) = &normal_form[0] else {
+            panic!("expected a lambda, got {}", normal_form[0]);
+        };
+        assert_eq!(p0, p1);
+    }
+
+    #[test]
+    fn test_template_body() {
+        // every var must be wired inside the template or exposed as a port
+        let unpaired = Template::from_ast(&ast::Net {
+            head: vec![Term::var("r")],
+            body: vec![(
+                Term::lam(Term::var("x"), Term::var("x")),
+                Term::app(Term::var("r"), Term::var("a")),
+            )],
+        });
+        assert_eq!(unpaired, Err(AstError::UnpairedVar("a".to_string())));
+
+        let apply_id = Template::from_ast(&ast::Net {
+            head: vec![Term::var("r"), Term::var("a")],
+            body: vec![(
+                Term::lam(Term::var("x"), Term::var("x")),
+                Term::app(Term::var("r"), Term::var("a")),
+            )],
+        })
+        .unwrap();
+
+        let mut net = Net::new();
+        net.instantiate(&apply_id);
+        let iface = net.instantiate(&apply_id);
+        assert_eq!(net.body.len(), 2);
+        // the second copy starts after the 3 vars and 2 cells of the first
+        assert_eq!(net.to_ast().unwrap().body[1].0.to_string(), "(λ x7 x7)");
+        assert_eq!(iface.ports.len(), 2);
+    }
+
+    #[test]
+    fn test_clone_into() {
+        // a dup whose two copies are applied to each other share its input
+        let template = Template::from_ast(&ast::Net {
+            head: vec![Term::var("r"), Term::var("f")],
+            // binds with their var on the left, as the net keeps them
+            body: vec![
+                (Term::var("f"), Term::dup(Term::var("f0"), Term::var("f1"))),
+                (Term::var("f0"), Term::app(Term::var("f1"), Term::var("r"))),
+            ],
+        })
+        .unwrap();
+
+        let mut net = Net::new();
+        let first = template.clone_into(&mut net);
+        let second = template.clone_into(&mut net);
+        assert_eq!(net.store.len() as usize, 2 * template.size());
+        assert_eq!(net.body.len(), 4);
+        // the copies share no pointer
+        assert!(first.iter().all(|port| !second.contains(port)));
+        // and each one is wired like the template
+        first.into_iter().for_each(|port| net.head(port));
+        let copies = net.to_ast().unwrap();
+        assert_eq!(
+            Template::from_ast(&ast::Net {
+                head: vec![copies.head[0].clone(), copies.head[1].clone()],
+                body: copies.body[..2].to_vec(),
+            }),
+            Ok(template)
+        );
+    }
+}
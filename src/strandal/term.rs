@@ -1,5 +1,9 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use super::{
-    store::Ptr,
+    num::Op,
+    refs::RefId,
+    store::{Index, Ptr},
     var::{Var, VarUse},
 };
 
@@ -8,46 +12,252 @@ pub enum TermPtr {
     Era,
     Ptr(Ptr),
 }
-unsafe impl Send for TermPtr {}
-unsafe impl Sync for TermPtr {}
+
+// the word a packed term pointer fits in: the index alone, unless it is wide
+// or carries a generation
+#[cfg(not(any(debug_assertions, feature = "wide-ptrs")))]
+type PackedBits = u32;
+#[cfg(any(debug_assertions, feature = "wide-ptrs"))]
+type PackedBits = u64;
+
+/// A [`TermPtr`] packed in a single word, all ones for an eraser: half the
+/// size of the enum, which pads the 32-bit index of a [`Ptr`] with its tag.
+///
+/// No slot has the all ones index, since the store cannot hold that many
+/// slots, nor the all ones bits of a pointer with a generation.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PackedTermPtr(PackedBits);
+
+impl PackedTermPtr {
+    const ERA: PackedBits = PackedBits::MAX;
+}
+
+impl From<TermPtr> for PackedTermPtr {
+    #[inline]
+    fn from(value: TermPtr) -> Self {
+        match value {
+            TermPtr::Era => PackedTermPtr(Self::ERA),
+            TermPtr::Ptr(ptr) => PackedTermPtr(ptr.to_bits() as PackedBits),
+        }
+    }
+}
+
+impl From<PackedTermPtr> for TermPtr {
+    #[inline]
+    fn from(value: PackedTermPtr) -> Self {
+        match value.0 {
+            PackedTermPtr::ERA => TermPtr::Era,
+            #[allow(clippy::unnecessary_cast)] // already a u64 in debug builds
+            bits => TermPtr::Ptr(Ptr::from_bits(bits as u64)),
+        }
+    }
+}
+
+impl std::fmt::Debug for PackedTermPtr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", TermPtr::from(*self))
+    }
+}
+
+/// The label of a duplicator.
+///
+/// User labels come from the source, from JSON nets and from
+/// [`NetBuilder::labeled_dup`](super::net::NetBuilder::labeled_dup). Fresh
+/// ones are drawn by [`Label::fresh`] from a counter shared by every net, so
+/// a fresh label equals neither a user label nor another fresh label, and
+/// copying a net to another store keeps them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Label {
+    User(Index),
+    Fresh(Index),
+}
+
+// the bit telling fresh labels from user labels in a slot payload
+const FRESH_LABEL: u64 = 1 << 63;
+
+impl Label {
+    /// A label no other label equals.
+    pub fn fresh() -> Label {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Label::Fresh(NEXT.fetch_add(1, Ordering::Relaxed) as Index)
+    }
+
+    /// The number of the label, whatever its kind.
+    pub fn index(&self) -> Index {
+        match *self {
+            Label::User(idx) | Label::Fresh(idx) => idx,
+        }
+    }
+
+    #[inline]
+    #[allow(clippy::useless_conversion)] // `Index` is already a u64 under wide-ptrs
+    pub(crate) fn to_bits(self) -> u64 {
+        match self {
+            Label::User(idx) => u64::from(idx),
+            Label::Fresh(idx) => u64::from(idx) | FRESH_LABEL,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn from_bits(bits: u64) -> Label {
+        let idx = (bits & !FRESH_LABEL) as Index;
+        match bits & FRESH_LABEL {
+            0 => Label::User(idx),
+            _ => Label::Fresh(idx),
+        }
+    }
+}
+
+// fresh labels are marked, so that a readback or a dump does not pass them
+// off as user labels
+impl std::fmt::Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Label::User(idx) => write!(f, "{idx}"),
+            Label::Fresh(idx) => write!(f, "~{idx}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Label {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('~') {
+            Some(idx) => idx.parse().map(Label::Fresh),
+            None => s.parse().map(Label::User),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Cell {
-    Dup(Option<(TermPtr, TermPtr)>, Option<Ptr>),
+    Dup(Option<(TermPtr, TermPtr)>, Option<Label>),
     App(Option<(TermPtr, TermPtr)>),
     Lam(Option<(TermPtr, TermPtr)>),
+    Num(u32),
+    /// An operator waiting for its left operand, with ports (right operand,
+    /// result). Once flipped, it holds the left operand in its first port
+    /// and waits for the right one.
+    Op(Option<(TermPtr, TermPtr)>, Op, bool),
+    /// A switch on a number, with ports (branches, result).
+    Swi(Option<(TermPtr, TermPtr)>),
+    /// A reference to a definition, expanded when it meets a cell.
+    Ref(RefId),
 }
 
-unsafe impl Send for Cell {}
-unsafe impl Sync for Cell {}
+impl Cell {
+    /// The auxiliary ports, `None` for a number, a reference or a cell with
+    /// its ports wired together.
+    pub fn ports(&self) -> Option<(TermPtr, TermPtr)> {
+        match self {
+            Cell::Dup(ports, _)
+            | Cell::App(ports)
+            | Cell::Lam(ports)
+            | Cell::Op(ports, _, _)
+            | Cell::Swi(ports) => *ports,
+            Cell::Num(_) | Cell::Ref(_) => None,
+        }
+    }
+
+    /// The same cell with other auxiliary ports; a number or a reference is
+    /// left as is.
+    pub fn with_ports(&self, ports: Option<(TermPtr, TermPtr)>) -> Cell {
+        match *self {
+            Cell::Dup(_, label) => Cell::Dup(ports, label),
+            Cell::App(_) => Cell::App(ports),
+            Cell::Lam(_) => Cell::Lam(ports),
+            Cell::Op(_, op, flipped) => Cell::Op(ports, op, flipped),
+            Cell::Swi(_) => Cell::Swi(ports),
+            Cell::Num(value) => Cell::Num(value),
+            Cell::Ref(id) => Cell::Ref(id),
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Term {
     Var(Var),
     Cell(Cell),
 }
-impl<'a> TryFrom<&'a Term> for &'a Cell {
-    type Error = &'a Term;
 
-    fn try_from(value: &'a Term) -> Result<Self, Self::Error> {
+/// A slot of a [`Store`](super::store::Store) as read: its var, which is
+/// updated in place, or a copy of its cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermRef<'a> {
+    Var(&'a Var),
+    Cell(Cell),
+}
+
+/// A copy of the slot, its var holding the value read.
+impl From<TermRef<'_>> for Term {
+    fn from(term: TermRef<'_>) -> Self {
+        match term {
+            TermRef::Var(var) => {
+                let copy = Var::new();
+                if let Some(value) = var.read() {
+                    copy.set(value);
+                }
+                Term::Var(copy)
+            }
+            TermRef::Cell(cell) => Term::Cell(cell),
+        }
+    }
+}
+
+impl<'a> TryFrom<TermRef<'a>> for Cell {
+    type Error = TermRef<'a>;
+
+    fn try_from(value: TermRef<'a>) -> Result<Self, Self::Error> {
         match value {
-            Term::Cell(cell) => Ok(cell),
+            TermRef::Cell(cell) => Ok(cell),
             _ => Err(value),
         }
     }
 }
 
-impl<'a> TryFrom<&'a Term> for &'a Var {
-    type Error = &'a Term;
+impl<'a> TryFrom<TermRef<'a>> for &'a Var {
+    type Error = TermRef<'a>;
 
-    fn try_from(value: &'a Term) -> Result<Self, Self::Error> {
+    fn try_from(value: TermRef<'a>) -> Result<Self, Self::Error> {
         match value {
-            Term::Var(var) => Ok(var),
+            TermRef::Var(var) => Ok(var),
             _ => Err(value),
         }
     }
 }
 
+/// A cell built by a [`NetBuilder`](super::net::NetBuilder) and not wired
+/// yet. Like [`VarUse`] it is not `Copy`: wiring it moves it, so the same
+/// cell cannot end up in two places.
+#[derive(Debug, Eq, PartialEq)]
+#[must_use]
+pub struct CellUse {
+    ptr: Ptr,
+}
+
+impl CellUse {
+    pub fn new(ptr: Ptr) -> Self {
+        CellUse { ptr }
+    }
+
+    pub fn ptr(&self) -> Ptr {
+        self.ptr
+    }
+
+    /// Copy the cell pointer out without consuming the handle. Wiring both
+    /// the copy and the handle makes the net non-linear.
+    pub fn unchecked_term_ptr(&self) -> TermPtr {
+        TermPtr::Ptr(self.ptr)
+    }
+}
+
+impl From<CellUse> for TermPtr {
+    fn from(value: CellUse) -> Self {
+        TermPtr::Ptr(value.ptr())
+    }
+}
+
 impl From<VarUse> for TermPtr {
     fn from(value: VarUse) -> Self {
         TermPtr::Ptr(value.ptr())
@@ -0,0 +1,176 @@
+use std::{
+    io::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+
+use super::{
+    store::Store,
+    taint,
+    term::{Cell, TermPtr, TermRef},
+};
+
+/// An evaluation task, with times relative to the start of the eval.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskEvent {
+    /// The first rule the task applied, named as in the stats.
+    pub rule: &'static str,
+    pub thread: usize,
+    pub spawned: Duration,
+    pub started: Duration,
+    pub ended: Duration,
+}
+
+impl TaskEvent {
+    /// The rule family: `ANNI`, `COMM`, `BIND` or `CONNECT`.
+    pub fn family(&self) -> &'static str {
+        self.rule.split(' ').next().unwrap_or(self.rule)
+    }
+}
+
+/// The tasks of an eval, recorded when enabled with
+/// [`RuntimeConfig::record_timeline`](super::runtime::RuntimeConfig::record_timeline).
+pub struct Timeline {
+    origin: Instant,
+    events: Mutex<Vec<TaskEvent>>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Time elapsed since the eval started.
+    #[inline]
+    pub fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    pub fn record(&self, event: TaskEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    /// The recorded tasks, by start time.
+    pub fn events(&self) -> Vec<TaskEvent> {
+        let mut events = self.events.lock().unwrap().clone();
+        events.sort_by_key(|event| event.started);
+        events
+    }
+
+    /// Write the tasks in the Chrome trace format, to open in
+    /// `chrome://tracing` or Perfetto: one track per thread, with the time
+    /// each task waited to start in its arguments.
+    pub fn write_chrome_trace(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let events = self.events();
+        let mut threads: Vec<_> = events.iter().map(|event| event.thread).collect();
+        threads.sort();
+        threads.dedup();
+        let names = threads.into_iter().map(|thread| {
+            json!({
+                "name": "thread_name",
+                "ph": "M",
+                "pid": 0,
+                "tid": thread,
+                "args": { "name": format!("worker {}", thread) },
+            })
+        });
+        let tasks = events.iter().map(|event| {
+            json!({
+                "name": event.rule,
+                "cat": event.family(),
+                "ph": "X",
+                "pid": 0,
+                "tid": event.thread,
+                "ts": micros(event.started),
+                "dur": micros(event.ended.saturating_sub(event.started)),
+                "args": { "queued_us": micros(event.started.saturating_sub(event.spawned)) },
+            })
+        });
+        let trace = json!({ "traceEvents": names.chain(tasks).collect::<Vec<_>>() });
+        serde_json::to_writer(&mut *out, &trace)?;
+        writeln!(out)
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e6
+}
+
+/// The rule an equation reduces by.
+pub(crate) fn rule(store: &Store, left: TermPtr, right: TermPtr) -> &'static str {
+    match (left, right) {
+        (TermPtr::Era, TermPtr::Era) => "ANNI ERA-ERA",
+        (TermPtr::Ptr(ptr), other) | (other, TermPtr::Ptr(ptr)) => match store.get(ptr) {
+            Some(TermRef::Cell(cell)) => cell_rule(store, &cell, other),
+            _ => match other {
+                TermPtr::Ptr(other) if matches!(store.get(other), Some(TermRef::Var(_))) => "CONNECT",
+                _ => "BIND",
+            },
+        },
+    }
+}
+
+/// The rule `cell` reduces by against `term_ptr`.
+pub(crate) fn cell_rule(store: &Store, cell: &Cell, term_ptr: TermPtr) -> &'static str {
+    match term_ptr {
+        TermPtr::Era => taint::erase_rule(cell),
+        TermPtr::Ptr(ptr) => match store.get(ptr) {
+            Some(TermRef::Cell(other)) => taint::rule(cell, &other),
+            _ => "BIND",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strandal::{
+        net::{Net, NetBuilder},
+        runtime::{Runtime, RuntimeConfig},
+    };
+
+    #[test]
+    fn test_timeline() {
+        // (λx.x) (λy.y)
+        let mut net = Net::new();
+        let x = net.var();
+        let id = net.lam(x.0, x.1);
+        let y = net.var();
+        let arg = net.lam(y.0, y.1);
+        let r = net.var();
+        let app = net.app(arg, r.0);
+        net.head(r.1);
+        net.eqn(id, app);
+        let mut runtime = Runtime::with_config(RuntimeConfig::default().record_timeline());
+        runtime.eval(&mut net).unwrap();
+
+        let timeline = runtime.timeline().unwrap();
+        let events = timeline.events();
+        assert_eq!(events[0].rule, "COMM APP-LAM");
+        assert_eq!(events[0].family(), "COMM");
+        // the ports are bound by subtasks
+        assert!(events.len() > 1);
+        for event in &events[1..] {
+            assert!(["BIND", "CONNECT"].contains(&event.rule));
+        }
+        for event in &events {
+            assert!(event.spawned <= event.started && event.started <= event.ended);
+        }
+
+        let mut out = Vec::new();
+        timeline.write_chrome_trace(&mut out).unwrap();
+        let trace: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let tasks: Vec<_> = trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|event| event["ph"] == "X")
+            .collect();
+        assert_eq!(tasks.len(), events.len());
+        assert_eq!(tasks[0]["cat"], "COMM");
+    }
+}
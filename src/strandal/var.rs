@@ -1,6 +1,9 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 
-use super::store::Ptr;
+use super::{
+    codec::{self, Tag},
+    store::Ptr,
+};
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum VarValue {
@@ -9,28 +12,23 @@ pub enum VarValue {
     Cell(Ptr),
 }
 
+// packed as in `codec`
 impl VarValue {
     fn to_u64(var_value: Option<VarValue>) -> u64 {
         match var_value {
-            None => return 0,
-            Some(VarValue::Var(ptr)) => return 1 << 62 | ptr.as_u32() as u64,
-            Some(VarValue::Era) => return 2 << 62,
-            Some(VarValue::Cell(ptr)) => return 3 << 62 | ptr.as_u32() as u64,
+            None => codec::pack(Tag::Empty, 0),
+            Some(VarValue::Var(ptr)) => codec::pack(Tag::Var, ptr.to_bits()),
+            Some(VarValue::Era) => codec::pack(Tag::Era, 0),
+            Some(VarValue::Cell(ptr)) => codec::pack(Tag::Cell, ptr.to_bits()),
         }
     }
 
-    fn from_u64(value: u64) -> Result<Option<VarValue>, u64> {
-        let tag = value >> 62;
-        if tag == 0 {
-            Ok(None)
-        } else if tag == 1 {
-            Ok(Some(VarValue::Var(Ptr::new((value & 0xFFFFFFFF) as u32))))
-        } else if tag == 2 {
-            Ok(Some(VarValue::Era))
-        } else if tag == 3 {
-            Ok(Some(VarValue::Cell(Ptr::new((value & 0xFFFFFFFF) as u32))))
-        } else {
-            Err(value)
+    fn from_u64(value: u64) -> Option<VarValue> {
+        match codec::unpack(value) {
+            (Tag::Empty, _) => None,
+            (Tag::Var, bits) => Some(VarValue::Var(Ptr::from_bits(bits))),
+            (Tag::Era, _) => Some(VarValue::Era),
+            (Tag::Cell, bits) => Some(VarValue::Cell(Ptr::from_bits(bits))),
         }
     }
 }
@@ -42,12 +40,19 @@ impl Var {
         Var(AtomicU64::new(VarValue::to_u64(None)))
     }
 
+    // the word of the var, which a store slot also uses for the first port
+    // of a cell
+    #[inline]
+    pub(crate) fn word(&self) -> &AtomicU64 {
+        &self.0
+    }
+
     pub fn set(&self, new_value: VarValue) -> Option<VarValue> {
         let old_value = self.0.swap(
             VarValue::to_u64(Some(new_value)),
             std::sync::atomic::Ordering::Relaxed,
         );
-        return VarValue::from_u64(old_value).unwrap();
+        return VarValue::from_u64(old_value);
     }
 
     pub fn link(&self, var_ptr: Ptr) -> Option<VarValue> {
@@ -64,7 +69,7 @@ impl Var {
 
     pub fn read(&self) -> Option<VarValue> {
         let val = self.0.load(std::sync::atomic::Ordering::Relaxed);
-        VarValue::from_u64(val).unwrap()
+        VarValue::from_u64(val)
     }
 }
 impl PartialEq for Var {
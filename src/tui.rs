@@ -0,0 +1,322 @@
+use std::{
+    fs, io,
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use ratatui::{
+    crossterm::event::{self, Event as TermEvent, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    widgets::{Block, List, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+use crate::strandal::{
+    debugger::{Breakpoint, Debugger, EquationView, Resume},
+    display::CellDisplay,
+    net::Net,
+    parser::{parse, parse_from_entry},
+    runtime::Runtime,
+    store::{Index, Ptr, Store},
+    term::TermRef,
+    var::VarValue,
+};
+
+/// Number of store slots shown on each side of the current redex.
+const STORE_WINDOW: Index = 8;
+
+/// Sent from the TUI to the paused evaluation.
+enum Command {
+    Step,
+    Continue,
+    Inspect(Index),
+}
+
+/// Sent from the evaluation thread to the TUI.
+enum Event {
+    Paused(Snapshot),
+    Inspected(String),
+    Finished(String),
+}
+
+/// An owned copy of an [`EquationView`], so it can cross threads.
+struct Snapshot {
+    redex: String,
+    pending: Vec<String>,
+    store: Vec<String>,
+    stats: String,
+}
+
+impl Snapshot {
+    fn new(view: &EquationView) -> Self {
+        let center = view
+            .left
+            .ptr()
+            .or(view.right.ptr())
+            .map_or(0, |ptr| ptr.index());
+        let start = center.saturating_sub(STORE_WINDOW);
+        let end = center + STORE_WINDOW + 1;
+        Snapshot {
+            redex: view.to_string(),
+            pending: view.pending_equations(),
+            store: (start..end)
+                .map(Ptr::new)
+                .filter(|ptr| view.store.handed_out(*ptr))
+                .map(|ptr| slot_line(view.store, ptr))
+                .collect(),
+            stats: view.stats.to_string(),
+        }
+    }
+}
+
+fn slot_line(store: &Store, ptr: Ptr) -> String {
+    match store.get(ptr) {
+        Some(TermRef::Cell(cell)) => {
+            format!("{:>6} {}", ptr, CellDisplay(store, Some(ptr), &cell))
+        }
+        Some(TermRef::Var(var)) => format!(
+            "{:>6} x.{} = {}",
+            ptr,
+            ptr.index(),
+            var_value(store, var.read())
+        ),
+        None => format!("{:>6} <free>", ptr),
+    }
+}
+
+fn var_value(store: &Store, value: Option<VarValue>) -> String {
+    match value {
+        None => "<unbound>".to_string(),
+        Some(VarValue::Era) => CellDisplay::ERA_SYMBOL.to_string(),
+        Some(VarValue::Var(ptr)) => format!("→ x.{}", ptr.index()),
+        Some(VarValue::Cell(ptr)) => match store.get(ptr) {
+            Some(TermRef::Cell(cell)) => CellDisplay(store, Some(ptr), &cell).to_string(),
+            _ => format!("<dangling {}>", ptr),
+        },
+    }
+}
+
+fn inspect(store: &Store, index: Index) -> String {
+    if !store.handed_out(Ptr::new(index)) {
+        return format!("#{} is out of bounds", index);
+    }
+    match store.get(Ptr::new(index)) {
+        Some(TermRef::Var(var)) => format!("x.{} = {}", index, var_value(store, var.read())),
+        Some(TermRef::Cell(_)) => format!("#{} is a cell, not a var", index),
+        None => format!("#{} is free", index),
+    }
+}
+
+struct App {
+    snapshot: Option<Snapshot>,
+    paused: bool,
+    finished: Option<String>,
+    inspect_input: Option<String>,
+    inspected: Option<String>,
+}
+
+impl App {
+    fn draw(&self, frame: &mut Frame) {
+        let [header, main, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(main);
+        let [store, stats] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(6)]).areas(right);
+
+        let redex = match (&self.finished, &self.snapshot) {
+            (Some(_), _) => "evaluation finished".to_string(),
+            (None, Some(snapshot)) => snapshot.redex.clone(),
+            (None, None) => "running...".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(redex).block(Block::bordered().title("Redex")),
+            header,
+        );
+
+        let pending = self
+            .snapshot
+            .as_ref()
+            .map(|s| s.pending.clone())
+            .unwrap_or_default();
+        frame.render_widget(
+            List::new(pending).block(Block::bordered().title("Pending equations")),
+            left,
+        );
+
+        let mut slots = self
+            .snapshot
+            .as_ref()
+            .map(|s| s.store.clone())
+            .unwrap_or_default();
+        if let Some(inspected) = &self.inspected {
+            slots.push(String::new());
+            slots.push(inspected.clone());
+        }
+        frame.render_widget(
+            List::new(slots).block(Block::bordered().title("Store")),
+            store,
+        );
+
+        let stats_text = match (&self.finished, &self.snapshot) {
+            (Some(stats), _) => stats.clone(),
+            (None, Some(snapshot)) => snapshot.stats.clone(),
+            (None, None) => String::new(),
+        };
+        frame.render_widget(
+            Paragraph::new(stats_text)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title("Stats")),
+            stats,
+        );
+
+        let help = match &self.inspect_input {
+            Some(input) => format!("inspect var #{}_   (enter: show, esc: cancel)", input),
+            None => "s: step   c: continue to breakpoint   i: inspect var   q: quit".to_string(),
+        };
+        frame.render_widget(Paragraph::new(help).block(Block::bordered()), footer);
+    }
+
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        events: Receiver<Event>,
+        commands: Sender<Command>,
+    ) -> io::Result<()> {
+        loop {
+            while let Ok(event) = events.try_recv() {
+                match event {
+                    Event::Paused(snapshot) => {
+                        self.snapshot = Some(snapshot);
+                        self.inspected = None;
+                        self.paused = true;
+                    }
+                    Event::Inspected(text) => self.inspected = Some(text),
+                    Event::Finished(stats) => self.finished = Some(stats),
+                }
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(Duration::from_millis(50))? {
+                continue;
+            }
+            let TermEvent::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some(input) = self.inspect_input.as_mut() {
+                match key.code {
+                    KeyCode::Char(c) if c.is_ascii_digit() => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Enter => {
+                        if let (true, Ok(index)) = (self.paused, input.parse()) {
+                            commands.send(Command::Inspect(index)).ok();
+                        }
+                        self.inspect_input = None;
+                    }
+                    KeyCode::Esc => self.inspect_input = None,
+                    _ => {}
+                }
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') => return Ok(()),
+                KeyCode::Char('s') if self.paused => {
+                    self.paused = false;
+                    commands.send(Command::Step).ok();
+                }
+                KeyCode::Char('c') if self.paused => {
+                    self.paused = false;
+                    commands.send(Command::Continue).ok();
+                }
+                KeyCode::Char('i') if self.paused => self.inspect_input = Some(String::new()),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Load a net file and step through its evaluation, pausing at the first
+/// interaction and at every breakpoint. With an `entry`, definitions it does
+/// not reference are left out.
+pub fn run(path: &Path, entry: Option<&str>, breakpoints: Vec<Breakpoint>) -> io::Result<()> {
+    let src = fs::read_to_string(path)?;
+    let mut net = Net::new();
+    let loaded = match entry {
+        Some(entry) => parse_from_entry(&src, &mut net, entry).map(|removed| {
+            if !removed.is_empty() {
+                eprintln!(
+                    "strandal: pruned unreferenced definitions: {}",
+                    removed.join(", ")
+                );
+            }
+        }),
+        None => parse(&src, &mut net).then_some(()),
+    };
+    if loaded.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("could not parse {}", path.display()),
+        ));
+    }
+
+    let (event_tx, event_rx) = mpsc::channel();
+    let (command_tx, command_rx) = mpsc::channel();
+    let command_rx = Mutex::new(command_rx);
+    let paused_tx = event_tx.clone();
+    let debugger = breakpoints.into_iter().fold(
+        Debugger::new(move |view| {
+            paused_tx.send(Event::Paused(Snapshot::new(view))).ok();
+            let commands = command_rx.lock().unwrap();
+            loop {
+                match commands.recv() {
+                    Ok(Command::Step) => return Resume::Step,
+                    Ok(Command::Continue) | Err(_) => return Resume::Continue,
+                    Ok(Command::Inspect(index)) => {
+                        paused_tx
+                            .send(Event::Inspected(inspect(view.store, index)))
+                            .ok();
+                    }
+                }
+            }
+        })
+        .step(),
+        |debugger, breakpoint| debugger.breakpoint(breakpoint),
+    );
+
+    // the evaluation thread is left blocked in the debugger if we quit early
+    thread::spawn(move || {
+        let mut runtime = Runtime::with_debugger(debugger);
+        let report = match runtime.eval(&mut net) {
+            Ok(()) => runtime.stats.to_string(),
+            Err(err) => format!("{}\n{}", err, runtime.stats),
+        };
+        event_tx.send(Event::Finished(report)).ok();
+    });
+
+    let mut app = App {
+        snapshot: None,
+        paused: false,
+        finished: None,
+        inspect_input: None,
+        inspected: None,
+    };
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, event_rx, command_tx);
+    ratatui::restore();
+    result
+}
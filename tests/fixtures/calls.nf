@@ -0,0 +1,2 @@
+(λ x0 (λ ε x0))
+9
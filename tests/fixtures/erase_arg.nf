@@ -0,0 +1 @@
+ε
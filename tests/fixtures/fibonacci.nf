@@ -0,0 +1 @@
+55
@@ -0,0 +1 @@
+(λ x0 (λ ε x0))
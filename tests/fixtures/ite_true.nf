@@ -0,0 +1 @@
+(λ x0 x0)
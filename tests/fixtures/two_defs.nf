@@ -0,0 +1,2 @@
+(λ x0 x0)
+(λ x1 (λ ε x1))